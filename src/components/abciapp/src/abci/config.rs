@@ -14,6 +14,7 @@ pub struct ABCIConfig {
     pub query_port: u16,
     pub evm_http_port: u16,
     pub evm_ws_port: u16,
+    pub metrics_port: Option<u16>,
     pub ledger_dir: String,
 }
 
@@ -27,6 +28,8 @@ pub struct ABCIConfigStr {
     pub ledger_port: String,
     pub evm_http_port: String,
     pub evm_ws_port: String,
+    #[serde(default)]
+    pub metrics_port: Option<String>,
     #[serde(skip)]
     pub ledger_dir: Option<String>,
 }
@@ -38,6 +41,10 @@ impl TryFrom<ABCIConfigStr> for ABCIConfig {
         let query_port = ledger_port - 1;
         let evm_http_port = cfg.evm_http_port.parse::<u16>().c(d!())?;
         let evm_ws_port = cfg.evm_ws_port.parse::<u16>().c(d!())?;
+        let metrics_port = cfg
+            .metrics_port
+            .map(|p| p.parse::<u16>().c(d!()))
+            .transpose()?;
         Ok(ABCIConfig {
             abci_host: cfg.abci_host,
             abci_port: cfg.abci_port.parse::<u16>().c(d!())?,
@@ -48,6 +55,7 @@ impl TryFrom<ABCIConfigStr> for ABCIConfig {
             query_port,
             evm_http_port,
             evm_ws_port,
+            metrics_port,
             ledger_dir: cfg.ledger_dir.unwrap_or(pnk!(env::var("LEDGER_DIR"))),
         })
     }
@@ -79,6 +87,7 @@ impl ABCIConfig {
             query_port,
             evm_http_port: CFG.evm_http_port,
             evm_ws_port: CFG.evm_ws_port,
+            metrics_port: CFG.metrics_port,
             ledger_dir: CFG.ledger_dir.clone(),
         })
     }
@@ -118,9 +127,19 @@ pub(crate) mod global_cfg {
         pub enable_eth_api_service: bool,
         pub evm_http_port: u16,
         pub evm_ws_port: u16,
+        pub metrics_port: Option<u16>,
         pub tendermint_node_self_addr: Option<String>,
         pub tendermint_node_key_config_path: Option<String>,
         pub ledger_dir: String,
+        pub txn_policy_deny_list: Option<String>,
+        pub txn_status_ttl: Option<u64>,
+        pub api_rate_limit: u32,
+        pub api_keys: Option<String>,
+        pub api_max_concurrency: usize,
+        pub api_body_limits: Option<String>,
+        pub api_trust_proxy: bool,
+        pub dev_mode: bool,
+        pub dev_block_interval: Option<u64>,
     }
 
     #[cfg(test)]
@@ -148,9 +167,19 @@ pub(crate) mod global_cfg {
                 .arg_from_usage("--enable-eth-api-service")
                 .arg_from_usage("--evm-http-port=[EVM Web3 Http Port]")
                 .arg_from_usage("--evm-ws-port=[EVM Web3 WS Port]")
+                .arg_from_usage("--metrics-port=[Metrics Port] 'serve prometheus metrics on this port'")
                 .arg_from_usage("--tendermint-node-self-addr=[Address] 'the address of your tendermint node, in upper-hex format'")
                 .arg_from_usage("--tendermint-node-key-config-path=[Path] 'such as: ${HOME}/.tendermint/config/priv_validator_key.json'")
                 .arg_from_usage("-d, --ledger-dir=[Path]")
+                .arg_from_usage("--txn-policy-deny-list=[Path] 'a json file of denied addresses, must be the same on all validators'")
+                .arg_from_usage("--txn-status-ttl=[Blocks] 'for how many blocks the statuses of transactions are kept'")
+                .arg_from_usage("--api-rate-limit=[Num] 'max requests per second of every client IP, 0 for unlimited'")
+                .arg_from_usage("--api-keys=[Path] 'a json file of API keys and their rate limits(requests per second)'")
+                .arg_from_usage("--api-max-concurrency=[Num] 'max number of concurrent requests of every http service, 0 for unlimited'")
+                .arg_from_usage("--api-body-limits=[Limits] 'max body sizes of routes, like submit_transaction=2097152,graphql=65536'")
+                .arg_from_usage("--api-trust-proxy 'get client IPs from the X-Forwarded-For header, only for nodes behind a trusted proxy'")
+                .arg_from_usage("--dev 'run a local devnet, blocks are produced in-process instead of by tendermint'")
+                .arg_from_usage("--dev-block-interval=[Seconds] 'in dev mode, also produce empty blocks at this interval'")
                 .arg(Arg::with_name("_a").long("ignored").hidden(true))
                 .arg(Arg::with_name("_b").long("nocapture").hidden(true))
                 .arg(Arg::with_name("_c").long("test-threads").hidden(true))
@@ -215,6 +244,47 @@ pub(crate) mod global_cfg {
                     format!("{}/.tendermint/__findora__", pnk!(env::var("HOME")))
                 })
             });
+        let tpdl = m
+            .value_of("txn-policy-deny-list")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("TXN_POLICY_DENY_LIST").ok());
+        let tst = m
+            .value_of("txn-status-ttl")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("TXN_STATUS_TTL").ok())
+            .map(|v| v.parse::<u64>().c(d!()))
+            .transpose()?;
+        let arl = m
+            .value_of("api-rate-limit")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("API_RATE_LIMIT").ok())
+            .unwrap_or_else(|| "0".to_owned())
+            .parse::<u32>()
+            .c(d!())?;
+        let ak = m
+            .value_of("api-keys")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("API_KEYS").ok());
+        let amc = m
+            .value_of("api-max-concurrency")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("API_MAX_CONCURRENCY").ok())
+            .unwrap_or_else(|| "0".to_owned())
+            .parse::<usize>()
+            .c(d!())?;
+        let abl = m
+            .value_of("api-body-limits")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("API_BODY_LIMITS").ok());
+        let atp = m.is_present("api-trust-proxy") || env::var("API_TRUST_PROXY").is_ok();
+
+        let dm = m.is_present("dev") || env::var("DEV_MODE").is_ok();
+        let dbi = m
+            .value_of("dev-block-interval")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("DEV_BLOCK_INTERVAL").ok())
+            .map(|v| v.parse::<u64>().c(d!()))
+            .transpose()?;
 
         let eeb = m.is_present("enable-eth-empty-blocks")
             || env::var("ENABLE_ETH_EMPTY_BLOCKS").is_ok();
@@ -234,6 +304,12 @@ pub(crate) mod global_cfg {
             .unwrap_or_else(|| "8546".to_owned())
             .parse::<u16>()
             .c(d!())?;
+        let mp = m
+            .value_of("metrics-port")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("METRICS_PORT").ok())
+            .map(|v| v.parse::<u16>().c(d!()))
+            .transpose()?;
 
         let res = Config {
             abci_host: ah,
@@ -247,9 +323,19 @@ pub(crate) mod global_cfg {
             enable_eth_api_service: eas,
             evm_http_port: ehp,
             evm_ws_port: ewp,
+            metrics_port: mp,
             tendermint_node_self_addr: tnsa,
             tendermint_node_key_config_path: tnkcp,
             ledger_dir: ld,
+            txn_policy_deny_list: tpdl,
+            txn_status_ttl: tst,
+            api_rate_limit: arl,
+            api_keys: ak,
+            api_max_concurrency: amc,
+            api_body_limits: abl,
+            api_trust_proxy: atp,
+            dev_mode: dm,
+            dev_block_interval: dbi,
         };
 
         Ok(res)
@@ -0,0 +1,287 @@
+//!
+//! # Deterministic accounts and the genesis of the devnet
+//!
+//! All keys are derived from `DEV_MNEMONIC`:
+//! - UTXO accounts: "m/44'/917'/0'/0/i", the first one issues FRA
+//! - EVM accounts: "m/44'/60'/0'/0/i", funded by the UTXO account with the same index
+//! - validators: "m/44'/917'/1'/0/i"
+//!
+
+use super::DevChain;
+use finutils::{
+    common::utils::{gen_transfer_op_with_utxos, TransferTarget},
+    txn_builder::{CoinSelection, TransactionBuilder},
+};
+use fp_types::crypto::MultiSigner;
+use fp_utils::ecdsa::SecpPair;
+use globutils::wallet::{self, BipPath, FRA_COIN};
+use ledger::{
+    data_model::{Operation, Transaction, BLACK_HOLE_PUBKEY_STAKING, TX_FEE_MIN},
+    staking::{
+        td_pubkey_to_td_addr, StakerMemo, Validator, ValidatorKind, FRA,
+        STAKING_VALIDATOR_MIN_POWER,
+    },
+    store::utils::fra_gen_initial_tx,
+};
+use ruc::*;
+use zei::{
+    serialization::ZeiFromToBytes,
+    xfr::sig::{XfrKeyPair, XfrPublicKey},
+};
+
+/// The mnemonic of all the accounts of the devnet,
+/// NEVER use it out of a local devnet.
+pub const DEV_MNEMONIC: &str = "zoo nerve assault talk depend approve mercy surge bicycle ridge dismiss satoshi boring opera next fat cinnamon valley office actor above spray alcohol giant";
+
+pub(super) const ACCOUNT_NUM: u32 = 10;
+
+// A single validator is enough for the stand-in of the consensus,
+// the staking rules on the number and the power of the validators
+// are relaxed by `DevChain`, see `ledger::staking::relax_validator_rules`.
+pub(super) const VALIDATOR_NUM: u32 = 1;
+
+// FRA units of each UTXO account, and of each EVM account.
+pub(super) const ACCOUNT_AMOUNT: u64 = 100_0000 * FRA;
+pub(super) const EVM_AMOUNT: u64 = 100_0000 * FRA;
+
+// Self-delegation of each validator.
+pub(super) const SELF_DELEGATION_AMOUNT: u64 = FRA;
+
+/// Keys of the deterministic accounts.
+pub struct DevAccounts {
+    pub(super) utxo: Vec<XfrKeyPair>,
+    pub(super) evm: Vec<SecpPair>,
+    pub(super) validators: Vec<XfrKeyPair>,
+}
+
+impl DevAccounts {
+    /// Derive all the keys from `DEV_MNEMONIC`.
+    pub fn new() -> Result<Self> {
+        let xfr_kp = |account, i| {
+            wallet::restore_keypair_from_mnemonic_bip44(
+                DEV_MNEMONIC,
+                "en",
+                &BipPath::new(FRA_COIN, account, 0, i),
+            )
+            .c(d!())
+        };
+
+        let utxo = (0..ACCOUNT_NUM)
+            .map(|i| xfr_kp(0, i))
+            .collect::<Result<Vec<_>>>()?;
+        let validators = (0..VALIDATOR_NUM)
+            .map(|i| xfr_kp(1, i))
+            .collect::<Result<Vec<_>>>()?;
+        let evm = (0..ACCOUNT_NUM)
+            .map(|i| {
+                SecpPair::from_phrase_with_path(
+                    DEV_MNEMONIC,
+                    None,
+                    &format!("m/44'/60'/0'/0/{}", i),
+                )
+                .map(|(kp, _)| kp)
+                .c(d!())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(DevAccounts {
+            utxo,
+            evm,
+            validators,
+        })
+    }
+
+    pub(super) fn root(&self) -> &XfrKeyPair {
+        &self.utxo[0]
+    }
+
+    /// Print the addresses and keys of the accounts.
+    pub fn print(&self) {
+        println!("\n>>> mnemonic: {}", DEV_MNEMONIC);
+
+        println!("\n>>> UTXO accounts, {} FRA units each:", ACCOUNT_AMOUNT);
+        self.utxo.iter().enumerate().for_each(|(i, kp)| {
+            println!(
+                "[{}] {}\n    {}",
+                i,
+                wallet::public_key_to_bech32(kp.get_pk_ref()),
+                pnk!(serde_json::to_string(kp))
+            );
+        });
+
+        println!("\n>>> EVM accounts, {} FRA units each:", EVM_AMOUNT);
+        self.evm.iter().enumerate().for_each(|(i, kp)| {
+            println!(
+                "[{}] {:?}\n    private key: 0x{}",
+                i,
+                kp.address(),
+                hex::encode(kp.seed())
+            );
+        });
+
+        println!("\n>>> validators:");
+        self.validators.iter().enumerate().for_each(|(i, kp)| {
+            println!(
+                "[{}] {}",
+                i,
+                td_pubkey_to_td_addr(&td_pubkey(kp.get_pk_ref()))
+            );
+        });
+    }
+}
+
+/// Define and issue FRA, set the validators and fund all the accounts,
+/// every step is delivered in its own block.
+pub fn init(chain: &mut DevChain, accounts: &DevAccounts) -> Result<()> {
+    println!(">>> define and issue FRA...");
+    deliver(chain, vec![fra_gen_initial_tx(accounts.root())]).c(d!())?;
+
+    println!(">>> set the validators and fund the accounts...");
+    let validators = accounts
+        .validators
+        .iter()
+        .map(|kp| {
+            Validator::new(
+                td_pubkey(kp.get_pk_ref()),
+                STAKING_VALIDATOR_MIN_POWER,
+                kp.get_pk(),
+                [1, 100],
+                StakerMemo::default(),
+                ValidatorKind::Initor,
+            )
+            .c(d!())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let targets = accounts
+        .utxo
+        .iter()
+        .skip(1)
+        .map(|kp| (kp.get_pk(), ACCOUNT_AMOUNT + EVM_AMOUNT + TX_FEE_MIN))
+        .chain(
+            accounts
+                .validators
+                .iter()
+                .map(|kp| (kp.get_pk(), SELF_DELEGATION_AMOUNT + TX_FEE_MIN)),
+        )
+        .collect();
+    let mut builder = new_tx_builder(chain);
+    builder
+        .add_operation(gen_transfer_op(chain, accounts.root(), targets).c(d!())?)
+        .add_operation_update_validator(&[], 1, validators)
+        .c(d!())?;
+    deliver(chain, vec![builder.take_transaction()]).c(d!())?;
+
+    println!(">>> propose self-delegations and deposit to the EVM accounts...");
+    let mut txs = vec![];
+    for kp in accounts.validators.iter() {
+        let mut builder = new_tx_builder(chain);
+        builder
+            .add_operation(
+                gen_transfer_op(
+                    chain,
+                    kp,
+                    vec![(*BLACK_HOLE_PUBKEY_STAKING, SELF_DELEGATION_AMOUNT)],
+                )
+                .c(d!())?,
+            )
+            .add_operation_delegation(
+                kp,
+                td_pubkey_to_td_addr(&td_pubkey(kp.get_pk_ref())),
+            );
+        txs.push(builder.take_transaction());
+    }
+    for (kp, evm_kp) in accounts.utxo.iter().zip(accounts.evm.iter()) {
+        let mut builder = new_tx_builder(chain);
+        builder
+            .add_operation(
+                gen_transfer_op(
+                    chain,
+                    kp,
+                    vec![(*BLACK_HOLE_PUBKEY_STAKING, EVM_AMOUNT)],
+                )
+                .c(d!())?,
+            )
+            .add_operation_convert_account(kp, MultiSigner::Ethereum(evm_kp.address()))
+            .c(d!())?;
+        txs.push(builder.take_transaction());
+    }
+    deliver(chain, txs).c(d!())?;
+
+    Ok(())
+}
+
+// The tendermint pubkey of a dev validator, just reuse its `XfrPublicKey`,
+// they are both ed25519 keys.
+pub(super) fn td_pubkey(pk: &XfrPublicKey) -> Vec<u8> {
+    pk.zei_to_bytes()
+}
+
+// Produce a block with the transactions, all of them must be accepted.
+fn deliver(chain: &mut DevChain, txs: Vec<Transaction>) -> Result<()> {
+    let txs = txs
+        .iter()
+        .map(|tx| serde_json::to_vec(tx).c(d!()))
+        .collect::<Result<Vec<_>>>()?;
+
+    for (i, resp) in chain.produce_block(&txs).into_iter().enumerate() {
+        if 0 != resp.code {
+            return Err(eg!(format!(
+                "genesis transaction {} of block {} is rejected: {}",
+                i, chain.height, resp.log
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn new_tx_builder(chain: &DevChain) -> TransactionBuilder {
+    let seq_id = chain
+        .app
+        .la
+        .read()
+        .get_committed_state()
+        .read()
+        .get_state_commitment()
+        .1;
+    TransactionBuilder::from_seq_id(seq_id)
+}
+
+// Transfer FRA from the committed utxos of `owner_kp`, with the fee.
+fn gen_transfer_op(
+    chain: &DevChain,
+    owner_kp: &XfrKeyPair,
+    targets: Vec<(XfrPublicKey, u64)>,
+) -> Result<Operation> {
+    let utxos = chain
+        .app
+        .la
+        .read()
+        .get_committed_state()
+        .read()
+        .get_owned_utxos(owner_kp.get_pk_ref())
+        .c(d!())?
+        .into_iter()
+        .collect();
+    let targets = targets
+        .into_iter()
+        .map(|(pk, am)| TransferTarget {
+            pk,
+            am,
+            confidential_am: false,
+            confidential_ty: false,
+            multisig: None,
+        })
+        .collect();
+
+    gen_transfer_op_with_utxos(
+        owner_kp,
+        utxos,
+        CoinSelection::default(),
+        targets,
+        None,
+        None,
+        true,
+    )
+    .c(d!())
+}
@@ -0,0 +1,249 @@
+//!
+//! # A local single-process devnet
+//!
+//! Blocks are produced in-process instead of by tendermint:
+//! a new block is produced as soon as transactions arrive,
+//! and the tendermint rpc is replaced by a minimal stand-in,
+//! see `rpc`, so that the submission server and the web3 service
+//! can forward transactions as usual.
+//!
+
+mod genesis;
+mod rpc;
+
+#[cfg(test)]
+mod test;
+
+use crate::abci::server::ABCISubmissionServer;
+use abci::{
+    Application, Header, LastCommitInfo, RequestBeginBlock, RequestCommit,
+    RequestDeliverTx, RequestEndBlock, RequestInfo, RequestInitChain, ResponseDeliverTx,
+    Validator as AbciValidator, VoteInfo,
+};
+use cryptohash::sha256;
+use ledger::staking::{relax_validator_rules, td_pubkey_to_td_addr_bytes};
+use parking_lot::Mutex;
+use protobuf::{well_known_types::Timestamp, RepeatedField};
+use ruc::*;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        mpsc::{channel, Receiver, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The chain id of the devnet.
+pub const DEV_CHAIN_ID: &str = "findora-dev";
+
+/// The in-process chain, a stand-in of the consensus of tendermint.
+pub struct DevChain {
+    app: ABCISubmissionServer,
+    // tendermint address => vote power
+    validators: BTreeMap<Vec<u8>, i64>,
+    height: i64,
+    last_block_hash: Vec<u8>,
+    last_app_hash: Vec<u8>,
+    last_block_time: Timestamp,
+}
+
+impl DevChain {
+    fn new(mut app: ABCISubmissionServer) -> Self {
+        // a single validator is enough for the stand-in of the consensus
+        relax_validator_rules();
+
+        let info = app.info(&RequestInfo::new());
+
+        if 0 == info.last_block_height {
+            let mut req = RequestInitChain::new();
+            req.set_chain_id(DEV_CHAIN_ID.to_owned());
+            req.set_time(now());
+            app.init_chain(&req);
+        }
+
+        // the validators known by tendermint are not persisted,
+        // restore them from the staking module after a restart
+        let validators = app
+            .la
+            .read()
+            .get_committed_state()
+            .read()
+            .get_staking()
+            .validator_get_current()
+            .map(|vd| {
+                vd.body
+                    .values()
+                    .filter(|v| 0 < v.td_power)
+                    .map(|v| (v.td_addr.clone(), v.td_power as i64))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        DevChain {
+            app,
+            validators,
+            height: info.last_block_height,
+            last_block_hash: vec![],
+            last_app_hash: info.last_block_app_hash,
+            last_block_time: now(),
+        }
+    }
+
+    /// Produce and commit a new block with the given transactions,
+    /// return the results of them.
+    fn produce_block(&mut self, txs: &[Vec<u8>]) -> Vec<ResponseDeliverTx> {
+        let h = 1 + self.height;
+        let time = now();
+
+        let mut header = Header::new();
+        header.set_chain_id(DEV_CHAIN_ID.to_owned());
+        header.set_height(h);
+        header.set_time(time.clone());
+        if let Some(proposer) = self.validators.keys().next() {
+            header.set_proposer_address(proposer.clone());
+        }
+
+        // all the validators are online
+        let votes = self
+            .validators
+            .iter()
+            .map(|(addr, power)| {
+                let mut v = AbciValidator::new();
+                v.set_address(addr.clone());
+                v.set_power(*power);
+                let mut vote = VoteInfo::new();
+                vote.set_validator(v);
+                vote.set_signed_last_block(true);
+                vote
+            })
+            .collect();
+        let mut lci = LastCommitInfo::new();
+        lci.set_votes(RepeatedField::from_vec(votes));
+
+        let block_hash = {
+            let mut data = h.to_be_bytes().to_vec();
+            data.extend_from_slice(&self.last_app_hash);
+            txs.iter()
+                .for_each(|tx| data.extend_from_slice(sha256::hash(tx).as_ref()));
+            sha256::hash(&data).as_ref().to_vec()
+        };
+
+        let mut req = RequestBeginBlock::new();
+        req.set_hash(block_hash.clone());
+        req.set_header(header);
+        req.set_last_commit_info(lci);
+        self.app.begin_block(&req);
+
+        let res = txs
+            .iter()
+            .map(|tx| {
+                let mut req = RequestDeliverTx::new();
+                req.set_tx(tx.clone());
+                self.app.deliver_tx(&req)
+            })
+            .collect();
+
+        let mut req = RequestEndBlock::new();
+        req.set_height(h);
+        let resp = self.app.end_block(&req);
+        for v in resp.validator_updates.iter() {
+            if let Some(pk) = v.pub_key.as_ref() {
+                let addr = td_pubkey_to_td_addr_bytes(pk.get_data());
+                if 0 < v.power {
+                    self.validators.insert(addr, v.power);
+                } else {
+                    self.validators.remove(&addr);
+                }
+            }
+        }
+
+        let resp = self.app.commit(&RequestCommit::new());
+
+        self.height = h;
+        self.last_block_hash = block_hash;
+        self.last_app_hash = resp.data;
+        self.last_block_time = time;
+
+        res
+    }
+}
+
+/// Run the devnet until the process exits,
+/// `rpc_addr` is where the tendermint rpc stand-in listens,
+/// empty blocks are produced every `block_interval` seconds if it is set.
+pub fn run(
+    app: ABCISubmissionServer,
+    rpc_addr: String,
+    block_interval: Option<u64>,
+) -> Result<()> {
+    let mut chain = DevChain::new(app);
+
+    let accounts = genesis::DevAccounts::new().c(d!())?;
+    if 0 == chain.height {
+        genesis::init(&mut chain, &accounts).c(d!())?;
+    }
+    accounts.print();
+
+    let chain = Arc::new(Mutex::new(chain));
+    let (sender, receiver) = channel();
+
+    let hdr = Arc::clone(&chain);
+    thread::spawn(move || {
+        pnk!(rpc::start_rpc_server(hdr, sender, &rpc_addr));
+    });
+
+    println!(
+        "\n>>> devnet started at height {}, blocks are produced {}",
+        chain.lock().height,
+        block_interval.map_or_else(
+            || "when transactions arrive".to_owned(),
+            |itv| format!("when transactions arrive, or every {} seconds", itv)
+        )
+    );
+
+    produce_blocks(&chain, receiver, block_interval);
+
+    Ok(())
+}
+
+// Collect the pending transactions and produce a block,
+// as soon as the first transaction arrives or the interval expires.
+fn produce_blocks(
+    chain: &Mutex<DevChain>,
+    receiver: Receiver<Vec<u8>>,
+    block_interval: Option<u64>,
+) {
+    loop {
+        let first = if let Some(itv) = block_interval {
+            match receiver.recv_timeout(Duration::from_secs(itv)) {
+                Ok(tx) => Some(tx),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        } else {
+            match receiver.recv() {
+                Ok(tx) => Some(tx),
+                Err(_) => return,
+            }
+        };
+
+        let txs = first
+            .into_iter()
+            .chain(receiver.try_iter())
+            .collect::<Vec<_>>();
+
+        chain.lock().produce_block(&txs);
+    }
+}
+
+fn now() -> Timestamp {
+    let d = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut ts = Timestamp::new();
+    ts.set_seconds(d.as_secs() as i64);
+    ts.set_nanos(d.subsec_nanos() as i32);
+    ts
+}
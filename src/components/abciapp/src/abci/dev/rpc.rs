@@ -0,0 +1,265 @@
+//!
+//! # A minimal stand-in of the tendermint rpc
+//!
+//! Only what the findora components and wallets use is served:
+//! - `GET /status`
+//! - JSON-RPC over `POST /`: `status`, `broadcast_tx_sync`,
+//! `broadcast_tx_async` and `abci_query`
+//!
+
+use super::{DevChain, DEV_CHAIN_ID};
+use abci::{Application, RequestCheckTx, RequestQuery};
+use actix_cors::Cors;
+use actix_web::{middleware, web, App, HttpResponse, HttpServer};
+use chrono::{SecondsFormat, TimeZone, Utc};
+use cryptohash::sha256;
+use log::info;
+use parking_lot::Mutex;
+use ruc::*;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::{mpsc::Sender, Arc};
+
+// JSON-RPC 2.0 error codes
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Clone)]
+struct RpcState {
+    chain: Arc<Mutex<DevChain>>,
+    // transactions waiting for the next block
+    mempool: Arc<Mutex<Sender<Vec<u8>>>>,
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Serve the rpc on `addr`, accepted transactions are sent to `mempool`.
+pub fn start_rpc_server(
+    chain: Arc<Mutex<DevChain>>,
+    mempool: Sender<Vec<u8>>,
+    addr: &str,
+) -> Result<()> {
+    let _ = actix_rt::System::new("findora dev rpc");
+
+    let state = RpcState {
+        chain,
+        mempool: Arc::new(Mutex::new(mempool)),
+    };
+
+    HttpServer::new(move || {
+        App::new()
+            .wrap(middleware::Logger::default())
+            .wrap(Cors::permissive().supports_credentials())
+            .data(state.clone())
+            .route("/status", web::get().to(get_status))
+            .route("/", web::post().to(json_rpc))
+    })
+    .bind(addr)
+    .c(d!())?
+    .run();
+
+    info!("Dev rpc server started");
+
+    Ok(())
+}
+
+async fn get_status(state: web::Data<RpcState>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "jsonrpc": "2.0",
+        "id": -1,
+        "result": status(&state.chain.lock()),
+    }))
+}
+
+async fn json_rpc(
+    state: web::Data<RpcState>,
+    req: web::Json<RpcRequest>,
+) -> HttpResponse {
+    let req = req.into_inner();
+
+    let res = match req.method.as_str() {
+        "status" => Ok(status(&state.chain.lock())),
+        "broadcast_tx_sync" => broadcast_tx(&state, &req.params, true),
+        "broadcast_tx_async" => broadcast_tx(&state, &req.params, false),
+        "abci_query" => abci_query(&state, &req.params),
+        _ => Err((METHOD_NOT_FOUND, format!("unknown method: {}", req.method))),
+    };
+
+    HttpResponse::Ok().json(match res {
+        Ok(result) => json!({
+            "jsonrpc": "2.0",
+            "id": req.id,
+            "result": result,
+        }),
+        Err((code, data)) => json!({
+            "jsonrpc": "2.0",
+            "id": req.id,
+            "error": {
+                "code": code,
+                "message": "Invalid request",
+                "data": data,
+            },
+        }),
+    })
+}
+
+fn status(chain: &DevChain) -> Value {
+    let (addr, power) = chain
+        .validators
+        .iter()
+        .next()
+        .map(|(addr, power)| (hex::encode_upper(addr), *power))
+        .unwrap_or_default();
+    let block_time = Utc
+        .timestamp_opt(
+            chain.last_block_time.get_seconds(),
+            chain.last_block_time.get_nanos() as u32,
+        )
+        .single()
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Nanos, true))
+        .unwrap_or_default();
+
+    json!({
+        "node_info": {
+            "protocol_version": {
+                "p2p": "8",
+                "block": "11",
+                "app": "0",
+            },
+            "id": "",
+            "listen_addr": "",
+            "network": DEV_CHAIN_ID,
+            "version": "dev",
+            "channels": "",
+            "moniker": "findora-dev",
+            "other": {
+                "tx_index": "off",
+                "rpc_address": "",
+            },
+        },
+        "sync_info": {
+            "latest_block_hash": hex::encode_upper(&chain.last_block_hash),
+            "latest_app_hash": hex::encode_upper(&chain.last_app_hash),
+            "latest_block_height": chain.height.to_string(),
+            "latest_block_time": block_time,
+            "earliest_block_hash": "",
+            "earliest_app_hash": "",
+            "earliest_block_height": "1",
+            "earliest_block_time": "",
+            "catching_up": false,
+        },
+        "validator_info": {
+            "address": addr,
+            "pub_key": {
+                "type": "tendermint/PubKeyEd25519",
+                "value": "",
+            },
+            "voting_power": power.to_string(),
+        },
+    })
+}
+
+// Run `check_tx` like the mempool of tendermint,
+// the transaction is included in the next block if it passes,
+// in the async mode the result of `check_tx` is not returned.
+fn broadcast_tx(
+    state: &RpcState,
+    params: &Value,
+    sync: bool,
+) -> std::result::Result<Value, (i64, String)> {
+    let tx = params
+        .get("tx")
+        .and_then(|tx| tx.as_str())
+        .and_then(|tx| {
+            base64::decode(tx)
+                .or_else(|_| base64::decode_config(tx, base64::URL_SAFE))
+                .ok()
+        })
+        .ok_or((INVALID_PARAMS, "missing or invalid 'tx'".to_owned()))?;
+    let hash = hex::encode_upper(sha256::hash(&tx));
+
+    let mut req = RequestCheckTx::new();
+    req.set_tx(tx.clone());
+    let resp = state.chain.lock().app.check_tx(&req);
+
+    if 0 == resp.code {
+        state
+            .mempool
+            .lock()
+            .send(tx)
+            .map_err(|e| (INTERNAL_ERROR, e.to_string()))?;
+    }
+
+    Ok(if sync {
+        json!({
+            "code": resp.code,
+            "data": base64::encode(&resp.data),
+            "log": resp.log,
+            "codespace": resp.codespace,
+            "hash": hash,
+        })
+    } else {
+        json!({
+            "code": 0,
+            "data": "",
+            "log": "",
+            "codespace": "",
+            "hash": hash,
+        })
+    })
+}
+
+fn abci_query(
+    state: &RpcState,
+    params: &Value,
+) -> std::result::Result<Value, (i64, String)> {
+    let mut req = RequestQuery::new();
+    if let Some(path) = params.get("path").and_then(|p| p.as_str()) {
+        req.set_path(path.to_owned());
+    }
+    if let Some(data) = params.get("data").and_then(|d| d.as_str()) {
+        req.set_data(
+            hex::decode(data.trim_start_matches("0x"))
+                .map_err(|e| (INVALID_PARAMS, e.to_string()))?,
+        );
+    }
+    if let Some(h) = params.get("height") {
+        let h = match h {
+            Value::String(s) => s.parse::<i64>().ok(),
+            v => v.as_i64(),
+        };
+        req.set_height(h.unwrap_or(0));
+    }
+    req.set_prove(
+        params
+            .get("prove")
+            .and_then(|p| p.as_bool())
+            .unwrap_or(false),
+    );
+
+    let mut chain = state.chain.lock();
+    let resp = chain.app.query(&req);
+    let height = alt!(0 == resp.height, chain.height, resp.height);
+
+    Ok(json!({
+        "response": {
+            "code": resp.code,
+            "log": resp.log,
+            "info": resp.info,
+            "index": resp.index.to_string(),
+            "key": base64::encode(&resp.key),
+            "value": base64::encode(&resp.value),
+            "proof": null,
+            "height": height.to_string(),
+            "codespace": resp.codespace,
+        }
+    }))
+}
@@ -0,0 +1,133 @@
+#![allow(missing_docs)]
+
+use super::{
+    genesis::{self, *},
+    DevChain,
+};
+use crate::abci::server::ABCISubmissionServer;
+use fp_traits::base::BaseProvider;
+use fp_types::crypto::{Address, MultiSigner};
+use globutils::{fresh_tmp_dir, wallet};
+use ledger::{
+    data_model::TX_FEE_MIN,
+    staking::{td_pubkey_to_td_addr_bytes, FRA_PRE_ISSUE_AMOUNT},
+};
+use ruc::*;
+use std::collections::BTreeSet;
+use zei::xfr::sig::XfrPublicKey;
+
+#[test]
+fn dev_accounts_are_deterministic() {
+    let accounts = pnk!(DevAccounts::new());
+    let again = pnk!(DevAccounts::new());
+
+    let pks = |a: &DevAccounts| {
+        a.utxo
+            .iter()
+            .chain(a.validators.iter())
+            .map(|kp| kp.get_pk())
+            .collect::<Vec<_>>()
+    };
+    let evm_addrs =
+        |a: &DevAccounts| a.evm.iter().map(|kp| kp.address()).collect::<Vec<_>>();
+
+    assert_eq!(pks(&accounts), pks(&again));
+    assert_eq!(evm_addrs(&accounts), evm_addrs(&again));
+
+    assert_eq!(ACCOUNT_NUM as usize, accounts.utxo.len());
+    assert_eq!(ACCOUNT_NUM as usize, accounts.evm.len());
+    assert_eq!(VALIDATOR_NUM as usize, accounts.validators.len());
+
+    // no key is derived twice
+    assert_eq!(
+        (ACCOUNT_NUM + VALIDATOR_NUM) as usize,
+        pks(&accounts).into_iter().collect::<BTreeSet<_>>().len()
+    );
+    assert_eq!(
+        ACCOUNT_NUM as usize,
+        evm_addrs(&accounts)
+            .into_iter()
+            .collect::<BTreeSet<_>>()
+            .len()
+    );
+
+    // the root is the default account of the mnemonic, eg. the one of `fn setup -O`
+    assert_eq!(
+        pnk!(wallet::restore_keypair_from_mnemonic_default(DEV_MNEMONIC)).get_pk(),
+        accounts.root().get_pk()
+    );
+}
+
+#[test]
+fn dev_genesis() {
+    let basedir = fresh_tmp_dir();
+    let app = pnk!(ABCISubmissionServer::new(
+        Some(pnk!(basedir.to_str())),
+        String::new()
+    ));
+    let mut chain = DevChain::new(app);
+    assert_eq!(0, chain.height);
+
+    let accounts = pnk!(DevAccounts::new());
+    pnk!(genesis::init(&mut chain, &accounts));
+    assert_eq!(3, chain.height);
+
+    let balance = |chain: &DevChain, pk: &XfrPublicKey| {
+        pnk!(chain
+            .app
+            .la
+            .read()
+            .get_committed_state()
+            .read()
+            .get_owned_utxos(pk))
+        .values()
+        .map(|(utxo, _)| pnk!(utxo.0.record.amount.get_amount()))
+        .sum::<u64>()
+    };
+
+    // the root pays all the funds, and its own deposit to the EVM side
+    let funded = (ACCOUNT_NUM as u64 - 1) * (ACCOUNT_AMOUNT + EVM_AMOUNT + TX_FEE_MIN)
+        + VALIDATOR_NUM as u64 * (SELF_DELEGATION_AMOUNT + TX_FEE_MIN)
+        + TX_FEE_MIN;
+    assert_eq!(
+        FRA_PRE_ISSUE_AMOUNT - funded - EVM_AMOUNT - TX_FEE_MIN,
+        balance(&chain, accounts.root().get_pk_ref())
+    );
+    for kp in accounts.utxo.iter().skip(1) {
+        assert_eq!(ACCOUNT_AMOUNT, balance(&chain, kp.get_pk_ref()));
+    }
+
+    {
+        let la = chain.app.la.read();
+        let state = la.get_committed_state().read();
+        let staking = state.get_staking();
+        let vd = pnk!(staking.validator_get_current());
+        assert_eq!(VALIDATOR_NUM as usize, vd.body.len());
+        for kp in accounts.validators.iter() {
+            let v = pnk!(vd.body.get(kp.get_pk_ref()));
+            assert_eq!(td_pubkey(kp.get_pk_ref()), v.td_pubkey);
+            assert_eq!(
+                SELF_DELEGATION_AMOUNT,
+                pnk!(staking.delegation_get(kp.get_pk_ref())).amount()
+            );
+        }
+    }
+
+    // the EVM accounts are funded by the UTXO accounts
+    {
+        let evm = chain.app.account_base_app.read();
+        for kp in accounts.evm.iter() {
+            let who = Address::from(MultiSigner::Ethereum(kp.address()));
+            assert_eq!(EVM_AMOUNT as u128, pnk!(evm.account_of(&who, None)).balance);
+        }
+    }
+
+    // the validators join the consensus at the next update of the validator set,
+    // which happens every 4 blocks
+    chain.produce_block(&[]);
+    assert_eq!(VALIDATOR_NUM as usize, chain.validators.len());
+    for kp in accounts.validators.iter() {
+        let addr = td_pubkey_to_td_addr_bytes(&td_pubkey(kp.get_pk_ref()));
+        assert!(chain.validators.contains_key(&addr));
+    }
+}
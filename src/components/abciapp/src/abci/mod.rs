@@ -6,11 +6,13 @@
 #![allow(clippy::needless_borrow)]
 
 mod config;
+mod dev;
 mod server;
 pub mod staking;
 
 use crate::api::{
-    query_server::query_api, submission_server::submission_api::SubmissionApi,
+    metrics, query_server::query_api, submission_server::submission_api::SubmissionApi,
+    throttle::ThrottleConfig,
 };
 use lazy_static::lazy_static;
 use ruc::*;
@@ -48,17 +50,28 @@ pub fn run() -> Result<()> {
 
     let submission_service_hdr = Arc::clone(&app.la);
 
-    if CFG.enable_query_service {
+    if let Some(port) = config.metrics_port {
+        let metrics_host = config.abci_host.clone();
+        thread::spawn(move || {
+            pnk!(metrics::start_metrics_server(&metrics_host, port));
+        });
+    }
+
+    if CFG.enable_query_service || CFG.dev_mode {
         env::set_var("FINDORA_KEEP_STAKING_HIST", "1");
 
+        let throttle = throttle_config().c(d!())?;
+
         let query_service_hdr = submission_service_hdr.read().borrowable_ledger_state();
         pnk!(query_api::service::start_query_server(
             Arc::clone(&query_service_hdr),
+            Some(Arc::clone(&app.account_base_app)),
             &[
                 (&config.abci_host, config.query_port),
                 (&config.abci_host, config.ledger_port)
             ],
             Some(&config.ledger_dir),
+            throttle.clone(),
         ))
         .write()
         .update(&config.ledger_dir);
@@ -70,12 +83,13 @@ pub fn run() -> Result<()> {
                 submission_service_hdr,
                 &submission_host,
                 submission_port,
+                throttle,
             ));
         });
     }
 
     let mut web3_rpc: Box<dyn std::any::Any + Send> = Box::new(());
-    if CFG.enable_eth_api_service {
+    if CFG.enable_eth_api_service || CFG.dev_mode {
         let base_app = app.account_base_app.clone();
         let evm_http = format!("{}:{}", config.abci_host, config.evm_http_port);
         let evm_ws = format!("{}:{}", config.abci_host, config.evm_ws_port);
@@ -92,12 +106,34 @@ pub fn run() -> Result<()> {
         );
     }
 
-    let addr_str = format!("{}:{}", config.abci_host, config.abci_port);
-    let addr = addr_str.parse::<SocketAddr>().c(d!())?;
+    if CFG.dev_mode {
+        let rpc_addr = format!("{}:{}", config.tendermint_host, config.tendermint_port);
+        dev::run(app, rpc_addr, CFG.dev_block_interval).c(d!())?;
+    } else {
+        let addr_str = format!("{}:{}", config.abci_host, config.abci_port);
+        let addr = addr_str.parse::<SocketAddr>().c(d!())?;
 
-    abci::run(addr, app);
+        abci::run(addr, app);
+    }
 
     drop(web3_rpc);
 
     Ok(())
 }
+
+// limits of the http services, see `api::throttle`
+fn throttle_config() -> Result<ThrottleConfig> {
+    let mut cfg = ThrottleConfig {
+        ip_rate: CFG.api_rate_limit,
+        max_concurrency: CFG.api_max_concurrency,
+        trust_proxy: CFG.api_trust_proxy,
+        ..Default::default()
+    };
+    if let Some(path) = CFG.api_keys.as_ref() {
+        cfg.load_api_keys(path).c(d!())?;
+    }
+    if let Some(limits) = CFG.api_body_limits.as_ref() {
+        cfg.parse_body_limits(limits).c(d!())?;
+    }
+    Ok(cfg)
+}
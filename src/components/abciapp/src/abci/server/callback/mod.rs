@@ -8,13 +8,19 @@ use crate::{
     abci::{
         config::global_cfg::CFG, server::ABCISubmissionServer, staking, IN_SAFE_ITV,
     },
-    api::{query_server::BLOCK_CREATED, submission_server::convert_tx},
+    api::{
+        metrics,
+        query_server::{BLOCK_CREATED, CHAN_BLOCK_META},
+        submission_server::{
+            check_transaction, convert_tx, RejectionCode, TxnRejection, TxnStatus,
+        },
+    },
 };
 use abci::{
-    Application, RequestBeginBlock, RequestCheckTx, RequestCommit, RequestDeliverTx,
-    RequestEndBlock, RequestInfo, RequestInitChain, RequestQuery, ResponseBeginBlock,
-    ResponseCheckTx, ResponseCommit, ResponseDeliverTx, ResponseEndBlock, ResponseInfo,
-    ResponseInitChain, ResponseQuery,
+    Application, CheckTxType, RequestBeginBlock, RequestCheckTx, RequestCommit,
+    RequestDeliverTx, RequestEndBlock, RequestInfo, RequestInitChain, RequestQuery,
+    ResponseBeginBlock, ResponseCheckTx, ResponseCommit, ResponseDeliverTx,
+    ResponseEndBlock, ResponseInfo, ResponseInitChain, ResponseQuery,
 };
 use fp_storage::hash::{Sha256, StorageHasher};
 use fp_traits::base::BaseProvider;
@@ -98,7 +104,34 @@ pub fn init_chain(
 
 /// any new tx will trigger this callback before it can enter the mem-pool of tendermint
 pub fn check_tx(s: &mut ABCISubmissionServer, req: &RequestCheckTx) -> ResponseCheckTx {
-    s.account_base_app.write().check_tx(req)
+    if let Some(tx) = convert_tx(req.get_tx()) {
+        let mut resp = ResponseCheckTx::new();
+        // only the stateless checks are done here,
+        // the others will be done when it is delivered,
+        // so the result of a recheck is always the same
+        if CheckTxType::Recheck == req.get_field_type() {
+            return resp;
+        }
+        let res = if is_coinbase_tx(&tx)
+            || !tx.is_basic_valid(TENDERMINT_BLOCK_HEIGHT.load(Ordering::Relaxed))
+        {
+            Err(basic_rejection())
+        } else {
+            let ledger = s.la.read().borrowable_ledger_state();
+            let ledger = ledger.read();
+            check_transaction(&tx, &ledger)
+        };
+        if let Err(rejection) = res {
+            s.la.write()
+                .reject_checked_transaction(&tx, rejection.clone());
+            metrics::observe_rejected("check_tx", &rejection);
+            resp.code = rejection.code as u32;
+            resp.log = pnk!(serde_json::to_string(&rejection));
+        }
+        resp
+    } else {
+        s.account_base_app.write().check_tx(req)
+    }
 }
 
 pub fn begin_block(
@@ -155,21 +188,51 @@ pub fn deliver_tx(
                 }
             }
 
-            if s.la.write().cache_transaction(tx.clone()).is_ok() {
-                if is_convert_tx(&tx)
-                    && s.account_base_app.write().deliver_findora_tx(&tx).is_err()
-                {
+            let mut la = s.la.write();
+            let resp = match la.cache_transaction(tx.clone()) {
+                Ok(_) => {
+                    drop(la);
+                    if is_convert_tx(&tx)
+                        && s.account_base_app.write().deliver_findora_tx(&tx).is_err()
+                    {
+                        resp.code = 1;
+                        resp.log = String::from("Failed to deliver transaction!");
+                    }
+                    resp
+                }
+                Err(handle) => {
+                    // NOTE: `code` is a part of the consensus data,
+                    // keep it unchanged, the details are put in the `log`
                     resp.code = 1;
-                    resp.log = String::from("Failed to deliver transaction!");
+                    resp.log = if let Some(TxnStatus::Rejected(rejection)) =
+                        la.get_txn_status(&handle)
+                    {
+                        metrics::observe_rejected("deliver_tx", &rejection);
+                        pnk!(serde_json::to_string(&rejection))
+                    } else {
+                        String::from("Failed to deliver transaction!")
+                    };
+                    resp
                 }
-                return resp;
+            };
+            if 0 == resp.code {
+                metrics::observe_staking_ops(&tx);
             }
+            metrics::observe_delivered("utxo", 0 == resp.code);
+            return resp;
         }
+        let rejection = basic_rejection();
+        metrics::observe_rejected("deliver_tx", &rejection);
+        s.la.write().reject_transaction(&tx, rejection.clone());
         resp.code = 1;
-        resp.log = String::from("Failed to deliver transaction!");
+        resp.log = pnk!(serde_json::to_string(&rejection));
+        metrics::observe_delivered("utxo", false);
         resp
     } else {
-        s.account_base_app.write().deliver_tx(req)
+        let resp = s.account_base_app.write().deliver_tx(req);
+        metrics::observe_evm_gas(resp.gas_used);
+        metrics::observe_delivered("evm", 0 == resp.code);
+        resp
     }
 }
 
@@ -193,16 +256,29 @@ pub fn end_block(
             staking::system_mint_pay(&*laa, &mut *s.account_base_app.write())
         {
             drop(laa);
-            // this unwrap should be safe
-            la.cache_transaction(tx).unwrap();
+            // a rejected coinbase transaction must not halt the chain,
+            // its payments will be retried in the next block
+            ruc::info_omit!(la.cache_transaction(tx));
         }
     }
 
     if !la.all_commited() && la.block_txn_count() != 0 {
+        // used by the history queries of query server
+        if *KEEP_HIST {
+            let idx = la.get_committed_state().read().blocks.len();
+            CHAN_BLOCK_META
+                .0
+                .lock()
+                .send((idx, header.height as u64, header.get_time().seconds))
+                .unwrap();
+        }
+        metrics::BLOCK_TXS.observe(la.block_txn_count() as f64);
         pnk!(la.end_block());
         HAS_ACTUAL_TXS.swap(true, Ordering::Relaxed);
     }
 
+    la.prune_txn_status();
+
     if let Ok(Some(vs)) = ruc::info!(staking::get_validators(
         la.get_committed_state().read().get_staking().deref(),
         begin_block_req.last_commit_info.as_ref()
@@ -210,18 +286,32 @@ pub fn end_block(
         resp.set_validator_updates(RepeatedField::from_vec(vs));
     }
 
-    staking::system_ops(
-        &mut *la.get_committed_state().write(),
-        &header,
-        begin_block_req.last_commit_info.as_ref(),
-        &begin_block_req.byzantine_validators.as_slice(),
-    );
+    {
+        let mut state = la.get_committed_state().write();
+        staking::system_ops(
+            &mut *state,
+            &header,
+            begin_block_req.last_commit_info.as_ref(),
+            &begin_block_req.byzantine_validators.as_slice(),
+        );
+        // the staking state of this block is final now
+        metrics::update_staking(state.get_staking());
+    }
 
     let _ = s.account_base_app.write().end_block(req);
 
     resp
 }
 
+// the rejection of transactions which fail to pass `is_basic_valid`
+fn basic_rejection() -> TxnRejection {
+    TxnRejection::new(
+        RejectionCode::InvalidTxn,
+        None,
+        "invalid coinbase transaction, or failed to pass the basic checks".to_owned(),
+    )
+}
+
 pub fn commit(s: &mut ABCISubmissionServer, req: &RequestCommit) -> ResponseCommit {
     let la = s.la.write();
     let mut state = la.get_committed_state().write();
@@ -229,6 +319,8 @@ pub fn commit(s: &mut ABCISubmissionServer, req: &RequestCommit) -> ResponseComm
     // will change `struct LedgerStatus`
     state.set_tendermint_commit(TENDERMINT_BLOCK_HEIGHT.load(Ordering::Relaxed) as u64);
 
+    metrics::BLOCK_HEIGHT.set(TENDERMINT_BLOCK_HEIGHT.load(Ordering::Relaxed));
+
     // snapshot them finally
     let path = format!("{}/{}", &CFG.ledger_dir, &state.get_status().snapshot_file);
     pnk!(serde_json::to_vec(&state.get_status())
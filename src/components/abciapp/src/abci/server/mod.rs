@@ -5,7 +5,7 @@
 use crate::abci::config::global_cfg::CFG;
 use crate::{
     abci::server::callback::TENDERMINT_BLOCK_HEIGHT,
-    api::submission_server::SubmissionServer,
+    api::{metrics, submission_server::SubmissionServer},
 };
 use abci::{
     RequestBeginBlock, RequestCheckTx, RequestCommit, RequestDeliverTx, RequestEndBlock,
@@ -14,7 +14,7 @@ use abci::{
     ResponseInitChain, ResponseQuery,
 };
 use baseapp::BaseApp as AccountBaseAPP;
-use ledger::store::LedgerState;
+use ledger::store::{policy::DenyListPolicy, LedgerState};
 use parking_lot::RwLock;
 use rand_chacha::ChaChaRng;
 use rand_core::SeedableRng;
@@ -42,10 +42,12 @@ impl ABCISubmissionServer {
         basedir: Option<&str>,
         tendermint_reply: String,
     ) -> Result<ABCISubmissionServer> {
-        let ledger_state = match basedir {
+        let mut ledger_state = match basedir {
             None => LedgerState::tmp_ledger(),
             Some(basedir) => pnk!(LedgerState::load_or_init(basedir)),
         };
+        register_txn_policies(&mut ledger_state).c(d!())?;
+
         let tendermint_height = ledger_state.get_staking().cur_height();
         TENDERMINT_BLOCK_HEIGHT.swap(tendermint_height as i64, Ordering::Relaxed);
 
@@ -65,15 +67,18 @@ impl ABCISubmissionServer {
         };
 
         let prng = rand_chacha::ChaChaRng::from_entropy();
+        let mut submission_server = SubmissionServer::new_no_auto_commit(
+            prng,
+            Arc::new(RwLock::new(ledger_state)),
+            TendermintForward { tendermint_reply },
+        )
+        .c(d!())?;
+        if let Some(ttl) = CFG.txn_status_ttl {
+            submission_server.set_txn_status_ttl(ttl);
+        }
+
         Ok(ABCISubmissionServer {
-            la: Arc::new(RwLock::new(
-                SubmissionServer::new_no_auto_commit(
-                    prng,
-                    Arc::new(RwLock::new(ledger_state)),
-                    TendermintForward { tendermint_reply },
-                )
-                .c(d!())?,
-            )),
+            la: Arc::new(RwLock::new(submission_server)),
             account_base_app: Arc::new(RwLock::new(account_base_app)),
         })
     }
@@ -92,7 +97,7 @@ impl abci::Application for ABCISubmissionServer {
 
     #[inline(always)]
     fn check_tx(&mut self, req: &RequestCheckTx) -> ResponseCheckTx {
-        callback::check_tx(self, req)
+        metrics::observe_abci("check_tx", || callback::check_tx(self, req))
     }
 
     #[inline(always)]
@@ -102,21 +107,32 @@ impl abci::Application for ABCISubmissionServer {
 
     #[inline(always)]
     fn begin_block(&mut self, req: &RequestBeginBlock) -> ResponseBeginBlock {
-        callback::begin_block(self, req)
+        metrics::observe_abci("begin_block", || callback::begin_block(self, req))
     }
 
     #[inline(always)]
     fn deliver_tx(&mut self, req: &RequestDeliverTx) -> ResponseDeliverTx {
-        callback::deliver_tx(self, req)
+        metrics::observe_abci("deliver_tx", || callback::deliver_tx(self, req))
     }
 
     #[inline(always)]
     fn end_block(&mut self, req: &RequestEndBlock) -> ResponseEndBlock {
-        callback::end_block(self, req)
+        metrics::observe_abci("end_block", || callback::end_block(self, req))
     }
 
     #[inline(always)]
     fn commit(&mut self, req: &RequestCommit) -> ResponseCommit {
-        callback::commit(self, req)
+        metrics::observe_abci("commit", || callback::commit(self, req))
+    }
+}
+
+// Custom transaction policies, configured at node start,
+// all validators must have the same configurations.
+fn register_txn_policies(ledger_state: &mut LedgerState) -> Result<()> {
+    if let Some(path) = CFG.txn_policy_deny_list.as_ref() {
+        let policy = DenyListPolicy::from_file(path).c(d!())?;
+        ledger_state.register_txn_policy(Arc::new(policy)).c(d!())?;
     }
+
+    Ok(())
 }
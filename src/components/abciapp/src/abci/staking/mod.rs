@@ -24,7 +24,8 @@ use ledger::{
             governance::{governance_penalty_tendermint_auto, ByzantineKind},
             mint_fra::{MintEntry, MintFraOps, MintKind},
         },
-        td_addr_to_string, Staking, VALIDATOR_UPDATE_BLOCK_ITV,
+        td_addr_to_string, td_pubkey_to_td_addr_bytes, BlockHeight, Staking,
+        CHAN_V_SIGN_HIST, KEEP_HIST, VALIDATOR_UPDATE_BLOCK_ITV,
     },
     store::LedgerState,
 };
@@ -35,6 +36,7 @@ use std::{
     ops::{Deref, DerefMut},
     sync::atomic::Ordering,
 };
+use zei::xfr::sig::XfrPublicKey;
 
 // The top 50~ candidate validators
 // will become official validators.
@@ -94,9 +96,9 @@ pub fn get_validators(
     // - current entries == last entries
     let cur_entries = last_entries;
 
-    let mut vs = staking
-        .validator_get_current()
-        .c(d!())?
+    let vd = staking.validator_get_current().c(d!())?;
+
+    let mut vs = vd
         .body
         .values()
         .filter(|v| {
@@ -117,7 +119,16 @@ pub fn get_validators(
         .map(|v| (&v.td_pubkey, v.td_power as i64))
         .collect::<Vec<_>>();
 
-    if vs.is_empty() {
+    // the keys replaced by key rotations are removed from tendermint,
+    // their validators have been added back with the new keys above
+    let retired = vd
+        .get_retired_td_pubkeys()
+        .values()
+        .filter(|pk| cur_entries.contains_key(&td_pubkey_to_td_addr_bytes(pk)))
+        .map(|pk| (pk, 0))
+        .collect::<Vec<_>>();
+
+    if vs.is_empty() && retired.is_empty() {
         return Ok(None);
     }
 
@@ -130,6 +141,8 @@ pub fn get_validators(
         alt!(cur_entries.contains_key(k), *power = 0, *power = -1);
     });
 
+    vs.extend(retired);
+
     Ok(Some(
         vs.iter()
             .filter(|(_, power)| -1 < *power)
@@ -188,10 +201,28 @@ pub fn system_ops(
             .flat_map(|info| info.validator.as_ref().map(|v| &v.address))
             .collect::<BTreeSet<_>>();
 
+        // a validator signs with its old key until a key rotation
+        // takes effect in tendermint, so match them by their ids
+        let online_ids = get_validator_ids(la.get_staking().deref(), &online_list);
+
+        if *KEEP_HIST {
+            record_signing_hist(la.get_staking().deref(), header, lci);
+        }
+
+        // the old keys of key rotations have been removed from tendermint
+        // once they disappear from the last commit
+        let active_list = lci
+            .votes
+            .iter()
+            .flat_map(|info| info.validator.as_ref().map(|v| &v.address))
+            .collect::<BTreeSet<_>>();
+        la.get_staking_mut()
+            .validator_clean_retired_td_keys(&active_list);
+
         // mark if a validator is online at last block
         if let Ok(vd) = ruc::info!(la.get_staking_mut().validator_get_current_mut()) {
             vd.body.values_mut().for_each(|v| {
-                if online_list.contains(&v.td_addr) {
+                if online_ids.contains(&v.id) {
                     v.signed_last_block = true;
                     v.signed_cnt += 1;
                 } else {
@@ -203,7 +234,7 @@ pub fn system_ops(
         if online_list.len() != lci.votes.len() {
             if let Ok(pl) = ruc::info!(gen_offline_punish_list(
                 la.get_staking().deref(),
-                &online_list
+                &online_ids
             )) {
                 pl.into_iter().for_each(|v| {
                     let bz = ByzantineInfo {
@@ -220,6 +251,49 @@ pub fn system_ops(
     }
 }
 
+// map the tendermint addresses to the ids of their validators,
+// the old addresses of key rotations are included
+fn get_validator_ids(
+    staking: &Staking,
+    addr_list: &BTreeSet<&Vec<u8>>,
+) -> BTreeSet<XfrPublicKey> {
+    staking
+        .validator_get_current()
+        .map(|vd| {
+            addr_list
+                .iter()
+                .flat_map(|addr| {
+                    vd.get_validator_addr_map().get(&td_addr_to_string(addr))
+                })
+                .copied()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// send the signing status of every validator at the last block
+// to the query server, see `CHAN_V_SIGN_HIST`
+fn record_signing_hist(staking: &Staking, header: &Header, lci: &LastCommitInfo) {
+    let h = header.height.saturating_sub(1) as BlockHeight;
+    if let Some(vd) = staking.validator_get_current() {
+        lci.votes
+            .iter()
+            .flat_map(|info| {
+                info.validator
+                    .as_ref()
+                    .map(|v| (&v.address, info.signed_last_block))
+            })
+            .flat_map(|(addr, signed)| {
+                vd.get_validator_addr_map()
+                    .get(&td_addr_to_string(addr))
+                    .map(|id| (*id, h, signed))
+            })
+            .for_each(|i| {
+                CHAN_V_SIGN_HIST.0.lock().send(i).unwrap();
+            });
+    }
+}
+
 /// Get the actual voted power of last block.
 fn get_last_vote_percent(last_commit_info: &LastCommitInfo) -> [u64; 2] {
     last_commit_info
@@ -270,7 +344,7 @@ fn system_governance(staking: &mut Staking, bz: &ByzantineInfo) -> Result<()> {
     governance_penalty_tendermint_auto(staking, bz.addr, &kind).c(d!())
 }
 
-/// Pay for freed 'Delegations' and 'FraDistributions'.
+/// Pay for freed 'Delegations', 'FraDistributions' and due 'TransferPlans'.
 pub fn system_mint_pay(
     la: &LedgerState,
     account_base_app: &mut AccountBaseApp,
@@ -305,6 +379,17 @@ pub fn system_mint_pay(
                     MintEntry::new(MintKind::Claim, k, None, n, ASSET_TYPE_FRA)
                 }),
         )
+        .chain(staking.transfer_plan_get_due().into_iter().filter_map(
+            |(id, k, n, asset_type)| {
+                let et =
+                    MintEntry::new(MintKind::TransferPlan(id), k, None, n, asset_type);
+                // skip a plan which can not be paid,
+                // or it would fail the whole coinbase transaction
+                ruc::info!(staking.transfer_plan_check_pay(id, &et))
+                    .ok()
+                    .map(|_| et)
+            },
+        ))
         .take(NUM_TO_PAY)
         .collect::<Vec<_>>();
 
@@ -344,7 +429,7 @@ pub fn system_mint_pay(
 /// filtering online lists from staking's validators
 fn gen_offline_punish_list(
     staking: &Staking,
-    online_list: &BTreeSet<&Vec<u8>>,
+    online_ids: &BTreeSet<XfrPublicKey>,
 ) -> Result<Vec<Vec<u8>>> {
     let last_height = TENDERMINT_BLOCK_HEIGHT
         .load(Ordering::Relaxed)
@@ -356,7 +441,7 @@ fn gen_offline_punish_list(
     let mut vs = validators
         .body
         .values()
-        .map(|v| (&v.td_addr, v.td_power))
+        .map(|v| (v, v.td_power))
         .collect::<Vec<_>>();
     vs.sort_by(|a, b| b.1.cmp(&a.1));
     vs.iter_mut().skip(VALIDATOR_LIMIT).for_each(|(_, power)| {
@@ -365,7 +450,7 @@ fn gen_offline_punish_list(
 
     Ok(vs
         .into_iter()
-        .filter(|v| 0 < v.1 && !online_list.contains(&v.0))
-        .map(|(id, _)| id.clone())
+        .filter(|(v, power)| 0 < *power && !online_ids.contains(&v.id))
+        .map(|(v, _)| v.td_addr.clone())
         .collect())
 }
@@ -0,0 +1,311 @@
+//!
+//! # Prometheus metrics
+//!
+//! Metrics of the node, served in the text format on `/metrics`:
+//! - ABCI callbacks: durations, block height, transactions per block
+//! - submission server: delivered, rejected and pending transactions
+//! - query server: indexed blocks
+//! - http services: request latencies, throttled requests
+//! - staking: delivered staking operations, validator powers,
+//!   coinbase balance, global delegation
+//! - EVM: gas used by delivered transactions
+//!
+//! Counters are increased where the events happen, so they are monotonic,
+//! gauges of the staking state are refreshed once the state of a block is final.
+//!
+
+#[cfg(test)]
+mod test;
+
+use crate::api::submission_server::TxnRejection;
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    error, web, App, Error, HttpResponse, HttpServer,
+};
+use futures::future::{ok, Ready};
+use lazy_static::lazy_static;
+use ledger::{
+    data_model::{Operation, Transaction},
+    staking::{td_addr_to_string, Staking},
+};
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, Encoder,
+    Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    TextEncoder,
+};
+use ruc::*;
+use std::{
+    future::Future,
+    pin::Pin,
+    result::Result as StdResult,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+lazy_static! {
+    /// Height of the latest committed block
+    pub static ref BLOCK_HEIGHT: IntGauge = pnk!(register_int_gauge!(
+        "findora_block_height",
+        "height of the latest committed block"
+    ));
+    /// Number of UTXO transactions of every non-empty block
+    pub static ref BLOCK_TXS: Histogram = pnk!(register_histogram!(
+        "findora_block_txs",
+        "number of utxo transactions of every non-empty block",
+        vec![1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0]
+    ));
+    /// Time spent in every ABCI callback
+    pub static ref ABCI_DURATION: HistogramVec = pnk!(register_histogram_vec!(
+        "findora_abci_duration_seconds",
+        "time spent in abci callbacks",
+        &["method"]
+    ));
+    /// Delivered transactions, by kind(`utxo`/`evm`) and result(`ok`/`rejected`)
+    pub static ref DELIVERED_TXS: IntCounterVec = pnk!(register_int_counter_vec!(
+        "findora_deliver_tx_total",
+        "number of delivered transactions",
+        &["kind", "result"]
+    ));
+    /// Rejected UTXO transactions, by stage(`check_tx`/`deliver_tx`)
+    /// and `RejectionCode`
+    pub static ref REJECTED_TXS: IntCounterVec = pnk!(register_int_counter_vec!(
+        "findora_txn_rejected_total",
+        "number of rejected utxo transactions",
+        &["stage", "code"]
+    ));
+    /// Transactions of the block in building
+    pub static ref PENDING_TXS: IntGauge = pnk!(register_int_gauge!(
+        "findora_submission_pending_txs",
+        "number of transactions of the block in building"
+    ));
+    /// Statuses kept by the submission server
+    pub static ref TXN_STATUSES: IntGauge = pnk!(register_int_gauge!(
+        "findora_submission_txn_statuses",
+        "number of transaction statuses kept by the submission server"
+    ));
+    /// Transactions forwarded to tendermint, by result(`ok`/`failed`)
+    pub static ref FORWARDED_TXS: IntCounterVec = pnk!(register_int_counter_vec!(
+        "findora_submission_forwarded_total",
+        "number of transactions forwarded to tendermint",
+        &["result"]
+    ));
+    /// Blocks indexed by the query server
+    pub static ref QUERY_SERVER_BLOCKS: IntGauge = pnk!(register_int_gauge!(
+        "findora_query_server_blocks",
+        "number of blocks indexed by the query server"
+    ));
+    /// Latencies of the http services, by service and response status
+    pub static ref HTTP_DURATION: HistogramVec = pnk!(register_histogram_vec!(
+        "findora_http_request_duration_seconds",
+        "latencies of http requests",
+        &["service", "status"]
+    ));
+    /// Requests checked by the throttle, by service and result
+    pub static ref THROTTLED_REQUESTS: IntCounterVec = pnk!(register_int_counter_vec!(
+        "findora_http_requests_throttled_total",
+        "number of http requests checked by the throttle, by result",
+        &["service", "result"]
+    ));
+    /// Staking operations of the delivered transactions, by operation
+    pub static ref STAKING_OPS: IntCounterVec = pnk!(register_int_counter_vec!(
+        "findora_staking_ops_total",
+        "number of delivered staking operations",
+        &["op"]
+    ));
+    /// Voting power of every current validator, by tendermint address
+    pub static ref VALIDATOR_POWER: IntGaugeVec = pnk!(register_int_gauge_vec!(
+        "findora_validator_power",
+        "voting power of current validators",
+        &["address"]
+    ));
+    /// Whether every current validator signed the last block
+    pub static ref VALIDATOR_SIGNED: IntGaugeVec = pnk!(register_int_gauge_vec!(
+        "findora_validator_signed_last_block",
+        "1 if the validator signed the last block, otherwise 0",
+        &["address"]
+    ));
+    /// FRA balance of the coinbase
+    pub static ref COINBASE_BALANCE: IntGauge = pnk!(register_int_gauge!(
+        "findora_coinbase_balance",
+        "fra balance of the coinbase"
+    ));
+    /// Total amount delegated
+    pub static ref GLOBAL_DELEGATION: IntGauge = pnk!(register_int_gauge!(
+        "findora_global_delegation",
+        "total amount of fra delegated"
+    ));
+    /// Gas used by the delivered EVM transactions
+    pub static ref EVM_GAS_USED: IntCounter = pnk!(register_int_counter!(
+        "findora_evm_gas_used_total",
+        "gas used by the delivered evm transactions"
+    ));
+}
+
+/// Time `f` as the ABCI callback `method`
+#[inline(always)]
+pub fn observe_abci<T>(method: &str, f: impl FnOnce() -> T) -> T {
+    let _timer = ABCI_DURATION.with_label_values(&[method]).start_timer();
+    f()
+}
+
+/// Count a delivered transaction, by kind(`utxo`/`evm`) and result
+#[inline(always)]
+pub fn observe_delivered(kind: &str, ok: bool) {
+    DELIVERED_TXS
+        .with_label_values(&[kind, alt!(ok, "ok", "rejected")])
+        .inc();
+}
+
+/// Count a rejected transaction, by stage and `RejectionCode`
+#[inline(always)]
+pub fn observe_rejected(stage: &str, rejection: &TxnRejection) {
+    REJECTED_TXS
+        .with_label_values(&[stage, &format!("{:?}", rejection.code)])
+        .inc();
+}
+
+/// Count the staking operations of a delivered transaction
+pub fn observe_staking_ops(tx: &Transaction) {
+    for op in tx.body.operations.iter() {
+        let op = match op {
+            Operation::Delegation(_) => "delegation",
+            Operation::UnDelegation(_) => "undelegation",
+            Operation::Claim(_) => "claim",
+            Operation::UpdateStaker(_) => "update_staker",
+            Operation::UpdateValidator(_) => "update_validator",
+            Operation::Governance(_) => "governance",
+            Operation::FraDistribution(_) => "fra_distribution",
+            Operation::TransferPlan(_) => "transfer_plan",
+            Operation::RotateKey(_) => "rotate_key",
+            _ => continue,
+        };
+        STAKING_OPS.with_label_values(&[op]).inc();
+    }
+}
+
+/// Count the gas used by a delivered EVM transaction
+#[inline(always)]
+pub fn observe_evm_gas(gas_used: i64) {
+    if 0 < gas_used {
+        EVM_GAS_USED.inc_by(gas_used as u64);
+    }
+}
+
+/// Refresh the staking gauges, call this once the staking state of a block is final
+pub fn update_staking(staking: &Staking) {
+    COINBASE_BALANCE.set(staking.coinbase_balance() as i64);
+    GLOBAL_DELEGATION.set(staking.get_global_delegation_amount() as i64);
+
+    // validators may be removed, so drop the old series first
+    VALIDATOR_POWER.reset();
+    VALIDATOR_SIGNED.reset();
+    if let Some(vd) = staking.validator_get_current() {
+        for v in vd.body.values() {
+            let addr = td_addr_to_string(&v.td_addr);
+            VALIDATOR_POWER
+                .with_label_values(&[&addr])
+                .set(v.td_power as i64);
+            VALIDATOR_SIGNED
+                .with_label_values(&[&addr])
+                .set(v.signed_last_block as i64);
+        }
+    }
+}
+
+/// All the metrics in the text format
+pub fn render() -> Result<String> {
+    let mut buf = vec![];
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buf)
+        .c(d!())?;
+    String::from_utf8(buf).c(d!())
+}
+
+async fn metrics() -> actix_web::Result<HttpResponse> {
+    let text =
+        render().map_err(|e| error::ErrorInternalServerError(e.generate_log(None)))?;
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(text))
+}
+
+/// Serve the metrics on `http://<host>:<port>/metrics`
+pub fn start_metrics_server(host: &str, port: u16) -> Result<()> {
+    let _ = actix_rt::System::new("findora metrics");
+
+    HttpServer::new(|| App::new().route("/metrics", web::get().to(metrics)))
+        .bind(&format!("{}:{}", host, port))
+        .c(d!())?
+        .run();
+
+    info!("Metrics server started");
+
+    Ok(())
+}
+
+/// A middleware recording latencies of a http service in `HTTP_DURATION`
+#[derive(Clone)]
+pub struct HttpMetrics(pub &'static str);
+
+impl<S, B> Transform<S> for HttpMetrics
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = HttpMetricsMiddleware<S>;
+    type Future = Ready<StdResult<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(HttpMetricsMiddleware {
+            service,
+            name: self.0,
+        })
+    }
+}
+
+#[allow(missing_docs)]
+pub struct HttpMetricsMiddleware<S> {
+    service: S,
+    name: &'static str,
+}
+
+impl<S, B> Service for HttpMetricsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = StdResult<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<StdResult<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let name = self.name;
+        let start = Instant::now();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            let status = match res.as_ref() {
+                Ok(r) => r.status(),
+                Err(e) => e.as_response_error().status_code(),
+            };
+            HTTP_DURATION
+                .with_label_values(&[name, status.as_str()])
+                .observe(start.elapsed().as_secs_f64());
+            res
+        })
+    }
+}
@@ -0,0 +1,104 @@
+#![allow(missing_docs)]
+
+// Counters are global, so only their increments are checked,
+// other tests may increase them at the same time, but never decrease them.
+
+use super::*;
+use crate::api::submission_server::RejectionCode;
+use ledger::staking::ops::fra_distribution::FraDistributionOps;
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use std::collections::BTreeMap;
+use zei::xfr::sig::XfrKeyPair;
+
+// The value of a sample in the rendered text, `0` if it is absent.
+fn sample(name: &str, labels: &[(&str, &str)]) -> u64 {
+    let text = pnk!(render());
+    text.lines()
+        .filter(|l| !l.starts_with('#'))
+        .find_map(|l| {
+            let mut kv = l.rsplitn(2, ' ');
+            let value = kv.next()?;
+            let series = kv.next()?;
+            let mut parts = series.splitn(2, '{');
+            if parts.next()? != name {
+                return None;
+            }
+            let series_labels = parts.next().unwrap_or_default();
+            if labels
+                .iter()
+                .all(|(k, v)| series_labels.contains(&format!("{}=\"{}\"", k, v)))
+            {
+                value.parse::<f64>().ok().map(|v| v as u64)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(0)
+}
+
+#[test]
+fn deliver_and_reject() {
+    let ok = [("kind", "utxo"), ("result", "ok")];
+    let rejected = [("kind", "utxo"), ("result", "rejected")];
+    let by_code = [("stage", "deliver_tx"), ("code", "LedgerCheck")];
+    let (ok_cnt, rejected_cnt, code_cnt) = (
+        sample("findora_deliver_tx_total", &ok),
+        sample("findora_deliver_tx_total", &rejected),
+        sample("findora_txn_rejected_total", &by_code),
+    );
+
+    observe_delivered("utxo", true);
+    observe_delivered("utxo", true);
+    observe_rejected(
+        "deliver_tx",
+        &TxnRejection::new(RejectionCode::LedgerCheck, None, String::new()),
+    );
+    observe_delivered("utxo", false);
+
+    assert!(ok_cnt + 2 <= sample("findora_deliver_tx_total", &ok));
+    assert!(rejected_cnt < sample("findora_deliver_tx_total", &rejected));
+    assert!(code_cnt < sample("findora_txn_rejected_total", &by_code));
+
+    // scraping does not reset the counters
+    let cnt = sample("findora_txn_rejected_total", &by_code);
+    assert!(cnt <= sample("findora_txn_rejected_total", &by_code));
+    assert!(code_cnt < sample("findora_txn_rejected_total", &by_code));
+}
+
+#[test]
+fn staking_and_evm() {
+    let op = [("op", "fra_distribution")];
+    let (op_cnt, gas) = (
+        sample("findora_staking_ops_total", &op),
+        sample("findora_evm_gas_used_total", &[]),
+    );
+
+    let kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+    let mut tx = Transaction::from_seq_id(0);
+    let nonce = tx.body.no_replay_token;
+    tx.add_operation(Operation::FraDistribution(pnk!(FraDistributionOps::new(
+        &[&kp],
+        BTreeMap::new(),
+        nonce,
+    ))));
+    observe_staking_ops(&tx);
+    observe_evm_gas(21000);
+    observe_evm_gas(0);
+
+    assert!(op_cnt < sample("findora_staking_ops_total", &op));
+    assert!(gas + 21000 <= sample("findora_evm_gas_used_total", &[]));
+}
+
+#[test]
+fn throttled_requests() {
+    let labels = [("service", "metrics_test"), ("result", "rejected_rate")];
+    let cnt = sample("findora_http_requests_throttled_total", &labels);
+    THROTTLED_REQUESTS
+        .with_label_values(&["metrics_test", "rejected_rate"])
+        .inc();
+    assert_eq!(
+        cnt + 1,
+        sample("findora_http_requests_throttled_total", &labels)
+    );
+}
@@ -2,8 +2,18 @@
 //! # Services provided by api
 //!
 
+/// Prometheus metrics of the node
+pub mod metrics;
+
+/// OpenAPI documents of the http services
+#[macro_use]
+pub mod openapi;
+
 /// Provide query service for ledgerState
 pub mod query_server;
 
 /// Provide services for operating transactions
 pub mod submission_server;
+
+/// Rate limits, body size limits and concurrency caps of the http services
+pub mod throttle;
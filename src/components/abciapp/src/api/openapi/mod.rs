@@ -0,0 +1,469 @@
+//!
+//! # OpenAPI documents of the http services
+//!
+//! Operations are described by the signatures of their handlers:
+//! - `web::Path<T>`: the path parameters, named by the route template
+//! - `web::Query<T>`: the query parameters, see `ApiParams`
+//! - `web::Json<T>` as an argument: the request body
+//! - `web::Json<T>` or `String` as the output: the response
+//!
+//! and the types are described by `ApiSchema`, so the documents follow
+//! the handlers, a handler using an undescribed type will not compile.
+//!
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+use std::{collections::BTreeMap, future::Future, result::Result as StdResult};
+
+/// Version of the OpenAPI specification
+pub const OPENAPI_VERSION: &str = "3.1.0";
+
+/// The JSON schema of a type
+pub trait ApiSchema {
+    /// Name of the component, the schema is inlined if none
+    fn name() -> Option<String> {
+        None
+    }
+
+    /// The schema, refer to other types with `Registry::schema`
+    fn schema(reg: &mut Registry) -> Value;
+
+    /// Whether the value may be absent in a query
+    fn optional() -> bool {
+        false
+    }
+
+    /// Serialized examples of a named type,
+    /// they must match the schema, see the contract test,
+    /// a named type without examples must be sampled there.
+    fn examples() -> Vec<Value> {
+        vec![]
+    }
+}
+
+/// Serialize the examples of an `ApiSchema`
+pub fn examples<T: Serialize>(values: Vec<T>) -> Vec<Value> {
+    values
+        .iter()
+        .filter_map(|v| serde_json::to_value(v).ok())
+        .collect()
+}
+
+/// The schema of a struct, all the fields are required,
+/// the nullable ones must be present with a `null`.
+pub fn object(fields: Vec<(&str, Value)>) -> Value {
+    let required = fields.iter().map(|(k, _)| json!(k)).collect::<Vec<_>>();
+    let properties = fields
+        .into_iter()
+        .map(|(k, v)| (k.to_owned(), v))
+        .collect::<Map<_, _>>();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// Named schemas, aka. `components/schemas`
+#[derive(Default)]
+pub struct Registry {
+    schemas: BTreeMap<String, Value>,
+    examples: BTreeMap<String, Vec<Value>>,
+}
+
+impl Registry {
+    /// A reference to the schema of `T` if it is named, or the schema itself
+    pub fn schema<T: ApiSchema>(&mut self) -> Value {
+        if let Some(name) = T::name() {
+            if !self.schemas.contains_key(&name) {
+                // a placeholder for recursive types
+                self.schemas.insert(name.clone(), Value::Null);
+                let schema = T::schema(self);
+                self.schemas.insert(name.clone(), schema);
+                self.examples.insert(name.clone(), T::examples());
+            }
+            json!({ "$ref": format!("#/components/schemas/{}", name) })
+        } else {
+            T::schema(self)
+        }
+    }
+}
+
+/// A query parameter
+pub struct Param {
+    name: &'static str,
+    required: bool,
+    schema: Value,
+}
+
+impl Param {
+    #[allow(missing_docs)]
+    pub fn new<T: ApiSchema>(name: &'static str, reg: &mut Registry) -> Self {
+        Param {
+            name,
+            required: !T::optional(),
+            schema: reg.schema::<T>(),
+        }
+    }
+}
+
+/// Parameters of a query string, see `api_params`
+pub trait ApiParams {
+    #[allow(missing_docs)]
+    fn params(reg: &mut Registry) -> Vec<Param>;
+}
+
+/// Describe the query parameters of a struct,
+/// use `Option` for the optional ones, eg.
+/// `api_params!(HistoryQuery { cursor: Option<u64>, limit: Option<usize> })`
+macro_rules! api_params {
+    ($ty: ty { $($name: ident: $fty: ty),* $(,)? }) => {
+        impl $crate::api::openapi::ApiParams for $ty {
+            fn params(
+                reg: &mut $crate::api::openapi::Registry,
+            ) -> Vec<$crate::api::openapi::Param> {
+                vec![$($crate::api::openapi::Param::new::<$fty>(stringify!($name), reg)),*]
+            }
+        }
+    };
+}
+
+/// Describe a struct by its fields, see `object`, eg.
+/// `api_object!(TxnHandle, { txn_sid: TxnSID }, examples: [..])`
+///
+/// All the fields of the struct must be listed, or it will not compile.
+/// Examples are required, use `sampled` instead for the types
+/// which can only be built by a ledger, the contract test samples them.
+macro_rules! api_object {
+    ($ty: ident, { $($field: ident: $fty: ty),* $(,)? }, examples: [$($ex: expr),+ $(,)?]) => {
+        api_object!(@impl $ty, { $($field: $fty),* },
+            $crate::api::openapi::examples::<$ty>(vec![$($ex),+]));
+    };
+    ($ty: ident, { $($field: ident: $fty: ty),* $(,)? }, sampled) => {
+        api_object!(@impl $ty, { $($field: $fty),* }, vec![]);
+    };
+    (@impl $ty: ident, { $($field: ident: $fty: ty),* }, $examples: expr) => {
+        impl $crate::api::openapi::ApiSchema for $ty {
+            fn name() -> Option<String> {
+                Some(stringify!($ty).to_owned())
+            }
+
+            fn schema(reg: &mut $crate::api::openapi::Registry) -> serde_json::Value {
+                // a missing or unknown field fails here
+                #[allow(dead_code)]
+                fn exhaustive(v: &$ty) {
+                    let $ty { $($field: _),* } = v;
+                }
+
+                $crate::api::openapi::object(vec![
+                    $((stringify!($field), reg.schema::<$fty>())),*
+                ])
+            }
+
+            fn examples() -> Vec<serde_json::Value> {
+                $examples
+            }
+        }
+    };
+}
+
+/// Describe types not detailed in the documents,
+/// only their names, json types and descriptions are given, eg.
+/// `api_opaque!(Transaction => ("object", "a transaction"), ..)`,
+/// give examples after the description, or they must be sampled.
+macro_rules! api_opaque {
+    ($($ty: ty => ($kind: expr, $desc: expr $(, [$($ex: expr),+ $(,)?])? $(,)?)),* $(,)?) => {
+        $(
+            impl $crate::api::openapi::ApiSchema for $ty {
+                fn name() -> Option<String> {
+                    Some(stringify!($ty).to_owned())
+                }
+
+                fn schema(_: &mut $crate::api::openapi::Registry) -> serde_json::Value {
+                    serde_json::json!({ "type": $kind, "description": $desc })
+                }
+
+                $(
+                fn examples() -> Vec<serde_json::Value> {
+                    $crate::api::openapi::examples::<$ty>(vec![$($ex),+])
+                }
+                )?
+            }
+        )*
+    };
+}
+
+// after the macros, they are used there
+mod schema;
+
+pub use schema::example_mint_entry;
+#[cfg(test)]
+mod test;
+
+/// A described operation
+#[derive(Default)]
+pub struct Operation {
+    summary: &'static str,
+    params: Vec<Param>,
+    path_schema: Option<Value>,
+    request: Option<Value>,
+    response: Option<(&'static str, Value)>,
+    // check if a query string is accepted by the `web::Query` arguments
+    query_checks: Vec<fn(&str) -> bool>,
+}
+
+/// An argument of a handler
+pub trait ApiArg {
+    #[allow(missing_docs)]
+    fn describe(op: &mut Operation, reg: &mut Registry);
+}
+
+impl<T> ApiArg for web::Data<T> {
+    fn describe(_: &mut Operation, _: &mut Registry) {}
+}
+
+impl ApiArg for HttpRequest {
+    fn describe(_: &mut Operation, _: &mut Registry) {}
+}
+
+impl<T: ApiSchema> ApiArg for web::Path<T> {
+    fn describe(op: &mut Operation, reg: &mut Registry) {
+        op.path_schema = Some(reg.schema::<T>());
+    }
+}
+
+fn check_query<T: DeserializeOwned>(qs: &str) -> bool {
+    web::Query::<T>::from_query(qs).is_ok()
+}
+
+impl<T: ApiParams + DeserializeOwned> ApiArg for web::Query<T> {
+    fn describe(op: &mut Operation, reg: &mut Registry) {
+        op.params.extend(T::params(reg));
+        op.query_checks.push(check_query::<T>);
+    }
+}
+
+impl<T: ApiSchema> ApiArg for web::Json<T> {
+    fn describe(op: &mut Operation, reg: &mut Registry) {
+        op.request = Some(reg.schema::<T>());
+    }
+}
+
+/// The output of a handler
+pub trait ApiResponse {
+    #[allow(missing_docs)]
+    fn describe(op: &mut Operation, reg: &mut Registry);
+}
+
+impl<T: ApiSchema> ApiResponse for web::Json<T> {
+    fn describe(op: &mut Operation, reg: &mut Registry) {
+        op.response = Some(("application/json", reg.schema::<T>()));
+    }
+}
+
+impl ApiResponse for String {
+    fn describe(op: &mut Operation, _: &mut Registry) {
+        op.response = Some(("text/plain", json!({ "type": "string" })));
+    }
+}
+
+// the content is not described
+impl ApiResponse for HttpResponse {
+    fn describe(_: &mut Operation, _: &mut Registry) {}
+}
+
+// errors are described as plain texts
+impl<R: ApiResponse, E> ApiResponse for StdResult<R, E> {
+    fn describe(op: &mut Operation, reg: &mut Registry) {
+        R::describe(op, reg);
+    }
+}
+
+/// A handler of actix-web, described by its signature
+pub trait Handler<Args> {
+    #[allow(missing_docs)]
+    fn describe(op: &mut Operation, reg: &mut Registry);
+}
+
+macro_rules! handler {
+    ($($arg: ident),*) => {
+        impl<F, Fut, $($arg,)*> Handler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Fut,
+            Fut: Future,
+            Fut::Output: ApiResponse,
+            $($arg: ApiArg,)*
+        {
+            fn describe(op: &mut Operation, reg: &mut Registry) {
+                $(<$arg as ApiArg>::describe(op, reg);)*
+                <Fut::Output as ApiResponse>::describe(op, reg);
+            }
+        }
+    };
+}
+
+handler!();
+handler!(A);
+handler!(A, B);
+handler!(A, B, C);
+handler!(A, B, C, D);
+
+/// An OpenAPI document
+pub struct ApiSpec {
+    title: &'static str,
+    // path => method => operation
+    paths: BTreeMap<String, BTreeMap<&'static str, Operation>>,
+    reg: Registry,
+}
+
+impl ApiSpec {
+    #[allow(missing_docs)]
+    pub fn new(title: &'static str) -> Self {
+        ApiSpec {
+            title,
+            paths: BTreeMap::new(),
+            reg: Registry::default(),
+        }
+    }
+
+    /// Describe a GET route with its handler
+    pub fn get<Args, H: Handler<Args>>(
+        &mut self,
+        path: &str,
+        summary: &'static str,
+        _handler: H,
+    ) -> &mut Self {
+        self.add::<Args, H>("get", path, summary)
+    }
+
+    /// Describe a POST route with its handler
+    pub fn post<Args, H: Handler<Args>>(
+        &mut self,
+        path: &str,
+        summary: &'static str,
+        _handler: H,
+    ) -> &mut Self {
+        self.add::<Args, H>("post", path, summary)
+    }
+
+    fn add<Args, H: Handler<Args>>(
+        &mut self,
+        method: &'static str,
+        path: &str,
+        summary: &'static str,
+    ) -> &mut Self {
+        let mut op = Operation {
+            summary,
+            ..Default::default()
+        };
+        H::describe(&mut op, &mut self.reg);
+        self.paths
+            .entry(path.to_owned())
+            .or_insert_with(BTreeMap::new)
+            .insert(method, op);
+        self
+    }
+
+    /// Generate the document
+    pub fn to_json(&self) -> Value {
+        let paths = self
+            .paths
+            .iter()
+            .map(|(path, ops)| {
+                let ops = ops
+                    .iter()
+                    .map(|(method, op)| (method.to_string(), operation_json(path, op)))
+                    .collect::<Map<_, _>>();
+                (path.clone(), Value::Object(ops))
+            })
+            .collect::<Map<_, _>>();
+
+        let schemas = self
+            .reg
+            .schemas
+            .iter()
+            .map(|(name, schema)| {
+                let mut schema = schema.clone();
+                if let (Some(s), Some(examples)) =
+                    (schema.as_object_mut(), self.reg.examples.get(name))
+                {
+                    if !examples.is_empty() {
+                        s.insert("examples".to_owned(), json!(examples));
+                    }
+                }
+                (name.clone(), schema)
+            })
+            .collect::<Map<_, _>>();
+
+        json!({
+            "openapi": OPENAPI_VERSION,
+            "info": {
+                "title": self.title,
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "paths": paths,
+            "components": { "schemas": schemas },
+        })
+    }
+}
+
+// names of the parameters in a route template, eg. `/utxo_sid/{sid}`
+fn path_params(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter(|s| s.starts_with('{') && s.ends_with('}'))
+        .map(|s| &s[1..s.len() - 1])
+}
+
+fn operation_json(path: &str, op: &Operation) -> Value {
+    let mut params = path_params(path)
+        .map(|name| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": op.path_schema.clone().unwrap_or_else(|| json!({"type": "string"})),
+            })
+        })
+        .collect::<Vec<_>>();
+    params.extend(op.params.iter().map(|p| {
+        json!({
+            "name": p.name,
+            "in": "query",
+            "required": p.required,
+            "schema": p.schema,
+        })
+    }));
+
+    let mut res = json!({ "description": "success" });
+    if let Some((content_type, schema)) = op.response.as_ref() {
+        let mut content = Map::new();
+        content.insert(content_type.to_string(), json!({ "schema": schema }));
+        res["content"] = Value::Object(content);
+    }
+
+    let mut ret = json!({
+        "summary": op.summary,
+        "parameters": params,
+        "responses": {
+            "200": res,
+            "400": {
+                "description": "invalid request, or the requested data is not found",
+                "content": { "text/plain": { "schema": { "type": "string" } } },
+            },
+        },
+    });
+    if let Some(schema) = op.request.as_ref() {
+        ret["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": schema } },
+        });
+    }
+    ret
+}
+
+/// Serve a generated document, see `ApiSpec::to_json`
+pub async fn openapi_json(spec: web::Data<Value>) -> web::Json<Value> {
+    web::Json(spec.get_ref().clone())
+}
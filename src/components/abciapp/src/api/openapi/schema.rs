@@ -0,0 +1,412 @@
+//!
+//! # Schemas of the common types
+//!
+
+use super::{ApiSchema, Registry};
+use finutils::api::{
+    DelegationInfo, DelegatorInfo, DelegatorList, SigningReport, Validator,
+    ValidatorDelegation, ValidatorDetail, ValidatorList,
+};
+use globutils::{HashOf, ProofOf};
+use ledger::{
+    data_model::{
+        AssetType, AssetTypeCode, AuthenticatedTransaction, AuthenticatedUtxo,
+        AuthenticatedUtxoRangeStatus, AuthenticatedUtxoStatus, DefineAsset,
+        FinalizedTransaction, OutputPosition, StateCommitmentData, Transaction,
+        TxOutput, TxnSID, TxoSID, UnAuthenticatedUtxo, Utxo, UtxoStatus, ASSET_TYPE_FRA,
+    },
+    staking::{
+        ops::{
+            mint_fra::{MintEntry, MintKind},
+            transfer_plan::{ScheduledTransfer, TransferPlan},
+        },
+        DelegationRwdDetail, StakerMemo,
+    },
+};
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use zei::xfr::{
+    sig::{XfrKeyPair, XfrPublicKey},
+    structs::OwnerMemo,
+};
+
+macro_rules! unsigned {
+    ($($ty: ty),*) => {
+        $(
+            impl ApiSchema for $ty {
+                fn schema(_: &mut Registry) -> Value {
+                    json!({ "type": "integer", "minimum": 0 })
+                }
+            }
+        )*
+    };
+}
+
+unsigned!(u8, u32, u64, u128, usize);
+
+impl ApiSchema for i64 {
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "integer" })
+    }
+}
+
+impl ApiSchema for bool {
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "boolean" })
+    }
+}
+
+impl ApiSchema for String {
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl<'a> ApiSchema for &'a str {
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+// any json value
+impl ApiSchema for Value {
+    fn schema(_: &mut Registry) -> Value {
+        json!({})
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Option<T> {
+    fn schema(reg: &mut Registry) -> Value {
+        json!({ "anyOf": [reg.schema::<T>(), { "type": "null" }] })
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Vec<T> {
+    fn schema(reg: &mut Registry) -> Value {
+        json!({ "type": "array", "items": reg.schema::<T>() })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for HashSet<T> {
+    fn schema(reg: &mut Registry) -> Value {
+        json!({ "type": "array", "items": reg.schema::<T>(), "uniqueItems": true })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for BTreeSet<T> {
+    fn schema(reg: &mut Registry) -> Value {
+        json!({ "type": "array", "items": reg.schema::<T>(), "uniqueItems": true })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for [T; 2] {
+    fn schema(reg: &mut Registry) -> Value {
+        json!({
+            "type": "array",
+            "items": reg.schema::<T>(),
+            "minItems": 2,
+            "maxItems": 2,
+        })
+    }
+}
+
+// keys are serialized as strings by serde_json
+impl<K, V: ApiSchema> ApiSchema for BTreeMap<K, V> {
+    fn schema(reg: &mut Registry) -> Value {
+        json!({ "type": "object", "additionalProperties": reg.schema::<V>() })
+    }
+}
+
+macro_rules! tuple {
+    ($n: expr, $($ty: ident),*) => {
+        impl<$($ty: ApiSchema),*> ApiSchema for ($($ty,)*) {
+            fn schema(reg: &mut Registry) -> Value {
+                json!({
+                    "type": "array",
+                    "prefixItems": [$(reg.schema::<$ty>()),*],
+                    "items": false,
+                    "minItems": $n,
+                    "maxItems": $n,
+                })
+            }
+        }
+    };
+}
+
+tuple!(2, A, B);
+tuple!(3, A, B, C);
+
+impl ApiSchema for TxnSID {
+    fn name() -> Option<String> {
+        Some("TxnSID".to_owned())
+    }
+
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "integer", "minimum": 0, "description": "sid of a transaction" })
+    }
+
+    fn examples() -> Vec<Value> {
+        super::examples(vec![TxnSID(1)])
+    }
+}
+
+impl ApiSchema for TxoSID {
+    fn name() -> Option<String> {
+        Some("TxoSID".to_owned())
+    }
+
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "integer", "minimum": 0, "description": "sid of a txo" })
+    }
+
+    fn examples() -> Vec<Value> {
+        super::examples(vec![TxoSID(1)])
+    }
+}
+
+impl ApiSchema for OutputPosition {
+    fn name() -> Option<String> {
+        Some("OutputPosition".to_owned())
+    }
+
+    fn schema(_: &mut Registry) -> Value {
+        json!({
+            "type": "integer",
+            "minimum": 0,
+            "description": "position of the txo in the outputs of its transaction",
+        })
+    }
+
+    fn examples() -> Vec<Value> {
+        super::examples(vec![OutputPosition(0)])
+    }
+}
+
+impl ApiSchema for UtxoStatus {
+    fn name() -> Option<String> {
+        Some("UtxoStatus".to_owned())
+    }
+
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "string", "enum": ["Spent", "Unspent", "Nonexistent"] })
+    }
+
+    fn examples() -> Vec<Value> {
+        super::examples(vec![
+            UtxoStatus::Spent,
+            UtxoStatus::Unspent,
+            UtxoStatus::Nonexistent,
+        ])
+    }
+}
+
+// sampled by the contract test
+impl<T> ApiSchema for HashOf<T> {
+    fn name() -> Option<String> {
+        Some("HashOf".to_owned())
+    }
+
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "description": "a typed hash" })
+    }
+}
+
+// sampled by the contract test
+impl<T> ApiSchema for ProofOf<T> {
+    fn name() -> Option<String> {
+        Some("ProofOf".to_owned())
+    }
+
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "description": "a typed merkle proof" })
+    }
+}
+
+fn example_pk() -> XfrPublicKey {
+    XfrKeyPair::generate(&mut ChaChaRng::seed_from_u64(0)).get_pk()
+}
+
+/// An example of `MintEntry`, for the types containing it
+pub fn example_mint_entry() -> MintEntry {
+    MintEntry::new(MintKind::Claim, example_pk(), None, 1, ASSET_TYPE_FRA)
+}
+
+// the types without examples are sampled by the contract test
+api_opaque!(
+    Utxo => ("object", "an unspent txo, see `TxOutput`"),
+    StateCommitmentData => ("object", "ledger state commitment data"),
+    OwnerMemo => ("object", "the memo to decrypt a confidential txo"),
+    DefineAsset => ("object", "a `DefineAsset` operation"),
+    TxOutput => ("object", "an output of a transaction"),
+    Transaction => ("object", "a transaction, see `ledger::data_model::Transaction`"),
+    AssetType => ("object", "properties of an asset and its issuance state"),
+    AssetTypeCode => ("object", "code of an asset", [AssetTypeCode {
+        val: ASSET_TYPE_FRA
+    }]),
+    MintEntry => ("object", "a coinbase mint of fra", [example_mint_entry()]),
+    ScheduledTransfer => ("object", "an active transfer plan", [ScheduledTransfer {
+        id: 1,
+        owner: example_pk(),
+        plan: TransferPlan {
+            recipient: example_pk(),
+            asset_type: ASSET_TYPE_FRA,
+            amount: 1,
+            interval: 10,
+            count: 2,
+        },
+        next_height: 10,
+        paid_cnt: 0,
+        cancelled: false,
+    }]),
+);
+
+api_object!(AuthenticatedUtxo, {
+    utxo: Utxo,
+    authenticated_txn: AuthenticatedTransaction,
+    authenticated_spent_status: AuthenticatedUtxoStatus,
+    utxo_location: OutputPosition,
+    state_commitment_data: StateCommitmentData,
+}, sampled);
+
+api_object!(UnAuthenticatedUtxo, {
+    utxo: Utxo,
+    txn: FinalizedTransaction,
+    utxo_location: OutputPosition,
+}, sampled);
+
+api_object!(FinalizedTransaction, {
+    txn: Transaction,
+    tx_id: TxnSID,
+    txo_ids: Vec<TxoSID>,
+    merkle_id: u64,
+}, sampled);
+
+api_object!(AuthenticatedTransaction, {
+    finalized_txn: FinalizedTransaction,
+    txn_inclusion_proof: ProofOf<(TxnSID, Transaction)>,
+    state_commitment_data: StateCommitmentData,
+    state_commitment: HashOf<Option<StateCommitmentData>>,
+}, sampled);
+
+api_object!(AuthenticatedUtxoStatus, {
+    status: UtxoStatus,
+    utxo_sid: TxoSID,
+    state_commitment_data: StateCommitmentData,
+    utxo_map_bytes: Option<Vec<u8>>,
+    state_commitment: HashOf<Option<StateCommitmentData>>,
+}, sampled);
+
+api_object!(AuthenticatedUtxoRangeStatus, {
+    first_sid: TxoSID,
+    count: u64,
+    state_commitment_data: StateCommitmentData,
+    utxo_map_bytes: Option<Vec<u8>>,
+    state_commitment: HashOf<Option<StateCommitmentData>>,
+}, sampled);
+
+api_object!(DelegationRwdDetail, {
+    bond: u64,
+    amount: u64,
+    penalty_amount: u64,
+    return_rate: Option<[u128; 2]>,
+    commission_rate: Option<[u64; 2]>,
+    global_delegation_percent: Option<[u64; 2]>,
+    block_height: u64,
+}, examples: [DelegationRwdDetail {
+    bond: 100,
+    amount: 1,
+    return_rate: Some([1, 10]),
+    ..Default::default()
+}]);
+
+api_object!(StakerMemo, {
+    name: String,
+    desc: String,
+    website: String,
+    logo: String,
+}, examples: [StakerMemo::default()]);
+
+api_object!(ValidatorDetail, {
+    addr: String,
+    kind: String,
+    is_online: bool,
+    voting_power: u64,
+    voting_power_rank: usize,
+    commission_rate: [u64; 2],
+    self_staking: u64,
+    fra_rewards: u64,
+    memo: StakerMemo,
+    start_height: u64,
+    cur_height: u64,
+    block_signed_cnt: u64,
+    block_proposed_cnt: u64,
+    expected_annualization: [u128; 2],
+}, examples: [ValidatorDetail::default()]);
+
+api_object!(SigningReport, {
+    addr: String,
+    from_height: u64,
+    to_height: u64,
+    expected: u64,
+    signed: u64,
+    missed_heights: Vec<u64>,
+}, examples: [SigningReport::default()]);
+
+api_object!(ValidatorDelegation, {
+    return_rate: [u128; 2],
+    self_delegation: u64,
+    delegated: u64,
+}, examples: [ValidatorDelegation::default()]);
+
+api_object!(Validator, {
+    addr: String,
+    power: u64,
+    commission_rate: [u64; 2],
+    accept_delegation: bool,
+    rank: u64,
+    extra: StakerMemo,
+}, examples: [Validator {
+    addr: "A1B2".to_owned(),
+    power: 1,
+    commission_rate: [1, 100],
+    accept_delegation: true,
+    rank: 1,
+    extra: StakerMemo::default(),
+}]);
+
+api_object!(ValidatorList, {
+    threshold: [u128; 2],
+    validator_cnt: u64,
+    cur_height: u64,
+    validators: Vec<Validator>,
+}, examples: [ValidatorList::new(0, vec![])]);
+
+api_object!(DelegatorInfo, {
+    addr: String,
+    amount: u64,
+}, examples: [DelegatorInfo::new("fra1".to_owned(), 1)]);
+
+api_object!(DelegatorList, {
+    delegators: Vec<DelegatorInfo>,
+}, examples: [DelegatorList::new(vec![DelegatorInfo::new("fra1".to_owned(), 1)])]);
+
+api_object!(DelegationInfo, {
+    bond: u64,
+    bond_entries: Vec<(String, u64)>,
+    unbond: u64,
+    rewards: u64,
+    return_rate: [u128; 2],
+    global_delegation: u64,
+    global_staking: u64,
+    start_height: u64,
+    end_height: u64,
+    current_height: u64,
+    delegation_rwd_cnt: u64,
+    proposer_rwd_cnt: u64,
+}, examples: [DelegationInfo::default()]);
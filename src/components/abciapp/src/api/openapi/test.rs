@@ -0,0 +1,326 @@
+//!
+//! The documents must agree with the handlers:
+//! - the examples of every named type match its schema,
+//!   the types without examples are sampled from a ledger
+//! - the sample query strings built from the documented parameters
+//!   are accepted by the `web::Query` arguments of the handlers
+//! - every path parameter is described, every `$ref` is resolvable
+//!
+
+#![allow(missing_docs)]
+
+use super::{ApiSchema, ApiSpec};
+use crate::api::{
+    query_server::query_api::openapi as query_openapi,
+    submission_server::{openapi as submission_openapi, TxnForward},
+};
+use globutils::{HashOf, ProofOf};
+use ledger::{
+    data_model::{
+        AssetType, AssetTypeCode, AuthenticatedTransaction, AuthenticatedUtxo,
+        AuthenticatedUtxoRangeStatus, AuthenticatedUtxoStatus, DefineAsset,
+        FinalizedTransaction, Operation, StateCommitmentData, Transaction, TxOutput,
+        TxoSID, UnAuthenticatedUtxo, Utxo, ASSET_TYPE_FRA,
+    },
+    store::{helpers::apply_transaction, utils::fra_gen_initial_tx, LedgerState},
+};
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use ruc::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use zei::{
+    setup::PublicParams,
+    xfr::{
+        asset_record::{build_blind_asset_record, AssetRecordType},
+        sig::XfrKeyPair,
+        structs::{AssetRecordTemplate, OwnerMemo},
+    },
+};
+
+struct NoForward;
+
+impl AsRef<str> for NoForward {
+    fn as_ref(&self) -> &str {
+        ""
+    }
+}
+
+impl TxnForward for NoForward {
+    fn forward_txn(&self, _: Transaction) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn openapi_query_server() {
+    let samples = pnk!(ledger_samples());
+    pnk!(check_spec(&query_openapi::spec(), &samples));
+}
+
+#[test]
+fn openapi_submission_server() {
+    let samples = pnk!(ledger_samples());
+    pnk!(check_spec(
+        &submission_openapi::spec::<ChaChaRng, NoForward>(),
+        &samples
+    ));
+}
+
+type Samples = BTreeMap<String, Vec<Value>>;
+
+fn add_sample<T: ApiSchema + Serialize>(samples: &mut Samples, v: &T) -> Result<()> {
+    let name = T::name().c(d!())?;
+    let v = serde_json::to_value(v).c(d!())?;
+    samples.entry(name).or_insert_with(Vec::new).push(v);
+    Ok(())
+}
+
+// the values of the types which can only be built by a ledger
+fn ledger_samples() -> Result<Samples> {
+    let mut samples = BTreeMap::new();
+    let mut prng = ChaChaRng::seed_from_u64(0);
+    let kp = XfrKeyPair::generate(&mut prng);
+
+    let mut ledger = LedgerState::tmp_ledger();
+    let tx = fra_gen_initial_tx(&kp);
+    apply_transaction(&mut ledger, tx.clone());
+
+    for op in tx.body.operations.iter() {
+        if let Operation::DefineAsset(d) = op {
+            add_sample::<DefineAsset>(&mut samples, d)?;
+        }
+    }
+    add_sample::<Transaction>(&mut samples, &tx)?;
+
+    let code = AssetTypeCode {
+        val: ASSET_TYPE_FRA,
+    };
+    let asset = ledger.get_asset_type(&code).c(d!())?;
+    add_sample::<AssetType>(&mut samples, &asset)?;
+
+    let utxo = ledger.get_utxo(TxoSID(0)).c(d!())?;
+    add_sample::<AuthenticatedUtxo>(&mut samples, &utxo)?;
+    add_sample::<Utxo>(&mut samples, &utxo.utxo)?;
+    add_sample::<TxOutput>(&mut samples, &utxo.utxo.0)?;
+    add_sample::<StateCommitmentData>(&mut samples, &utxo.state_commitment_data)?;
+    add_sample::<AuthenticatedUtxoStatus>(
+        &mut samples,
+        &utxo.authenticated_spent_status,
+    )?;
+    let txn = &utxo.authenticated_txn;
+    add_sample::<AuthenticatedTransaction>(&mut samples, txn)?;
+    add_sample::<FinalizedTransaction>(&mut samples, &txn.finalized_txn)?;
+    add_sample::<HashOf<Option<StateCommitmentData>>>(
+        &mut samples,
+        &txn.state_commitment,
+    )?;
+    add_sample::<ProofOf<_>>(&mut samples, &txn.txn_inclusion_proof)?;
+
+    let light = ledger.get_utxo_light(TxoSID(0)).c(d!())?;
+    add_sample::<UnAuthenticatedUtxo>(&mut samples, &light)?;
+
+    // a nonexistent txo at the end of the range
+    let range = ledger.get_utxo_range_status(TxoSID(0), 3).c(d!())?;
+    add_sample::<AuthenticatedUtxoRangeStatus>(&mut samples, &range)?;
+
+    let template = AssetRecordTemplate::with_no_asset_tracing(
+        1,
+        ASSET_TYPE_FRA,
+        AssetRecordType::ConfidentialAmount_ConfidentialAssetType,
+        kp.get_pk(),
+    );
+    let (_, _, memo) = build_blind_asset_record(
+        &mut prng,
+        &PublicParams::default().pc_gens,
+        &template,
+        vec![],
+    );
+    add_sample::<OwnerMemo>(&mut samples, memo.as_ref().c(d!())?)?;
+
+    Ok(samples)
+}
+
+fn check_spec(spec: &ApiSpec, samples: &Samples) -> Result<()> {
+    let schemas = &spec.reg.schemas;
+
+    for (name, schema) in schemas.iter() {
+        if schema.is_null() {
+            return Err(eg!(format!("{}: unresolved schema", name)));
+        }
+    }
+
+    for (name, examples) in spec.reg.examples.iter() {
+        let examples = if examples.is_empty() {
+            samples
+                .get(name)
+                .ok_or_else(|| eg!(format!("{}: neither examples nor samples", name)))?
+        } else {
+            examples
+        };
+        for example in examples.iter() {
+            if !matches(schemas, &schemas[name], example) {
+                return Err(eg!(format!("{}: mismatched example {}", name, example)));
+            }
+        }
+    }
+
+    for (path, ops) in spec.paths.iter() {
+        for (method, op) in ops.iter() {
+            let at = format!("{} {}", method, path);
+
+            if super::path_params(path).next().is_some() && op.path_schema.is_none() {
+                return Err(eg!(format!("{}: undescribed path parameters", at)));
+            }
+
+            let all = op
+                .params
+                .iter()
+                .map(|p| sample(schemas, &p.schema).map(|v| format!("{}={}", p.name, v)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| eg!(format!("{}: unsupported parameters", at)))?;
+            let required = op
+                .params
+                .iter()
+                .zip(all.iter())
+                .filter(|(p, _)| p.required)
+                .map(|(_, kv)| kv.clone())
+                .collect::<Vec<_>>();
+            for qs in [all.join("&"), required.join("&")].iter() {
+                if !op.query_checks.iter().all(|check| check(qs)) {
+                    return Err(eg!(format!("{}: rejected query '{}'", at, qs)));
+                }
+            }
+        }
+    }
+
+    let mut refs = vec![];
+    collect_refs(&spec.to_json(), &mut refs);
+    for r in refs.iter() {
+        let name = r.trim_start_matches("#/components/schemas/");
+        if !schemas.contains_key(name) {
+            return Err(eg!(format!("unresolved reference: {}", r)));
+        }
+    }
+
+    Ok(())
+}
+
+fn resolve<'a>(schemas: &'a BTreeMap<String, Value>, schema: &'a Value) -> &'a Value {
+    schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|r| schemas.get(r.trim_start_matches("#/components/schemas/")))
+        .unwrap_or(schema)
+}
+
+// a valid value of a query parameter
+fn sample(schemas: &BTreeMap<String, Value>, schema: &Value) -> Option<String> {
+    let schema = resolve(schemas, schema);
+    if let Some(any) = schema.get("anyOf").and_then(Value::as_array) {
+        return any.iter().find_map(|s| sample(schemas, s));
+    }
+    if let Some(e) = schema.get("enum").and_then(Value::as_array) {
+        return e.first().and_then(Value::as_str).map(|s| s.to_owned());
+    }
+    match schema.get("type").and_then(Value::as_str)? {
+        "integer" => Some("1".to_owned()),
+        "boolean" => Some("true".to_owned()),
+        "string" => Some("x".to_owned()),
+        _ => None,
+    }
+}
+
+// a subset of JSON schema used by the documents
+fn matches(schemas: &BTreeMap<String, Value>, schema: &Value, v: &Value) -> bool {
+    let schema = resolve(schemas, schema);
+    let s = match schema.as_object() {
+        Some(s) => s,
+        None => return schema.as_bool().unwrap_or(false),
+    };
+
+    if let Some(any) = s.get("anyOf").and_then(Value::as_array) {
+        return any.iter().any(|s| matches(schemas, s, v));
+    }
+    if let Some(one) = s.get("oneOf").and_then(Value::as_array) {
+        return 1 == one.iter().filter(|s| matches(schemas, s, v)).count();
+    }
+    if let Some(e) = s.get("enum").and_then(Value::as_array) {
+        if !e.contains(v) {
+            return false;
+        }
+    }
+    if let Some(t) = s.get("type").and_then(Value::as_str) {
+        let ok = match t {
+            "integer" => v.is_u64() || v.is_i64(),
+            "boolean" => v.is_boolean(),
+            "string" => v.is_string(),
+            "array" => v.is_array(),
+            "object" => v.is_object(),
+            "null" => v.is_null(),
+            _ => false,
+        };
+        if !ok {
+            return false;
+        }
+    }
+
+    if let Some(a) = v.as_array() {
+        let len = a.len() as u64;
+        if s.get("minItems").and_then(Value::as_u64).unwrap_or(0) > len
+            || s.get("maxItems")
+                .and_then(Value::as_u64)
+                .unwrap_or(u64::MAX)
+                < len
+        {
+            return false;
+        }
+        let prefix = s.get("prefixItems").and_then(Value::as_array);
+        for (i, x) in a.iter().enumerate() {
+            if let Some(item) = prefix.and_then(|p| p.get(i)).or_else(|| s.get("items"))
+            {
+                if !matches(schemas, item, x) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    if let Some(o) = v.as_object() {
+        if let Some(required) = s.get("required").and_then(Value::as_array) {
+            if required
+                .iter()
+                .any(|k| k.as_str().map(|k| !o.contains_key(k)).unwrap_or(true))
+            {
+                return false;
+            }
+        }
+        let props = s.get("properties").and_then(Value::as_object);
+        for (k, x) in o.iter() {
+            if let Some(p) = props
+                .and_then(|p| p.get(k))
+                .or_else(|| s.get("additionalProperties"))
+            {
+                if !matches(schemas, p, x) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    true
+}
+
+fn collect_refs(v: &Value, refs: &mut Vec<String>) {
+    match v {
+        Value::Object(o) => {
+            if let Some(r) = o.get("$ref").and_then(Value::as_str) {
+                refs.push(r.to_owned());
+            }
+            o.values().for_each(|v| collect_refs(v, refs));
+        }
+        Value::Array(a) => a.iter().for_each(|v| collect_refs(v, refs)),
+        _ => {}
+    }
+}
@@ -6,3 +6,6 @@ pub mod query_api;
 
 /// used to notify `query server` to do updating
 pub use query_api::server::BLOCK_CREATED;
+
+/// used to send heights and timestamps of blocks to `query server`
+pub use query_api::server::CHAN_BLOCK_META;
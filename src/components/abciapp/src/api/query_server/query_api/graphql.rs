@@ -0,0 +1,682 @@
+//!
+//! # GraphQL Interface
+//!
+//! A single endpoint over ledger, staking and EVM data,
+//! hosted by the same http server as the REST routes.
+//!
+//! Queries are limited in depth and complexity,
+//! list fields count as `limit * <complexity of an item>`.
+//!
+
+use super::server::{HistoryQuery, OrderOption, QueryServer};
+use actix_web::{web, HttpResponse};
+use async_graphql::{
+    http::{playground_source, GraphQLPlaygroundConfig},
+    Context, EmptyMutation, EmptySubscription, Error, Json, Object, Result, Schema,
+    SimpleObject,
+};
+use async_graphql_actix_web::{Request, Response};
+use baseapp::BaseApp;
+use fp_evm::BlockId;
+use fp_traits::base::BaseProvider;
+use globutils::wallet;
+use ledger::{
+    data_model::{
+        AssetTypeCode, FinalizedTransaction, TxnSID, TxoSID, UnAuthenticatedUtxo,
+        XfrAddress,
+    },
+    staking::{td_addr_to_string, Delegation as StakingDelegation, Staking},
+    store::LedgerState,
+};
+use parking_lot::RwLock;
+use ruc::*;
+use std::sync::Arc;
+use zei::xfr::structs::{XfrAmount, XfrAssetType};
+
+/// Max depth of a query
+pub const MAX_DEPTH: usize = 8;
+
+/// Max complexity of a query
+pub const MAX_COMPLEXITY: usize = 2000;
+
+/// Max length of a list field
+pub const MAX_LIST_LEN: usize = 100;
+
+// complexity weight of short lists without a limit,
+// eg. operations of a transaction
+const SHORT_LIST_WEIGHT: usize = 10;
+
+#[allow(missing_docs)]
+pub type LedgerSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+// `None` if the EVM is not available on this node
+struct EvmProvider(Option<Arc<RwLock<BaseApp>>>);
+
+/// Build the schema, data are resolved from the query server,
+/// the ledger state it holds, and the `BaseApp` if any.
+pub fn build_schema(
+    qs: Arc<RwLock<QueryServer>>,
+    base_app: Option<Arc<RwLock<BaseApp>>>,
+) -> LedgerSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(qs)
+        .data(EvmProvider(base_app))
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+pub(super) async fn graphql(schema: web::Data<LedgerSchema>, req: Request) -> Response {
+    schema.execute(req.into_inner()).await.into()
+}
+
+pub(super) async fn graphql_playground() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
+}
+
+macro_rules! gql {
+    ($e: expr) => {
+        $e.map_err(|e| Error::new(e.generate_log(None)))
+    };
+}
+
+#[inline(always)]
+fn list_len(limit: usize) -> Result<usize> {
+    if 0 == limit || MAX_LIST_LEN < limit {
+        Err(Error::new(format!(
+            "limit must be in [1, {}]",
+            MAX_LIST_LEN
+        )))
+    } else {
+        Ok(limit)
+    }
+}
+
+fn query_server<'a>(ctx: &'a Context<'_>) -> Result<&'a Arc<RwLock<QueryServer>>> {
+    ctx.data::<Arc<RwLock<QueryServer>>>()
+}
+
+fn with_ledger<T>(ctx: &Context<'_>, f: impl FnOnce(&LedgerState) -> T) -> Result<T> {
+    let qs = query_server(ctx)?.read();
+    let ledger = qs
+        .state
+        .as_ref()
+        .ok_or_else(|| Error::new("ledger is not available"))?
+        .read();
+    Ok(f(&ledger))
+}
+
+fn with_staking<T>(ctx: &Context<'_>, f: impl FnOnce(&Staking) -> T) -> Result<T> {
+    with_ledger(ctx, |l| f(l.get_staking()))
+}
+
+fn with_evm<T>(ctx: &Context<'_>, f: impl FnOnce(&BaseApp) -> T) -> Result<T> {
+    ctx.data::<EvmProvider>()?
+        .0
+        .as_ref()
+        .map(|app| f(&app.read()))
+        .ok_or_else(|| Error::new("EVM is not available on this node"))
+}
+
+fn parse_address(address: &str) -> Result<XfrAddress> {
+    gql!(wallet::public_key_from_base64(address)).map(|key| XfrAddress { key })
+}
+
+fn to_json<T: serde::Serialize>(v: &T) -> Result<Json<serde_json::Value>> {
+    gql!(serde_json::to_value(v).c(d!())).map(Json)
+}
+
+#[allow(missing_docs)]
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A transaction by its sid
+    async fn transaction(&self, ctx: &Context<'_>, sid: u64) -> Result<Option<Txn>> {
+        let qs = query_server(ctx)?.read();
+        Ok(Txn::load(&qs, TxnSID(sid as usize)))
+    }
+
+    /// A transaction by its hash
+    async fn transaction_by_hash(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+    ) -> Result<Option<Txn>> {
+        let qs = query_server(ctx)?.read();
+        Ok(qs
+            .get_transaction_sid(hash.to_uppercase())
+            .and_then(|sid| Txn::load(&qs, sid)))
+    }
+
+    /// Transactions related to a base64 address, ordered by sid,
+    /// the same as the `txn_history` route
+    #[graphql(complexity = "limit * child_complexity")]
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        address: String,
+        cursor: Option<u64>,
+        #[graphql(default = 20)] limit: usize,
+        #[graphql(default)] desc: bool,
+    ) -> Result<TxnPage> {
+        let address = parse_address(&address)?;
+        let q = HistoryQuery {
+            cursor,
+            limit: Some(list_len(limit)?),
+            order: Some(alt!(desc, OrderOption::Desc, OrderOption::Asc)),
+            ..Default::default()
+        };
+
+        let qs = query_server(ctx)?.read();
+        let page = qs.get_txn_history(&address, &q);
+        Ok(TxnPage {
+            items: page
+                .items
+                .into_iter()
+                .filter_map(|i| Txn::load(&qs, i.txn_sid))
+                .collect(),
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// A txo by its sid, spent or unspent
+    async fn utxo(&self, ctx: &Context<'_>, sid: u64) -> Result<Option<Utxo>> {
+        with_ledger(ctx, |l| Utxo::load(l, TxoSID(sid)))
+    }
+
+    /// Unspent txos owned by a base64 address, ordered by sid
+    #[graphql(complexity = "limit * child_complexity")]
+    async fn owned_utxos(
+        &self,
+        ctx: &Context<'_>,
+        address: String,
+        #[graphql(default = 20)] limit: usize,
+        #[graphql(default)] offset: usize,
+    ) -> Result<Vec<Utxo>> {
+        let address = parse_address(&address)?;
+        let limit = list_len(limit)?;
+        with_ledger(ctx, |l| {
+            gql!(l.get_owned_utxos(&address.key)).map(|utxos| {
+                utxos
+                    .keys()
+                    .skip(offset)
+                    .take(limit)
+                    .filter_map(|sid| Utxo::load(l, *sid))
+                    .collect()
+            })
+        })?
+    }
+
+    /// An asset by its base64 code
+    async fn asset(&self, ctx: &Context<'_>, code: String) -> Result<Option<Asset>> {
+        let code = gql!(AssetTypeCode::new_from_base64(&code))?;
+        with_ledger(ctx, |l| {
+            l.get_asset_type(&code)
+                .map(|at| {
+                    Ok(Asset {
+                        code: code.to_base64(),
+                        issuer: wallet::public_key_to_base64(&at.properties.issuer.key),
+                        memo: at.properties.memo.0.clone(),
+                        rules: to_json(&at.properties.asset_rules)?,
+                        issuance_num: l.get_issuance_num(&code),
+                    })
+                })
+                .transpose()
+        })?
+    }
+
+    /// Current validators, ordered by voting power
+    #[graphql(complexity = "limit * child_complexity")]
+    async fn validators(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20)] limit: usize,
+        #[graphql(default)] offset: usize,
+    ) -> Result<Vec<Validator>> {
+        let limit = list_len(limit)?;
+        with_staking(ctx, |staking| {
+            let mut vs = staking
+                .validator_get_current()
+                .map(|vd| vd.body.values().collect::<Vec<_>>())
+                .unwrap_or_default();
+            vs.sort_by(|a, b| b.td_power.cmp(&a.td_power));
+            vs.into_iter()
+                .skip(offset)
+                .take(limit)
+                .map(Validator::from)
+                .collect()
+        })
+    }
+
+    /// A current validator by its tendermint address
+    async fn validator(
+        &self,
+        ctx: &Context<'_>,
+        address: String,
+    ) -> Result<Option<Validator>> {
+        with_staking(ctx, |staking| {
+            let id = staking.validator_td_addr_to_app_pk(&address).ok()?;
+            staking
+                .validator_get_current()
+                .and_then(|vd| vd.body.get(&id))
+                .map(Validator::from)
+        })
+    }
+
+    /// The delegation of a base64 address
+    async fn delegation(
+        &self,
+        ctx: &Context<'_>,
+        address: String,
+    ) -> Result<Option<Delegation>> {
+        let address = parse_address(&address)?;
+        with_staking(ctx, |staking| {
+            staking
+                .delegation_get(&address.key)
+                .map(|d| Delegation::new(staking, d))
+        })
+    }
+
+    /// An EVM block by its number, the latest one if not specified
+    async fn evm_block(
+        &self,
+        ctx: &Context<'_>,
+        number: Option<u64>,
+    ) -> Result<Option<EvmBlock>> {
+        let id = number.map(|n| BlockId::number(n.into()));
+        with_evm(ctx, |app| {
+            app.current_block(id).map(|b| {
+                let number = b.header.number.low_u64();
+                EvmBlock {
+                    number,
+                    hash: app
+                        .block_hash(Some(BlockId::number(number.into())))
+                        .map(|h| format!("{:?}", h)),
+                    parent_hash: format!("{:?}", b.header.parent_hash),
+                    timestamp: b.header.timestamp,
+                    gas_used: b.header.gas_used.to_string(),
+                    gas_limit: b.header.gas_limit.to_string(),
+                    transaction_count: b.transactions.len(),
+                }
+            })
+        })
+    }
+}
+
+/// A page of transactions
+#[derive(SimpleObject)]
+pub struct TxnPage {
+    items: Vec<Txn>,
+    /// none if there are no more transactions
+    next_cursor: Option<u64>,
+}
+
+#[allow(missing_docs)]
+pub struct Txn {
+    sid: TxnSID,
+    hash: Option<String>,
+    height: Option<u64>,
+    timestamp: Option<i64>,
+    txn: FinalizedTransaction,
+}
+
+impl Txn {
+    fn load(qs: &QueryServer, sid: TxnSID) -> Option<Self> {
+        let txn = qs.state.as_ref()?.read().get_transaction_light(sid).ok()?;
+        let (height, timestamp) = qs
+            .get_transaction_height(sid)
+            .map(|(h, ts)| (Some(h), ts))
+            .unwrap_or((None, None));
+
+        Some(Txn {
+            sid,
+            hash: qs.get_transaction_hash(sid),
+            height,
+            timestamp,
+            txn,
+        })
+    }
+}
+
+#[Object(name = "Transaction")]
+impl Txn {
+    async fn sid(&self) -> u64 {
+        self.sid.0 as u64
+    }
+
+    async fn hash(&self) -> Option<&str> {
+        self.hash.as_deref()
+    }
+
+    /// tendermint height
+    async fn height(&self) -> Option<u64> {
+        self.height
+    }
+
+    /// unix timestamp of the block
+    async fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+
+    #[graphql(complexity = "SHORT_LIST_WEIGHT * child_complexity")]
+    async fn operations(&self) -> Result<Vec<Op>> {
+        self.txn
+            .txn
+            .body
+            .operations
+            .iter()
+            .map(|op| {
+                // operations are serialized as `{"<kind>": <detail>}`
+                let v = to_json(op)?.0;
+                let (kind, detail) = v
+                    .as_object()
+                    .and_then(|m| m.iter().next())
+                    .map(|(k, d)| (k.clone(), d.clone()))
+                    .unwrap_or_default();
+                Ok(Op {
+                    kind,
+                    detail: Json(detail),
+                })
+            })
+            .collect()
+    }
+
+    /// txos created by this transaction
+    #[graphql(complexity = "SHORT_LIST_WEIGHT * child_complexity")]
+    async fn outputs(&self, ctx: &Context<'_>) -> Result<Vec<Utxo>> {
+        with_ledger(ctx, |l| {
+            self.txn
+                .txo_ids
+                .iter()
+                .filter_map(|sid| Utxo::load(l, *sid))
+                .collect()
+        })
+    }
+}
+
+/// An operation of a transaction
+#[derive(SimpleObject)]
+pub struct Op {
+    /// eg. `TransferAsset`, `Delegation`
+    kind: String,
+    /// the json representation of the operation body
+    detail: Json<serde_json::Value>,
+}
+
+/// A txo, spent or unspent
+#[derive(SimpleObject)]
+pub struct Utxo {
+    sid: u64,
+    /// base64 address
+    owner: String,
+    /// base64 code, none if confidential
+    asset_type: Option<String>,
+    /// none if confidential
+    amount: Option<u64>,
+    spent: bool,
+    /// the transaction which creates this txo
+    txn_sid: u64,
+}
+
+impl Utxo {
+    fn load(ledger: &LedgerState, sid: TxoSID) -> Option<Self> {
+        let (u, spent): (UnAuthenticatedUtxo, bool) = ledger
+            .get_utxo_light(sid)
+            .map(|u| (u, false))
+            .or_else(|| ledger.get_spent_utxo_light(sid).map(|u| (u, true)))?;
+        let record = &(u.utxo.0).record;
+
+        Some(Utxo {
+            sid: sid.0,
+            owner: wallet::public_key_to_base64(&record.public_key),
+            asset_type: if let XfrAssetType::NonConfidential(ty) = record.asset_type {
+                Some(AssetTypeCode { val: ty }.to_base64())
+            } else {
+                None
+            },
+            amount: if let XfrAmount::NonConfidential(am) = record.amount {
+                Some(am)
+            } else {
+                None
+            },
+            spent,
+            txn_sid: u.txn.tx_id.0 as u64,
+        })
+    }
+}
+
+/// A defined asset
+#[derive(SimpleObject)]
+pub struct Asset {
+    /// base64 code
+    code: String,
+    /// base64 address
+    issuer: String,
+    memo: String,
+    rules: Json<serde_json::Value>,
+    issuance_num: Option<u64>,
+}
+
+/// A validator of current height
+pub struct Validator {
+    address: String,
+    inner: ledger::staking::Validator,
+    power: u64,
+}
+
+impl From<&ledger::staking::Validator> for Validator {
+    fn from(v: &ledger::staking::Validator) -> Self {
+        Validator {
+            address: td_addr_to_string(&v.td_addr),
+            inner: v.clone(),
+            power: v.td_power,
+        }
+    }
+}
+
+#[Object]
+impl Validator {
+    /// tendermint address
+    async fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// base64 address of the staker
+    async fn staker(&self) -> String {
+        wallet::public_key_to_base64(&self.inner.id)
+    }
+
+    async fn power(&self) -> u64 {
+        self.power
+    }
+
+    /// [numerator, denominator]
+    async fn commission_rate(&self) -> Vec<u64> {
+        self.inner.get_commission_rate().to_vec()
+    }
+
+    async fn signed_last_block(&self) -> bool {
+        self.inner.signed_last_block
+    }
+
+    async fn signed_cnt(&self) -> u64 {
+        self.inner.signed_cnt
+    }
+
+    async fn memo(&self) -> Result<Json<serde_json::Value>> {
+        to_json(&self.inner.memo)
+    }
+
+    /// Delegators of this validator, in the order of delegation
+    #[graphql(complexity = "limit * child_complexity")]
+    async fn delegators(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20)] limit: usize,
+        #[graphql(default)] offset: usize,
+    ) -> Result<Vec<Delegator>> {
+        let limit = list_len(limit)?;
+        with_staking(ctx, |staking| {
+            staking
+                .validator_get_delegator_list(
+                    &self.address,
+                    offset,
+                    offset.saturating_add(limit),
+                )
+                .map(|l| {
+                    l.into_iter()
+                        .map(|(pk, am)| Delegator {
+                            address: wallet::public_key_to_base64(pk),
+                            amount: *am,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// A delegator of a validator
+#[derive(SimpleObject)]
+pub struct Delegator {
+    /// base64 address
+    address: String,
+    amount: u64,
+}
+
+/// A delegation
+#[derive(SimpleObject)]
+pub struct Delegation {
+    /// base64 address
+    address: String,
+    /// eg. `Bond`, `Free`, `Paid`
+    state: String,
+    amount: u64,
+    rwd_amount: u64,
+    start_height: u64,
+    end_height: u64,
+    entries: Vec<DelegationEntry>,
+}
+
+impl Delegation {
+    fn new(staking: &Staking, d: &StakingDelegation) -> Self {
+        Delegation {
+            address: wallet::public_key_to_base64(&d.id),
+            state: format!("{:?}", d.state),
+            amount: d.amount(),
+            rwd_amount: d.rwd_amount,
+            start_height: d.start_height(),
+            end_height: d.end_height(),
+            entries: d
+                .entries
+                .iter()
+                .map(|(pk, am)| DelegationEntry {
+                    validator: staking.validator_app_pk_to_td_addr(pk).ok(),
+                    amount: *am,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The amount delegated to a validator
+#[derive(SimpleObject)]
+pub struct DelegationEntry {
+    /// tendermint address, none if the validator has gone
+    validator: Option<String>,
+    amount: u64,
+}
+
+/// An EVM block
+pub struct EvmBlock {
+    number: u64,
+    hash: Option<String>,
+    parent_hash: String,
+    timestamp: u64,
+    gas_used: String,
+    gas_limit: String,
+    transaction_count: usize,
+}
+
+#[Object]
+impl EvmBlock {
+    async fn number(&self) -> u64 {
+        self.number
+    }
+
+    async fn hash(&self) -> Option<&str> {
+        self.hash.as_deref()
+    }
+
+    async fn parent_hash(&self) -> &str {
+        &self.parent_hash
+    }
+
+    async fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    async fn gas_used(&self) -> &str {
+        &self.gas_used
+    }
+
+    async fn gas_limit(&self) -> &str {
+        &self.gas_limit
+    }
+
+    async fn transaction_count(&self) -> usize {
+        self.transaction_count
+    }
+
+    /// Receipts of the transactions, in the order of execution
+    #[graphql(complexity = "limit * child_complexity")]
+    async fn receipts(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20)] limit: usize,
+        #[graphql(default)] offset: usize,
+    ) -> Result<Vec<EvmReceipt>> {
+        let limit = list_len(limit)?;
+        with_evm(ctx, |app| {
+            let id = Some(BlockId::number(self.number.into()));
+            let statuses = app.current_transaction_statuses(id.clone());
+            let receipts = app.current_receipts(id);
+            statuses
+                .zip(receipts)
+                .map(|(s, r)| {
+                    s.into_iter()
+                        .zip(r.into_iter())
+                        .skip(offset)
+                        .take(limit)
+                        .map(|(s, r)| EvmReceipt {
+                            transaction_hash: format!("{:?}", s.transaction_hash),
+                            transaction_index: s.transaction_index,
+                            from: format!("{:?}", s.from),
+                            to: s.to.map(|i| format!("{:?}", i)),
+                            contract_address: s
+                                .contract_address
+                                .map(|i| format!("{:?}", i)),
+                            gas_used: r.used_gas.to_string(),
+                            log_count: r.logs.len(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// The receipt of an EVM transaction
+#[derive(SimpleObject)]
+pub struct EvmReceipt {
+    transaction_hash: String,
+    transaction_index: u32,
+    from: String,
+    to: Option<String>,
+    contract_address: Option<String>,
+    gas_used: String,
+    log_count: usize,
+}
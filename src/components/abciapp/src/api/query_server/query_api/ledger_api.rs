@@ -5,21 +5,26 @@
 use super::server::QueryServer;
 use actix_web::{error, web};
 use finutils::api::{
-    DelegationInfo, DelegatorInfo, DelegatorList, NetworkRoute, Validator,
-    ValidatorDetail, ValidatorList,
+    DelegationInfo, DelegatorInfo, DelegatorList, NetworkRoute, SigningReport,
+    Validator, ValidatorDelegation, ValidatorDetail, ValidatorList, TXN_LIST_LIMIT,
 };
 use globutils::HashOf;
 use ledger::{
     data_model::{
-        AssetType, AssetTypeCode, AuthenticatedUtxo, StateCommitmentData, TxnSID,
+        AssetType, AssetTypeCode, AuthenticatedUtxo, AuthenticatedUtxoRangeStatus,
+        AuthenticatedUtxoStatus, FinalizedTransaction, StateCommitmentData, TxnSID,
         TxoSID, UnAuthenticatedUtxo, Utxo,
     },
-    staking::{DelegationRwdDetail, DelegationState, TendermintAddr, UNBOND_BLOCK_CNT},
+    staking::{
+        ops::transfer_plan::ScheduledTransfer, td_pubkey_to_td_addr,
+        DelegationRwdDetail, DelegationState, TendermintAddr, UNBOND_BLOCK_CNT,
+    },
 };
 use parking_lot::RwLock;
 use ruc::*;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use std::{collections::BTreeMap, mem, sync::Arc};
+use strum::EnumIter;
 use zei::xfr::{sig::XfrPublicKey, structs::OwnerMemo};
 
 // Ping route to check for liveness of API
@@ -72,6 +77,48 @@ pub(super) async fn query_utxo_light(
     }
 }
 
+// query the spent status of a `TxoSID`,
+// return the bitmap proof which can also prove that a txo is spent or nonexistent
+pub(super) async fn query_utxo_status(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    info: web::Path<String>,
+) -> actix_web::Result<web::Json<AuthenticatedUtxoStatus>> {
+    let read = data.read();
+    let read = read.state.as_ref().unwrap().read();
+    if let Ok(txo_sid) = info.parse::<u64>() {
+        if 0 < read.get_block_commit_count() {
+            Ok(web::Json(read.get_utxo_status(TxoSID(txo_sid))))
+        } else {
+            Err(actix_web::error::ErrorNotFound(
+                "No block has been committed.",
+            ))
+        }
+    } else {
+        Err(actix_web::error::ErrorBadRequest(
+            "Invalid txo sid encoding",
+        ))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(super) struct UtxoRangeQueryParams {
+    start: u64,
+    count: u64,
+}
+
+// query the spent status of txos in `[start, start + count)`,
+// return a partial bitmap proof which only contains the blocks covering the range
+pub(super) async fn query_utxo_range_status(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(info): web::Query<UtxoRangeQueryParams>,
+) -> actix_web::Result<web::Json<AuthenticatedUtxoRangeStatus>> {
+    let read = data.read();
+    let read = read.state.as_ref().unwrap().read();
+    read.get_utxo_range_status(TxoSID(info.start), info.count)
+        .map(web::Json)
+        .map_err(|e| error::ErrorBadRequest(e.generate_log(None)))
+}
+
 // query issuance num according to `AssetTypeCode`
 pub(super) async fn query_asset_issuance_num(
     data: web::Data<Arc<RwLock<QueryServer>>>,
@@ -187,6 +234,42 @@ pub(super) async fn query_txn_light(
     }
 }
 
+// query txs according to a list of `TxnSID` separated by ',', lighter and faster version,
+// `null` for the nonexistent ones
+pub(super) async fn query_txns_light(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    info: web::Path<String>,
+) -> actix_web::Result<web::Json<Vec<Option<FinalizedTransaction>>>> {
+    let sid_list = info
+        .as_ref()
+        .split(',')
+        .map(|i| {
+            i.parse::<usize>()
+                .map(TxnSID)
+                .map_err(actix_web::error::ErrorBadRequest)
+        })
+        .collect::<actix_web::Result<Vec<_>, actix_web::error::Error>>()?;
+
+    if sid_list.len() > TXN_LIST_LIMIT || sid_list.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("Invalid Query List"));
+    }
+
+    let read = data.read();
+    let read = read.state.as_ref().unwrap().read();
+
+    Ok(web::Json(
+        sid_list
+            .into_iter()
+            .map(|sid| {
+                read.get_transaction_light(sid).ok().map(|mut txn| {
+                    txn.set_txo_id();
+                    txn
+                })
+            })
+            .collect(),
+    ))
+}
+
 // query global state, return (apphash, block count, apphash and block count signatures)
 #[allow(clippy::type_complexity)]
 pub(super) async fn query_global_state(
@@ -210,6 +293,21 @@ pub(super) async fn query_global_state_version(
     web::Json(hash)
 }
 
+// query the state commitment data according to `block_height`,
+// its hash equals to the global state version of the same height
+pub(super) async fn query_state_commitment_data(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    version: web::Path<u64>,
+) -> actix_web::Result<web::Json<StateCommitmentData>> {
+    let read = data.read();
+    let read = read.state.as_ref().unwrap().read();
+    read.get_state_commitment_data_at_block_height(*version)
+        .map(web::Json)
+        .ok_or_else(|| {
+            actix_web::error::ErrorNotFound("Specified block height does not exist.")
+        })
+}
+
 // Query current validator list,
 // validtors who have not completed self-deletagion will be filtered out.
 #[allow(unused)]
@@ -224,6 +322,13 @@ pub(super) async fn query_validators(
         let validators = validator_data.get_validator_addr_map();
         let validators_list = validators
             .iter()
+            // skip the old addresses of key rotations
+            .filter(|(tendermint_addr, pk)| {
+                validator_data
+                    .get_retired_td_pubkeys()
+                    .get(*pk)
+                    .map_or(true, |k| **tendermint_addr != td_pubkey_to_td_addr(k))
+            })
             .flat_map(|(tendermint_addr, pk)| {
                 validator_data.get_powered_validator_by_id(pk).map(|v| {
                     let rank = if v.td_power == 0 {
@@ -295,13 +400,6 @@ pub(super) struct ValidatorDelegationQueryParams {
     epoch_cnt: u8,
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
-pub(super) struct ValidatorDelegation {
-    return_rate: [u128; 2],
-    self_delegation: u64,
-    delegated: u64,
-}
-
 // get history according to `ValidatorDelegationQueryParams`
 pub(super) async fn get_validator_delegation_history(
     data: web::Data<Arc<RwLock<QueryServer>>>,
@@ -379,6 +477,64 @@ pub(super) async fn get_validator_delegation_history(
     Ok(web::Json(history))
 }
 
+/// Default number of blocks of a signing report
+pub const SIGNING_REPORT_BLOCKS_DEFAULT: u64 = 1000;
+
+/// Max number of blocks of a signing report
+pub const SIGNING_REPORT_BLOCKS_MAX: u64 = 20000;
+
+#[derive(Deserialize, Debug)]
+pub(super) struct SigningQueryParams {
+    address: TendermintAddr,
+    blocks: Option<u64>,
+}
+
+// get the signing report of a validator in the recent `blocks` blocks,
+// the data is kept by nodes with `FINDORA_KEEP_STAKING_HIST` set
+pub(super) async fn get_validator_signing_report(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(info): web::Query<SigningQueryParams>,
+) -> actix_web::Result<web::Json<SigningReport>> {
+    let qs = data.read();
+    let read = qs.state.as_ref().unwrap().read();
+    let staking = read.get_staking();
+
+    let v_id = staking
+        .validator_td_addr_to_app_pk(info.address.as_ref())
+        .c(d!())
+        .map_err(error::ErrorBadRequest)?;
+
+    let blocks = info
+        .blocks
+        .unwrap_or(SIGNING_REPORT_BLOCKS_DEFAULT)
+        .clamp(1, SIGNING_REPORT_BLOCKS_MAX);
+    // the last commit of a block is about its previous block
+    let to_height = staking.cur_height().saturating_sub(1);
+    let from_height = to_height.saturating_sub(blocks - 1).max(1);
+
+    let mut report = SigningReport {
+        addr: info.address,
+        from_height,
+        to_height,
+        ..Default::default()
+    };
+
+    if let Some(hist) = qs.staking_signing_hist.get(&v_id) {
+        (from_height..=to_height)
+            .filter_map(|h| hist.get(&h).map(|signed| (h, signed)))
+            .for_each(|(h, signed)| {
+                report.expected += 1;
+                if signed {
+                    report.signed += 1;
+                } else {
+                    report.missed_heights.push(h);
+                }
+            });
+    }
+
+    Ok(web::Json(report))
+}
+
 #[derive(Deserialize, Debug)]
 pub(super) struct DelegatorQueryParams {
     address: String,
@@ -389,7 +545,7 @@ pub(super) struct DelegatorQueryParams {
 
 #[derive(Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "snake_case")]
-enum OrderOption {
+pub(super) enum OrderOption {
     Desc,
     Asc,
 }
@@ -618,22 +774,64 @@ pub(super) async fn query_owned_utxos(
         .map(|pk| web::Json(pnk!(read.get_owned_utxos(&pk))))
 }
 
+#[derive(Deserialize, Debug)]
+pub(super) struct TransferPlanQueryParams {
+    // base64 public key of the owner or the recipient
+    address: Option<String>,
+}
+
+// query active transfer plans, optionally filtered by the owner or the recipient
+pub(super) async fn query_transfer_plans(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(info): web::Query<TransferPlanQueryParams>,
+) -> actix_web::Result<web::Json<Vec<ScheduledTransfer>>> {
+    let pk = info
+        .address
+        .map(|addr| {
+            globutils::wallet::public_key_from_base64(&addr)
+                .c(d!())
+                .map_err(|e| error::ErrorBadRequest(e.generate_log(None)))
+        })
+        .transpose()?;
+
+    let read = data.read();
+    let read = read.state.as_ref().unwrap().read();
+    let plans = read
+        .get_staking()
+        .transfer_plan_get_all()
+        .values()
+        .filter(|p| {
+            pk.map(|pk| pk == p.owner || pk == p.plan.recipient)
+                .unwrap_or(true)
+        })
+        .cloned()
+        .collect();
+
+    Ok(web::Json(plans))
+}
+
 #[allow(missing_docs)]
+#[derive(EnumIter)]
 pub enum ApiRoutes {
     UtxoSid,
     UtxoSidLight,
     UtxoSidList,
+    UtxoSidStatus,
+    UtxoSidRangeStatus,
     AssetIssuanceNum,
     AssetToken,
     GlobalState,
     TxnSid,
     TxnSidLight,
+    TxnSidLightList,
     GlobalStateVersion,
+    StateCommitmentData,
     OwnedUtxos,
     ValidatorList,
     DelegationInfo,
     DelegatorList,
     ValidatorDetail,
+    TransferPlans,
 }
 
 impl NetworkRoute for ApiRoutes {
@@ -642,17 +840,22 @@ impl NetworkRoute for ApiRoutes {
             ApiRoutes::UtxoSid => "utxo_sid",
             ApiRoutes::UtxoSidLight => "utxo_sid_light",
             ApiRoutes::UtxoSidList => "utxo_sid_list",
+            ApiRoutes::UtxoSidStatus => "utxo_sid_status",
+            ApiRoutes::UtxoSidRangeStatus => "utxo_sid_range_status",
             ApiRoutes::AssetIssuanceNum => "asset_issuance_num",
             ApiRoutes::AssetToken => "asset_token",
             ApiRoutes::GlobalState => "global_state",
             ApiRoutes::TxnSid => "txn_sid",
             ApiRoutes::TxnSidLight => "txn_sid_light",
+            ApiRoutes::TxnSidLightList => "txn_sid_light_list",
             ApiRoutes::GlobalStateVersion => "global_state_version",
+            ApiRoutes::StateCommitmentData => "state_commitment_data",
             ApiRoutes::OwnedUtxos => "owned_utxos",
             ApiRoutes::ValidatorList => "validator_list",
             ApiRoutes::DelegationInfo => "delegation_info",
             ApiRoutes::DelegatorList => "delegator_list",
             ApiRoutes::ValidatorDetail => "validator_detail",
+            ApiRoutes::TransferPlans => "transfer_plans",
         };
         "/".to_owned() + endpoint
     }
@@ -2,12 +2,24 @@
 //! need to transform the data in ledgerState to store
 //!
 
+pub mod graphql;
 mod ledger_api;
+pub mod openapi;
 pub mod server;
 pub mod service;
+pub mod stream;
 
+#[cfg(test)]
+mod test;
+
+use crate::api::{
+    metrics::HttpMetrics,
+    openapi::openapi_json,
+    throttle::{Throttle, ThrottleConfig},
+};
 use actix_cors::Cors;
 use actix_web::{error, middleware, web, App, HttpServer};
+use baseapp::BaseApp;
 use finutils::api::NetworkRoute;
 use ledger::{
     data_model::{
@@ -21,8 +33,15 @@ use log::info;
 use parking_lot::RwLock;
 use ruc::*;
 use serde::{Deserialize, Serialize};
-use server::{QueryServer, TxnIDHash};
-use std::{collections::HashSet, sync::Arc};
+use server::{
+    AssetSupply, CoinbaseHistoryItem, HistoryPage, HistoryQuery, MemoTransferItem,
+    OrderOption, QueryServer, TxnHistoryItem, TxnIDHash,
+};
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::Arc,
+};
+use strum::EnumIter;
 use zei::{
     serialization::ZeiFromToBytes,
     xfr::{sig::XfrPublicKey, structs::OwnerMemo},
@@ -99,6 +118,7 @@ async fn get_owned_utxos(
 
 /// Define interface type
 #[allow(missing_docs)]
+#[derive(EnumIter)]
 pub enum QueryServerRoutes {
     GetAddress,
     GetOwnerMemo,
@@ -109,10 +129,21 @@ pub enum QueryServerRoutes {
     GetIssuedRecordsByCode,
     GetRelatedTxns,
     GetRelatedXfrs,
+    TxnHistory,
+    XfrHistory,
+    ClaimTxnHistory,
+    CoinbaseOperHistory,
+    MemoTransfers,
+    AssetSupply,
+    AssetHolderCount,
+    AssetTopHolders,
     GetAuthencatedTxnIDHash,
     GetTransactionHash,
     GetTransactionSid,
     GetCommits,
+    GraphQL,
+    Stream,
+    OpenApi,
 }
 
 impl NetworkRoute for QueryServerRoutes {
@@ -121,6 +152,14 @@ impl NetworkRoute for QueryServerRoutes {
             QueryServerRoutes::GetAddress => "get_address",
             QueryServerRoutes::GetRelatedTxns => "get_related_txns",
             QueryServerRoutes::GetRelatedXfrs => "get_related_xfrs",
+            QueryServerRoutes::TxnHistory => "txn_history",
+            QueryServerRoutes::XfrHistory => "xfr_history",
+            QueryServerRoutes::ClaimTxnHistory => "claim_txn_history",
+            QueryServerRoutes::CoinbaseOperHistory => "coinbase_oper_history",
+            QueryServerRoutes::MemoTransfers => "memo_transfers",
+            QueryServerRoutes::AssetSupply => "asset_supply",
+            QueryServerRoutes::AssetHolderCount => "asset_holder_count",
+            QueryServerRoutes::AssetTopHolders => "asset_top_holders",
             QueryServerRoutes::GetOwnedUtxos => "get_owned_utxos",
             QueryServerRoutes::GetOwnerMemo => "get_owner_memo",
             QueryServerRoutes::GetOwnerMemoBatch => "get_owner_memo_batch",
@@ -131,6 +170,9 @@ impl NetworkRoute for QueryServerRoutes {
             QueryServerRoutes::GetTransactionHash => "get_transaction_hash",
             QueryServerRoutes::GetTransactionSid => "get_transaction_sid",
             QueryServerRoutes::GetCommits => "get_commits",
+            QueryServerRoutes::GraphQL => "graphql",
+            QueryServerRoutes::Stream => "stream",
+            QueryServerRoutes::OpenApi => "openapi.json",
         };
         "/".to_owned() + endpoint
     }
@@ -253,14 +295,6 @@ pub struct WalletQueryParams {
     order: OrderOption,
 }
 
-#[allow(missing_docs)]
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(rename_all = "snake_case")]
-enum OrderOption {
-    Desc,
-    Asc,
-}
-
 #[allow(missing_docs)]
 #[derive(Debug, Deserialize, Serialize)]
 struct CoinbaseTxnBody {
@@ -364,11 +398,12 @@ async fn get_claim_txns(
     Ok(web::Json(records))
 }
 
-/// Returns the list of transations associated with a given ledger address
+/// Returns the list of transations associated with a given ledger address,
+/// use `txn_history` instead for busy addresses.
 async fn get_related_txns(
     data: web::Data<Arc<RwLock<QueryServer>>>,
     info: web::Path<String>,
-) -> actix_web::Result<web::Json<HashSet<TxnSID>>> {
+) -> actix_web::Result<web::Json<BTreeSet<TxnSID>>> {
     // Convert from base64 representation
     let key: XfrPublicKey = XfrPublicKey::zei_from_bytes(
         &b64dec(&*info)
@@ -382,11 +417,12 @@ async fn get_related_txns(
     Ok(web::Json(records.unwrap_or_default()))
 }
 
-/// Returns the list of transfer transations associated with a given asset
+/// Returns the list of transfer transations associated with a given asset,
+/// use `xfr_history` instead for busy assets.
 async fn get_related_xfrs(
     data: web::Data<Arc<RwLock<QueryServer>>>,
     info: web::Path<String>,
-) -> actix_web::Result<web::Json<HashSet<TxnSID>>> {
+) -> actix_web::Result<web::Json<BTreeSet<TxnSID>>> {
     let server = data.read();
     if let Ok(token_code) = AssetTypeCode::new_from_base64(&*info) {
         if let Some(records) = server.get_related_transfers(&token_code) {
@@ -403,6 +439,173 @@ async fn get_related_xfrs(
     }
 }
 
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct AddressQueryParams {
+    address: String,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct AssetQueryParams {
+    asset: String,
+}
+
+impl AssetQueryParams {
+    fn to_code(&self) -> actix_web::Result<AssetTypeCode> {
+        AssetTypeCode::new_from_base64(&self.asset)
+            .c(d!())
+            .map_err(|e| error::ErrorBadRequest(e.generate_log(None)))
+    }
+}
+
+impl AddressQueryParams {
+    fn to_address(&self) -> actix_web::Result<XfrAddress> {
+        globutils::wallet::public_key_from_base64(&self.address)
+            .c(d!())
+            .map_err(|e| error::ErrorBadRequest(e.generate_log(None)))
+            .map(|key| XfrAddress { key })
+    }
+}
+
+// 400 if the range can not be applied, see `QueryServer::check_history_range`
+fn check_history_range(qs: &QueryServer, q: &HistoryQuery) -> actix_web::Result<()> {
+    qs.check_history_range(q)
+        .c(d!())
+        .map_err(|e| error::ErrorBadRequest(e.generate_log(None)))
+}
+
+/// Paging query of transactions associated with a given ledger address
+async fn get_txn_history(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(addr): web::Query<AddressQueryParams>,
+    web::Query(q): web::Query<HistoryQuery>,
+) -> actix_web::Result<web::Json<HistoryPage<TxnHistoryItem>>> {
+    let address = addr.to_address()?;
+    let qs = data.read();
+    check_history_range(&qs, &q)?;
+    Ok(web::Json(qs.get_txn_history(&address, &q)))
+}
+
+/// Paging query of transfer transactions associated with a given asset
+async fn get_xfr_history(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(asset): web::Query<AssetQueryParams>,
+    web::Query(q): web::Query<HistoryQuery>,
+) -> actix_web::Result<web::Json<HistoryPage<TxnHistoryItem>>> {
+    let code = asset.to_code()?;
+    let qs = data.read();
+    check_history_range(&qs, &q)?;
+    Ok(web::Json(qs.get_xfr_history(&code, &q)))
+}
+
+/// Paging query of claim transactions of a given ledger address
+async fn get_claim_txn_history(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(addr): web::Query<AddressQueryParams>,
+    web::Query(q): web::Query<HistoryQuery>,
+) -> actix_web::Result<web::Json<HistoryPage<TxnHistoryItem>>> {
+    let address = addr.to_address()?;
+    let qs = data.read();
+    check_history_range(&qs, &q)?;
+    Ok(web::Json(qs.get_claim_history(&address, &q)))
+}
+
+/// Paging query of coinbase payments of a given ledger address
+async fn get_coinbase_oper_history(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(addr): web::Query<AddressQueryParams>,
+    web::Query(q): web::Query<HistoryQuery>,
+) -> actix_web::Result<web::Json<HistoryPage<CoinbaseHistoryItem>>> {
+    let address = addr.to_address()?;
+    let qs = data.read();
+    check_history_range(&qs, &q)?;
+    Ok(web::Json(qs.get_coinbase_history(&address, &q)))
+}
+
+/// Memo to look up
+#[derive(Debug, Deserialize)]
+pub struct MemoQueryParams {
+    memo: String,
+    /// match memos starting with `memo`, exact match if none
+    prefix: Option<bool>,
+}
+
+/// Paging query of transactions received by a given ledger address with given memos,
+/// eg. deposits to an exchange
+async fn get_memo_transfers(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(addr): web::Query<AddressQueryParams>,
+    web::Query(m): web::Query<MemoQueryParams>,
+    web::Query(q): web::Query<HistoryQuery>,
+) -> actix_web::Result<web::Json<HistoryPage<MemoTransferItem>>> {
+    let address = addr.to_address()?;
+    let qs = data.read();
+    check_history_range(&qs, &q)?;
+    Ok(web::Json(qs.get_memo_transfers(
+        &address,
+        &m.memo,
+        m.prefix.unwrap_or(false),
+        &q,
+    )))
+}
+
+/// Total, circulating and locked supply of a given asset
+async fn get_asset_supply(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(asset): web::Query<AssetQueryParams>,
+) -> actix_web::Result<web::Json<AssetSupply>> {
+    let code = asset.to_code()?;
+    data.read()
+        .get_asset_supply(&code)
+        .map(web::Json)
+        .ok_or_else(|| error::ErrorNotFound("no records of this asset"))
+}
+
+/// Number of addresses holding a given asset
+async fn get_asset_holder_count(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(asset): web::Query<AssetQueryParams>,
+) -> actix_web::Result<web::Json<u64>> {
+    let code = asset.to_code()?;
+    Ok(web::Json(data.read().get_asset_holder_count(&code)))
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize)]
+pub struct TopHoldersQueryParams {
+    // `HISTORY_PAGE_DEFAULT` if none, at most `TOP_HOLDERS_MAX`
+    limit: Option<usize>,
+}
+
+/// An entry of the rich list
+#[allow(missing_docs)]
+#[derive(Debug, Serialize)]
+pub struct HolderBalance {
+    pub address: String,
+    pub amount: u64,
+}
+
+/// Rich list of a given asset, the pseudo-addresses are excluded
+async fn get_asset_top_holders(
+    data: web::Data<Arc<RwLock<QueryServer>>>,
+    web::Query(asset): web::Query<AssetQueryParams>,
+    web::Query(q): web::Query<TopHoldersQueryParams>,
+) -> actix_web::Result<web::Json<Vec<HolderBalance>>> {
+    let code = asset.to_code()?;
+    let limit = q.limit.unwrap_or(server::HISTORY_PAGE_DEFAULT);
+    Ok(web::Json(
+        data.read()
+            .get_asset_top_holders(&code, limit)
+            .into_iter()
+            .map(|(pk, amount)| HolderBalance {
+                address: globutils::wallet::public_key_to_bech32(&pk),
+                amount,
+            })
+            .collect(),
+    ))
+}
+
 /// Structures exposed to the outside world
 pub struct QueryApi;
 
@@ -410,15 +613,27 @@ impl QueryApi {
     /// create query api
     pub fn create(
         server: Arc<RwLock<QueryServer>>,
+        base_app: Option<Arc<RwLock<BaseApp>>>,
         addrs: &[(&str, u16)],
+        throttle: ThrottleConfig,
     ) -> Result<QueryApi> {
         let _ = actix_rt::System::new("findora API");
 
+        let throttle = Throttle::new("query", throttle);
+
+        let schema = graphql::build_schema(Arc::clone(&server), base_app);
+
+        let spec = openapi::spec().to_json();
+
         let mut hdr = HttpServer::new(move || {
             App::new()
+                .wrap(throttle.clone())
+                .wrap(HttpMetrics("query"))
                 .wrap(middleware::Logger::default())
                 .wrap(Cors::permissive().supports_credentials())
                 .data(Arc::clone(&server))
+                .data(schema.clone())
+                .data(spec.clone())
                 .route("/ping", web::get().to(ping))
                 .route("/version", web::get().to(version))
                 .route(
@@ -453,6 +668,38 @@ impl QueryApi {
                     &QueryServerRoutes::GetRelatedXfrs.with_arg_template("asset_token"),
                     web::get().to(get_related_xfrs),
                 )
+                .route(
+                    &QueryServerRoutes::TxnHistory.route(),
+                    web::get().to(get_txn_history),
+                )
+                .route(
+                    &QueryServerRoutes::XfrHistory.route(),
+                    web::get().to(get_xfr_history),
+                )
+                .route(
+                    &QueryServerRoutes::ClaimTxnHistory.route(),
+                    web::get().to(get_claim_txn_history),
+                )
+                .route(
+                    &QueryServerRoutes::CoinbaseOperHistory.route(),
+                    web::get().to(get_coinbase_oper_history),
+                )
+                .route(
+                    &QueryServerRoutes::MemoTransfers.route(),
+                    web::get().to(get_memo_transfers),
+                )
+                .route(
+                    &QueryServerRoutes::AssetSupply.route(),
+                    web::get().to(get_asset_supply),
+                )
+                .route(
+                    &QueryServerRoutes::AssetHolderCount.route(),
+                    web::get().to(get_asset_holder_count),
+                )
+                .route(
+                    &QueryServerRoutes::AssetTopHolders.route(),
+                    web::get().to(get_asset_top_holders),
+                )
                 .route(
                     &QueryServerRoutes::GetCreatedAssets.with_arg_template("address"),
                     web::get().to(get_created_assets),
@@ -483,6 +730,22 @@ impl QueryApi {
                     &QueryServerRoutes::GetCommits.route(),
                     web::get().to(get_commits),
                 )
+                .route(
+                    &QueryServerRoutes::GraphQL.route(),
+                    web::post().to(graphql::graphql),
+                )
+                .route(
+                    &QueryServerRoutes::GraphQL.route(),
+                    web::get().to(graphql::graphql_playground),
+                )
+                .route(
+                    &QueryServerRoutes::Stream.route(),
+                    web::get().to(stream::stream_events),
+                )
+                .route(
+                    &QueryServerRoutes::OpenApi.route(),
+                    web::get().to(openapi_json),
+                )
                 .route(
                     &ApiRoutes::UtxoSid.with_arg_template("sid"),
                     web::get().to(query_utxo),
@@ -495,6 +758,14 @@ impl QueryApi {
                     &ApiRoutes::UtxoSidList.with_arg_template("sid_list"),
                     web::get().to(query_utxos),
                 )
+                .route(
+                    &ApiRoutes::UtxoSidStatus.with_arg_template("sid"),
+                    web::get().to(query_utxo_status),
+                )
+                .route(
+                    &ApiRoutes::UtxoSidRangeStatus.route(),
+                    web::get().to(query_utxo_range_status),
+                )
                 .route(
                     &ApiRoutes::AssetIssuanceNum.with_arg_template("code"),
                     web::get().to(query_asset_issuance_num),
@@ -515,10 +786,18 @@ impl QueryApi {
                     &ApiRoutes::TxnSidLight.with_arg_template("sid"),
                     web::get().to(query_txn_light),
                 )
+                .route(
+                    &ApiRoutes::TxnSidLightList.with_arg_template("sid_list"),
+                    web::get().to(query_txns_light),
+                )
                 .route(
                     &ApiRoutes::GlobalStateVersion.with_arg_template("version"),
                     web::get().to(query_global_state_version),
                 )
+                .route(
+                    &ApiRoutes::StateCommitmentData.with_arg_template("version"),
+                    web::get().to(query_state_commitment_data),
+                )
                 .route(
                     &ApiRoutes::OwnedUtxos.with_arg_template("owner"),
                     web::get().to(query_owned_utxos),
@@ -535,6 +814,10 @@ impl QueryApi {
                     &ApiRoutes::DelegatorList.with_arg_template("NodeAddress"),
                     web::get().to(query_delegator_list),
                 )
+                .route(
+                    &ApiRoutes::TransferPlans.route(),
+                    web::get().to(query_transfer_plans),
+                )
                 .service(
                     web::resource("/delegator_list")
                         .route(web::get().to(get_delegators_with_params)),
@@ -547,6 +830,10 @@ impl QueryApi {
                     web::resource("/validator_delegation")
                         .route(web::get().to(get_validator_delegation_history)),
                 )
+                .service(
+                    web::resource("/validator_signing")
+                        .route(web::get().to(get_validator_signing_report)),
+                )
                 .route(
                     &ApiRoutes::ValidatorDetail.with_arg_template("NodeAddress"),
                     web::get().to(query_validator_detail),
@@ -0,0 +1,457 @@
+//!
+//! # OpenAPI document of the query server
+//!
+//! Every route of `QueryServerRoutes` and `ApiRoutes` must be described here,
+//! the `match`es will not compile if a new one is not.
+//!
+
+use super::*;
+use crate::api::openapi::{
+    example_mint_entry, ApiArg, ApiResponse, ApiSchema, ApiSpec, Operation, Registry,
+};
+use async_graphql_actix_web::{Request, Response};
+use serde_json::{json, Value};
+use strum::IntoEnumIterator;
+
+impl ApiSchema for server::OrderOption {
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "string", "enum": ["desc", "asc"] })
+    }
+}
+
+impl ApiSchema for ledger_api::OrderOption {
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "string", "enum": ["desc", "asc"] })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for HistoryPage<T> {
+    fn name() -> Option<String> {
+        T::name().map(|n| format!("HistoryPage_{}", n))
+    }
+
+    fn schema(reg: &mut Registry) -> Value {
+        #[allow(dead_code)]
+        fn exhaustive<T>(v: &HistoryPage<T>) {
+            let HistoryPage {
+                items: _,
+                next_cursor: _,
+            } = v;
+        }
+
+        crate::api::openapi::object(vec![
+            ("items", reg.schema::<Vec<T>>()),
+            ("next_cursor", reg.schema::<Option<u64>>()),
+        ])
+    }
+
+    // the items are the examples of `T`
+    fn examples() -> Vec<Value> {
+        vec![
+            json!({ "items": T::examples(), "next_cursor": 1 }),
+            json!({ "items": [], "next_cursor": null }),
+        ]
+    }
+}
+
+api_object!(TxnHistoryItem, {
+    txn_sid: TxnSID,
+    txn_hash: Option<String>,
+    height: Option<u64>,
+    timestamp: Option<i64>,
+}, examples: [TxnHistoryItem {
+    txn_sid: TxnSID(1),
+    txn_hash: Some("a1b2".to_owned()),
+    height: Some(10),
+    timestamp: None,
+}]);
+
+api_object!(CoinbaseHistoryItem, {
+    height: u64,
+    timestamp: Option<i64>,
+    data: MintEntry,
+}, examples: [CoinbaseHistoryItem {
+    height: 10,
+    timestamp: Some(1_600_000_000),
+    data: example_mint_entry(),
+}]);
+
+api_object!(MemoTransferItem, {
+    txn_sid: TxnSID,
+    txn_hash: Option<String>,
+    height: Option<u64>,
+    timestamp: Option<i64>,
+    memos: Vec<String>,
+    txo_sids: Vec<TxoSID>,
+    asset: Option<AssetTypeCode>,
+}, examples: [MemoTransferItem {
+    txn_sid: TxnSID(1),
+    txn_hash: None,
+    height: Some(10),
+    timestamp: Some(1_600_000_000),
+    memos: vec!["deposit-1".to_owned()],
+    txo_sids: vec![TxoSID(2)],
+    asset: None,
+}]);
+
+api_object!(AssetSupply, {
+    total: u64,
+    circulating: u64,
+    locked: u64,
+    reserved: u64,
+    holders: u64,
+}, examples: [AssetSupply::default()]);
+
+api_object!(HolderBalance, {
+    address: String,
+    amount: u64,
+}, examples: [HolderBalance {
+    address: "fra1".to_owned(),
+    amount: 1,
+}]);
+
+api_object!(CoinbaseTxnBody, {
+    height: u64,
+    data: MintEntry,
+}, examples: [CoinbaseTxnBody {
+    height: 10,
+    data: example_mint_entry(),
+}]);
+
+api_object!(CoinbaseOperInfo, {
+    total_count: u64,
+    txs: Vec<CoinbaseTxnBody>,
+}, examples: [CoinbaseOperInfo {
+    total_count: 1,
+    txs: vec![CoinbaseTxnBody {
+        height: 10,
+        data: example_mint_entry(),
+    }],
+}]);
+
+api_params!(AddressQueryParams { address: String });
+api_params!(AssetQueryParams { asset: String });
+api_params!(HistoryQuery {
+    cursor: Option<u64>,
+    limit: Option<usize>,
+    order: Option<server::OrderOption>,
+    from_height: Option<u64>,
+    to_height: Option<u64>,
+    from_time: Option<i64>,
+    to_time: Option<i64>,
+});
+api_params!(MemoQueryParams {
+    memo: String,
+    prefix: Option<bool>,
+});
+api_params!(TopHoldersQueryParams {
+    limit: Option<usize>
+});
+api_params!(WalletQueryParams {
+    address: String,
+    page: usize,
+    per_page: usize,
+    order: server::OrderOption,
+});
+api_params!(UtxoRangeQueryParams {
+    start: u64,
+    count: u64
+});
+api_params!(DelegationRwdQueryParams {
+    address: String,
+    height: u64
+});
+api_params!(ValidatorDelegationQueryParams {
+    address: String,
+    epoch_size: u32,
+    epoch_cnt: u8,
+});
+api_params!(SigningQueryParams {
+    address: String,
+    blocks: Option<u64>,
+});
+api_params!(DelegatorQueryParams {
+    address: String,
+    page: usize,
+    per_page: usize,
+    order: ledger_api::OrderOption,
+});
+api_params!(TransferPlanQueryParams {
+    address: Option<String>
+});
+api_params!(stream::StreamQueryParams {
+    blocks: Option<bool>,
+    address: Option<String>,
+    asset: Option<String>,
+    from_height: Option<u64>,
+});
+
+// a graphql request, in the body of a POST
+impl ApiArg for Request {
+    fn describe(op: &mut Operation, reg: &mut Registry) {
+        <web::Json<Value> as ApiArg>::describe(op, reg);
+    }
+}
+
+impl ApiResponse for Response {
+    fn describe(op: &mut Operation, reg: &mut Registry) {
+        <web::Json<Value> as ApiResponse>::describe(op, reg);
+    }
+}
+
+/// Describe the routes registered in `QueryApi::create`
+pub fn spec() -> ApiSpec {
+    let mut spec = ApiSpec::new("Findora query server");
+
+    spec.get("/ping", "liveness of the server", ping)
+        .get("/version", "git commit and build date", version)
+        .get(
+            "/claim_history",
+            "paging query of claim transactions",
+            get_claim_txns,
+        )
+        .get(
+            "/coinbase_history",
+            "paging query of coinbase operations",
+            get_coinbase_oper_list,
+        )
+        .get(
+            "/delegator_list",
+            "paging query of the delegators of a validator",
+            get_delegators_with_params,
+        )
+        .get(
+            "/delegation_rewards",
+            "delegation rewards of an address at a height",
+            get_delegation_reward,
+        )
+        .get(
+            "/validator_delegation",
+            "delegation history of a validator, by epochs",
+            get_validator_delegation_history,
+        )
+        .get(
+            "/validator_signing",
+            "blocks signed and missed by a validator recently",
+            get_validator_signing_report,
+        );
+
+    for r in QueryServerRoutes::iter() {
+        match r {
+            QueryServerRoutes::GetAddress => spec.get(
+                &r.with_arg_template("txo_sid"),
+                "owner of a txo",
+                get_address,
+            ),
+            QueryServerRoutes::GetOwnerMemo => spec.get(
+                &r.with_arg_template("txo_sid"),
+                "owner memo of a txo",
+                get_owner_memo,
+            ),
+            QueryServerRoutes::GetOwnerMemoBatch => spec.get(
+                &r.with_arg_template("txo_sid_list"),
+                "owner memos of txos, separated by ','",
+                get_owner_memo_batch,
+            ),
+            QueryServerRoutes::GetOwnedUtxos => spec.get(
+                &r.with_arg_template("address"),
+                "sids of the utxos owned by an address",
+                get_owned_utxos,
+            ),
+            QueryServerRoutes::GetCreatedAssets => spec.get(
+                &r.with_arg_template("address"),
+                "assets defined by an issuer",
+                get_created_assets,
+            ),
+            QueryServerRoutes::GetIssuedRecords => spec.get(
+                &r.with_arg_template("address"),
+                "records issued by an issuer",
+                get_issued_records,
+            ),
+            QueryServerRoutes::GetIssuedRecordsByCode => spec.get(
+                &r.with_arg_template("asset_token"),
+                "records issued of an asset",
+                get_issued_records_by_code,
+            ),
+            QueryServerRoutes::GetRelatedTxns => spec.get(
+                &r.with_arg_template("address"),
+                "transactions related to an address",
+                get_related_txns,
+            ),
+            QueryServerRoutes::GetRelatedXfrs => spec.get(
+                &r.with_arg_template("asset_token"),
+                "transfers of an asset",
+                get_related_xfrs,
+            ),
+            QueryServerRoutes::TxnHistory => spec.get(
+                &r.route(),
+                "paging query of transactions related to an address",
+                get_txn_history,
+            ),
+            QueryServerRoutes::XfrHistory => spec.get(
+                &r.route(),
+                "paging query of transfers of an asset",
+                get_xfr_history,
+            ),
+            QueryServerRoutes::ClaimTxnHistory => spec.get(
+                &r.route(),
+                "paging query of claim transactions of an address",
+                get_claim_txn_history,
+            ),
+            QueryServerRoutes::CoinbaseOperHistory => spec.get(
+                &r.route(),
+                "paging query of coinbase operations of an address",
+                get_coinbase_oper_history,
+            ),
+            QueryServerRoutes::MemoTransfers => spec.get(
+                &r.route(),
+                "paging query of transactions received by an address with given memos",
+                get_memo_transfers,
+            ),
+            QueryServerRoutes::AssetSupply => spec.get(
+                &r.route(),
+                "total, circulating and locked supply of an asset",
+                get_asset_supply,
+            ),
+            QueryServerRoutes::AssetHolderCount => spec.get(
+                &r.route(),
+                "number of addresses holding an asset",
+                get_asset_holder_count,
+            ),
+            QueryServerRoutes::AssetTopHolders => {
+                spec.get(&r.route(), "rich list of an asset", get_asset_top_holders)
+            }
+            QueryServerRoutes::GetAuthencatedTxnIDHash => spec.get(
+                &r.with_arg_template("txo_sid"),
+                "sid and hash of the transaction creating a txo",
+                get_authenticated_txnid_hash,
+            ),
+            QueryServerRoutes::GetTransactionHash => spec.get(
+                &r.with_arg_template("txn_sid"),
+                "hash of a transaction",
+                get_transaction_hash,
+            ),
+            QueryServerRoutes::GetTransactionSid => spec.get(
+                &r.with_arg_template("txn_hash"),
+                "sid of a transaction",
+                get_transaction_sid,
+            ),
+            QueryServerRoutes::GetCommits => {
+                spec.get(&r.route(), "number of blocks indexed", get_commits)
+            }
+            QueryServerRoutes::GraphQL => spec
+                .post(&r.route(), "graphql queries", graphql::graphql)
+                .get(
+                    &r.route(),
+                    "graphql playground",
+                    graphql::graphql_playground,
+                ),
+            QueryServerRoutes::Stream => spec.get(
+                &r.route(),
+                "server-sent events of blocks, transfers and assets",
+                stream::stream_events,
+            ),
+            QueryServerRoutes::OpenApi => {
+                spec.get(&r.route(), "this document", openapi_json)
+            }
+        };
+    }
+
+    for r in ApiRoutes::iter() {
+        match r {
+            ApiRoutes::UtxoSid => {
+                spec.get(&r.with_arg_template("sid"), "utxo with proofs", query_utxo)
+            }
+            ApiRoutes::UtxoSidLight => spec.get(
+                &r.with_arg_template("sid"),
+                "utxo without proofs",
+                query_utxo_light,
+            ),
+            ApiRoutes::UtxoSidList => spec.get(
+                &r.with_arg_template("sid_list"),
+                "utxos with proofs, sids are separated by ','",
+                query_utxos,
+            ),
+            ApiRoutes::UtxoSidStatus => spec.get(
+                &r.with_arg_template("sid"),
+                "spent status of a txo",
+                query_utxo_status,
+            ),
+            ApiRoutes::UtxoSidRangeStatus => spec.get(
+                &r.route(),
+                "spent statuses of a range of txos",
+                query_utxo_range_status,
+            ),
+            ApiRoutes::AssetIssuanceNum => spec.get(
+                &r.with_arg_template("code"),
+                "issuance number of an asset",
+                query_asset_issuance_num,
+            ),
+            ApiRoutes::AssetToken => spec.get(
+                &r.with_arg_template("code"),
+                "properties of an asset",
+                query_asset,
+            ),
+            ApiRoutes::GlobalState => spec.get(
+                &r.route(),
+                "state commitment, block count and its signature",
+                query_global_state,
+            ),
+            ApiRoutes::TxnSid => spec.get(
+                &r.with_arg_template("sid"),
+                "a transaction with proofs, serialized",
+                query_txn,
+            ),
+            ApiRoutes::TxnSidLight => spec.get(
+                &r.with_arg_template("sid"),
+                "a transaction without proofs, serialized",
+                query_txn_light,
+            ),
+            ApiRoutes::TxnSidLightList => spec.get(
+                &r.with_arg_template("sid_list"),
+                "transactions without proofs, sids are separated by ','",
+                query_txns_light,
+            ),
+            ApiRoutes::GlobalStateVersion => spec.get(
+                &r.with_arg_template("version"),
+                "state commitment of a version",
+                query_global_state_version,
+            ),
+            ApiRoutes::StateCommitmentData => spec.get(
+                &r.with_arg_template("version"),
+                "state commitment data of a version",
+                query_state_commitment_data,
+            ),
+            ApiRoutes::OwnedUtxos => spec.get(
+                &r.with_arg_template("owner"),
+                "utxos owned by an address",
+                query_owned_utxos,
+            ),
+            ApiRoutes::ValidatorList => {
+                spec.get(&r.route(), "current validators", query_validators)
+            }
+            ApiRoutes::DelegationInfo => spec.get(
+                &r.with_arg_template("XfrPublicKey"),
+                "delegation of an address",
+                query_delegation_info,
+            ),
+            ApiRoutes::DelegatorList => spec.get(
+                &r.with_arg_template("NodeAddress"),
+                "delegators of a validator",
+                query_delegator_list,
+            ),
+            ApiRoutes::ValidatorDetail => spec.get(
+                &r.with_arg_template("NodeAddress"),
+                "details of a validator",
+                query_validator_detail,
+            ),
+            ApiRoutes::TransferPlans => spec.get(
+                &r.route(),
+                "active transfer plans of an address",
+                query_transfer_plans,
+            ),
+        };
+    }
+
+    spec
+}
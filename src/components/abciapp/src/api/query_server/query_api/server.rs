@@ -2,16 +2,20 @@
 //! data sources for the query api
 //!
 
+use crate::api::metrics;
+pub use finutils::api::{HistoryPage, TxnHistoryItem};
 use globutils::wallet;
 use lazy_static::lazy_static;
 use ledger::{
     data_model::{
         AssetTypeCode, DefineAsset, IssueAsset, IssuerPublicKey, Operation, Transaction,
-        TxOutput, TxnSID, TxoSID, XfrAddress,
+        TxOutput, TxnSID, TxoRef, TxoSID, XfrAddress, ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY,
+        BLACK_HOLE_PUBKEY_STAKING,
     },
     staking::{
         ops::mint_fra::MintEntry, Amount, BlockHeight, DelegationRwdDetail,
         CHAN_D_AMOUNT_HIST, CHAN_D_RWD_HIST, CHAN_GLOB_RATE_HIST, CHAN_V_SELF_D_HIST,
+        CHAN_V_SIGN_HIST, FF_PK_LIST,
     },
     store::{
         bnc::{self, new_mapx, Mapx},
@@ -21,14 +25,218 @@ use ledger::{
 use parking_lot::{Condvar, Mutex, RwLock};
 use ruc::*;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fs, io::ErrorKind, sync::Arc};
-use zei::xfr::{sig::XfrPublicKey, structs::OwnerMemo};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    io::ErrorKind,
+    ops::Bound,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+};
+use zei::xfr::{
+    sig::XfrPublicKey,
+    structs::{BlindAssetRecord, OwnerMemo},
+};
+
+use super::stream::{BlockEvents, StreamEvent, EVENT_HUB};
 
 lazy_static! {
     /// the query_server will be notified every time
     /// a block is added to the ledgerState to update the data
     pub static ref BLOCK_CREATED: Arc<(Mutex<bool>, Condvar)> =
         Arc::new((Mutex::new(false), Condvar::new()));
+
+    /// `BlockMeta` of every non-empty block, sent by abci
+    /// before the block is committed to the ledgerState
+    pub static ref CHAN_BLOCK_META: BlockMetaChan = {
+        let (s, r) = channel();
+        (Mutex::new(s), Mutex::new(r))
+    };
+}
+
+/// (index of the block in ledgerState, tendermint height, unix timestamp)
+pub type BlockMeta = (usize, BlockHeight, i64);
+
+type BlockMetaChan = (Mutex<Sender<BlockMeta>>, Mutex<Receiver<BlockMeta>>);
+
+/// Default number of records per page of history queries
+pub const HISTORY_PAGE_DEFAULT: usize = 20;
+
+/// Max number of records per page of history queries
+pub const HISTORY_PAGE_MAX: usize = 500;
+
+/// Order of history records
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderOption {
+    Desc,
+    Asc,
+}
+
+/// Paging options and filters of history queries,
+/// all the ranges are inclusive.
+///
+/// Records indexed before their block heights were tracked
+/// have no height or timestamp, so a range must start at or after
+/// the first tracked block, see `QueryServer::check_history_range`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct HistoryQuery {
+    /// `next_cursor` of the previous page, none for the first page
+    pub cursor: Option<u64>,
+    /// `HISTORY_PAGE_DEFAULT` if none, at most `HISTORY_PAGE_MAX`
+    pub limit: Option<usize>,
+    /// ascending if none
+    pub order: Option<OrderOption>,
+    #[allow(missing_docs)]
+    pub from_height: Option<BlockHeight>,
+    #[allow(missing_docs)]
+    pub to_height: Option<BlockHeight>,
+    /// unix timestamp, in seconds
+    pub from_time: Option<i64>,
+    /// unix timestamp, in seconds
+    pub to_time: Option<i64>,
+}
+
+impl HistoryQuery {
+    #[inline(always)]
+    fn is_desc(&self) -> bool {
+        Some(OrderOption::Desc) == self.order
+    }
+
+    #[inline(always)]
+    fn page_size(&self) -> usize {
+        self.limit
+            .unwrap_or(HISTORY_PAGE_DEFAULT)
+            .clamp(1, HISTORY_PAGE_MAX)
+    }
+
+    // - `Ok(true)`: in range
+    // - `Ok(false)`: out of range, but later records may be in range
+    // - `Err(_)`: out of range, and so are all the later records
+    fn check_range(
+        &self,
+        height: Option<BlockHeight>,
+        timestamp: Option<i64>,
+    ) -> std::result::Result<bool, ()> {
+        macro_rules! check {
+            ($v: expr, $from: expr, $to: expr) => {
+                if $from.is_some() || $to.is_some() {
+                    if let Some(v) = $v {
+                        let (head, tail) =
+                            alt!(self.is_desc(), ($to, $from), ($from, $to));
+                        let desc = self.is_desc();
+                        if tail.map(|t| alt!(desc, t > v, t < v)).unwrap_or(false) {
+                            return Err(());
+                        }
+                        if head.map(|h| alt!(desc, v > h, v < h)).unwrap_or(false) {
+                            return Ok(false);
+                        }
+                    } else {
+                        return Ok(false);
+                    }
+                }
+            };
+        }
+
+        check!(height, self.from_height, self.to_height);
+        check!(timestamp, self.from_time, self.to_time);
+
+        Ok(true)
+    }
+
+    // `records` must be ordered as required and start after the cursor,
+    // the key of a record is used as the cursor.
+    fn paginate<T>(
+        &self,
+        records: impl Iterator<Item = (u64, T)>,
+        meta: impl Fn(&T) -> (Option<BlockHeight>, Option<i64>),
+    ) -> HistoryPage<T> {
+        let size = self.page_size();
+        let mut items = Vec::with_capacity(size);
+        let mut last_key = None;
+        let mut next_cursor = None;
+
+        for (k, r) in records {
+            let (h, ts) = meta(&r);
+            match self.check_range(h, ts) {
+                Ok(true) => {
+                    if items.len() == size {
+                        next_cursor = last_key;
+                        break;
+                    }
+                    items.push(r);
+                    last_key = Some(k);
+                }
+                Ok(false) => {}
+                Err(_) => break,
+            }
+        }
+
+        HistoryPage { items, next_cursor }
+    }
+
+    fn paginate_txns(
+        &self,
+        sids: impl Iterator<Item = TxnSID>,
+        qs: &QueryServer,
+    ) -> HistoryPage<TxnHistoryItem> {
+        self.paginate(
+            sids.map(|sid| {
+                let height = qs.txn_heights.get(&sid);
+                let item = TxnHistoryItem {
+                    txn_sid: sid,
+                    txn_hash: qs.txn_sid_to_hash.get(&sid),
+                    height,
+                    timestamp: height.and_then(|h| qs.block_times.get(&h)),
+                };
+                (sid.0 as u64, item)
+            }),
+            |i| (i.height, i.timestamp),
+        )
+    }
+}
+
+/// A coinbase payment in history queries
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CoinbaseHistoryItem {
+    pub height: BlockHeight,
+    pub timestamp: Option<i64>,
+    pub data: MintEntry,
+}
+
+/// Max length in bytes of the indexed part of a memo,
+/// longer memos are truncated, so are the memos to look up.
+pub const MEMO_INDEX_MAX_LEN: usize = 256;
+
+/// A transaction indexed by one of its memos
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MemoRecord {
+    #[allow(missing_docs)]
+    pub txn_sid: TxnSID,
+    /// outputs received by the address, empty for asset memo updates
+    pub txo_sids: Vec<TxoSID>,
+    /// the asset whose memo is updated, only for `UpdateMemo`
+    pub asset: Option<AssetTypeCode>,
+}
+
+/// A transaction received by an address with matched memos
+#[allow(missing_docs)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MemoTransferItem {
+    pub txn_sid: TxnSID,
+    pub txn_hash: Option<String>,
+    pub height: Option<BlockHeight>,
+    pub timestamp: Option<i64>,
+    /// matched memos, truncated to `MEMO_INDEX_MAX_LEN`
+    pub memos: Vec<String>,
+    /// outputs received by the address
+    pub txo_sids: Vec<TxoSID>,
+    /// the asset whose memo is updated, only for `UpdateMemo`
+    pub asset: Option<AssetTypeCode>,
 }
 
 /// (sid, hash)
@@ -36,6 +244,44 @@ pub type TxnIDHash = (TxnSID, String);
 
 type Issuances = Vec<(TxOutput, Option<OwnerMemo>)>;
 
+/// Max number of entries of a rich list
+pub const TOP_HOLDERS_MAX: usize = 1000;
+
+/// Aggregated holdings of an asset, only fully non-confidential
+/// outputs are counted, confidential amounts or types are unknown.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AssetHolding {
+    /// number of addresses with a positive balance,
+    /// the pseudo-addresses are excluded
+    pub holders: u64,
+    /// sum of all the unspent outputs
+    pub total: Amount,
+    /// held by the pseudo-addresses
+    pub locked: Amount,
+}
+
+/// Supply of an asset, see `AssetHolding`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AssetSupply {
+    /// sum of all the unspent outputs
+    pub total: Amount,
+    /// `total` - `locked` - `reserved`
+    pub circulating: Amount,
+    /// held by the pseudo-addresses, eg. delegated FRAs and fees
+    pub locked: Amount,
+    /// held by the reserved accounts of Findora Foundation, only for FRA
+    pub reserved: Amount,
+    #[allow(missing_docs)]
+    pub holders: u64,
+}
+
+/// Addresses of the staking escrow and the black hole(fees, coinbase),
+/// they are not real holders.
+#[inline(always)]
+fn is_pseudo_address(pk: &XfrPublicKey) -> bool {
+    *BLACK_HOLE_PUBKEY == *pk || *BLACK_HOLE_PUBKEY_STAKING == *pk
+}
+
 /// data from ledgerState
 #[derive(Serialize, Deserialize)]
 pub struct QueryServer {
@@ -43,8 +289,8 @@ pub struct QueryServer {
     snapshot_file: String,
     #[serde(skip)]
     pub(crate) state: Option<Arc<RwLock<LedgerState>>>,
-    related_transactions: Mapx<XfrAddress, HashSet<TxnSID>>, // Set of transactions related to a ledger address
-    related_transfers: Mapx<AssetTypeCode, HashSet<TxnSID>>, // Set of transfer transactions related to an asset code
+    related_transactions: Mapx<XfrAddress, BTreeSet<TxnSID>>, // Set of transactions related to a ledger address
+    related_transfers: Mapx<AssetTypeCode, BTreeSet<TxnSID>>, // Set of transfer transactions related to an asset code
     claim_hist_txns: Mapx<XfrAddress, Vec<TxnSID>>, // List of claim transactions related to a ledger address
     coinbase_oper_hist: Mapx<XfrAddress, Vec<(BlockHeight, MintEntry)>>,
     created_assets: Mapx<IssuerPublicKey, Vec<DefineAsset>>,
@@ -55,6 +301,44 @@ pub struct QueryServer {
     txo_to_txnid: Mapx<TxoSID, TxnIDHash>, // txo(spent, unspent) to authenticated txn (sid, hash)
     txn_sid_to_hash: Mapx<TxnSID, String>, // txn sid to txn hash
     txn_hash_to_sid: Mapx<String, TxnSID>, // txn hash to txn sid
+    #[serde(default = "default_txn_heights")]
+    txn_heights: Mapx<TxnSID, BlockHeight>, // txn sid to tendermint height
+    #[serde(default = "default_block_times")]
+    block_times: Mapx<BlockHeight, i64>, // tendermint height to unix timestamp
+    // received from `CHAN_BLOCK_META`, waiting for their blocks
+    #[serde(skip)]
+    pending_block_meta: BTreeMap<usize, (BlockHeight, i64)>,
+    // (index of the block in ledgerState, tendermint height)
+    // of the first block whose height is tracked
+    #[serde(default)]
+    first_tracked_block: Option<(usize, BlockHeight)>,
+    // NOTE: for the data created before these two indexes,
+    // they are rebuilt from the unspent txos of the ledger on the first update.
+    #[serde(default = "default_asset_holdings")]
+    asset_holdings: Mapx<AssetTypeCode, AssetHolding>,
+    // non-confidential balances of every asset, the pseudo-addresses are excluded
+    #[serde(default = "default_asset_balances")]
+    asset_balances: Mapx<AssetTypeCode, Mapx<XfrPublicKey, Amount>>,
+    // false if the asset indexes do not cover all the blocks
+    #[serde(default)]
+    asset_index_synced: bool,
+    // the top `TOP_HOLDERS_MAX` holders of the assets queried,
+    // dropped when the balances of an asset change
+    #[serde(skip)]
+    top_holders: Mutex<HashMap<AssetTypeCode, Vec<(XfrPublicKey, Amount)>>>,
+    // transaction memos indexed by recipients,
+    // and asset memo updates indexed by issuers,
+    // only blocks processed after the creation of this index are counted:
+    // - address => memos(truncated), ordered for the prefix range scans,
+    //   only updated when an address receives a new memo
+    // - (address, memo(truncated)) => transactions, ordered for the paginations
+    // - (address, memo(truncated), txn) => record
+    #[serde(default = "default_memo_keys")]
+    memo_keys: Mapx<XfrAddress, BTreeSet<String>>,
+    #[serde(default = "default_memo_txns")]
+    memo_txns: Mapx<(XfrAddress, String), BTreeSet<TxnSID>>,
+    #[serde(default = "default_memo_records")]
+    memo_records: Mapx<(XfrAddress, String, TxnSID), MemoRecord>,
 
     // global rate history
     pub(crate) staking_global_rate_hist: Mapx<BlockHeight, [u128; 2]>,
@@ -74,6 +358,12 @@ pub struct QueryServer {
     pub(crate) staking_delegation_rwd_hist:
         Mapx<XfrPublicKey, Mapx<BlockHeight, DelegationRwdDetail>>,
 
+    // - if a validator signed the blocks it was expected to sign,
+    //   aka the blocks with it in the last commit
+    // - keyed by the validator id, so key rotations do not reset it
+    #[serde(default = "default_staking_signing_hist")]
+    pub(crate) staking_signing_hist: Mapx<XfrPublicKey, Mapx<BlockHeight, bool>>,
+
     app_block_cnt: usize,
 }
 
@@ -132,6 +422,17 @@ impl QueryServer {
             txo_to_txnid: new_mapx!("query_server_subdata/txo_to_txnid"),
             txn_sid_to_hash: new_mapx!("query_server_subdata/txn_sid_to_hash"),
             txn_hash_to_sid: new_mapx!("query_server_subdata/txn_hash_to_sid"),
+            txn_heights: default_txn_heights(),
+            block_times: default_block_times(),
+            pending_block_meta: BTreeMap::new(),
+            first_tracked_block: None,
+            asset_holdings: default_asset_holdings(),
+            asset_balances: default_asset_balances(),
+            asset_index_synced: true,
+            top_holders: Mutex::new(HashMap::new()),
+            memo_keys: default_memo_keys(),
+            memo_txns: default_memo_txns(),
+            memo_records: default_memo_records(),
             staking_global_rate_hist: new_mapx!(
                 "query_server_subdata/staking_rate_hist"
             ),
@@ -144,6 +445,7 @@ impl QueryServer {
             staking_delegation_rwd_hist: new_mapx!(
                 "query_server_subdata/staking_rwd_hist"
             ),
+            staking_signing_hist: default_staking_signing_hist(),
             app_block_cnt: 0,
         }
     }
@@ -157,6 +459,148 @@ impl QueryServer {
         self.issuances.get(issuer)
     }
 
+    /// Supply statistics of an asset
+    pub fn get_asset_supply(&self, code: &AssetTypeCode) -> Option<AssetSupply> {
+        self.asset_holdings.get(code).map(|h| {
+            let reserved = alt!(
+                ASSET_TYPE_FRA == code.val,
+                self.asset_balances
+                    .get(code)
+                    .map(|b| FF_PK_LIST.iter().filter_map(|pk| b.get(pk)).sum())
+                    .unwrap_or(0),
+                0
+            );
+            AssetSupply {
+                total: h.total,
+                circulating: h.total.saturating_sub(h.locked).saturating_sub(reserved),
+                locked: h.locked,
+                reserved,
+                holders: h.holders,
+            }
+        })
+    }
+
+    /// Number of addresses holding a positive balance of an asset
+    #[inline(always)]
+    pub fn get_asset_holder_count(&self, code: &AssetTypeCode) -> u64 {
+        self.asset_holdings
+            .get(code)
+            .map(|h| h.holders)
+            .unwrap_or(0)
+    }
+
+    /// The top `limit` holders of an asset, in descending order of balances,
+    /// the balances are scanned at most once for each change of them.
+    pub fn get_asset_top_holders(
+        &self,
+        code: &AssetTypeCode,
+        limit: usize,
+    ) -> Vec<(XfrPublicKey, Amount)> {
+        let limit = limit.clamp(1, TOP_HOLDERS_MAX);
+        let mut cache = self.top_holders.lock();
+        let holders = cache.entry(*code).or_insert_with(|| {
+            let mut holders = self
+                .asset_balances
+                .get(code)
+                .map(|b| b.iter().collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            let cmp = |a: &(XfrPublicKey, Amount), b: &(XfrPublicKey, Amount)| {
+                b.1.cmp(&a.1)
+                    .then_with(|| a.0.as_bytes().cmp(b.0.as_bytes()))
+            };
+            if TOP_HOLDERS_MAX < holders.len() {
+                holders.select_nth_unstable_by(TOP_HOLDERS_MAX, cmp);
+                holders.truncate(TOP_HOLDERS_MAX);
+            }
+            holders.sort_unstable_by(cmp);
+            holders
+        });
+
+        holders.iter().take(limit).copied().collect()
+    }
+
+    // Rebuild the holding statistics from the unspent txos of the ledger,
+    // the query server must have caught up with the ledger.
+    fn reindex_asset_holdings(&mut self, ledger: &LedgerState) {
+        let codes = self
+            .asset_holdings
+            .iter()
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>();
+        for code in codes.iter() {
+            self.asset_holdings.remove(code);
+        }
+        let codes = self
+            .asset_balances
+            .iter()
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>();
+        for code in codes.iter() {
+            // the nested data would be reused by the next map of the same asset
+            if let Some(mut balances) = self.asset_balances.get_mut(code) {
+                let pks = balances.iter().map(|(k, _)| k).collect::<Vec<_>>();
+                for pk in pks.iter() {
+                    balances.remove(pk);
+                }
+            }
+            self.asset_balances.remove(code);
+        }
+        self.top_holders.lock().clear();
+
+        self.asset_index_synced = true;
+        for (_, utxo) in ledger.get_status().iter_utxos() {
+            self.update_asset_holding(&utxo.0.record, true);
+        }
+    }
+
+    // Update the holding statistics with a fully non-confidential output,
+    // `credit` is false if the output has been spent.
+    fn update_asset_holding(&mut self, record: &BlindAssetRecord, credit: bool) {
+        // to be rebuilt after the update
+        if !self.asset_index_synced {
+            return;
+        }
+
+        let (code, am) = match (
+            record.asset_type.get_asset_type(),
+            record.amount.get_amount(),
+        ) {
+            (Some(val), Some(am)) => (AssetTypeCode { val }, am),
+            _ => return,
+        };
+        let update =
+            |n: Amount| alt!(credit, n.saturating_add(am), n.saturating_sub(am));
+
+        let mut holding = self.asset_holdings.get(&code).unwrap_or_default();
+        holding.total = update(holding.total);
+
+        if is_pseudo_address(&record.public_key) {
+            holding.locked = update(holding.locked);
+        } else {
+            #[allow(unused_mut)]
+            let mut balances = self.asset_balances.entry(code).or_insert_with(|| {
+                new_mapx!(format!("asset_balances_subdata/{}", code.to_base64()))
+            });
+            let old = balances.get(&record.public_key).unwrap_or(0);
+            let new = update(old);
+            if 0 == new {
+                balances.remove(&record.public_key);
+            } else {
+                balances.insert(record.public_key, new);
+            }
+
+            if 0 == old && 0 < new {
+                holding.holders += 1;
+            } else if 0 < old && 0 == new {
+                holding.holders = holding.holders.saturating_sub(1);
+            }
+        }
+
+        self.asset_holdings.insert(code, holding);
+        self.top_holders.lock().remove(&code);
+    }
+
     /// Returns the set of records issued by a certain token code.
     #[inline(always)]
     pub fn get_issued_records_by_code(
@@ -264,6 +708,179 @@ impl QueryServer {
         Err(eg!("Record not found"))
     }
 
+    /// Returns a page of transactions related to a given ledger address,
+    /// see `get_related_transactions` for the definition of 'related'.
+    pub fn get_txn_history(
+        &self,
+        address: &XfrAddress,
+        q: &HistoryQuery,
+    ) -> HistoryPage<TxnHistoryItem> {
+        let set = self.related_transactions.get(address).unwrap_or_default();
+        q.paginate_txns(sid_range(&set, q), self)
+    }
+
+    /// Returns a page of transfer transactions associated with a given asset.
+    pub fn get_xfr_history(
+        &self,
+        code: &AssetTypeCode,
+        q: &HistoryQuery,
+    ) -> HistoryPage<TxnHistoryItem> {
+        let set = self.related_transfers.get(code).unwrap_or_default();
+        q.paginate_txns(sid_range(&set, q), self)
+    }
+
+    /// Returns a page of claim transactions of a given ledger address.
+    pub fn get_claim_history(
+        &self,
+        address: &XfrAddress,
+        q: &HistoryQuery,
+    ) -> HistoryPage<TxnHistoryItem> {
+        let hist = self.claim_hist_txns.get(address).unwrap_or_default();
+        let sids: Box<dyn Iterator<Item = TxnSID>> = match (q.cursor, q.is_desc()) {
+            (None, false) => Box::new(hist.into_iter()),
+            (None, true) => Box::new(hist.into_iter().rev()),
+            (Some(c), false) => {
+                let idx = hist.partition_point(|sid| sid.0 as u64 <= c);
+                Box::new(hist.into_iter().skip(idx))
+            }
+            (Some(c), true) => {
+                let idx = hist.partition_point(|sid| (sid.0 as u64) < c);
+                Box::new(hist.into_iter().take(idx).rev())
+            }
+        };
+        q.paginate_txns(sids, self)
+    }
+
+    /// Returns a page of coinbase payments of a given ledger address,
+    /// the cursor is the position of a payment in the history.
+    pub fn get_coinbase_history(
+        &self,
+        address: &XfrAddress,
+        q: &HistoryQuery,
+    ) -> HistoryPage<CoinbaseHistoryItem> {
+        let hist = self.coinbase_oper_hist.get(address).unwrap_or_default();
+        let len = hist.len() as u64;
+        let records = hist.into_iter().enumerate().map(|(idx, (height, data))| {
+            let item = CoinbaseHistoryItem {
+                height,
+                timestamp: self.block_times.get(&height),
+                data,
+            };
+            (idx as u64, item)
+        });
+        let records: Box<dyn Iterator<Item = (u64, CoinbaseHistoryItem)>> =
+            match (q.cursor, q.is_desc()) {
+                (None, false) => Box::new(records),
+                (None, true) => Box::new(records.rev()),
+                (Some(c), false) => Box::new(records.skip(c.saturating_add(1) as usize)),
+                (Some(c), true) => Box::new(records.take(c.min(len) as usize).rev()),
+            };
+        q.paginate(records, |i| (Some(i.height), i.timestamp))
+    }
+
+    /// Height and time ranges can only be applied to the records with tracked heights,
+    /// if some blocks are processed before the tracking begins,
+    /// eg. the data of an old version, the ranges must start at or after
+    /// the first tracked block, or the results would be silently incomplete.
+    pub fn check_history_range(&self, q: &HistoryQuery) -> Result<()> {
+        let by_height = q.from_height.is_some() || q.to_height.is_some();
+        let by_time = q.from_time.is_some() || q.to_time.is_some();
+        if !by_height && !by_time {
+            return Ok(());
+        }
+
+        match self.first_tracked_block {
+            // all the blocks are tracked
+            Some((0, _)) => Ok(()),
+            None if 0 == self.app_block_cnt => Ok(()),
+            None => Err(eg!("block heights are not tracked yet")),
+            Some((_, h)) => {
+                let height_ok =
+                    !by_height || q.from_height.map(|f| h <= f).unwrap_or(false);
+                let time_ok = !by_time
+                    || matches!(
+                        (q.from_time, self.block_times.get(&h)),
+                        (Some(f), Some(ts)) if ts <= f
+                    );
+                if height_ok && time_ok {
+                    Ok(())
+                } else {
+                    Err(eg!(format!(
+                        "block heights are tracked since height {}, \
+                        the range must start at or after it",
+                        h
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Returns a page of transactions received by a given ledger address
+    /// with a memo equal to `memo`, or starting with `memo` if `prefix` is true,
+    /// asset memo updates signed by the address are included.
+    pub fn get_memo_transfers(
+        &self,
+        address: &XfrAddress,
+        memo: &str,
+        prefix: bool,
+        q: &HistoryQuery,
+    ) -> HistoryPage<MemoTransferItem> {
+        let memo = truncate_memo(memo);
+        let matched = if prefix {
+            self.memo_keys
+                .get(address)
+                .unwrap_or_default()
+                .range(memo.to_owned()..)
+                .take_while(|m| m.starts_with(memo))
+                .cloned()
+                .collect::<Vec<_>>()
+        } else {
+            vec![memo.to_owned()]
+        };
+        let sets = matched
+            .into_iter()
+            .filter_map(|m| self.memo_txns.get(&(*address, m.clone())).map(|s| (m, s)))
+            .collect::<Vec<_>>();
+
+        // merge the transactions of the matched memos in the required order,
+        // a transaction may match several memos,
+        // the scan stops once the page is filled
+        let mut heads = sets
+            .iter()
+            .map(|(m, s)| (m, sid_range(s, q).peekable()))
+            .collect::<Vec<_>>();
+        let records = std::iter::from_fn(|| {
+            let heads_sids = heads.iter_mut().filter_map(|(_, h)| h.peek().copied());
+            let sid = alt!(q.is_desc(), heads_sids.max(), heads_sids.min())?;
+
+            let height = self.txn_heights.get(&sid);
+            let mut item = MemoTransferItem {
+                txn_sid: sid,
+                txn_hash: self.txn_sid_to_hash.get(&sid),
+                height,
+                timestamp: height.and_then(|h| self.block_times.get(&h)),
+                memos: vec![],
+                txo_sids: vec![],
+                asset: None,
+            };
+            for (m, h) in heads.iter_mut() {
+                if Some(&sid) != h.peek() {
+                    continue;
+                }
+                h.next();
+                if let Some(r) = self.memo_records.get(&(*address, (*m).clone(), sid)) {
+                    item.memos.push((*m).clone());
+                    item.txo_sids = r.txo_sids;
+                    if r.asset.is_some() {
+                        item.asset = r.asset;
+                    }
+                }
+            }
+            Some((sid.0 as u64, item))
+        });
+        q.paginate(records, |i| (i.height, i.timestamp))
+    }
+
     /// Returns the set of transactions that are in some way related to a given ledger address.
     /// An xfr address is related to a transaction if it is one of the following:
     /// 1. Owner of a transfer output
@@ -275,7 +892,7 @@ impl QueryServer {
     pub fn get_related_transactions(
         &self,
         address: &XfrAddress,
-    ) -> Option<HashSet<TxnSID>> {
+    ) -> Option<BTreeSet<TxnSID>> {
         self.related_transactions.get(&address)
     }
 
@@ -285,7 +902,7 @@ impl QueryServer {
     pub fn get_related_transfers(
         &self,
         code: &AssetTypeCode,
-    ) -> Option<HashSet<TxnSID>> {
+    ) -> Option<BTreeSet<TxnSID>> {
         self.related_transfers.get(&code)
     }
 
@@ -307,6 +924,18 @@ impl QueryServer {
         self.txn_sid_to_hash.get(&txn_sid)
     }
 
+    /// Returns the tendermint height and the block timestamp of a given txn_sid,
+    /// if they have been tracked.
+    #[inline(always)]
+    pub fn get_transaction_height(
+        &self,
+        txn_sid: TxnSID,
+    ) -> Option<(BlockHeight, Option<i64>)> {
+        self.txn_heights
+            .get(&txn_sid)
+            .map(|h| (h, self.block_times.get(&h)))
+    }
+
     /// Returns the transaction sid of a given txn_hash.
     #[inline(always)]
     pub fn get_transaction_sid(&self, txn_hash: String) -> Option<TxnSID> {
@@ -359,6 +988,14 @@ impl QueryServer {
     }
 
     fn cache_hist_data(&mut self) {
+        CHAN_BLOCK_META
+            .1
+            .lock()
+            .try_iter()
+            .for_each(|(idx, h, ts)| {
+                self.pending_block_meta.insert(idx, (h, ts));
+            });
+
         CHAN_GLOB_RATE_HIST.1.lock().try_iter().for_each(|(h, r)| {
             self.staking_global_rate_hist.insert(h, r);
         });
@@ -391,6 +1028,20 @@ impl QueryServer {
                     .insert(h, r);
             });
 
+        CHAN_V_SIGN_HIST
+            .1
+            .lock()
+            .try_iter()
+            .for_each(|(pk, h, signed)| {
+                self.staking_signing_hist
+                    .entry(pk)
+                    .or_insert(new_mapx!(format!(
+                        "staking_signing_hist_subdata/{}",
+                        wallet::public_key_to_base64(&pk)
+                    )))
+                    .insert(h, signed);
+            });
+
         CHAN_D_RWD_HIST.1.lock().try_iter().for_each(|(pk, h, r)| {
             #[allow(unused_mut)]
             let mut dd =
@@ -419,6 +1070,107 @@ impl QueryServer {
         });
     }
 
+    // Index the memos of a transaction by the recipients of its outputs,
+    // the senders are excluded, they only receive changes,
+    // and index the asset memo updates by their issuers.
+    fn index_memos(
+        &mut self,
+        txn: &Transaction,
+        txn_sid: TxnSID,
+        txo_sids: &[TxoSID],
+        addresses: &[XfrAddress],
+    ) {
+        let memos = txn
+            .body
+            .memos
+            .iter()
+            .map(|m| truncate_memo(&m.0))
+            .filter(|m| !m.is_empty())
+            .collect::<BTreeSet<_>>();
+
+        if !memos.is_empty() {
+            let senders = txn
+                .body
+                .operations
+                .iter()
+                .filter_map(|op| {
+                    if let Operation::TransferAsset(i) = op {
+                        Some(i.body.inputs.iter())
+                    } else {
+                        None
+                    }
+                })
+                .flatten()
+                .filter_map(|input| {
+                    if let TxoRef::Absolute(sid) = input {
+                        self.utxos_to_map_index.get(sid)
+                    } else {
+                        None
+                    }
+                })
+                .collect::<HashSet<_>>();
+
+            let mut received: BTreeMap<XfrAddress, Vec<TxoSID>> = BTreeMap::new();
+            for (sid, address) in txo_sids.iter().zip(addresses.iter()) {
+                if !senders.contains(address) && !is_pseudo_address(&address.key) {
+                    received.entry(*address).or_insert_with(Vec::new).push(*sid);
+                }
+            }
+
+            for (address, sids) in received {
+                for memo in memos.iter() {
+                    self.add_memo_record(
+                        address,
+                        memo,
+                        MemoRecord {
+                            txn_sid,
+                            txo_sids: sids.clone(),
+                            asset: None,
+                        },
+                    );
+                }
+            }
+        }
+
+        for op in txn.body.operations.iter() {
+            if let Operation::UpdateMemo(i) = op {
+                let memo = truncate_memo(&i.body.new_memo.0);
+                if !memo.is_empty() {
+                    self.add_memo_record(
+                        XfrAddress { key: i.pubkey },
+                        memo,
+                        MemoRecord {
+                            txn_sid,
+                            txo_sids: vec![],
+                            asset: Some(i.body.asset_type),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fn add_memo_record(&mut self, address: XfrAddress, memo: &str, record: MemoRecord) {
+        let key = (address, memo.to_owned());
+        let mut sids = match self.memo_txns.get(&key) {
+            Some(sids) => sids,
+            None => {
+                self.memo_keys
+                    .entry(address)
+                    .or_insert_with(BTreeSet::new)
+                    .insert(memo.to_owned());
+                BTreeSet::new()
+            }
+        };
+
+        // a transaction is indexed at most once
+        if sids.insert(record.txn_sid) {
+            self.memo_records
+                .insert((address, memo.to_owned(), record.txn_sid), record);
+            self.memo_txns.insert(key, sids);
+        }
+    }
+
     /// Updates query server cache with new transactions from a block.
     /// Each new block must be consistent with the state of the cached ledger up until this point
     fn apply_new_blocks(&mut self, basedir: &str) -> Result<()> {
@@ -427,7 +1179,7 @@ impl QueryServer {
         let ledger = Arc::clone(self.state.as_ref().unwrap());
         let ledger = ledger.read();
 
-        if self.app_block_cnt == ledger.blocks.len() {
+        if self.app_block_cnt == ledger.blocks.len() && self.asset_index_synced {
             return Ok(());
         }
 
@@ -436,31 +1188,52 @@ impl QueryServer {
             return Err(eg!("The fucking world is over!"));
         }
 
-        for block in ledger.blocks.iter().skip(self.app_block_cnt) {
+        for (block_idx, block) in
+            ledger.blocks.iter().enumerate().skip(self.app_block_cnt)
+        {
+            // not available for blocks committed before the query server starts
+            let block_meta = self.pending_block_meta.remove(&block_idx);
+            if let Some((h, ts)) = block_meta {
+                self.block_times.insert(h, ts);
+                if self.first_tracked_block.is_none() {
+                    self.first_tracked_block = Some((block_idx, h));
+                }
+            }
+
+            // events to be pushed to the subscribers of the event stream
+            let mut events = vec![];
+
             // Update ownership status
             for (txn_sid, txo_sids) in
                 block.txns.iter().map(|v| (v.tx_id, v.txo_ids.as_slice()))
             {
                 let curr_txn = ledger.get_transaction_light(txn_sid).c(d!())?.txn;
+                if let Some((h, _)) = block_meta {
+                    self.txn_heights.insert(txn_sid, h);
+                }
                 // get the transaction, ownership addresses, and memos associated with each transaction
-                let (addresses, owner_memos) = {
-                    let addresses: Vec<XfrAddress> = txo_sids
+                let (records, addresses, owner_memos) = {
+                    let status = ledger.get_status();
+                    let records: Vec<BlindAssetRecord> = txo_sids
                         .iter()
-                        .map(|sid| XfrAddress {
-                            key: ((ledger
-                                .get_utxo_light(*sid)
-                                .or_else(|| ledger.get_spent_utxo_light(*sid))
+                        .map(|sid| {
+                            (status
+                                .get_utxo(*sid)
+                                .or_else(|| status.get_spent_utxo(*sid))
                                 .unwrap()
-                                .utxo)
                                 .0)
                                 .record
-                                .public_key,
                         })
                         .collect();
 
+                    let addresses: Vec<XfrAddress> = records
+                        .iter()
+                        .map(|r| XfrAddress { key: r.public_key })
+                        .collect();
+
                     let owner_memos = curr_txn.get_owner_memos_ref();
 
-                    (addresses, owner_memos)
+                    (records, addresses, owner_memos)
                 };
 
                 let classify_op = |op: &Operation| {
@@ -498,7 +1271,7 @@ impl QueryServer {
                 for address in &related_addresses {
                     self.related_transactions
                         .entry(*address)
-                        .or_insert_with(HashSet::new)
+                        .or_insert_with(BTreeSet::new)
                         .insert(txn_sid);
                 }
 
@@ -508,8 +1281,47 @@ impl QueryServer {
                 for asset in &transferred_assets {
                     self.related_transfers
                         .entry(*asset)
-                        .or_insert_with(HashSet::new)
+                        .or_insert_with(BTreeSet::new)
                         .insert(txn_sid);
+                    events.push(StreamEvent::AssetTransfer {
+                        asset: asset.to_base64(),
+                        txn_sid: txn_sid.0,
+                    });
+                }
+
+                // Spent utxos, must be collected before the new ones are indexed,
+                // relative inputs point to outputs of the same transaction,
+                // they are never visible as utxos, so they are skipped here.
+                for op in &curr_txn.body.operations {
+                    if let Operation::TransferAsset(i) = op {
+                        let spent = i.body.inputs.iter().filter_map(|input| {
+                            if let TxoRef::Absolute(sid) = input {
+                                Some(*sid)
+                            } else {
+                                None
+                            }
+                        });
+                        for sid in spent {
+                            if let Some(owner) = self.utxos_to_map_index.get(&sid) {
+                                events.push(StreamEvent::UtxoSpent {
+                                    address: wallet::public_key_to_base64(&owner.key),
+                                    txo_sid: sid.0,
+                                    txn_sid: txn_sid.0,
+                                });
+                            }
+                            if let Some(utxo) = ledger.get_status().get_spent_utxo(sid) {
+                                self.update_asset_holding(&utxo.0.record, false);
+                            }
+                        }
+                    }
+                }
+
+                // Index memos, must be done before the new utxos are indexed
+                self.index_memos(&curr_txn, txn_sid, txo_sids, &addresses);
+
+                // Update holdings with the new utxos
+                for record in records.iter() {
+                    self.update_asset_holding(record, true);
                 }
 
                 // Add created asset
@@ -538,11 +1350,35 @@ impl QueryServer {
                     if let Some(owner_memo) = owner_memo {
                         self.owner_memos.insert(*txo_sid, (*owner_memo).clone());
                     }
+                    events.push(StreamEvent::UtxoCreated {
+                        address: wallet::public_key_to_base64(&address.key),
+                        txo_sid: txo_sid.0,
+                        txn_sid: txn_sid.0,
+                    });
                 }
             }
+
+            // the `Block` event goes first, then the details of its transactions
+            events.insert(
+                0,
+                StreamEvent::Block {
+                    block: block_idx,
+                    timestamp: block_meta.map(|(_, ts)| ts),
+                    txns: block.txns.iter().map(|v| v.tx_id.0).collect(),
+                },
+            );
+            EVENT_HUB.lock().publish(BlockEvents {
+                height: block_meta.map(|(h, _)| h),
+                events,
+            });
         }
 
         self.app_block_cnt = ledger.blocks.len();
+        metrics::QUERY_SERVER_BLOCKS.set(self.app_block_cnt as i64);
+
+        if !self.asset_index_synced {
+            self.reindex_asset_holdings(&ledger);
+        }
 
         flush_data();
 
@@ -567,6 +1403,68 @@ impl QueryServer {
     }
 }
 
+fn default_txn_heights() -> Mapx<TxnSID, BlockHeight> {
+    new_mapx!("query_server_subdata/txn_heights")
+}
+
+fn default_block_times() -> Mapx<BlockHeight, i64> {
+    new_mapx!("query_server_subdata/block_times")
+}
+
+fn default_asset_holdings() -> Mapx<AssetTypeCode, AssetHolding> {
+    new_mapx!("query_server_subdata/asset_holdings")
+}
+
+fn default_asset_balances() -> Mapx<AssetTypeCode, Mapx<XfrPublicKey, Amount>> {
+    new_mapx!("query_server_subdata/asset_balances")
+}
+
+fn default_memo_keys() -> Mapx<XfrAddress, BTreeSet<String>> {
+    new_mapx!("query_server_subdata/memo_keys")
+}
+
+fn default_memo_txns() -> Mapx<(XfrAddress, String), BTreeSet<TxnSID>> {
+    new_mapx!("query_server_subdata/memo_txns")
+}
+
+fn default_memo_records() -> Mapx<(XfrAddress, String, TxnSID), MemoRecord> {
+    new_mapx!("query_server_subdata/memo_records")
+}
+
+fn default_staking_signing_hist() -> Mapx<XfrPublicKey, Mapx<BlockHeight, bool>> {
+    new_mapx!("query_server_subdata/staking_signing_hist")
+}
+
+// Truncate a memo to `MEMO_INDEX_MAX_LEN` on a char boundary
+fn truncate_memo(memo: &str) -> &str {
+    if memo.len() <= MEMO_INDEX_MAX_LEN {
+        return memo;
+    }
+    let mut end = MEMO_INDEX_MAX_LEN;
+    while !memo.is_char_boundary(end) {
+        end -= 1;
+    }
+    &memo[..end]
+}
+
+// Transactions after the cursor of `q`, in the required order.
+fn sid_range<'a>(
+    set: &'a BTreeSet<TxnSID>,
+    q: &HistoryQuery,
+) -> Box<dyn Iterator<Item = TxnSID> + 'a> {
+    let cursor = q.cursor.map(|c| TxnSID(c as usize));
+    let sids = match (cursor, q.is_desc()) {
+        (None, _) => set.range(..),
+        (Some(c), false) => set.range((Bound::Excluded(c), Bound::Unbounded)),
+        (Some(c), true) => set.range(..c),
+    };
+    alt!(
+        q.is_desc(),
+        Box::new(sids.rev().copied()),
+        Box::new(sids.copied())
+    )
+}
+
 /// An xfr address is related to a transaction if it is one of the following:
 /// 1. Owner of a transfer output
 /// 2. Transfer signer (owner of input or co-signer)
@@ -598,6 +1496,8 @@ where
             Operation::Governance(i) => staking_gen!(i),
             Operation::FraDistribution(i) => staking_gen!(i),
             Operation::MintFra(i) => staking_gen!(i),
+            Operation::TransferPlan(i) => staking_gen!(i),
+            Operation::RotateKey(i) => staking_gen!(i),
 
             Operation::ConvertAccount(i) => {
                 related_addresses.insert(XfrAddress {
@@ -4,18 +4,24 @@
 
 use super::{
     server::{QueryServer, BLOCK_CREATED},
+    stream::{EVENT_HUB, HEARTBEAT_INTERVAL},
     QueryApi,
 };
+use crate::api::throttle::ThrottleConfig;
+use baseapp::BaseApp;
 use ledger::store::LedgerState;
 use parking_lot::RwLock;
 use ruc::*;
-use std::{sync::Arc, thread};
+use std::{sync::Arc, thread, time::Duration};
 
-/// create query server
+/// create query server,
+/// EVM data are available in GraphQL queries if `base_app` is specified
 pub fn start_query_server(
     ledger: Arc<RwLock<LedgerState>>,
+    base_app: Option<Arc<RwLock<BaseApp>>>,
     addrs: &[(&str, u16)],
     basedir: Option<&str>,
+    throttle: ThrottleConfig,
 ) -> Result<Arc<RwLock<QueryServer>>> {
     let qs = QueryServer::new(ledger, basedir).c(d!())?;
     let basedir = qs.basedir.clone();
@@ -24,15 +30,21 @@ pub fn start_query_server(
     let qs1 = Arc::clone(&qs);
     let qs2 = Arc::clone(&qs);
 
-    QueryApi::create(qs1, addrs).c(d!()).map(|_| {
-        thread::spawn(move || loop {
-            let mut created = BLOCK_CREATED.0.lock();
-            if !*created {
-                BLOCK_CREATED.1.wait(&mut created);
-            }
-            qs2.write().update(&basedir);
-            *created = false;
-        });
-        qs
-    })
+    QueryApi::create(qs1, base_app, addrs, throttle)
+        .c(d!())
+        .map(|_| {
+            thread::spawn(move || loop {
+                let mut created = BLOCK_CREATED.0.lock();
+                if !*created {
+                    BLOCK_CREATED.1.wait(&mut created);
+                }
+                qs2.write().update(&basedir);
+                *created = false;
+            });
+            thread::spawn(|| loop {
+                thread::sleep(Duration::from_secs(HEARTBEAT_INTERVAL));
+                EVENT_HUB.lock().heartbeat();
+            });
+            qs
+        })
 }
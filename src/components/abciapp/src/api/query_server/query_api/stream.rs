@@ -0,0 +1,304 @@
+//!
+//! # Event Streaming
+//!
+//! Push committed blocks, utxo changes of addresses,
+//! and transfers of assets to clients through server-sent events.
+//!
+//! The `id` of every event is the tendermint height of its block,
+//! a reconnecting client(with the `Last-Event-ID` header) or a client
+//! with the `from_height` param will receive the missed events first,
+//! as long as they are still buffered, see `MAX_BUFFERED_BLOCKS`.
+//! If some of them are not buffered any more(or the node has restarted),
+//! a `gap` event goes first, the client should fill the gap with the history routes.
+//!
+//! A comment line is sent every `HEARTBEAT_INTERVAL` seconds,
+//! to keep idle connections alive through proxies.
+//!
+
+use actix_web::{error, web, HttpRequest, HttpResponse};
+use futures::{channel::mpsc, StreamExt};
+use lazy_static::lazy_static;
+use ledger::staking::BlockHeight;
+use parking_lot::Mutex;
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashSet, VecDeque},
+    mem,
+};
+
+/// Max number of recent blocks whose events can be replayed
+pub const MAX_BUFFERED_BLOCKS: usize = 1000;
+
+/// Max number of concurrent subscribers
+pub const MAX_SUBSCRIBERS: usize = 1024;
+
+/// Max number of pending events of a subscriber,
+/// slow subscribers will be disconnected, and can resume later.
+pub const MAX_PENDING_EVENTS: usize = 4096;
+
+/// Seconds between two heartbeats
+pub const HEARTBEAT_INTERVAL: u64 = 15;
+
+lazy_static! {
+    /// Events of every block processed by the query server
+    pub static ref EVENT_HUB: Mutex<EventHub> = Mutex::new(EventHub::default());
+}
+
+/// An event of the stream
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// A new block has been committed
+    Block {
+        /// index in the ledgerState
+        block: usize,
+        timestamp: Option<i64>,
+        txns: Vec<usize>,
+    },
+    /// A txo has been created for `address`
+    UtxoCreated {
+        address: String,
+        txo_sid: u64,
+        txn_sid: usize,
+    },
+    /// A txo of `address` has been spent by `txn_sid`
+    UtxoSpent {
+        address: String,
+        txo_sid: u64,
+        txn_sid: usize,
+    },
+    /// `asset` has been transferred by `txn_sid`
+    AssetTransfer { asset: String, txn_sid: usize },
+    /// Events of the blocks in `[from_height, oldest_height)` can not be replayed,
+    /// `oldest_height` is none if nothing is buffered, eg. after a restart
+    Gap {
+        from_height: BlockHeight,
+        oldest_height: Option<BlockHeight>,
+    },
+}
+
+impl StreamEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            StreamEvent::Block { .. } => "block",
+            StreamEvent::UtxoCreated { .. } => "utxo_created",
+            StreamEvent::UtxoSpent { .. } => "utxo_spent",
+            StreamEvent::AssetTransfer { .. } => "asset_transfer",
+            StreamEvent::Gap { .. } => "gap",
+        }
+    }
+}
+
+/// All the events of a block
+#[derive(Clone, Debug)]
+pub struct BlockEvents {
+    /// tendermint height, none if it is not tracked
+    pub height: Option<BlockHeight>,
+    #[allow(missing_docs)]
+    pub events: Vec<StreamEvent>,
+}
+
+impl BlockEvents {
+    fn encode(&self, filter: &StreamFilter) -> Vec<web::Bytes> {
+        self.events
+            .iter()
+            .filter(|e| filter.matches(e))
+            .map(|e| encode_event(self.height, e))
+            .collect()
+    }
+}
+
+fn encode_event(height: Option<BlockHeight>, e: &StreamEvent) -> web::Bytes {
+    let id = height.map(|h| format!("id: {}\n", h)).unwrap_or_default();
+    web::Bytes::from(format!(
+        "{}event: {}\ndata: {}\n\n",
+        id,
+        e.name(),
+        pnk!(serde_json::to_string(e))
+    ))
+}
+
+/// What a subscriber is interested in
+#[derive(Clone, Debug, Default)]
+pub struct StreamFilter {
+    /// receive `Block` events
+    pub blocks: bool,
+    /// base64 addresses
+    pub addresses: HashSet<String>,
+    /// base64 asset codes
+    pub assets: HashSet<String>,
+}
+
+impl StreamFilter {
+    fn matches(&self, e: &StreamEvent) -> bool {
+        match e {
+            StreamEvent::Block { .. } => self.blocks,
+            StreamEvent::UtxoCreated { address, .. }
+            | StreamEvent::UtxoSpent { address, .. } => self.addresses.contains(address),
+            StreamEvent::AssetTransfer { asset, .. } => self.assets.contains(asset),
+            StreamEvent::Gap { .. } => true,
+        }
+    }
+}
+
+struct Subscriber {
+    filter: StreamFilter,
+    tx: mpsc::Sender<web::Bytes>,
+}
+
+/// Recent events and subscribers
+#[derive(Default)]
+pub struct EventHub {
+    recent: VecDeque<BlockEvents>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventHub {
+    /// Buffer the events of a new block and push them to the subscribers.
+    pub fn publish(&mut self, be: BlockEvents) {
+        self.push(|s| be.encode(&s.filter));
+
+        if MAX_BUFFERED_BLOCKS == self.recent.len() {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(be);
+    }
+
+    /// Send a comment line to every subscriber,
+    /// called every `HEARTBEAT_INTERVAL` seconds.
+    pub fn heartbeat(&mut self) {
+        self.push(|_| vec![web::Bytes::from_static(b": heartbeat\n\n")]);
+    }
+
+    // Disconnected or too slow subscribers are dropped.
+    fn push(&mut self, msgs: impl Fn(&Subscriber) -> Vec<web::Bytes>) {
+        self.subscribers = mem::take(&mut self.subscribers)
+            .into_iter()
+            .filter_map(|mut s| {
+                let alive = msgs(&s).into_iter().all(|msg| s.tx.try_send(msg).is_ok());
+                alt!(alive, Some(s), None)
+            })
+            .collect();
+    }
+
+    /// Subscribe to new events, the buffered events of blocks
+    /// whose heights are not less than `from_height` will be sent first,
+    /// after a `gap` event if some of them are not buffered any more.
+    pub fn subscribe(
+        &mut self,
+        filter: StreamFilter,
+        from_height: Option<BlockHeight>,
+    ) -> Result<mpsc::Receiver<web::Bytes>> {
+        if MAX_SUBSCRIBERS <= self.subscribers.len() {
+            return Err(eg!("too many subscribers"));
+        }
+
+        let (mut tx, rx) = mpsc::channel(MAX_PENDING_EVENTS);
+
+        if let Some(from) = from_height {
+            // NOTE: if no block has been buffered, eg. just after the restart
+            // of the node, whether anything has been missed is unknown
+            let oldest = self.recent.iter().find_map(|be| be.height);
+            if oldest.map(|h| from < h).unwrap_or(true) {
+                let gap = StreamEvent::Gap {
+                    from_height: from,
+                    oldest_height: oldest,
+                };
+                tx.try_send(encode_event(None, &gap)).c(d!())?;
+            }
+
+            for msg in self
+                .recent
+                .iter()
+                .filter(|be| be.height.map(|h| from <= h).unwrap_or(false))
+                .flat_map(|be| be.encode(&filter))
+            {
+                tx.try_send(msg)
+                    .c(d!("too many events to replay, use the history routes"))?;
+            }
+        }
+
+        self.subscribers.push(Subscriber { filter, tx });
+
+        Ok(rx)
+    }
+
+    /// Number of the connected subscribers
+    #[inline(always)]
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+}
+
+// `from_height`, or the next height of `Last-Event-ID`
+pub(super) fn resume_height(
+    req: &HttpRequest,
+    from_height: Option<BlockHeight>,
+) -> Option<BlockHeight> {
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse::<BlockHeight>().ok());
+    from_height.or_else(|| last_event_id.map(|h| h.saturating_add(1)))
+}
+
+#[derive(Deserialize, Debug)]
+pub(super) struct StreamQueryParams {
+    // receive block events
+    #[serde(default)]
+    blocks: bool,
+    // base64 addresses, separated by ','
+    address: Option<String>,
+    // base64 asset codes, separated by ','
+    asset: Option<String>,
+    // resume from this height, `Last-Event-ID` + 1 if not specified
+    from_height: Option<BlockHeight>,
+}
+
+// subscribe to the event stream according to `StreamQueryParams`
+pub(super) async fn stream_events(
+    req: HttpRequest,
+    web::Query(info): web::Query<StreamQueryParams>,
+) -> actix_web::Result<HttpResponse> {
+    let split = |s: Option<String>, f: fn(&str) -> Result<String>| {
+        s.map(|s| {
+            s.split(',')
+                .filter(|i| !i.is_empty())
+                .map(f)
+                .collect::<Result<HashSet<_>>>()
+        })
+        .transpose()
+        .map(|i| i.unwrap_or_default())
+        .map_err(|e| error::ErrorBadRequest(e.generate_log(None)))
+    };
+
+    let filter = StreamFilter {
+        blocks: info.blocks,
+        // normalize them to be comparable
+        addresses: split(info.address, |a| {
+            globutils::wallet::public_key_from_base64(a)
+                .c(d!())
+                .map(|pk| globutils::wallet::public_key_to_base64(&pk))
+        })?,
+        assets: split(info.asset, |a| {
+            ledger::data_model::AssetTypeCode::new_from_base64(a)
+                .c(d!())
+                .map(|code| code.to_base64())
+        })?,
+    };
+
+    let from_height = resume_height(&req, info.from_height);
+
+    let rx = EVENT_HUB
+        .lock()
+        .subscribe(filter, from_height)
+        .map_err(|e| error::ErrorBadRequest(e.generate_log(None)))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(rx.map(Ok::<_, actix_web::Error>)))
+}
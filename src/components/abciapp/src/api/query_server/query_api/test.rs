@@ -0,0 +1,792 @@
+#![allow(missing_docs)]
+
+use super::{
+    graphql::{build_schema, MAX_LIST_LEN},
+    ledger_api::query_txns_light,
+    server::{
+        HistoryQuery, OrderOption, QueryServer, CHAN_BLOCK_META, MEMO_INDEX_MAX_LEN,
+    },
+    stream::{
+        resume_height, BlockEvents, EventHub, StreamEvent, StreamFilter,
+        MAX_BUFFERED_BLOCKS, MAX_PENDING_EVENTS,
+    },
+};
+use actix_web::{test::TestRequest, web};
+use finutils::api::TXN_LIST_LIMIT;
+use futures::channel::mpsc;
+use lazy_static::lazy_static;
+use ledger::{
+    data_model::{
+        AssetTypeCode, Memo, Operation, Transaction, TransferAsset, TransferAssetBody,
+        TransferType, TxnSID, TxoRef, TxoSID, XfrAddress, ASSET_TYPE_FRA,
+    },
+    staking::FRA_PRE_ISSUE_AMOUNT,
+    store::{helpers::apply_transaction, utils::fra_gen_initial_tx, LedgerState},
+};
+use parking_lot::{Mutex, RwLock};
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use ruc::*;
+use std::{collections::HashSet, sync::Arc};
+use zei::xfr::{
+    asset_record::{open_blind_asset_record, AssetRecordType},
+    sig::{XfrKeyPair, XfrPublicKey},
+    structs::{AssetRecord, AssetRecordTemplate},
+};
+
+lazy_static! {
+    // all the query servers share the paths of their data
+    static ref QUERY_SERVER_LOCK: Mutex<()> = Mutex::new(());
+}
+
+struct Env {
+    ledger: Arc<RwLock<LedgerState>>,
+    qs: QueryServer,
+    fra_owner: XfrKeyPair,
+    // unspent FRA of `fra_owner`
+    utxo: TxoSID,
+    // heights are tracked since this block
+    track_from: usize,
+}
+
+// Tendermint height of the block with index `idx`
+fn block_height(idx: usize) -> u64 {
+    10 * (1 + idx as u64)
+}
+
+// Timestamp of the block at height `h`
+fn block_time(h: u64) -> i64 {
+    1_000 + h as i64
+}
+
+impl Env {
+    fn new() -> Self {
+        Self::new_with(0)
+    }
+
+    fn new_with(track_from: usize) -> Self {
+        let ledger = Arc::new(RwLock::new(LedgerState::tmp_ledger()));
+        let qs = pnk!(QueryServer::new(Arc::clone(&ledger), None));
+        let fra_owner = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+
+        let mut env = Env {
+            ledger,
+            qs,
+            fra_owner,
+            utxo: TxoSID(0),
+            track_from,
+        };
+        let tx = fra_gen_initial_tx(&env.fra_owner);
+        env.utxo = env.apply(tx).1[0];
+        env
+    }
+
+    fn apply(&mut self, tx: Transaction) -> (TxnSID, Vec<TxoSID>) {
+        let res = apply_transaction(&mut self.ledger.write(), tx);
+        let idx = self.ledger.read().blocks.len() - 1;
+        if self.track_from <= idx {
+            let h = block_height(idx);
+            pnk!(CHAN_BLOCK_META.0.lock().send((idx, h, block_time(h))));
+        }
+        let basedir = self.qs.basedir.clone();
+        self.qs.update(&basedir);
+        res
+    }
+
+    // Send FRA of `fra_owner` to the receivers, the change is the last output.
+    fn transfer(
+        &mut self,
+        receivers: &[(XfrPublicKey, u64)],
+        memos: &[&str],
+    ) -> (TxnSID, Vec<TxoSID>) {
+        let mut prng = ChaChaRng::from_entropy();
+        let bar = self
+            .ledger
+            .read()
+            .get_utxo_light(self.utxo)
+            .unwrap()
+            .utxo
+            .0
+            .record;
+        let oar = pnk!(open_blind_asset_record(&bar, &None, &self.fra_owner));
+
+        let change = oar.amount - receivers.iter().map(|(_, am)| am).sum::<u64>();
+        let outputs = receivers
+            .iter()
+            .chain([(self.fra_owner.get_pk(), change)].iter())
+            .map(|(pk, am)| {
+                let template = AssetRecordTemplate::with_no_asset_tracing(
+                    *am,
+                    ASSET_TYPE_FRA,
+                    AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                    *pk,
+                );
+                pnk!(AssetRecord::from_template_no_identity_tracing(
+                    &mut prng, &template
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let mut transfer = pnk!(TransferAsset::new(pnk!(TransferAssetBody::new(
+            &mut prng,
+            vec![TxoRef::Absolute(self.utxo)],
+            &[AssetRecord::from_open_asset_record_no_asset_tracing(oar)],
+            &outputs,
+            None,
+            vec![],
+            TransferType::Standard,
+        ))));
+        transfer.sign(&self.fra_owner);
+
+        let seq_id = self.ledger.read().get_block_commit_count();
+        let mut tx =
+            Transaction::from_operation(Operation::TransferAsset(transfer), seq_id);
+        tx.body.memos = memos.iter().map(|m| Memo((*m).to_owned())).collect();
+
+        let res = self.apply(tx);
+        self.utxo = *res.1.last().unwrap();
+        res
+    }
+}
+
+fn new_address() -> XfrAddress {
+    XfrAddress {
+        key: XfrKeyPair::generate(&mut ChaChaRng::from_entropy()).get_pk(),
+    }
+}
+
+#[test]
+fn memo_index() {
+    let _lock = QUERY_SERVER_LOCK.lock();
+    let mut env = Env::new();
+    let (alice, bob) = (new_address(), new_address());
+    let long_memo = "x".repeat(MEMO_INDEX_MAX_LEN + 10);
+
+    let (tx1, sids1) = env.transfer(&[(alice.key, 10), (alice.key, 20)], &["invoice-1"]);
+    let (tx2, _) = env.transfer(&[(alice.key, 30)], &["invoice-2", &long_memo]);
+    let (tx3, _) = env.transfer(&[(bob.key, 40)], &["invoice-1"]);
+    // empty memos are not indexed
+    env.transfer(&[(bob.key, 50)], &[""]);
+
+    let q = HistoryQuery::default();
+    let sids = |addr: &XfrAddress, memo: &str, prefix: bool| {
+        env.qs
+            .get_memo_transfers(addr, memo, prefix, &q)
+            .items
+            .into_iter()
+            .map(|i| i.txn_sid)
+            .collect::<Vec<_>>()
+    };
+
+    // Exact matches, with the outputs received by the address
+    let page = env.qs.get_memo_transfers(&alice, "invoice-1", false, &q);
+    assert_eq!(1, page.items.len());
+    assert_eq!(tx1, page.items[0].txn_sid);
+    assert_eq!(vec!["invoice-1".to_owned()], page.items[0].memos);
+    assert_eq!(sids1[..2].to_vec(), page.items[0].txo_sids);
+    assert_eq!(vec![tx3], sids(&bob, "invoice-1", false));
+    assert!(sids(&alice, "invoice", false).is_empty());
+    assert!(sids(&bob, "", false).is_empty());
+
+    // Prefix matches, a transaction is listed once with all its matched memos
+    assert_eq!(vec![tx1, tx2], sids(&alice, "invoice-", true));
+    let page = env.qs.get_memo_transfers(&alice, "", true, &q);
+    assert_eq!(
+        vec![tx1, tx2],
+        page.items.iter().map(|i| i.txn_sid).collect::<Vec<_>>()
+    );
+    assert_eq!(2, page.items[1].memos.len());
+
+    // Prefix matches are paged across the matched memos, in both orders
+    let mut q1 = HistoryQuery {
+        limit: Some(1),
+        ..Default::default()
+    };
+    let page = env.qs.get_memo_transfers(&alice, "", true, &q1);
+    assert_eq!(
+        vec![tx1],
+        page.items.iter().map(|i| i.txn_sid).collect::<Vec<_>>()
+    );
+    assert_eq!(Some(tx1.0 as u64), page.next_cursor);
+    q1.cursor = page.next_cursor;
+    let page = env.qs.get_memo_transfers(&alice, "", true, &q1);
+    assert_eq!(
+        vec![tx2],
+        page.items.iter().map(|i| i.txn_sid).collect::<Vec<_>>()
+    );
+    assert_eq!(2, page.items[0].memos.len());
+    assert!(page.next_cursor.is_none());
+    let desc = HistoryQuery {
+        limit: Some(1),
+        order: Some(OrderOption::Desc),
+        ..Default::default()
+    };
+    let page = env.qs.get_memo_transfers(&alice, "invoice-", true, &desc);
+    assert_eq!(
+        vec![tx2],
+        page.items.iter().map(|i| i.txn_sid).collect::<Vec<_>>()
+    );
+    assert_eq!(Some(tx2.0 as u64), page.next_cursor);
+
+    // The senders only receive changes, they are not indexed
+    let owner = XfrAddress {
+        key: env.fra_owner.get_pk(),
+    };
+    assert!(sids(&owner, "invoice-", true).is_empty());
+
+    // Long memos are truncated, so are the memos to look up
+    let page = env.qs.get_memo_transfers(&alice, &long_memo, false, &q);
+    assert_eq!(1, page.items.len());
+    assert_eq!(tx2, page.items[0].txn_sid);
+    assert_eq!(MEMO_INDEX_MAX_LEN, page.items[0].memos[0].len());
+    assert_eq!(
+        vec![tx2],
+        sids(&alice, &long_memo[..MEMO_INDEX_MAX_LEN], false)
+    );
+    assert_eq!(vec![tx2], sids(&alice, "xxx", true));
+
+    // The index is kept after a restart
+    let basedir = env.qs.basedir.clone();
+    let qs = pnk!(QueryServer::new(Arc::clone(&env.ledger), Some(&basedir)));
+    assert_eq!(
+        vec![tx1, tx2],
+        qs.get_memo_transfers(&alice, "invoice-", true, &q)
+            .items
+            .into_iter()
+            .map(|i| i.txn_sid)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn asset_holdings() {
+    let _lock = QUERY_SERVER_LOCK.lock();
+    let mut env = Env::new();
+    let (alice, bob) = (new_address(), new_address());
+    let owner = env.fra_owner.get_pk();
+    let fra = AssetTypeCode {
+        val: ASSET_TYPE_FRA,
+    };
+
+    env.transfer(&[(alice.key, 30), (bob.key, 20)], &[]);
+    env.transfer(&[(alice.key, 10)], &[]);
+
+    let supply = env.qs.get_asset_supply(&fra).unwrap();
+    assert_eq!(FRA_PRE_ISSUE_AMOUNT, supply.total);
+    assert_eq!(FRA_PRE_ISSUE_AMOUNT, supply.circulating);
+    assert_eq!(0, supply.locked);
+    assert_eq!(3, supply.holders);
+    assert_eq!(3, env.qs.get_asset_holder_count(&fra));
+
+    let top = env.qs.get_asset_top_holders(&fra, 10);
+    assert_eq!(
+        vec![
+            (owner, FRA_PRE_ISSUE_AMOUNT - 60),
+            (alice.key, 40),
+            (bob.key, 20)
+        ],
+        top
+    );
+    assert_eq!(top[..2].to_vec(), env.qs.get_asset_top_holders(&fra, 2));
+    assert_eq!(top[..1].to_vec(), env.qs.get_asset_top_holders(&fra, 0));
+
+    // The cached list is dropped by new balances
+    env.transfer(&[(bob.key, 30)], &[]);
+    assert_eq!(
+        vec![(bob.key, 50), (alice.key, 40)],
+        env.qs.get_asset_top_holders(&fra, 3)[1..].to_vec()
+    );
+
+    // The indexes of a snapshot created before them are rebuilt from the ledger,
+    // including the blocks committed after the snapshot
+    let mut snapshot = pnk!(serde_json::to_value(&env.qs));
+    snapshot
+        .as_object_mut()
+        .unwrap()
+        .remove("asset_index_synced");
+    let mut qs: QueryServer = pnk!(serde_json::from_value(snapshot));
+    qs.state = Some(Arc::clone(&env.ledger));
+    env.qs = qs;
+    env.transfer(&[(alice.key, 5)], &[]);
+
+    let supply = env.qs.get_asset_supply(&fra).unwrap();
+    assert_eq!(FRA_PRE_ISSUE_AMOUNT, supply.total);
+    assert_eq!(3, supply.holders);
+    assert_eq!(
+        vec![
+            (owner, FRA_PRE_ISSUE_AMOUNT - 95),
+            (bob.key, 50),
+            (alice.key, 45)
+        ],
+        env.qs.get_asset_top_holders(&fra, 3)
+    );
+}
+
+#[test]
+fn history_pagination() {
+    let _lock = QUERY_SERVER_LOCK.lock();
+    // the first block is processed before the heights are tracked
+    let mut env = Env::new_with(1);
+    let alice = new_address();
+    // at the heights of 20, 30, .., 60
+    let txns = (0..5)
+        .map(|_| env.transfer(&[(alice.key, 10)], &[]).0)
+        .collect::<Vec<_>>();
+
+    let pages = |q: HistoryQuery| {
+        let mut q = q;
+        let mut pages = vec![];
+        loop {
+            let page = env.qs.get_txn_history(&alice, &q);
+            pages.push(page.items.iter().map(|i| i.txn_sid).collect::<Vec<_>>());
+            if page.next_cursor.is_none() {
+                break;
+            }
+            q.cursor = page.next_cursor;
+        }
+        pages
+    };
+
+    // Cursor continuation in both orders
+    let asc = HistoryQuery {
+        limit: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(
+        pages(asc.clone()),
+        vec![txns[..2].to_vec(), txns[2..4].to_vec(), txns[4..].to_vec()]
+    );
+    let desc = HistoryQuery {
+        order: Some(OrderOption::Desc),
+        ..asc.clone()
+    };
+    assert_eq!(
+        pages(desc.clone()),
+        vec![
+            vec![txns[4], txns[3]],
+            vec![txns[2], txns[1]],
+            vec![txns[0]]
+        ]
+    );
+
+    // The heights and timestamps are tracked
+    let page = env.qs.get_txn_history(&alice, &asc);
+    assert_eq!(Some(block_height(1)), page.items[0].height);
+    assert_eq!(Some(block_time(block_height(1))), page.items[0].timestamp);
+
+    // Stop at the end of a range, no cursor is returned for a full last page
+    let range = |q: &HistoryQuery| HistoryQuery {
+        from_height: Some(30),
+        to_height: Some(40),
+        ..q.clone()
+    };
+    assert_eq!(pages(range(&asc)), vec![txns[1..3].to_vec()]);
+    assert_eq!(pages(range(&desc)), vec![vec![txns[2], txns[1]]]);
+    let by_time = HistoryQuery {
+        from_time: Some(block_time(40)),
+        ..desc.clone()
+    };
+    assert_eq!(pages(by_time), vec![vec![txns[4], txns[3]], vec![txns[2]]]);
+
+    // A range must start at or after the first tracked height
+    pnk!(env.qs.check_history_range(&asc));
+    pnk!(env.qs.check_history_range(&range(&desc)));
+    for q in [
+        HistoryQuery {
+            from_height: Some(10),
+            ..Default::default()
+        },
+        HistoryQuery {
+            to_height: Some(40),
+            ..Default::default()
+        },
+        HistoryQuery {
+            from_time: Some(block_time(10)),
+            ..Default::default()
+        },
+    ]
+    .iter()
+    {
+        assert!(env.qs.check_history_range(q).is_err());
+    }
+
+    // All the blocks are tracked
+    drop(env);
+    let env = Env::new();
+    pnk!(env.qs.check_history_range(&HistoryQuery {
+        to_height: Some(40),
+        ..Default::default()
+    }));
+}
+
+fn block_events(height: Option<u64>, events: Vec<StreamEvent>) -> BlockEvents {
+    let mut events = events;
+    events.insert(
+        0,
+        StreamEvent::Block {
+            block: 0,
+            timestamp: None,
+            txns: vec![],
+        },
+    );
+    BlockEvents { height, events }
+}
+
+fn utxo_created(address: &str) -> StreamEvent {
+    StreamEvent::UtxoCreated {
+        address: address.to_owned(),
+        txo_sid: 0,
+        txn_sid: 0,
+    }
+}
+
+// All the pending messages, and whether the stream is still open
+fn drain(rx: &mut mpsc::Receiver<web::Bytes>) -> (Vec<String>, bool) {
+    let mut msgs = vec![];
+    loop {
+        match rx.try_next() {
+            Ok(Some(msg)) => msgs.push(String::from_utf8(msg.to_vec()).unwrap()),
+            Ok(None) => return (msgs, false),
+            Err(_) => return (msgs, true),
+        }
+    }
+}
+
+fn blocks_only() -> StreamFilter {
+    StreamFilter {
+        blocks: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn event_replay() {
+    let mut hub = EventHub::default();
+
+    // nothing has been buffered, eg. after a restart
+    let mut rx = pnk!(hub.subscribe(blocks_only(), Some(100)));
+
+    // the first block is not tracked
+    hub.publish(block_events(None, vec![]));
+    for h in [10, 20, 30].iter() {
+        hub.publish(block_events(Some(*h), vec![]));
+    }
+    let (msgs, open) = drain(&mut rx);
+    assert!(open);
+    assert_eq!(5, msgs.len());
+    assert!(msgs[0].starts_with("event: gap\n"));
+    assert!(msgs[0].contains("\"oldest_height\":null"));
+    assert!(msgs[1].starts_with("event: block\n"));
+    assert!(msgs[2].starts_with("id: 10\nevent: block\n"));
+
+    // Replay from a height
+    let mut rx = pnk!(hub.subscribe(blocks_only(), Some(20)));
+    let (msgs, _) = drain(&mut rx);
+    assert_eq!(2, msgs.len());
+    assert!(msgs[0].starts_with("id: 20\n"));
+    assert!(msgs[1].starts_with("id: 30\n"));
+
+    // Out of the replayable range, a gap event goes first
+    let mut rx = pnk!(hub.subscribe(blocks_only(), Some(9)));
+    let (msgs, _) = drain(&mut rx);
+    assert_eq!(4, msgs.len());
+    assert!(msgs[0].starts_with("event: gap\n"));
+    assert!(msgs[0].contains("\"from_height\":9,\"oldest_height\":10"));
+    assert!(msgs[1].starts_with("id: 10\n"));
+    let mut rx = pnk!(hub.subscribe(blocks_only(), Some(10)));
+    assert_eq!(3, drain(&mut rx).0.len());
+    let mut rx = pnk!(hub.subscribe(blocks_only(), Some(31)));
+    assert!(drain(&mut rx).0.is_empty());
+
+    for h in 0..MAX_BUFFERED_BLOCKS as u64 {
+        hub.publish(block_events(Some(40 + h), vec![]));
+    }
+    let mut rx = pnk!(hub.subscribe(blocks_only(), Some(39)));
+    let msgs = drain(&mut rx).0;
+    assert_eq!(MAX_BUFFERED_BLOCKS + 1, msgs.len());
+    assert!(msgs[0].starts_with("event: gap\n"));
+    let mut rx = pnk!(hub.subscribe(blocks_only(), Some(40)));
+    assert_eq!(MAX_BUFFERED_BLOCKS, drain(&mut rx).0.len());
+
+    // `from_height` goes first, then the next height of `Last-Event-ID`
+    let req = TestRequest::default()
+        .header("Last-Event-ID", "41")
+        .to_http_request();
+    assert_eq!(Some(42), resume_height(&req, None));
+    assert_eq!(Some(7), resume_height(&req, Some(7)));
+    let req = TestRequest::default()
+        .header("Last-Event-ID", "invalid")
+        .to_http_request();
+    assert_eq!(None, resume_height(&req, None));
+    assert_eq!(
+        None,
+        resume_height(&TestRequest::default().to_http_request(), None)
+    );
+}
+
+#[test]
+fn event_filter() {
+    let mut hub = EventHub::default();
+    let (alice, bob, asset) = ("alice", "bob", "asset");
+
+    let mut by_address = pnk!(hub.subscribe(
+        StreamFilter {
+            addresses: [alice.to_owned()].iter().cloned().collect::<HashSet<_>>(),
+            ..Default::default()
+        },
+        None
+    ));
+    let mut by_asset = pnk!(hub.subscribe(
+        StreamFilter {
+            assets: [asset.to_owned()].iter().cloned().collect::<HashSet<_>>(),
+            ..Default::default()
+        },
+        None
+    ));
+    let mut by_block = pnk!(hub.subscribe(blocks_only(), None));
+
+    hub.publish(block_events(
+        Some(1),
+        vec![
+            utxo_created(alice),
+            utxo_created(bob),
+            StreamEvent::UtxoSpent {
+                address: alice.to_owned(),
+                txo_sid: 0,
+                txn_sid: 1,
+            },
+            StreamEvent::AssetTransfer {
+                asset: asset.to_owned(),
+                txn_sid: 1,
+            },
+            StreamEvent::AssetTransfer {
+                asset: "other".to_owned(),
+                txn_sid: 1,
+            },
+        ],
+    ));
+
+    let (msgs, _) = drain(&mut by_address);
+    assert_eq!(2, msgs.len());
+    assert!(msgs[0].contains("event: utxo_created\n"));
+    assert!(msgs[0].contains("\"address\":\"alice\""));
+    assert!(msgs[1].contains("event: utxo_spent\n"));
+
+    let (msgs, _) = drain(&mut by_asset);
+    assert_eq!(1, msgs.len());
+    assert!(msgs[0].contains("event: asset_transfer\n"));
+    assert!(msgs[0].contains("\"asset\":\"asset\""));
+
+    let (msgs, _) = drain(&mut by_block);
+    assert_eq!(1, msgs.len());
+    assert!(msgs[0].starts_with("id: 1\nevent: block\n"));
+}
+
+#[test]
+fn heartbeats() {
+    let mut hub = EventHub::default();
+    let mut rx = pnk!(hub.subscribe(blocks_only(), None));
+    let gone = pnk!(hub.subscribe(blocks_only(), None));
+
+    // Disconnected subscribers are also dropped by heartbeats
+    drop(gone);
+    hub.heartbeat();
+    assert_eq!(1, hub.subscriber_count());
+    let (msgs, open) = drain(&mut rx);
+    assert!(open);
+    assert_eq!(vec![": heartbeat\n\n".to_owned()], msgs);
+}
+
+#[test]
+fn slow_subscribers() {
+    let mut hub = EventHub::default();
+    let mut fast = pnk!(hub.subscribe(blocks_only(), None));
+    let mut slow = pnk!(hub.subscribe(blocks_only(), None));
+    let gone = pnk!(hub.subscribe(blocks_only(), None));
+    assert_eq!(3, hub.subscriber_count());
+
+    // Disconnected subscribers are dropped on the next publication
+    drop(gone);
+    hub.publish(block_events(Some(1), vec![]));
+    assert_eq!(2, hub.subscriber_count());
+    assert_eq!(1, drain(&mut fast).0.len());
+
+    // A subscriber with too many pending events is dropped,
+    // the others are not affected
+    for h in 0..=MAX_PENDING_EVENTS as u64 {
+        hub.publish(block_events(Some(2 + h), vec![]));
+        assert_eq!(1, drain(&mut fast).0.len());
+    }
+    assert_eq!(1, hub.subscriber_count());
+
+    let (msgs, open) = drain(&mut slow);
+    assert!(!open);
+    // the buffer of a channel is extended by one for its sender
+    assert_eq!(MAX_PENDING_EVENTS + 1, msgs.len());
+
+    hub.publish(block_events(Some(1_000_000), vec![]));
+    let (msgs, open) = drain(&mut fast);
+    assert!(open);
+    assert_eq!(1, msgs.len());
+}
+
+#[test]
+fn txn_list() {
+    let _lock = QUERY_SERVER_LOCK.lock();
+    let mut env = Env::new();
+    let alice = new_address();
+    let (tx1, sids1) = env.transfer(&[(alice.key, 10)], &[]);
+    let (tx2, _) = env.transfer(&[(alice.key, 20)], &[]);
+    let data = web::Data::new(Arc::new(RwLock::new(env.qs)));
+    let query = |list: String| {
+        futures::executor::block_on(query_txns_light(
+            data.clone(),
+            web::Path::from(list),
+        ))
+    };
+
+    let txns = pnk!(query(format!("{},100000,{}", tx2.0, tx1.0))).into_inner();
+    assert_eq!(3, txns.len());
+    assert_eq!(tx2, txns[0].as_ref().unwrap().tx_id);
+    assert!(txns[1].is_none());
+    let txn = txns[2].as_ref().unwrap();
+    assert_eq!(tx1, txn.tx_id);
+    // the ids of the outputs are set
+    match &txn.txn.body.operations[0] {
+        Operation::TransferAsset(xfr) => {
+            assert_eq!(Some(sids1[0]), xfr.body.outputs[0].id)
+        }
+        _ => panic!("not a transfer"),
+    }
+
+    assert!(query("".to_owned()).is_err());
+    assert!(query("1,x".to_owned()).is_err());
+    let too_many = (0..=TXN_LIST_LIMIT)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    assert!(query(too_many).is_err());
+}
+
+// Run a query, returns the data and the error messages
+fn graphql(qs: &Arc<RwLock<QueryServer>>, q: &str) -> (serde_json::Value, Vec<String>) {
+    let schema = build_schema(Arc::clone(qs), None);
+    let resp = futures::executor::block_on(schema.execute(q));
+    (
+        pnk!(serde_json::to_value(&resp.data)),
+        resp.errors.into_iter().map(|e| e.message).collect(),
+    )
+}
+
+#[test]
+fn graphql_resolvers() {
+    let _lock = QUERY_SERVER_LOCK.lock();
+    let mut env = Env::new();
+    let alice = new_address();
+    let (tx1, sids1) = env.transfer(&[(alice.key, 10)], &[]);
+    let (tx2, _) = env.transfer(&[(alice.key, 20)], &[]);
+    let owner = globutils::wallet::public_key_to_base64(&env.fra_owner.get_pk());
+    let alice = globutils::wallet::public_key_to_base64(&alice.key);
+    let qs = Arc::new(RwLock::new(env.qs));
+
+    // A transaction with its operations and outputs
+    let (data, errors) = graphql(
+        &qs,
+        &format!(
+            "{{ transaction(sid: {}) {{ sid height operations {{ kind }} \
+             outputs {{ sid owner amount spent }} }} }}",
+            tx1.0
+        ),
+    );
+    assert!(errors.is_empty(), "{:?}", errors);
+    let txn = &data["transaction"];
+    assert_eq!(tx1.0 as u64, txn["sid"].as_u64().unwrap());
+    assert_eq!(block_height(tx1.0), txn["height"].as_u64().unwrap());
+    assert_eq!("TransferAsset", txn["operations"][0]["kind"]);
+    assert_eq!(sids1[0].0, txn["outputs"][0]["sid"].as_u64().unwrap());
+    assert_eq!(alice.as_str(), txn["outputs"][0]["owner"]);
+    assert_eq!(10, txn["outputs"][0]["amount"].as_u64().unwrap());
+    // the change is spent by the next transaction
+    assert!(txn["outputs"][1]["spent"].as_bool().unwrap());
+
+    let (data, errors) = graphql(&qs, "{ transaction(sid: 100000) { sid } }");
+    assert!(errors.is_empty());
+    assert!(data["transaction"].is_null());
+
+    // Pages of the transactions of an address
+    let q = |cursor: &str| {
+        format!(
+            "{{ transactions(address: \"{}\", limit: 1{}) {{ items {{ sid }} nextCursor }} }}",
+            alice, cursor
+        )
+    };
+    let (data, errors) = graphql(&qs, &q(""));
+    assert!(errors.is_empty(), "{:?}", errors);
+    let page = &data["transactions"];
+    assert_eq!(tx1.0 as u64, page["items"][0]["sid"].as_u64().unwrap());
+    let cursor = page["nextCursor"].as_u64().unwrap();
+    let (data, _) = graphql(&qs, &q(&format!(", cursor: {}", cursor)));
+    let page = &data["transactions"];
+    assert_eq!(tx2.0 as u64, page["items"][0]["sid"].as_u64().unwrap());
+    assert!(page["nextCursor"].is_null());
+
+    // Utxos and assets
+    let (data, errors) = graphql(
+        &qs,
+        &format!(
+            "{{ ownedUtxos(address: \"{}\") {{ amount }} \
+             asset(code: \"{}\") {{ issuer rules }} }}",
+            alice,
+            AssetTypeCode {
+                val: ASSET_TYPE_FRA
+            }
+            .to_base64()
+        ),
+    );
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(2, data["ownedUtxos"].as_array().unwrap().len());
+    assert_eq!(owner.as_str(), data["asset"]["issuer"]);
+    assert!(data["asset"]["rules"].is_object());
+
+    // Pages of the validators
+    let (data, errors) = graphql(&qs, "{ validators(limit: 1, offset: 0) { address } }");
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(1 >= data["validators"].as_array().unwrap().len());
+
+    // Bad arguments and unavailable data are errors, not panics
+    for q in [
+        "{ ownedUtxos(address: \"invalid\") { sid } }".to_owned(),
+        "{ asset(code: \"invalid\") { code } }".to_owned(),
+        format!(
+            "{{ ownedUtxos(address: \"{}\", limit: 0) {{ sid }} }}",
+            alice
+        ),
+        format!(
+            "{{ ownedUtxos(address: \"{}\", limit: {}) {{ sid }} }}",
+            alice,
+            MAX_LIST_LEN + 1
+        ),
+        "{ evmBlock { number receipts { gasUsed } } }".to_owned(),
+        "{ validators(limit: 0) { address } }".to_owned(),
+        format!(
+            "{{ validators(limit: {}) {{ address }} }}",
+            MAX_LIST_LEN + 1
+        ),
+    ]
+    .iter()
+    {
+        let (_, errors) = graphql(&qs, q);
+        assert_eq!(1, errors.len(), "{}", q);
+    }
+
+    // Too complex
+    let (_, errors) = graphql(
+        &qs,
+        &format!(
+            "{{ transactions(address: \"{}\", limit: {}) \
+             {{ items {{ operations {{ kind }} outputs {{ sid owner }} }} }} }}",
+            alice, MAX_LIST_LEN
+        ),
+    );
+    assert!(!errors.is_empty());
+}
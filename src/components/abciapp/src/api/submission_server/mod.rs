@@ -2,10 +2,18 @@
 //! # service of operating tx
 //!
 
+pub mod openapi;
+pub mod simulation;
 pub mod submission_api;
+pub mod txn_status;
 
+#[cfg(test)]
+mod test;
+
+use crate::api::metrics;
 use ledger::{
     data_model::{BlockEffect, Transaction, TxnEffect, TxnSID, TxnTempSID, TxoSID},
+    staking::BlockHeight,
     store::LedgerState,
 };
 use log::info;
@@ -14,6 +22,7 @@ use rand_core::{CryptoRng, RngCore};
 use ruc::*;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, fmt, result::Result as StdResult, sync::Arc};
+use txn_status::{TxnStatusStore, DEFAULT_TXN_STATUS_TTL};
 
 /// Query handle for user
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
@@ -36,11 +45,44 @@ impl fmt::Display for TxnHandle {
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[allow(missing_docs)]
 pub enum TxnStatus {
-    Rejected(String),
+    Rejected(TxnRejection),
     Committed((TxnSID, Vec<TxoSID>)),
     Pending,
 }
 
+/// Kinds of rejections, also used as the response code of `CheckTx`/`DeliverTx`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RejectionCode {
+    /// Malformed, or failed to pass the basic checks, eg. signatures
+    InvalidTxn = 1,
+    /// An operation is invalid by itself, see `op_index`
+    InvalidOperation = 2,
+    /// Conflicted with the ledger state, eg. double spending
+    LedgerCheck = 3,
+}
+
+/// Why a transaction is rejected
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TxnRejection {
+    #[allow(missing_docs)]
+    pub code: RejectionCode,
+    /// Index of the failed operation, if it can be located
+    pub op_index: Option<usize>,
+    #[allow(missing_docs)]
+    pub message: String,
+}
+
+impl TxnRejection {
+    #[allow(missing_docs)]
+    pub fn new(code: RejectionCode, op_index: Option<usize>, message: String) -> Self {
+        TxnRejection {
+            code,
+            op_index,
+            message,
+        }
+    }
+}
+
 /// use to create submissionServer
 pub enum CommitMode {
     /// all block
@@ -68,7 +110,10 @@ where
     committed_state: Arc<RwLock<LedgerState>>,
     block: Option<BlockEffect>,
     pending_txns: Vec<(TxnTempSID, TxnHandle, Transaction)>,
-    txn_status: HashMap<TxnHandle, TxnStatus>,
+    txn_status: TxnStatusStore,
+    // transactions forwarded by `handle_transaction` => height,
+    // rejections in `CheckTx` are only recorded for them
+    submitted: HashMap<TxnHandle, BlockHeight>,
     block_capacity: usize,
     prng: RNG,
     commit_mode: CommitMode,
@@ -90,7 +135,8 @@ where
         Ok(SubmissionServer {
             committed_state: ledger_state,
             block: None,
-            txn_status: HashMap::new(),
+            txn_status: TxnStatusStore::new(DEFAULT_TXN_STATUS_TTL),
+            submitted: HashMap::new(),
             pending_txns: vec![],
             prng,
             block_capacity,
@@ -108,7 +154,8 @@ where
         Ok(SubmissionServer {
             committed_state: ledger_state,
             block: None,
-            txn_status: HashMap::new(),
+            txn_status: TxnStatusStore::new(DEFAULT_TXN_STATUS_TTL),
+            submitted: HashMap::new(),
             pending_txns: vec![],
             prng,
             block_capacity: 0,
@@ -119,7 +166,46 @@ where
 
     /// Query operation results
     pub fn get_txn_status(&self, txn_handle: &TxnHandle) -> Option<TxnStatus> {
-        self.txn_status.get(txn_handle).cloned()
+        self.txn_status.get(txn_handle)
+    }
+
+    /// Set the number of blocks for which statuses are kept
+    pub fn set_txn_status_ttl(&mut self, ttl: BlockHeight) {
+        self.txn_status.set_ttl(ttl);
+    }
+
+    /// Evict expired statuses, call this once per block
+    pub fn prune_txn_status(&mut self) {
+        let h = self.cur_height();
+        self.txn_status.prune(h);
+        let expired = h.saturating_sub(self.txn_status.ttl());
+        self.submitted.retain(|_, sh| expired <= *sh);
+        metrics::TXN_STATUSES.set(self.txn_status.len() as i64);
+    }
+
+    /// Record a rejection found out of the ledger, eg. in `DeliverTx`
+    pub fn reject_transaction(&mut self, txn: &Transaction, rejection: TxnRejection) {
+        let h = self.cur_height();
+        self.txn_status
+            .set(TxnHandle::new(txn), TxnStatus::Rejected(rejection), h);
+    }
+
+    /// Record a rejection found by `CheckTx`, only if the transaction
+    /// is submitted by this server, the others are gossiped by the peers,
+    /// nobody will query their statuses here.
+    pub fn reject_checked_transaction(
+        &mut self,
+        txn: &Transaction,
+        rejection: TxnRejection,
+    ) {
+        if self.submitted.remove(&TxnHandle::new(txn)).is_some() {
+            self.reject_transaction(txn, rejection);
+        }
+    }
+
+    #[inline(always)]
+    fn cur_height(&self) -> BlockHeight {
+        self.committed_state.read().get_staking().cur_height()
     }
 
     /// Determine if block is empty
@@ -182,15 +268,20 @@ where
             let mut ledger = self.committed_state.write();
             let finalized_txns = ledger.finish_block(block).c(d!())?;
             // Update status of all committed transactions
+            let h = ledger.get_staking().cur_height();
             for (txn_temp_sid, handle, _txn) in self.pending_txns.drain(..) {
                 let committed_txn_info = finalized_txns.get(&txn_temp_sid).c(d!())?;
-                self.txn_status
-                    .insert(handle, TxnStatus::Committed(committed_txn_info.clone()));
+                self.txn_status.set(
+                    handle,
+                    TxnStatus::Committed(committed_txn_info.clone()),
+                    h,
+                );
             }
             info!("Block ended. Statuses of committed transactions are now updated");
             // Empty temp_sids after the block is finished
             // If begin_commit or end_commit is no longer empty, move this line to the end of end_commit
             self.pending_txns = Vec::new();
+            metrics::PENDING_TXS.set(0);
             // Finally, return the finalized txn sids
             debug_assert!(self.block.is_none());
             return Ok(());
@@ -218,23 +309,28 @@ where
         let mut block = self.block.as_mut().unwrap();
         let ledger = self.committed_state.read();
         let handle = TxnHandle::new(&txn);
-        let temp_sid = TxnEffect::compute_effect(txn.clone())
-            .c(d!("Failed to compute txn effect"))
+        self.submitted.remove(&handle);
+        let h = ledger.get_staking().cur_height();
+        let temp_sid = TxnEffect::compute_effect_with_op_index(txn.clone())
+            .map_err(|(idx, e)| (RejectionCode::InvalidOperation, Some(idx), e))
             .and_then(|txn_effect| {
                 ledger
                     .apply_transaction(&mut block, txn_effect, false)
                     .c(d!("Failed to apply transaction"))
+                    .map_err(|e| (RejectionCode::LedgerCheck, None, e))
             });
         match temp_sid {
             Ok(temp_sid) => {
                 self.pending_txns.push((temp_sid, handle.clone(), txn));
-                self.txn_status.insert(handle.clone(), TxnStatus::Pending);
+                metrics::PENDING_TXS.set(self.pending_txns.len() as i64);
+                self.txn_status.set(handle.clone(), TxnStatus::Pending, h);
                 Ok(handle)
             }
-            Err(e) => {
+            Err((code, op_index, e)) => {
                 e.print(None);
+                let rejection = TxnRejection::new(code, op_index, e.generate_log(None));
                 self.txn_status
-                    .insert(handle.clone(), TxnStatus::Rejected(e.generate_log(None)));
+                    .set(handle.clone(), TxnStatus::Rejected(rejection), h);
                 Err(handle)
             }
         }
@@ -243,8 +339,16 @@ where
     /// Handle the whole process when there's a new transaction
     pub fn handle_transaction(&mut self, txn: Transaction) -> Result<TxnHandle> {
         let txn_handle = TxnHandle::new(&txn);
-        self.txn_forwarder.forward_txn(txn).c(d!())?;
-        Ok(txn_handle)
+        let h = self.cur_height();
+        self.submitted.insert(txn_handle.clone(), h);
+        let res = self.txn_forwarder.forward_txn(txn).c(d!());
+        if res.is_err() {
+            self.submitted.remove(&txn_handle);
+        }
+        metrics::FORWARDED_TXS
+            .with_label_values(&[alt!(res.is_ok(), "ok", "failed")])
+            .inc();
+        res.map(|_| txn_handle)
     }
 
     #[allow(missing_docs)]
@@ -253,6 +357,27 @@ where
     }
 }
 
+/// Check a transaction without changing the ledger state, this is needed by `CheckTx`,
+/// the custom policies of the ledger are also run, so that the transactions
+/// they reject never enter the mempool, the caller decides whether to record the rejection.
+pub fn check_transaction(
+    txn: &Transaction,
+    ledger: &LedgerState,
+) -> StdResult<(), TxnRejection> {
+    let txe =
+        TxnEffect::compute_effect_with_op_index(txn.clone()).map_err(|(idx, e)| {
+            TxnRejection::new(
+                RejectionCode::InvalidOperation,
+                Some(idx),
+                e.generate_log(None),
+            )
+        })?;
+
+    ledger.check_txn_policies(&txe).map_err(|e| {
+        TxnRejection::new(RejectionCode::LedgerCheck, None, e.generate_log(None))
+    })
+}
+
 /// Convert incoming tx data to the proper Transaction format
 pub fn convert_tx(tx: &[u8]) -> Option<Transaction> {
     let transaction: Option<Transaction> = serde_json::from_slice(tx).ok();
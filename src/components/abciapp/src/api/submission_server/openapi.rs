@@ -0,0 +1,171 @@
+//!
+//! # OpenAPI document of the submission server
+//!
+//! Every route of `SubmissionRoutes` must be described here,
+//! the `match` will not compile if a new one is not.
+//!
+
+use super::{
+    simulation::{SimulatedEffect, TxnSimulation},
+    submission_api::*,
+    RejectionCode, TxnForward, TxnHandle, TxnRejection, TxnStatus,
+};
+use crate::api::openapi::{openapi_json, ApiSchema, ApiSpec, Registry};
+use finutils::api::NetworkRoute;
+use ledger::data_model::{AssetTypeCode, TxnSID, TxoSID};
+use rand_core::{CryptoRng, RngCore};
+use serde_json::{json, Value};
+use strum::IntoEnumIterator;
+
+impl ApiSchema for TxnHandle {
+    fn name() -> Option<String> {
+        Some("TxnHandle".to_owned())
+    }
+
+    fn schema(_: &mut Registry) -> Value {
+        json!({ "type": "string", "description": "hash of a transaction" })
+    }
+
+    fn examples() -> Vec<Value> {
+        crate::api::openapi::examples(vec![TxnHandle("a1b2".to_owned())])
+    }
+}
+
+impl ApiSchema for RejectionCode {
+    fn name() -> Option<String> {
+        Some("RejectionCode".to_owned())
+    }
+
+    fn schema(_: &mut Registry) -> Value {
+        json!({
+            "type": "string",
+            "enum": ["InvalidTxn", "InvalidOperation", "LedgerCheck"],
+        })
+    }
+
+    fn examples() -> Vec<Value> {
+        crate::api::openapi::examples(vec![
+            RejectionCode::InvalidTxn,
+            RejectionCode::InvalidOperation,
+            RejectionCode::LedgerCheck,
+        ])
+    }
+}
+
+api_object!(TxnRejection, {
+    code: RejectionCode,
+    op_index: Option<usize>,
+    message: String,
+}, examples: [TxnRejection {
+    code: RejectionCode::InvalidOperation,
+    op_index: Some(0),
+    message: "insufficient fee".to_owned(),
+}]);
+
+// an externally tagged enum of serde
+impl ApiSchema for TxnStatus {
+    fn name() -> Option<String> {
+        Some("TxnStatus".to_owned())
+    }
+
+    fn schema(reg: &mut Registry) -> Value {
+        let rejected = crate::api::openapi::object(vec![(
+            "Rejected",
+            reg.schema::<TxnRejection>(),
+        )]);
+        let committed = crate::api::openapi::object(vec![(
+            "Committed",
+            reg.schema::<(TxnSID, Vec<TxoSID>)>(),
+        )]);
+        json!({
+            "oneOf": [
+                { "type": "string", "enum": ["Pending"] },
+                rejected,
+                committed,
+            ]
+        })
+    }
+
+    fn examples() -> Vec<Value> {
+        crate::api::openapi::examples(vec![
+            TxnStatus::Pending,
+            TxnStatus::Committed((TxnSID(1), vec![TxoSID(2), TxoSID(3)])),
+            TxnStatus::Rejected(TxnRejection {
+                code: RejectionCode::LedgerCheck,
+                op_index: None,
+                message: "double spending".to_owned(),
+            }),
+        ])
+    }
+}
+
+api_object!(SimulatedEffect, {
+    txn_sid: TxnSID,
+    txo_sids: Vec<Option<TxoSID>>,
+    input_txos: Vec<TxoSID>,
+    new_asset_codes: Vec<AssetTypeCode>,
+    issuance_amounts: Vec<(AssetTypeCode, u64)>,
+    memo_updates: Vec<AssetTypeCode>,
+    staking_ops: usize,
+}, examples: [SimulatedEffect::default()]);
+
+api_object!(TxnSimulation, {
+    handle: TxnHandle,
+    fee_ok: bool,
+    accepted: bool,
+    effect: Option<SimulatedEffect>,
+    rejection: Option<TxnRejection>,
+}, examples: [TxnSimulation {
+    handle: TxnHandle("a1b2".to_owned()),
+    fee_ok: true,
+    accepted: true,
+    effect: Some(SimulatedEffect::default()),
+    rejection: None,
+}]);
+
+/// Describe the routes registered in `SubmissionApi::create`
+pub fn spec<RNG, TF>() -> ApiSpec
+where
+    RNG: 'static + RngCore + CryptoRng + Sync + Send,
+    TF: 'static + TxnForward + Sync + Send,
+{
+    let mut spec = ApiSpec::new("Findora submission server");
+
+    for r in SubmissionRoutes::iter() {
+        match r {
+            SubmissionRoutes::SubmitTransaction => spec.post(
+                &r.route(),
+                "submit a transaction, returns its handle",
+                submit_transaction::<RNG, TF>,
+            ),
+            SubmissionRoutes::SimulateTransaction => spec.post(
+                &r.route(),
+                "dry-run a transaction, 503 if too many are running",
+                simulate_transaction::<RNG, TF>,
+            ),
+            SubmissionRoutes::TxnStatus => spec.get(
+                &r.with_arg_template("handle"),
+                "status of a transaction, a serialized `TxnStatus` if found",
+                txn_status::<RNG, TF>,
+            ),
+            SubmissionRoutes::TxnStatusBatch => spec.post(
+                &r.route(),
+                "statuses of transactions, `null` if unknown",
+                txn_status_batch::<RNG, TF>,
+            ),
+            SubmissionRoutes::Ping => {
+                spec.get(&r.route(), "liveness of the server", ping)
+            }
+            SubmissionRoutes::Version => {
+                spec.get(&r.route(), "git commit and build date", version)
+            }
+            SubmissionRoutes::OpenApi => {
+                spec.get(&r.route(), "this document", openapi_json)
+            }
+            // not served
+            SubmissionRoutes::ForceEndBlock => &mut spec,
+        };
+    }
+
+    spec
+}
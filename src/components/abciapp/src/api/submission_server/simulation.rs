@@ -0,0 +1,207 @@
+//!
+//! # Dry-run of transactions
+//!
+//! Check a transaction against a copy of the current block,
+//! nothing will be persisted or forwarded to tendermint.
+//!
+//! Only the parts of the block needed by the checks are copied,
+//! the staking simulator is only copied for the staking operations,
+//! and at most `SIMULATION_CONCURRENCY_MAX` simulations run at the same time.
+//!
+
+use super::{RejectionCode, SubmissionServer, TxnForward, TxnHandle, TxnRejection};
+use ledger::{
+    data_model::{AssetTypeCode, BlockEffect, Transaction, TxnEffect, TxnSID, TxoSID},
+    staking::is_coinbase_tx,
+};
+use rand_core::{CryptoRng, RngCore};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    result::Result as StdResult,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Max number of simulations running at the same time
+pub const SIMULATION_CONCURRENCY_MAX: usize = 4;
+
+static SIMULATING: AtomicUsize = AtomicUsize::new(0);
+
+// A slot of the running simulations, released on drop
+struct SimulationSlot;
+
+impl SimulationSlot {
+    fn acquire() -> Option<Self> {
+        if SIMULATION_CONCURRENCY_MAX <= SIMULATING.fetch_add(1, Ordering::SeqCst) {
+            SIMULATING.fetch_sub(1, Ordering::SeqCst);
+            None
+        } else {
+            Some(SimulationSlot)
+        }
+    }
+}
+
+impl Drop for SimulationSlot {
+    fn drop(&mut self) {
+        SIMULATING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The result of a dry-run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TxnSimulation {
+    #[allow(missing_docs)]
+    pub handle: TxnHandle,
+    /// Whether the fee is paid, see `Transaction::check_fee`
+    pub fee_ok: bool,
+    /// Whether the transaction will be accepted
+    pub accepted: bool,
+    /// Present if `accepted` is true
+    pub effect: Option<SimulatedEffect>,
+    /// Present if `accepted` is false
+    pub rejection: Option<TxnRejection>,
+}
+
+/// What the transaction would do if it were committed
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SimulatedEffect {
+    /// Expected position of the transaction
+    pub txn_sid: TxnSID,
+    /// Expected positions of the outputs, in order,
+    /// none for the outputs spent within the transaction itself,
+    /// NOTE: positions will shift if other transactions are committed before it.
+    pub txo_sids: Vec<Option<TxoSID>>,
+    /// Utxos to be spent
+    pub input_txos: Vec<TxoSID>,
+    /// Assets to be defined
+    pub new_asset_codes: Vec<AssetTypeCode>,
+    /// Amounts to be issued
+    pub issuance_amounts: Vec<(AssetTypeCode, u64)>,
+    /// Assets whose memos are to be updated
+    pub memo_updates: Vec<AssetTypeCode>,
+    /// Number of staking operations
+    pub staking_ops: usize,
+}
+
+impl<RNG, TF> SubmissionServer<RNG, TF>
+where
+    RNG: RngCore + CryptoRng,
+    TF: TxnForward,
+{
+    /// Run all the checks of `cache_transaction` on a throwaway copy
+    /// of the current block and its staking simulator,
+    /// fail if too many simulations are running.
+    pub fn simulate_transaction(&self, txn: Transaction) -> Result<TxnSimulation> {
+        let _slot = SimulationSlot::acquire()
+            .c(d!("too many simulations are running, retry later"))?;
+
+        let handle = TxnHandle::new(&txn);
+        let fee_ok = txn.check_fee();
+
+        let (effect, rejection) = match self.simulate(txn) {
+            Ok(effect) => (Some(effect), None),
+            Err(rejection) => (None, Some(rejection)),
+        };
+
+        Ok(TxnSimulation {
+            handle,
+            fee_ok,
+            accepted: effect.is_some(),
+            effect,
+            rejection,
+        })
+    }
+
+    fn simulate(&self, txn: Transaction) -> StdResult<SimulatedEffect, TxnRejection> {
+        let ledger = self.committed_state.read();
+
+        let h = ledger.get_staking().cur_height();
+        if is_coinbase_tx(&txn) || !txn.is_basic_valid(h as i64) {
+            return Err(TxnRejection::new(
+                RejectionCode::InvalidTxn,
+                None,
+                "failed to pass the basic checks".to_owned(),
+            ));
+        }
+
+        let te = TxnEffect::compute_effect_with_op_index(txn).map_err(|(idx, e)| {
+            TxnRejection::new(
+                RejectionCode::InvalidOperation,
+                Some(idx),
+                e.generate_log(None),
+            )
+        })?;
+
+        let mut effect = SimulatedEffect {
+            input_txos: te.input_txos.keys().copied().collect(),
+            new_asset_codes: te.new_asset_codes.keys().copied().collect(),
+            issuance_amounts: te
+                .issuance_amounts
+                .iter()
+                .map(|(code, am)| (*code, *am))
+                .collect(),
+            memo_updates: te.memo_updates.iter().map(|(code, _, _)| *code).collect(),
+            staking_ops: te.delegations.len()
+                + te.undelegations.len()
+                + te.claims.len()
+                + te.governances.len()
+                + te.update_validators.len()
+                + te.fra_distributions.len()
+                + te.update_stakers.len()
+                + te.transfer_plans.len()
+                + te.rotate_keys.len(),
+            ..Default::default()
+        };
+        effect.input_txos.sort_unstable();
+
+        let mut block = BlockEffect::default();
+        let (mut pending_txns, mut pending_txos) = (0, 0);
+        if let Some(b) = self.block.as_ref() {
+            // `BlockEffect::check_txn_effect` looks for replays in the transactions,
+            // which are not copied
+            if b.no_replay_tokens.contains(&te.txn.body.no_replay_token) {
+                return Err(TxnRejection::new(
+                    RejectionCode::LedgerCheck,
+                    None,
+                    "replayed in the current block".to_owned(),
+                ));
+            }
+            block.input_txos = b.input_txos.clone();
+            block.new_asset_codes = b.new_asset_codes.clone();
+            block.new_issuance_nums = b.new_issuance_nums.clone();
+            block.issuance_amounts = b.issuance_amounts.clone();
+            block.issuance_keys = b.issuance_keys.clone();
+            block.memo_updates = b.memo_updates.clone();
+            if 0 < effect.staking_ops {
+                block.staking_simulator = b.staking_simulator.clone();
+            }
+            pending_txns = b.txns.len();
+            pending_txos = b.txos.iter().map(|txos| txos.len() as u64).sum::<u64>();
+        } else if 0 < effect.staking_ops {
+            // the same as `update_staking_simulator`
+            block.staking_simulator = ledger.get_staking().clone();
+        }
+
+        ledger
+            .apply_transaction(&mut block, te, false)
+            .c(d!())
+            .map_err(|e| {
+                TxnRejection::new(RejectionCode::LedgerCheck, None, e.generate_log(None))
+            })?;
+
+        // every txo takes a position, see `LedgerStatus::apply_block_effects`,
+        // the copied block only contains this transaction
+        let mut next_txo = ledger.get_next_txo().0 + pending_txos;
+        effect.txn_sid = TxnSID(ledger.get_next_txn().0 + pending_txns);
+        effect.txo_sids = block.txos[0]
+            .iter()
+            .map(|txo| {
+                let sid = TxoSID(next_txo);
+                next_txo += 1;
+                txo.as_ref().map(|_| sid)
+            })
+            .collect();
+
+        Ok(effect)
+    }
+}
@@ -2,7 +2,15 @@
 //! # interface of operating tx
 //!
 
-use super::{SubmissionServer, TxnForward, TxnHandle};
+use super::{
+    openapi, simulation::TxnSimulation, SubmissionServer, TxnForward, TxnHandle,
+    TxnStatus,
+};
+use crate::api::{
+    metrics::HttpMetrics,
+    openapi::openapi_json,
+    throttle::{Throttle, ThrottleConfig},
+};
 use actix_cors::Cors;
 use actix_web::{error, middleware, web, App, HttpServer};
 use finutils::api::NetworkRoute;
@@ -13,16 +21,17 @@ use rand_core::{CryptoRng, RngCore};
 use ruc::*;
 use std::result::Result as StdResult;
 use std::sync::Arc;
+use strum::EnumIter;
 
 /// Ping route to check for liveness of API
 #[allow(clippy::unnecessary_wraps)]
-async fn ping() -> actix_web::Result<String> {
+pub(super) async fn ping() -> actix_web::Result<String> {
     Ok("success".into())
 }
 
 /// Returns the git commit hash and commit date of this build
 #[allow(clippy::unnecessary_wraps)]
-async fn version() -> actix_web::Result<String> {
+pub(super) async fn version() -> actix_web::Result<String> {
     Ok(format!(
         "Build: {} {}",
         option_env!("VERGEN_SHA_EXTERN").unwrap_or(env!("VERGEN_SHA")),
@@ -76,27 +85,81 @@ where
     Ok(res)
 }
 
+/// Dry-run a transaction, nothing will be persisted or sent to tendermint
+pub async fn simulate_transaction<RNG, TF>(
+    data: web::Data<Arc<RwLock<SubmissionServer<RNG, TF>>>>,
+    body: web::Json<Transaction>,
+) -> StdResult<web::Json<TxnSimulation>, actix_web::error::Error>
+where
+    RNG: RngCore + CryptoRng,
+    TF: TxnForward + Sync + Send,
+{
+    let tx = body.into_inner();
+    data.read()
+        .simulate_transaction(tx)
+        .map(web::Json)
+        .map_err(|e| error::ErrorServiceUnavailable(e.generate_log(None)))
+}
+
+/// Max number of handles in a batch query
+const TXN_STATUS_BATCH_MAX: usize = 1000;
+
+/// Queries the statuses of many transactions at once,
+/// the results are in the same order as the handles,
+/// `null` means the handle is unknown or has expired.
+pub async fn txn_status_batch<RNG, TF>(
+    data: web::Data<Arc<RwLock<SubmissionServer<RNG, TF>>>>,
+    body: web::Json<Vec<String>>,
+) -> StdResult<web::Json<Vec<Option<TxnStatus>>>, actix_web::error::Error>
+where
+    RNG: RngCore + CryptoRng,
+    TF: TxnForward + Sync + Send,
+{
+    let handles = body.into_inner();
+    if TXN_STATUS_BATCH_MAX < handles.len() {
+        return Err(error::ErrorBadRequest(format!(
+            "too many handles, at most {} are allowed",
+            TXN_STATUS_BATCH_MAX
+        )));
+    }
+
+    let submission_server = data.read();
+    Ok(web::Json(
+        handles
+            .into_iter()
+            .map(|h| submission_server.get_txn_status(&TxnHandle(h)))
+            .collect(),
+    ))
+}
+
 /// Structures exposed to the outside world
 pub struct SubmissionApi;
 
 /// Define interface
 #[allow(missing_docs)]
+#[derive(EnumIter)]
 pub enum SubmissionRoutes {
     SubmitTransaction,
+    SimulateTransaction,
     TxnStatus,
+    TxnStatusBatch,
     Ping,
     ForceEndBlock,
     Version,
+    OpenApi,
 }
 
 impl NetworkRoute for SubmissionRoutes {
     fn route(&self) -> String {
         let endpoint = match *self {
             SubmissionRoutes::SubmitTransaction => "submit_transaction",
+            SubmissionRoutes::SimulateTransaction => "simulate_transaction",
             SubmissionRoutes::TxnStatus => "txn_status",
+            SubmissionRoutes::TxnStatusBatch => "txn_status_batch",
             SubmissionRoutes::Ping => "ping",
             SubmissionRoutes::ForceEndBlock => "force_end_block",
             SubmissionRoutes::Version => "version",
+            SubmissionRoutes::OpenApi => "openapi.json",
         };
         "/".to_owned() + endpoint
     }
@@ -111,25 +174,45 @@ impl SubmissionApi {
         submission_server: Arc<RwLock<SubmissionServer<RNG, TF>>>,
         host: &str,
         port: u16,
+        throttle: ThrottleConfig,
     ) -> Result<SubmissionApi> {
         let _ = actix_rt::System::new("findora API");
 
+        let throttle = Throttle::new("submission", throttle);
+
+        let spec = openapi::spec::<RNG, TF>().to_json();
+
         HttpServer::new(move || {
             App::new()
+                .wrap(throttle.clone())
+                .wrap(HttpMetrics("submission"))
                 .wrap(middleware::Logger::default())
                 .wrap(Cors::permissive().supports_credentials())
                 .data(web::JsonConfig::default().limit(2048 * 1024))
                 .data(submission_server.clone())
+                .data(spec.clone())
                 .route(
                     &SubmissionRoutes::SubmitTransaction.route(),
                     web::post().to(submit_transaction::<RNG, TF>),
                 )
+                .route(
+                    &SubmissionRoutes::SimulateTransaction.route(),
+                    web::post().to(simulate_transaction::<RNG, TF>),
+                )
                 .route(&SubmissionRoutes::Ping.route(), web::get().to(ping))
                 .route(&SubmissionRoutes::Version.route(), web::get().to(version))
+                .route(
+                    &SubmissionRoutes::OpenApi.route(),
+                    web::get().to(openapi_json),
+                )
                 .route(
                     &SubmissionRoutes::TxnStatus.with_arg_template("handle"),
                     web::get().to(txn_status::<RNG, TF>),
                 )
+                .route(
+                    &SubmissionRoutes::TxnStatusBatch.route(),
+                    web::post().to(txn_status_batch::<RNG, TF>),
+                )
         })
         .bind(&format!("{}:{}", host, port))
         .c(d!())?
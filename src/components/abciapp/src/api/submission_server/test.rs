@@ -0,0 +1,248 @@
+#![allow(missing_docs)]
+
+use super::{
+    submission_api::txn_status_batch, txn_status::TxnStatusStore, RejectionCode,
+    SubmissionServer, TxnForward, TxnHandle, TxnRejection, TxnStatus,
+};
+use actix_web::web;
+use ledger::{
+    data_model::{
+        Operation, Transaction, TransferAsset, TransferAssetBody, TransferType, TxnSID,
+        TxoRef, TxoSID, ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY, TX_FEE_MIN,
+    },
+    store::{bnc, utils::fra_gen_initial_tx, LedgerState},
+};
+use parking_lot::RwLock;
+use rand_chacha::ChaChaRng;
+use rand_core::{RngCore, SeedableRng};
+use ruc::*;
+use std::sync::Arc;
+use zei::xfr::{
+    asset_record::{open_blind_asset_record, AssetRecordType},
+    sig::XfrKeyPair,
+    structs::{AssetRecord, AssetRecordTemplate},
+};
+
+struct NoForward;
+
+impl AsRef<str> for NoForward {
+    fn as_ref(&self) -> &str {
+        ""
+    }
+}
+
+impl TxnForward for NoForward {
+    fn forward_txn(&self, _: Transaction) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn rejection() -> TxnRejection {
+    TxnRejection::new(RejectionCode::InvalidTxn, None, "invalid".to_owned())
+}
+
+fn new_server() -> SubmissionServer<ChaChaRng, NoForward> {
+    let ledger = Arc::new(RwLock::new(LedgerState::tmp_ledger()));
+    pnk!(SubmissionServer::new_no_auto_commit(
+        ChaChaRng::from_entropy(),
+        ledger,
+        NoForward
+    ))
+}
+
+#[test]
+fn txn_status_store() {
+    let path = format!("test/txn_status/{}", ChaChaRng::from_entropy().next_u64());
+    let pending = TxnHandle("pending".to_owned());
+    let committed = TxnHandle("committed".to_owned());
+    let committed_status = TxnStatus::Committed((TxnSID(1), vec![TxoSID(2)]));
+
+    let mut store = TxnStatusStore::open(&path, 10);
+    assert!(store.is_empty());
+
+    store.set(pending.clone(), TxnStatus::Pending, 1);
+    store.set(committed.clone(), TxnStatus::Pending, 1);
+    store.set(committed.clone(), committed_status.clone(), 3);
+    assert_eq!(2, store.len());
+
+    // A committed status is final
+    store.set(committed.clone(), TxnStatus::Rejected(rejection()), 4);
+    assert_eq!(Some(committed_status.clone()), store.get(&committed));
+
+    // Statuses and their heights are loaded after a restart
+    drop(store);
+    bnc::flush_data();
+    let mut store = TxnStatusStore::open(&path, 10);
+    assert_eq!(2, store.len());
+    assert_eq!(Some(TxnStatus::Pending), store.get(&pending));
+    assert_eq!(Some(committed_status.clone()), store.get(&committed));
+
+    // Only the statuses updated in the latest `ttl` blocks are kept
+    store.prune(11);
+    assert_eq!(2, store.len());
+    store.prune(12);
+    assert_eq!(1, store.len());
+    assert!(store.get(&pending).is_none());
+    assert_eq!(Some(committed_status), store.get(&committed));
+    store.prune(14);
+    assert!(store.is_empty());
+    assert!(store.get(&committed).is_none());
+}
+
+#[test]
+fn txn_status_of_checked_txns() {
+    let mut server = new_server();
+    let submitted = Transaction::from_seq_id(0);
+    let gossiped = Transaction::from_seq_id(0);
+
+    let handle = pnk!(server.handle_transaction(submitted.clone()));
+    assert_eq!(handle, TxnHandle::new(&submitted));
+
+    // Only the transactions submitted to this server are recorded
+    server.reject_checked_transaction(&submitted, rejection());
+    server.reject_checked_transaction(&gossiped, rejection());
+    assert_eq!(
+        Some(TxnStatus::Rejected(rejection())),
+        server.get_txn_status(&handle)
+    );
+    assert!(server.get_txn_status(&TxnHandle::new(&gossiped)).is_none());
+
+    // But all the delivered transactions are
+    server.reject_transaction(&gossiped, rejection());
+    assert_eq!(
+        Some(TxnStatus::Rejected(rejection())),
+        server.get_txn_status(&TxnHandle::new(&gossiped))
+    );
+}
+
+#[test]
+fn txn_status_batch_route() {
+    let mut server = new_server();
+    let tx = Transaction::from_seq_id(0);
+    let handle = pnk!(server.handle_transaction(tx.clone()));
+    server.reject_checked_transaction(&tx, rejection());
+    let data = web::Data::new(Arc::new(RwLock::new(server)));
+
+    let statuses = futures::executor::block_on(txn_status_batch(
+        data.clone(),
+        web::Json(vec![handle.0.clone(), "unknown".to_owned(), handle.0]),
+    ))
+    .unwrap()
+    .into_inner();
+    assert_eq!(
+        statuses,
+        vec![
+            Some(TxnStatus::Rejected(rejection())),
+            None,
+            Some(TxnStatus::Rejected(rejection()))
+        ]
+    );
+
+    // Too many handles
+    assert!(futures::executor::block_on(txn_status_batch(
+        data,
+        web::Json(vec!["unknown".to_owned(); 1001])
+    ))
+    .is_err());
+}
+
+// Pay the fee with the FRA in `sid`, the change is sent back to the owner
+fn gen_fee_tx(
+    server: &SubmissionServer<ChaChaRng, NoForward>,
+    sid: TxoSID,
+    owner: &XfrKeyPair,
+) -> Transaction {
+    let ledger = server.get_committed_state().read();
+    let bar = ledger.get_utxo_light(sid).unwrap().utxo.0.record;
+    let oar = pnk!(open_blind_asset_record(&bar, &None, owner));
+    drop(ledger);
+
+    let mut prng = ChaChaRng::from_entropy();
+    let outputs = [
+        (oar.amount - TX_FEE_MIN, owner.get_pk()),
+        (TX_FEE_MIN, *BLACK_HOLE_PUBKEY),
+    ]
+    .iter()
+    .map(|(am, pk)| {
+        let template = AssetRecordTemplate::with_no_asset_tracing(
+            *am,
+            ASSET_TYPE_FRA,
+            AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+            *pk,
+        );
+        pnk!(AssetRecord::from_template_no_identity_tracing(
+            &mut prng, &template
+        ))
+    })
+    .collect::<Vec<_>>();
+
+    let mut transfer = pnk!(TransferAsset::new(pnk!(TransferAssetBody::new(
+        &mut prng,
+        vec![TxoRef::Absolute(sid)],
+        &[AssetRecord::from_open_asset_record_no_asset_tracing(oar)],
+        &outputs,
+        None,
+        vec![],
+        TransferType::Standard,
+    ))));
+    transfer.sign(owner);
+
+    Transaction::from_operation(Operation::TransferAsset(transfer), 0)
+}
+
+#[test]
+fn simulate_transaction() {
+    let mut server = new_server();
+    let fra_owner = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+
+    // Against an empty ledger
+    let tx = fra_gen_initial_tx(&fra_owner);
+    let sim = pnk!(server.simulate_transaction(tx.clone()));
+    assert!(sim.accepted);
+    assert_eq!(sim.handle, TxnHandle::new(&tx));
+    let effect = sim.effect.unwrap();
+    assert_eq!(effect.txn_sid, TxnSID(0));
+    assert_eq!(effect.txo_sids, vec![Some(TxoSID(0)), Some(TxoSID(1))]);
+
+    pnk!(server.cache_transaction(tx.clone()));
+    pnk!(server.end_block());
+
+    // A replay of a committed transaction
+    let sim = pnk!(server.simulate_transaction(tx));
+    assert!(!sim.accepted);
+    assert!(sim.effect.is_none());
+    assert!(sim.rejection.is_some());
+
+    // The positions come after the transactions in the pending block
+    let (next_txn, next_txo) = {
+        let ledger = server.get_committed_state().read();
+        (ledger.get_next_txn().0, ledger.get_next_txo().0)
+    };
+    let pending = gen_fee_tx(&server, TxoSID(0), &fra_owner);
+    pnk!(server.cache_transaction(pending.clone()));
+    let sim =
+        pnk!(server.simulate_transaction(gen_fee_tx(&server, TxoSID(1), &fra_owner)));
+    assert!(sim.fee_ok);
+    assert!(sim.accepted);
+    let effect = sim.effect.unwrap();
+    assert_eq!(effect.txn_sid, TxnSID(next_txn + 1));
+    assert_eq!(
+        effect.txo_sids,
+        vec![Some(TxoSID(next_txo + 2)), Some(TxoSID(next_txo + 3))]
+    );
+    assert_eq!(effect.input_txos, vec![TxoSID(1)]);
+
+    // Spent by the pending block, or replayed in it
+    for tx in [gen_fee_tx(&server, TxoSID(0), &fra_owner), pending].iter() {
+        let sim = pnk!(server.simulate_transaction(tx.clone()));
+        assert!(!sim.accepted);
+        assert_eq!(sim.rejection.unwrap().code, RejectionCode::LedgerCheck);
+    }
+
+    // Nothing is changed by the simulations
+    assert_eq!(1, server.block_txn_count());
+    assert_eq!(
+        next_txn,
+        server.get_committed_state().read().get_next_txn().0
+    );
+}
@@ -0,0 +1,110 @@
+//!
+//! # Persistent storage of transaction statuses
+//!
+//! Statuses are kept on disk, so they are still available after a restart,
+//! and are evicted `ttl` blocks after their last update.
+//!
+
+use super::{TxnHandle, TxnStatus};
+use ledger::{
+    staking::BlockHeight,
+    store::bnc::{self, new_mapx, Mapx},
+};
+use std::collections::{BTreeMap, HashSet};
+
+/// The default number of blocks for which a status is kept
+pub const DEFAULT_TXN_STATUS_TTL: BlockHeight = 100_000;
+
+/// Statuses of transactions, bounded by age
+pub struct TxnStatusStore {
+    // handle => (status, height of the last update)
+    statuses: Mapx<TxnHandle, (TxnStatus, BlockHeight)>,
+    // height of the last update => handles,
+    // only live in memory, rebuilt from `statuses` at start
+    by_height: BTreeMap<BlockHeight, HashSet<TxnHandle>>,
+    ttl: BlockHeight,
+}
+
+impl TxnStatusStore {
+    /// Open the store, statuses saved before will be loaded.
+    pub fn new(ttl: BlockHeight) -> Self {
+        Self::open("submission_server_subdata/txn_status", ttl)
+    }
+
+    /// Open the store at `path`, relative to the data dir of `bnc`.
+    pub(crate) fn open(path: &str, ttl: BlockHeight) -> Self {
+        let statuses: Mapx<TxnHandle, (TxnStatus, BlockHeight)> = new_mapx!(path);
+
+        let mut by_height = BTreeMap::new();
+        for (handle, (_, h)) in statuses.iter() {
+            by_height
+                .entry(h)
+                .or_insert_with(HashSet::new)
+                .insert(handle);
+        }
+
+        TxnStatusStore {
+            statuses,
+            by_height,
+            ttl,
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn get(&self, handle: &TxnHandle) -> Option<TxnStatus> {
+        self.statuses.get(handle).map(|(status, _)| status)
+    }
+
+    /// Set the status of a transaction at height `h`,
+    /// a committed status is final, it will not be overwritten,
+    /// eg. by a rejection of a replayed transaction.
+    pub fn set(&mut self, handle: TxnHandle, status: TxnStatus, h: BlockHeight) {
+        if let Some((old, old_h)) = self.statuses.get(&handle) {
+            if let TxnStatus::Committed(_) = old {
+                return;
+            }
+            if let Some(hdrs) = self.by_height.get_mut(&old_h) {
+                hdrs.remove(&handle);
+                if hdrs.is_empty() {
+                    self.by_height.remove(&old_h);
+                }
+            }
+        }
+
+        self.by_height
+            .entry(h)
+            .or_insert_with(HashSet::new)
+            .insert(handle.clone());
+        self.statuses.insert(handle, (status, h));
+    }
+
+    /// Evict all the statuses which have not been updated
+    /// in the latest `ttl` blocks before height `h`.
+    pub fn prune(&mut self, h: BlockHeight) {
+        let kept = self.by_height.split_off(&h.saturating_sub(self.ttl));
+        for handle in self.by_height.values().flatten() {
+            self.statuses.remove(handle);
+        }
+        self.by_height = kept;
+    }
+
+    /// Number of statuses kept
+    pub fn len(&self) -> usize {
+        self.by_height.values().map(|hdrs| hdrs.len()).sum()
+    }
+
+    #[allow(missing_docs)]
+    pub fn is_empty(&self) -> bool {
+        self.by_height.is_empty()
+    }
+
+    #[allow(missing_docs)]
+    pub fn set_ttl(&mut self, ttl: BlockHeight) {
+        self.ttl = ttl;
+    }
+
+    #[allow(missing_docs)]
+    pub fn ttl(&self) -> BlockHeight {
+        self.ttl
+    }
+}
@@ -0,0 +1,368 @@
+//!
+//! # Request throttling
+//!
+//! An actix middleware shared by the query server and the submission server:
+//! - per-IP rate limits, or per-API-key rate limits if the `X-Api-Key` header is present
+//! - request body size limits per route, checked with the `Content-Length` header,
+//!   and while the body is read, so chunked bodies can not bypass them
+//! - a cap of concurrent in-flight requests
+//!
+//! Rejections are counted in `ThrottleStats`,
+//! and in the `findora_http_requests_throttled_total` metric.
+//!
+
+#[cfg(test)]
+mod test;
+
+use crate::api::metrics::THROTTLED_REQUESTS;
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    error::{self, PayloadError},
+    http::header,
+    web, Error,
+};
+use futures::{
+    future::{ok, ready, Ready},
+    Stream,
+};
+use parking_lot::Mutex;
+use ruc::*;
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    result::Result as StdResult,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+/// Name of the header carrying API keys
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+// Idle buckets will be cleaned when the number of tracked clients exceeds this
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+/// Limits of a http service, `0` means unlimited
+#[derive(Clone, Debug, Default)]
+pub struct ThrottleConfig {
+    /// requests per second of every client IP
+    pub ip_rate: u32,
+    /// API key => requests per second
+    pub api_keys: HashMap<String, u32>,
+    /// max number of concurrent in-flight requests
+    pub max_concurrency: usize,
+    /// route prefix => max body size in bytes, the longest prefix wins
+    pub body_limits: Vec<(String, usize)>,
+    /// use the `X-Forwarded-For` / `Forwarded` headers to get client IPs,
+    /// only enable this behind a trusted reverse proxy
+    pub trust_proxy: bool,
+}
+
+impl ThrottleConfig {
+    /// Load API keys from a json file like `{"<key>": <requests per second>}`
+    pub fn load_api_keys(&mut self, path: &str) -> Result<()> {
+        fs::read(path)
+            .c(d!(path))
+            .and_then(|s| serde_json::from_slice(&s).c(d!()))
+            .map(|keys| {
+                self.api_keys = keys;
+            })
+    }
+
+    /// Parse body limits like `submit_transaction=2097152,graphql=65536`
+    pub fn parse_body_limits(&mut self, s: &str) -> Result<()> {
+        self.body_limits = s
+            .split(',')
+            .filter(|i| !i.is_empty())
+            .map(|i| {
+                let kv = i.splitn(2, '=').collect::<Vec<_>>();
+                if 2 != kv.len() {
+                    return Err(eg!(format!("invalid body limit: {}", i)));
+                }
+                let route = "/".to_owned() + kv[0].trim().trim_start_matches('/');
+                kv[1].trim().parse::<usize>().c(d!()).map(|n| (route, n))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(())
+    }
+
+    fn body_limit(&self, path: &str) -> Option<usize> {
+        self.body_limits
+            .iter()
+            .filter(|(route, _)| path.starts_with(route.as_str()))
+            .max_by_key(|(route, _)| route.len())
+            .map(|(_, n)| *n)
+    }
+}
+
+/// Counters of the throttle, for monitoring
+#[allow(missing_docs)]
+#[derive(Debug, Default)]
+pub struct ThrottleStats {
+    /// name of the throttled service
+    pub service: &'static str,
+    pub allowed: AtomicU64,
+    pub rejected_rate: AtomicU64,
+    pub rejected_api_key: AtomicU64,
+    pub rejected_body_size: AtomicU64,
+    pub rejected_concurrency: AtomicU64,
+}
+
+impl ThrottleStats {
+    // Count a request in `counter`, and in the metric with the label `result`.
+    fn count(&self, counter: &AtomicU64, result: &str) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        THROTTLED_REQUESTS
+            .with_label_values(&[self.service, result])
+            .inc();
+    }
+}
+
+// A token bucket, refilled with `rate` tokens per second, at most `rate` tokens
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn take(&mut self, rate: u32, now: Instant) -> bool {
+        let rate = rate as f64;
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.last = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+// A request body which fails once more than `limit` bytes are read
+struct LimitedPayload {
+    inner: Payload,
+    limit: usize,
+    read: usize,
+    stats: Arc<ThrottleStats>,
+}
+
+impl Stream for LimitedPayload {
+    type Item = StdResult<web::Bytes, PayloadError>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.limit < this.read {
+            return Poll::Ready(None);
+        }
+
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                this.read = this.read.saturating_add(chunk.len());
+                if this.limit < this.read {
+                    this.stats
+                        .count(&this.stats.rejected_body_size, "rejected_body_size");
+                    Poll::Ready(Some(Err(PayloadError::Overflow)))
+                } else {
+                    Poll::Ready(Some(Ok(chunk)))
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+struct ThrottleState {
+    cfg: ThrottleConfig,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    in_flight: AtomicUsize,
+    stats: Arc<ThrottleStats>,
+}
+
+impl ThrottleState {
+    fn admit(&self, req: &ServiceRequest) -> StdResult<(), Error> {
+        if let Some(limit) = self.cfg.body_limit(req.path()) {
+            let len = req
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<usize>().ok());
+            if len.map(|n| limit < n).unwrap_or(false) {
+                self.stats
+                    .count(&self.stats.rejected_body_size, "rejected_body_size");
+                return Err(error::ErrorPayloadTooLarge("request body is too large"));
+            }
+        }
+
+        let (client, rate) = if let Some(key) = req.headers().get(API_KEY_HEADER) {
+            let key = key.to_str().unwrap_or_default();
+            if let Some(rate) = self.cfg.api_keys.get(key) {
+                (format!("key:{}", key), *rate)
+            } else {
+                self.stats
+                    .count(&self.stats.rejected_api_key, "rejected_api_key");
+                return Err(error::ErrorUnauthorized("invalid api key"));
+            }
+        } else {
+            let conn = req.connection_info();
+            let ip = alt!(
+                self.cfg.trust_proxy,
+                conn.realip_remote_addr(),
+                conn.remote_addr()
+            )
+            .map(|addr| {
+                // strip the port if any
+                addr.parse::<SocketAddr>()
+                    .map(|a| a.ip().to_string())
+                    .unwrap_or_else(|_| addr.to_owned())
+            })
+            .unwrap_or_default();
+            (format!("ip:{}", ip), self.cfg.ip_rate)
+        };
+
+        if 0 < rate && !self.take(client, rate) {
+            self.stats.count(&self.stats.rejected_rate, "rejected_rate");
+            return Err(error::ErrorTooManyRequests("rate limit exceeded"));
+        }
+
+        Ok(())
+    }
+
+    fn take(&self, client: String, rate: u32) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+
+        if MAX_TRACKED_CLIENTS < buckets.len() {
+            // buckets idle for more than 1 second are full, no need to keep them
+            buckets.retain(|_, b| now.duration_since(b.last).as_secs() < 1);
+        }
+
+        buckets
+            .entry(client)
+            .or_insert(Bucket {
+                tokens: rate as f64,
+                last: now,
+            })
+            .take(rate, now)
+    }
+}
+
+/// The throttle middleware, share one instance between all the workers
+#[derive(Clone)]
+pub struct Throttle(Arc<ThrottleState>);
+
+impl Throttle {
+    /// Create a throttle for the service `name`,
+    /// its requests are counted with the label `service=<name>`.
+    pub fn new(name: &'static str, cfg: ThrottleConfig) -> Self {
+        let stats = Arc::new(ThrottleStats {
+            service: name,
+            ..Default::default()
+        });
+
+        Throttle(Arc::new(ThrottleState {
+            cfg,
+            buckets: Mutex::new(HashMap::new()),
+            in_flight: AtomicUsize::new(0),
+            stats,
+        }))
+    }
+}
+
+impl<S, B> Transform<S> for Throttle
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ThrottleMiddleware<S>;
+    type Future = Ready<StdResult<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ThrottleMiddleware {
+            service,
+            state: Arc::clone(&self.0),
+        })
+    }
+}
+
+// decrease the in-flight counter when a request is finished
+struct InFlight(Arc<ThrottleState>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[allow(missing_docs)]
+pub struct ThrottleMiddleware<S> {
+    service: S,
+    state: Arc<ThrottleState>,
+}
+
+impl<S, B> Service for ThrottleMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = StdResult<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<StdResult<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: ServiceRequest) -> Self::Future {
+        if let Err(e) = self.state.admit(&req) {
+            return Box::pin(ready(Err(e)));
+        }
+
+        // the `Content-Length` header may be absent or wrong
+        if let Some(limit) = self.state.cfg.body_limit(req.path()) {
+            let payload = LimitedPayload {
+                inner: req.take_payload(),
+                limit,
+                read: 0,
+                stats: Arc::clone(&self.state.stats),
+            };
+            req.set_payload(Payload::Stream(Box::pin(payload)));
+        }
+
+        let n = self.state.in_flight.fetch_add(1, Ordering::Relaxed);
+        let guard = InFlight(Arc::clone(&self.state));
+        if 0 < self.state.cfg.max_concurrency && self.state.cfg.max_concurrency <= n {
+            let stats = &self.state.stats;
+            stats.count(&stats.rejected_concurrency, "rejected_concurrency");
+            return Box::pin(ready(Err(error::ErrorServiceUnavailable(
+                "too many concurrent requests",
+            ))));
+        }
+
+        let stats = &self.state.stats;
+        stats.count(&stats.allowed, "allowed");
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            drop(guard);
+            res
+        })
+    }
+}
@@ -0,0 +1,222 @@
+#![allow(missing_docs)]
+
+use super::{
+    Bucket, LimitedPayload, Throttle, ThrottleConfig, ThrottleStats, API_KEY_HEADER,
+};
+use actix_service::Service;
+use actix_web::{
+    dev::{Payload, ServiceResponse},
+    error::PayloadError,
+    http::{header, StatusCode},
+    test::{init_service, TestRequest},
+    web, App, Error, HttpResponse,
+};
+use futures::{executor::block_on, stream, StreamExt};
+use ruc::*;
+use std::{
+    result::Result as StdResult,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+#[test]
+fn bucket_refill() {
+    let now = Instant::now();
+    let mut b = Bucket {
+        tokens: 2.0,
+        last: now,
+    };
+
+    assert!(b.take(2, now));
+    assert!(b.take(2, now));
+    assert!(!b.take(2, now));
+
+    // refilled with `rate` tokens per second
+    let now = now + Duration::from_millis(500);
+    assert!(b.take(2, now));
+    assert!(!b.take(2, now));
+
+    // at most `rate` tokens
+    let now = now + Duration::from_secs(10);
+    assert!(b.take(2, now));
+    assert!(b.take(2, now));
+    assert!(!b.take(2, now));
+}
+
+#[test]
+fn parse_body_limits() {
+    let mut cfg = ThrottleConfig::default();
+    pnk!(cfg.parse_body_limits(
+        "submit_transaction=10, /graphql=20,,/=5,graphql/playground=30"
+    ));
+    assert_eq!(
+        cfg.body_limits,
+        vec![
+            ("/submit_transaction".to_owned(), 10),
+            ("/graphql".to_owned(), 20),
+            ("/".to_owned(), 5),
+            ("/graphql/playground".to_owned(), 30),
+        ]
+    );
+
+    // the longest prefix wins
+    assert_eq!(Some(10), cfg.body_limit("/submit_transaction"));
+    assert_eq!(Some(20), cfg.body_limit("/graphql"));
+    assert_eq!(Some(30), cfg.body_limit("/graphql/playground"));
+    assert_eq!(Some(5), cfg.body_limit("/ledger"));
+
+    pnk!(cfg.parse_body_limits(""));
+    assert!(cfg.body_limits.is_empty());
+    assert_eq!(None, cfg.body_limit("/graphql"));
+
+    for s in ["graphql", "graphql=", "graphql=-1", "graphql=1=2"].iter() {
+        assert!(cfg.parse_body_limits(s).is_err(), "{}", s);
+    }
+}
+
+#[test]
+fn rate_limits() {
+    let throttle = Throttle::new(
+        "test_rate_limits",
+        ThrottleConfig {
+            ip_rate: 1,
+            api_keys: vec![("key".to_owned(), 2)].into_iter().collect(),
+            ..Default::default()
+        },
+    );
+    let state = &throttle.0;
+    let from_ip = |ip: &str| {
+        TestRequest::default()
+            .peer_addr(pnk!(format!("{}:8000", ip).parse()))
+            .to_srv_request()
+    };
+    let with_key = |key: &str| {
+        TestRequest::default()
+            .peer_addr(pnk!("127.0.0.1:8000".parse()))
+            .header(API_KEY_HEADER, key)
+            .to_srv_request()
+    };
+
+    // Per IP
+    assert!(state.admit(&from_ip("10.0.0.1")).is_ok());
+    assert!(state.admit(&from_ip("10.0.0.1")).is_err());
+    assert!(state.admit(&from_ip("10.0.0.2")).is_ok());
+
+    // Per API key, not limited by the IP
+    assert!(state.admit(&with_key("key")).is_ok());
+    assert!(state.admit(&with_key("key")).is_ok());
+    let e = state.admit(&with_key("key")).unwrap_err();
+    assert_eq!(
+        StatusCode::TOO_MANY_REQUESTS,
+        e.as_response_error().status_code()
+    );
+
+    // Unknown keys are rejected, not downgraded to IP limits
+    let e = state.admit(&with_key("unknown")).unwrap_err();
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        e.as_response_error().status_code()
+    );
+
+    assert_eq!(2, state.stats.rejected_rate.load(Ordering::Relaxed));
+    assert_eq!(1, state.stats.rejected_api_key.load(Ordering::Relaxed));
+}
+
+fn chunks(n: usize, size: usize) -> Payload {
+    Payload::Stream(Box::pin(stream::iter((0..n).map(move |_| {
+        Ok::<_, PayloadError>(web::Bytes::from(vec![0u8; size]))
+    }))))
+}
+
+#[test]
+fn limited_payload() {
+    let stats = Arc::new(ThrottleStats::default());
+    let read = |n, size| {
+        let payload = LimitedPayload {
+            inner: chunks(n, size),
+            limit: 10,
+            read: 0,
+            stats: Arc::clone(&stats),
+        };
+        block_on(payload.collect::<Vec<_>>())
+    };
+
+    let res = read(2, 5);
+    assert_eq!(2, res.len());
+    assert!(res.iter().all(|c| c.is_ok()));
+    assert_eq!(0, stats.rejected_body_size.load(Ordering::Relaxed));
+
+    // stops at the chunk crossing the limit
+    let res = read(4, 4);
+    assert_eq!(3, res.len());
+    assert!(matches!(res[2], Err(PayloadError::Overflow)));
+    assert_eq!(1, stats.rejected_body_size.load(Ordering::Relaxed));
+}
+
+fn status(res: StdResult<ServiceResponse, Error>) -> StatusCode {
+    match res {
+        Ok(r) => r.status(),
+        Err(e) => e.as_response_error().status_code(),
+    }
+}
+
+#[test]
+fn middleware() {
+    let throttle = Throttle::new(
+        "test_middleware",
+        ThrottleConfig {
+            max_concurrency: 1,
+            body_limits: vec![("/submit".to_owned(), 10)],
+            ..Default::default()
+        },
+    );
+    let stats = Arc::clone(&throttle.0.stats);
+
+    actix_rt::System::new("test_middleware").block_on(async move {
+        let mut app = init_service(
+            App::new()
+                .wrap(throttle)
+                .route("/", web::get().to(HttpResponse::Ok))
+                .route(
+                    "/submit",
+                    web::post().to(|body: web::Bytes| async move {
+                        HttpResponse::Ok().body(body)
+                    }),
+                ),
+        )
+        .await;
+
+        // The concurrency cap, the first request is still in flight
+        let first = app.call(TestRequest::get().uri("/").to_request());
+        let second = app.call(TestRequest::get().uri("/").to_request());
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, status(second.await));
+        assert_eq!(StatusCode::OK, status(first.await));
+        let third = app.call(TestRequest::get().uri("/").to_request());
+        assert_eq!(StatusCode::OK, status(third.await));
+        assert_eq!(1, stats.rejected_concurrency.load(Ordering::Relaxed));
+
+        // Body limits, checked with the header
+        let req = TestRequest::post()
+            .uri("/submit")
+            .header(header::CONTENT_LENGTH, "11")
+            .set_payload(vec![0u8; 11])
+            .to_request();
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, status(app.call(req).await));
+        assert_eq!(1, stats.rejected_body_size.load(Ordering::Relaxed));
+
+        // and with the body, without the header
+        let (req, _) = TestRequest::post()
+            .uri("/submit")
+            .to_request()
+            .replace_payload(chunks(3, 4));
+        assert!(!status(app.call(req).await).is_success());
+        assert_eq!(2, stats.rejected_body_size.load(Ordering::Relaxed));
+
+        let (req, _) = TestRequest::post()
+            .uri("/submit")
+            .to_request()
+            .replace_payload(chunks(2, 5));
+        assert_eq!(StatusCode::OK, status(app.call(req).await));
+        assert_eq!(2, stats.rejected_body_size.load(Ordering::Relaxed));
+    });
+}
@@ -58,6 +58,10 @@ fn node_command() -> Result<()> {
         abcid.arg("--enable-eth-empty-blocks");
     }
 
+    if let Some(v) = CFG.metrics_port {
+        abcid.arg("--metrics-port").arg(v.to_string());
+    }
+
     let mut abcid_child = abcid
         .stdin(Stdio::null())
         .stdout(Stdio::inherit())
@@ -95,6 +99,53 @@ fn node_command() -> Result<()> {
     .c(d!())
 }
 
+// A local devnet in a single process,
+// blocks are produced by abcid itself instead of by tendermint.
+fn dev_command() -> Result<()> {
+    if CFG.dev_reset && metadata(&CFG.ledger_dir).is_ok() {
+        fs::remove_dir_all(&CFG.ledger_dir).c(d!())?;
+    }
+
+    let mut abcid = Command::new("/tmp/abcid__");
+
+    abcid
+        .arg("--dev")
+        .arg("--enable-query-service")
+        .arg("--enable-eth-api-service")
+        .arg("--tendermint-host")
+        .arg(&CFG.tendermint_host)
+        .arg("--tendermint-port")
+        .arg(CFG.tendermint_port.to_string())
+        .arg("--submission-service-port")
+        .arg(CFG.submission_service_port.to_string())
+        .arg("--ledger-service-port")
+        .arg(CFG.ledger_service_port.to_string())
+        .arg("--ledger-dir")
+        .arg(&CFG.ledger_dir);
+
+    if let Some(v) = CFG.dev_block_interval {
+        abcid.arg("--dev-block-interval").arg(v.to_string());
+    }
+
+    if CFG.enable_eth_empty_blocks {
+        abcid.arg("--enable-eth-empty-blocks");
+    }
+
+    if let Some(v) = CFG.metrics_port {
+        abcid.arg("--metrics-port").arg(v.to_string());
+    }
+
+    abcid
+        .stdin(Stdio::null())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .c(d!())?
+        .wait()
+        .c(d!())
+        .map(|s| println!("{}", s))
+}
+
 fn init_command() -> Result<()> {
     Command::new("/tmp/tendermint__")
         .arg("init")
@@ -204,8 +255,9 @@ fn main() {
     let res = match CFG.command.as_str() {
         "init" => unpack().c(d!()).and_then(|_| init_command().c(d!())),
         "node" => unpack().c(d!()).and_then(|_| node_command().c(d!())),
+        "dev" => unpack().c(d!()).and_then(|_| dev_command().c(d!())),
         "pack" => pack().c(d!()),
-        _ => Err(eg!("The available options are 'node'/'init'")),
+        _ => Err(eg!("The available options are 'node'/'init'/'dev'")),
     };
 
     pnk!(res);
@@ -355,6 +407,7 @@ mod config {
         pub enable_eth_api_service: bool,
         pub enable_eth_empty_blocks: bool,
         pub no_fast_sync: bool,
+        pub metrics_port: Option<u16>,
         pub tendermint_node_self_addr: Option<String>,
         pub tendermint_node_key_config_path: Option<String>,
         pub ledger_dir: String,
@@ -362,6 +415,8 @@ mod config {
         pub tendermint_config: Option<String>,
         pub command: String,
         pub init_mode: InitMode,
+        pub dev_block_interval: Option<u64>,
+        pub dev_reset: bool,
     }
 
     fn get_config() -> Result<Config> {
@@ -377,6 +432,7 @@ mod config {
                     .arg_from_usage("--enable-eth-api-service")
                     .arg_from_usage("--enable-eth-empty-blocks")
                     .arg_from_usage("-N, --no-fast-sync")
+                    .arg_from_usage("--metrics-port=[Metrics Port] 'serve prometheus metrics on this port'")
                     .arg_from_usage("--tendermint-node-self-addr=[Address] 'the address of your tendermint node, in upper-hex format'")
                     .arg_from_usage("--tendermint-node-key-config-path=[Path] 'such as: ${HOME}/.tendermint/config/priv_validator_key.json'")
                     .arg_from_usage("-d, --ledger-dir=[Path]")
@@ -396,6 +452,21 @@ mod config {
                         "-b, --base-dir=[DIR] 'The root directory for tendermint config, aka $TENDERMINT_HOME'",
                     );
 
+            let dev = SubCommand::with_name("dev")
+                    .about("Start a local devnet in a single process, with pre-funded accounts.")
+                    .arg_from_usage("--block-interval=[Seconds] 'also produce empty blocks at this interval, by default blocks are only produced for new transactions'")
+                    .arg_from_usage("--reset 'remove the data of the previous devnet'")
+                    .arg_from_usage("-H, --tendermint-host=[Tendermint RPC IP]")
+                    .arg_from_usage("-P, --tendermint-port=[Tendermint RPC Port]")
+                    .arg_from_usage("--submission-service-port=[Submission Service Port]")
+                    .arg_from_usage("--ledger-service-port=[Ledger Service Port]")
+                    .arg_from_usage("--enable-eth-empty-blocks")
+                    .arg_from_usage("--metrics-port=[Metrics Port] 'serve prometheus metrics on this port'")
+                    .arg_from_usage("-d, --ledger-dir=[Path]")
+                    .arg_from_usage(
+                        "-b, --base-dir=[DIR] 'The root directory for tendermint config, aka $TENDERMINT_HOME'",
+                    );
+
             let pack = SubCommand::with_name("pack");
 
             App::new("findorad")
@@ -404,6 +475,7 @@ mod config {
                 .about("An ABCI node implementation of FindoraNetwork.")
                 .subcommand(node)
                 .subcommand(init)
+                .subcommand(dev)
                 .subcommand(pack)
                 .arg(Arg::with_name("_a").long("ignored").hidden(true))
                 .arg(Arg::with_name("_b").long("nocapture").hidden(true))
@@ -472,6 +544,12 @@ mod config {
         let eeeb = m.is_present("enable-eth-empty-blocks")
             || env::var("ENABLE_ETH_EMPTY_BLOCKS").is_ok();
         let nfs = m.is_present("no-fast-sync") || env::var("NO_FAST_SYNC").is_ok();
+        let mp = m
+            .value_of("metrics-port")
+            .map(|v| v.to_owned())
+            .or_else(|| env::var("METRICS_PORT").ok())
+            .map(|v| v.parse::<u16>().c(d!()))
+            .transpose()?;
         let tnsa = m
             .value_of("tendermint-node-self-addr")
             .map(|v| v.to_owned())
@@ -484,9 +562,20 @@ mod config {
             .value_of("ledger-dir")
             .map(|v| v.to_owned())
             .unwrap_or_else(|| {
-                env::var("LEDGER_DIR")
-                    .unwrap_or_else(|_| format!("{}/__findora__", &tdir))
+                env::var("LEDGER_DIR").unwrap_or_else(|_| {
+                    // never mix the data of a devnet with a real node
+                    if "dev" == cmd {
+                        format!("{}/__findora_dev__", &tdir)
+                    } else {
+                        format!("{}/__findora__", &tdir)
+                    }
+                })
             });
+        let dbi = m
+            .value_of("block-interval")
+            .map(|v| v.parse::<u64>().c(d!()))
+            .transpose()?;
+        let dr = m.is_present("reset");
 
         let init_mode = if m.is_present("devnet") {
             InitMode::Dev
@@ -511,6 +600,7 @@ mod config {
             enable_eth_api_service: eeas,
             enable_eth_empty_blocks: eeeb,
             no_fast_sync: nfs,
+            metrics_port: mp,
             tendermint_node_self_addr: tnsa,
             tendermint_node_key_config_path: tnkcp,
             ledger_dir: ld,
@@ -518,6 +608,8 @@ mod config {
             tendermint_config: tcfg,
             tendermint_home: tdir,
             init_mode,
+            dev_block_interval: dbi,
+            dev_reset: dr,
         };
 
         Ok(res)
@@ -278,6 +278,7 @@ fn generate_evil_tx_big_output_issue(seq_id: u64) -> Result<Transaction> {
                 id: None,
                 record: ba,
                 lien: None,
+                multisig: None,
             },
             None,
         );
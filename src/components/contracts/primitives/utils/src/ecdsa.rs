@@ -318,14 +318,23 @@ impl SecpPair {
     pub fn from_phrase(
         phrase: &str,
         password: Option<&str>,
+    ) -> ruc::Result<(SecpPair, Seed)> {
+        Self::from_phrase_with_path(phrase, password, "m/44'/60'/0'/0/0")
+    }
+
+    /// Generate key pair from given recovery phrase, password and derivation path,
+    /// such as `m/44'/60'/0'/0/1`.
+    pub fn from_phrase_with_path(
+        phrase: &str,
+        password: Option<&str>,
+        path: &str,
     ) -> ruc::Result<(SecpPair, Seed)> {
         let mnemonic = Mnemonic::from_phrase_in(Language::English, phrase)
             .map_err(|_| eg!("InvalidPhrase"))?;
         let bs = mnemonic.to_seed(password.unwrap_or(""));
         let ext = XPrv::derive_from_path(
             &bs,
-            &DerivationPath::from_str("m/44'/60'/0'/0/0")
-                .map_err(|_| eg!("InvalidDerivationPath"))?,
+            &DerivationPath::from_str(path).map_err(|_| eg!("InvalidDerivationPath"))?,
         )
         .map_err(|_| eg!("Failed to ExtendedPrivateKey"))?;
         let mut seed = Seed::default();
@@ -473,6 +482,19 @@ mod test {
         assert_ne!(pair1.public(), pair2.public());
     }
 
+    #[test]
+    fn derivation_path_does_something() {
+        let (pair1, phrase, _) = SecpPair::generate_with_phrase(None);
+        let (pair2, _) =
+            SecpPair::from_phrase_with_path(&phrase, None, "m/44'/60'/0'/0/0").unwrap();
+        let (pair3, _) =
+            SecpPair::from_phrase_with_path(&phrase, None, "m/44'/60'/0'/0/1").unwrap();
+
+        assert_eq!(pair1.public(), pair2.public());
+        assert_ne!(pair1.public(), pair3.public());
+        assert!(SecpPair::from_phrase_with_path(&phrase, None, "m/x").is_err());
+    }
+
     #[test]
     fn public_serialization_works() {
         let pair = SecpPair::from_seed(b"12345678901234567890123456789012");
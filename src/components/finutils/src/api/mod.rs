@@ -2,18 +2,28 @@
 //! This module defines findora ledger/query rpc apis for server and client.
 //!
 
-use ledger::staking::{
-    self, StakerMemo, TendermintAddr, MAX_POWER_PERCENT_PER_VALIDATOR,
+use ledger::{
+    data_model::TxnSID,
+    staking::{
+        self, BlockHeight, StakerMemo, TendermintAddr, MAX_POWER_PERCENT_PER_VALIDATOR,
+    },
 };
 use serde::{Deserialize, Serialize};
 
+/// Max number of the transactions in a `txn_sid_light_list` query
+pub const TXN_LIST_LIMIT: usize = 100;
+
 /// A list of basic validator information of current height
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ValidatorList {
-    threshold: [u128; 2],
-    validator_cnt: u64,
-    cur_height: u64,
-    validators: Vec<Validator>,
+    /// max voting power of a validator, in percent
+    pub threshold: [u128; 2],
+    /// number of the validators
+    pub validator_cnt: u64,
+    /// current block height
+    pub cur_height: u64,
+    #[allow(missing_docs)]
+    pub validators: Vec<Validator>,
 }
 
 impl ValidatorList {
@@ -31,12 +41,18 @@ impl ValidatorList {
 /// The basic inforamtion of a validator
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Validator {
-    addr: TendermintAddr,
-    power: u64,
-    commission_rate: [u64; 2],
-    accept_delegation: bool,
-    rank: u64,
-    extra: StakerMemo,
+    /// tendermint node address
+    pub addr: TendermintAddr,
+    /// voting power
+    pub power: u64,
+    /// commission rate of this staker
+    pub commission_rate: [u64; 2],
+    /// if it accepts new delegations
+    pub accept_delegation: bool,
+    /// the rank of voting power
+    pub rank: u64,
+    /// staker information
+    pub extra: StakerMemo,
 }
 
 impl Validator {
@@ -93,11 +109,41 @@ pub struct ValidatorDetail {
     pub expected_annualization: [u128; 2],
 }
 
+/// The blocks signed and missed by a validator in a range of recent heights,
+/// only the blocks with the validator in their last commits are expected.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SigningReport {
+    /// tendermint node address
+    pub addr: TendermintAddr,
+    /// the first height of the range
+    pub from_height: BlockHeight,
+    /// the last height of the range
+    pub to_height: BlockHeight,
+    /// how many blocks the validator was expected to sign
+    pub expected: u64,
+    /// how many blocks the validator signed
+    pub signed: u64,
+    /// heights of the missed blocks, in ascending order
+    pub missed_heights: Vec<BlockHeight>,
+}
+
+/// The stakes and the return rate of a validator at a height,
+/// see the `validator_delegation` route.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ValidatorDelegation {
+    /// the block rewards rate
+    pub return_rate: [u128; 2],
+    /// the amount staked by the validator itself
+    pub self_delegation: u64,
+    /// the amount delegated by others
+    pub delegated: u64,
+}
+
 #[allow(missing_docs)]
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct DelegatorInfo {
-    addr: String,
-    amount: u64,
+    pub addr: String,
+    pub amount: u64,
 }
 
 impl DelegatorInfo {
@@ -110,7 +156,7 @@ impl DelegatorInfo {
 #[allow(missing_docs)]
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct DelegatorList {
-    delegators: Vec<DelegatorInfo>,
+    pub delegators: Vec<DelegatorInfo>,
 }
 
 #[allow(missing_docs)]
@@ -181,6 +227,25 @@ impl DelegationInfo {
     }
 }
 
+/// A page of history records
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HistoryPage<T> {
+    #[allow(missing_docs)]
+    pub items: Vec<T>,
+    /// none if there are no more records
+    pub next_cursor: Option<u64>,
+}
+
+/// A transaction in history queries
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TxnHistoryItem {
+    pub txn_sid: TxnSID,
+    pub txn_hash: Option<String>,
+    pub height: Option<BlockHeight>,
+    pub timestamp: Option<i64>,
+}
+
 #[allow(missing_docs)]
 pub trait NetworkRoute {
     fn route(&self) -> String;
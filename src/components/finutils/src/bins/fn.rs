@@ -20,6 +20,32 @@
 //!     - "--serv-addr=[URL/IP]"
 //!     - "--owner-mnemonic-path=[File Path]"
 //!         - the `id` of your validator will be drived from this
+//! - key, the encrypted keystore
+//!     - add/list/export/remove
+//!     - select an account by "--account=[Name]" in other subcommands
+//! - wallet
+//!     - consolidate, merge the small utxos of an asset
+//! - transfer/transfer-batch
+//!     - "--coin-selection=[in-order|largest-first|smallest-first|exact|random]"
+//!     - every line of the batch file: "[Receiver] [Amount] [confidential-amount] [confidential-type]",
+//!       the amount and the confidentiality default to the command options
+//! - history
+//!     - "--asset=[Asset Code]", "--csv"
+//! - tx, the offline signing
+//!     - "--build-only=[File Path]" in the staking, transfer, wallet and validator
+//!       subcommands, save an unsigned transaction, not supported by "asset" and "contract-*"
+//!     - sign/broadcast
+//! - multisig, the accounts controlled by a threshold of weighted signers
+//!     - create/propose/approve/submit
+//!     - "--signers=[Wallet Address]:[Weight],..."
+//! - validator, the toolkit of validator operators
+//!     - rotate-key/status/rewards
+//!     - "--validator-addr=[Tendermint Address]", default to your validator
+//! - asset, the custom assets
+//!     - "--create", with "--updatable",
+//!       "--tracer-key=[File Path]" to trace the transfers
+//!     - "--issue", "--show", "--update-memo"
+//!     - "--gen-tracer-key", "--trace" by "--code" or "--addr"
 //! ```
 //!
 
@@ -27,12 +53,13 @@
 
 use clap::{crate_authors, load_yaml, App};
 use finutils::common;
-use finutils::common::evm::*;
+use finutils::common::{evm::*, utils::TransferTarget};
+use finutils::txn_builder::CoinSelection;
 use fp_utils::ecdsa::SecpPair;
 use globutils::wallet;
-use ledger::data_model::{AssetTypeCode, FRA_DECIMALS};
+use ledger::data_model::{AssetRules, AssetTypeCode, ASSET_TYPE_FRA, FRA_DECIMALS};
 use ruc::*;
-use std::{fmt, fs};
+use std::{fmt, fs, str::FromStr};
 
 fn main() {
     if let Err(e) = run() {
@@ -49,12 +76,41 @@ fn run() -> Result<()> {
         .author(crate_authors!())
         .get_matches();
 
+    if let (_, Some(m)) = matches.subcommand() {
+        // the common options may be in a nested subcommand, eg. `fn tx sign`
+        let m = match m.subcommand() {
+            (_, Some(nested)) => nested,
+            _ => m,
+        };
+        common::keystore::select_account(m.value_of("account"));
+        common::offline::set_build_only(
+            m.value_of("build-only"),
+            m.value_of("owner-address"),
+        )
+        .c(d!())?;
+    }
+
     if matches.is_present("version") {
         println!("{}", env!("VERGEN_SHA"));
     } else if matches.is_present("genkey") {
         common::gen_key_and_print();
     } else if let Some(m) = matches.subcommand_matches("wallet") {
-        if m.is_present("create") {
+        if let Some(m) = m.subcommand_matches("consolidate") {
+            let seckey = match m.value_of("seckey") {
+                Some(path) => {
+                    Some(fs::read_to_string(path).c(d!("Failed to read seckey file"))?)
+                }
+                None => None,
+            };
+            let token_code = match m.value_of("asset") {
+                Some(code) if code.to_uppercase() != "FRA" => {
+                    Some(AssetTypeCode::new_from_base64(code).c(d!())?)
+                }
+                _ => None,
+            };
+            common::consolidate(seckey.as_deref(), token_code, m.value_of("max-inputs"))
+                .c(d!())?;
+        } else if m.is_present("create") {
             common::gen_key_and_print();
         } else if m.is_present("show") {
             let seckey = match m.value_of("seckey") {
@@ -76,6 +132,28 @@ fn run() -> Result<()> {
         } else {
             println!("{}", m.usage());
         }
+    } else if let Some(m) = matches.subcommand_matches("history") {
+        let seckey = match m.value_of("seckey") {
+            Some(path) => {
+                Some(fs::read_to_string(path).c(d!("Failed to read seckey file"))?)
+            }
+            None => None,
+        };
+        let token_code = match m.value_of("asset") {
+            Some(code) if code.to_uppercase() != "FRA" => {
+                Some(AssetTypeCode::new_from_base64(code).c(d!())?)
+            }
+            Some(_) => Some(AssetTypeCode {
+                val: ASSET_TYPE_FRA,
+            }),
+            None => None,
+        };
+        common::history::show_history(
+            seckey.as_deref(),
+            token_code,
+            m.is_present("csv"),
+        )
+        .c(d!())?;
     } else if let Some(m) = matches.subcommand_matches("delegate") {
         let seckey = match m.value_of("seckey") {
             Some(path) => {
@@ -148,16 +226,21 @@ fn run() -> Result<()> {
             } else {
                 None
             };
+            let mut rules = AssetRules::default();
+            rules
+                .set_decimals(decimal)
+                .c(d!())?
+                .set_max_units(max_units)
+                .set_transferable(transferable)
+                .set_updatable(m.is_present("updatable"));
+            if let Some(path) = m.value_of("tracer-key") {
+                rules.add_tracing_policy(
+                    common::tracing::asset_tracing_policy(path).c(d!())?,
+                );
+            }
             let token_code = m.value_of("code");
-            common::create_asset(
-                seckey.as_deref(),
-                memo.unwrap(),
-                decimal,
-                max_units,
-                transferable,
-                token_code,
-            )
-            .c(d!())?;
+            common::create_asset(seckey.as_deref(), memo.unwrap(), rules, token_code)
+                .c(d!())?;
         } else if m.is_present("show") {
             let addr = m.value_of("addr");
             if addr.is_none() {
@@ -187,8 +270,40 @@ fn run() -> Result<()> {
 
             common::issue_asset(seckey.as_deref(), code.unwrap(), amount, hidden)
                 .c(d!())?;
+        } else if m.is_present("update-memo") {
+            let seckey = match m.value_of("seckey") {
+                Some(path) => {
+                    Some(fs::read_to_string(path).c(d!("Failed to read seckey file"))?)
+                }
+                None => None,
+            };
+            let code = m.value_of("code");
+            let memo = m.value_of("memo");
+            if code.is_none() || memo.is_none() {
+                println!("{}", m.usage());
+                return Ok(());
+            }
+
+            common::update_asset_memo(seckey.as_deref(), code.unwrap(), memo.unwrap())
+                .c(d!())?;
+        } else if m.is_present("trace") {
+            let code = m
+                .value_of("code")
+                .map(|code| AssetTypeCode::new_from_base64(code).c(d!()))
+                .transpose()?;
+            let addr = m.value_of("addr");
+            if code.is_none() && addr.is_none() {
+                println!("{}", m.usage());
+                return Ok(());
+            }
+
+            common::tracing::show_traced(m.value_of("tracer-key").c(d!())?, code, addr)
+                .c(d!())?;
+        } else if m.is_present("gen-tracer-key") {
+            common::tracing::gen_tracer_key(m.value_of("tracer-key").c(d!())?)
+                .c(d!())?;
         } else {
-            let help = "fn asset [--create | --issue | --show]";
+            let help = "fn asset [--create | --issue | --show | --update-memo | --trace | --gen-tracer-key]";
             println!("{}", help);
         }
     } else if let Some(m) = matches.subcommand_matches("staker-update") {
@@ -252,6 +367,132 @@ fn run() -> Result<()> {
     } else if let Some(m) = matches.subcommand_matches("show") {
         let basic = m.is_present("basic");
         common::show(basic).c(d!())?;
+    } else if let Some(m) = matches.subcommand_matches("key") {
+        if let Some(m) = m.subcommand_matches("add") {
+            let mnemonic = if m.is_present("generate") {
+                None
+            } else if let Some(path) = m.value_of("mnemonic-path") {
+                Some(fs::read_to_string(path).c(d!("Failed to read mnemonic file"))?)
+            } else {
+                Some(common::keystore::read_mnemonic().c(d!())?)
+            };
+            common::keystore::add_account(
+                m.value_of("name").c(d!())?,
+                mnemonic.as_deref(),
+                m.value_of("path"),
+                m.value_of("lang").unwrap_or("en"),
+                m.is_present("default"),
+            )
+            .c(d!())?;
+        } else if m.is_present("list") {
+            common::keystore::list_accounts().c(d!())?;
+        } else if let Some(m) = m.subcommand_matches("export") {
+            common::keystore::export_account(m.value_of("name").c(d!())?).c(d!())?;
+        } else if let Some(m) = m.subcommand_matches("remove") {
+            common::keystore::remove_account(m.value_of("name").c(d!())?).c(d!())?;
+        } else {
+            println!("{}", m.usage());
+        }
+    } else if let Some(m) = matches.subcommand_matches("tx") {
+        if let Some(m) = m.subcommand_matches("sign") {
+            let seckey = match m.value_of("seckey") {
+                Some(path) => {
+                    Some(fs::read_to_string(path).c(d!("Failed to read seckey file"))?)
+                }
+                None => None,
+            };
+            common::offline::sign(
+                m.value_of("file").c(d!())?,
+                m.value_of("out"),
+                seckey.as_deref(),
+                m.is_present("cosig"),
+            )
+            .c(d!())?;
+        } else if let Some(m) = m.subcommand_matches("broadcast") {
+            common::offline::broadcast(m.value_of("file").c(d!())?).c(d!())?;
+        } else {
+            println!("{}", m.usage());
+        }
+    } else if let Some(m) = matches.subcommand_matches("multisig") {
+        if let Some(m) = m.subcommand_matches("create") {
+            let seckey = match m.value_of("seckey") {
+                Some(path) => {
+                    Some(fs::read_to_string(path).c(d!("Failed to read seckey file"))?)
+                }
+                None => None,
+            };
+            let rules = common::multisig::parse_rules(
+                m.value_of("threshold").c(d!())?,
+                m.value_of("signers").c(d!())?,
+            )
+            .c(d!())?;
+            let token_code = m
+                .value_of("asset")
+                .map(|asset| AssetTypeCode::new_from_base64(asset).c(d!()))
+                .transpose()?;
+            common::multisig::create(
+                rules,
+                seckey.as_deref(),
+                token_code,
+                m.value_of("amount"),
+            )
+            .c(d!())?;
+        } else if let Some(m) = m.subcommand_matches("propose") {
+            let target = m.value_of("to-wallet-address").c(d!()).and_then(|addr| {
+                wallet::public_key_from_bech32(addr).c(d!("invalid wallet address"))
+            })?;
+            let token_code = m
+                .value_of("asset")
+                .map(|asset| AssetTypeCode::new_from_base64(asset).c(d!()))
+                .transpose()?;
+            let rules = match (m.value_of("threshold"), m.value_of("signers")) {
+                (Some(threshold), Some(signers)) => {
+                    Some(common::multisig::parse_rules(threshold, signers).c(d!())?)
+                }
+                _ => None,
+            };
+            common::multisig::propose(
+                m.value_of("multisig-address").c(d!())?,
+                rules,
+                target,
+                token_code,
+                m.value_of("amount").c(d!())?,
+                coin_selection(m.value_of("coin-selection")).c(d!())?,
+                m.value_of("out").c(d!())?,
+            )
+            .c(d!())?;
+        } else if let Some(m) = m.subcommand_matches("approve") {
+            let seckey = match m.value_of("seckey") {
+                Some(path) => {
+                    Some(fs::read_to_string(path).c(d!("Failed to read seckey file"))?)
+                }
+                None => None,
+            };
+            common::multisig::approve(m.value_of("file").c(d!())?, seckey.as_deref())
+                .c(d!())?;
+        } else if let Some(m) = m.subcommand_matches("submit") {
+            common::multisig::submit(m.value_of("file").c(d!())?).c(d!())?;
+        } else {
+            println!("{}", m.usage());
+        }
+    } else if let Some(m) = matches.subcommand_matches("validator") {
+        if let Some(m) = m.subcommand_matches("rotate-key") {
+            common::validator::rotate_key(m.value_of("new-key").c(d!())?).c(d!())?;
+        } else if let Some(m) = m.subcommand_matches("status") {
+            common::validator::status(
+                m.value_of("validator-addr"),
+                m.value_of("blocks"),
+            )
+            .c(d!())?;
+        } else if let Some(m) = m.subcommand_matches("rewards") {
+            common::validator::rewards(
+                m.value_of("validator-addr"),
+                m.value_of("commission-rate"),
+            )
+            .c(d!())?;
+        } else {
+            println!("{}", m.usage());
+        }
     } else if let Some(m) = matches.subcommand_matches("setup") {
         let sa = m.value_of("serv-addr");
         let om = m.value_of("owner-mnemonic-path");
@@ -295,6 +536,7 @@ fn run() -> Result<()> {
                 am.unwrap(),
                 m.is_present("confidential-amount"),
                 m.is_present("confidential-type"),
+                coin_selection(m.value_of("coin-selection")).c(d!())?,
             )
             .c(d!())?;
         }
@@ -305,40 +547,44 @@ fn run() -> Result<()> {
             }
             None => None,
         };
-        let t = m
-            .value_of("to-pubkey-file")
-            .c(d!())
-            .and_then(|f| {
-                fs::read_to_string(f).c(d!()).and_then(|pks| {
-                    pks.lines()
-                        .map(|pk| wallet::public_key_from_base64(pk.trim()))
-                        .collect::<Result<Vec<_>>>()
-                        .c(d!("invalid file"))
-                })
+        let (file, parse_pk): (_, fn(&str) -> Result<_>) =
+            if let Some(f) = m.value_of("to-pubkey-file") {
+                (f, wallet::public_key_from_base64)
+            } else {
+                (
+                    m.value_of("to-wallet-address-file").c(d!())?,
+                    wallet::public_key_from_bech32,
+                )
+            };
+        let am = m
+            .value_of("amount")
+            .map(|am| am.parse::<u64>().c(d!("'amount' must be an integer")))
+            .transpose()?;
+        let t = fs::read_to_string(file)
+            .c(d!())?
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| {
+                TransferTarget::parse_line(
+                    l,
+                    parse_pk,
+                    am,
+                    m.is_present("confidential-amount"),
+                    m.is_present("confidential-type"),
+                )
+                .c(d!(format!("invalid line: {}", l)))
             })
-            .or_else(|_| {
-                m.value_of("to-wallet-address-file").c(d!()).and_then(|f| {
-                    fs::read_to_string(f).c(d!()).and_then(|addrs| {
-                        addrs
-                            .lines()
-                            .map(|addr| wallet::public_key_from_bech32(addr.trim()))
-                            .collect::<Result<Vec<_>>>()
-                            .c(d!("invalid file"))
-                    })
-                })
-            })?;
-        let am = m.value_of("amount");
+            .collect::<Result<Vec<_>>>()?;
 
-        if am.is_none() || t.is_empty() {
+        if t.is_empty() {
             println!("{}", m.usage());
         } else {
             common::transfer_asset_batch(
                 f.as_deref(),
-                &t,
+                t,
                 None,
-                am.unwrap(),
-                m.is_present("confidential-amount"),
-                m.is_present("confidential-type"),
+                coin_selection(m.value_of("coin-selection")).c(d!())?,
             )
             .c(d!())?;
         }
@@ -373,6 +619,12 @@ fn run() -> Result<()> {
     Ok(())
 }
 
+fn coin_selection(s: Option<&str>) -> Result<CoinSelection> {
+    s.map_or(Ok(CoinSelection::default()), |s| {
+        CoinSelection::from_str(s).c(d!())
+    })
+}
+
 fn tip_fail(e: impl fmt::Display) {
     eprintln!("\n\x1b[31;01mFAIL !!!\x1b[00m");
     eprintln!(
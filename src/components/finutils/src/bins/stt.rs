@@ -5,6 +5,14 @@
 //! - delegate --user=<cat1> --amount=<N> --validator=<dog1>
 //! - undelegate --user=<cat1>
 //! - claim --user=<cat1> --amount=<N>
+//! - run --scenario=<path>, run a scenario file, see `finutils::common::scenario`
+//!
+//! The commands other than `run` are kept as they are, rather than as scenarios:
+//! `init` funds and self-delegates all the pre-defined users and validators
+//! (`show -U -v`), which are generated here and used by `tools/staking/demo.sh`,
+//! and the others are one-shot actions of those users for manual testing,
+//! while a scenario is a whole flow with its own accounts and expectations,
+//! eg. `tools/scenarios/staking.yml` for the flow of `init`.
 //!
 
 #![deny(warnings)]
@@ -68,6 +76,11 @@ fn run() -> Result<()> {
         .arg_from_usage("-f, --from-user=[User] 'transfer sender'")
         .arg_from_usage("-t, --to-user=[User] 'transfer receiver'")
         .arg_from_usage("-n, --amount=[Amount] 'how much FRA to transfer'");
+    let subcmd_run = SubCommand::with_name("run")
+        .about("run a scenario file against the network set by `fn setup -S`")
+        .arg_from_usage(
+            "-f, --scenario=[Path] 'path of the scenario file, in YAML or JSON'",
+        );
     let subcmd_show = SubCommand::with_name("show")
         .arg_from_usage("-r, --root-mnemonic 'show the pre-defined root mnemonic'")
         .arg_from_usage("-U, --user-list 'show the pre-defined user list'")
@@ -85,6 +98,7 @@ fn run() -> Result<()> {
         .subcommand(subcmd_undelegate)
         .subcommand(subcmd_claim)
         .subcommand(subcmd_transfer)
+        .subcommand(subcmd_run)
         .subcommand(subcmd_show)
         .get_matches();
 
@@ -165,6 +179,12 @@ fn run() -> Result<()> {
                 println!("{}", m.usage());
             }
         }
+    } else if let Some(m) = matches.subcommand_matches("run") {
+        if let Some(path) = m.value_of("scenario") {
+            common::scenario::run(path).c(d!())?;
+        } else {
+            println!("{}", m.usage());
+        }
     } else if let Some(m) = matches.subcommand_matches("show") {
         let rm = m.is_present("root-mnemonic");
         let ul = m.is_present("user-list");
@@ -298,6 +318,7 @@ mod issue {
                         id: None,
                         record: ba,
                         lien: None,
+                        multisig: None,
                     },
                     None,
                 )
@@ -26,23 +26,32 @@ use zei::xfr::sig::XfrKeyPair;
 
 /// transfer utxo assets to account(ed25519 or ecdsa address) balance.
 pub fn transfer_to_account(amount: u64, address: Option<&str>) -> Result<()> {
+    let kp = get_keypair()?;
+    let target_address = match address {
+        Some(s) => MultiSigner::from_str(s).c(d!())?,
+        None => MultiSigner::Xfr(kp.get_pk()),
+    };
+    deposit_to_account(&kp, amount, target_address)
+}
+
+/// Same as `transfer_to_account`, with the assets of `kp`.
+pub fn deposit_to_account(
+    kp: &XfrKeyPair,
+    amount: u64,
+    target_address: MultiSigner,
+) -> Result<()> {
     let mut builder = utils::new_tx_builder()?;
 
-    let kp = get_keypair()?;
     let transfer_op = utils::gen_transfer_op(
-        &kp,
+        kp,
         vec![(&BLACK_HOLE_PUBKEY_STAKING, amount)],
         None,
         false,
         false,
     )?;
-    let target_address = match address {
-        Some(s) => MultiSigner::from_str(s).c(d!())?,
-        None => MultiSigner::Xfr(kp.get_pk()),
-    };
     builder
         .add_operation(transfer_op)
-        .add_operation_convert_account(&kp, target_address)?;
+        .add_operation_convert_account(kp, target_address)?;
     utils::send_tx(&builder.take_transaction())?;
     Ok(())
 }
@@ -142,6 +151,11 @@ pub fn contract_account_info(address: Option<&str>) -> Result<(Address, SmartAcc
         Some(s) => MultiSigner::from_str(s).c(d!())?,
         None => MultiSigner::Xfr(fra_kp.get_pk()),
     };
+    account_info(address).c(d!())
+}
+
+/// Query the account info of `address` by abci/query
+pub fn account_info(address: MultiSigner) -> Result<(Address, SmartAccount)> {
     let account: Address = address.into();
 
     let tm_client = tendermint_rpc::HttpClient::new(
@@ -0,0 +1,632 @@
+//!
+//! # Transaction history
+//!
+//! Rebuild the history of a findora account from the query server,
+//! the owner memos are opened with its key, so confidential records are also counted.
+//!
+
+use crate::api::TxnHistoryItem;
+use crate::common::utils;
+use globutils::wallet;
+use ledger::{
+    data_model::{
+        AssetType, AssetTypeCode, FinalizedTransaction, Operation, TxOutput, TxoRef,
+        TxoSID, ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY, BLACK_HOLE_PUBKEY_STAKING,
+    },
+    staking::ops::mint_fra::MintKind,
+};
+use ruc::*;
+use std::collections::{BTreeSet, HashMap};
+use zei::xfr::{
+    asset_record::open_blind_asset_record,
+    sig::{XfrKeyPair, XfrPublicKey},
+    structs::OwnerMemo,
+};
+
+// the page size of the `txn_history` queries
+const HISTORY_PAGE_SIZE: usize = 200;
+
+const COLUMNS: [&str; 9] = [
+    "TIME", "HEIGHT", "TXN", "KIND", "ASSET", "CHANGE", "FEE", "BALANCE", "DETAIL",
+];
+
+/// The change of an asset of the account made by a transaction
+#[derive(Clone, Debug)]
+pub struct HistoryRecord {
+    #[allow(missing_docs)]
+    pub txn: TxnHistoryItem,
+    /// what the transaction does, eg. `send`, `receive`, `delegate`, `coinbase`
+    pub kind: String,
+    #[allow(missing_docs)]
+    pub asset: AssetType,
+    /// received minus spent, the fee is included
+    pub change: i128,
+    /// FRA paid to the black hole by the account
+    pub fee: u64,
+    /// the balance of `asset` after this transaction
+    pub balance: i128,
+    /// counterparties, validators, etc.
+    pub detail: String,
+}
+
+/// Print the history of an account, as a table or in CSV,
+/// only the records of `asset` are printed if it is set.
+pub fn show_history(
+    sk_str: Option<&str>,
+    asset: Option<AssetTypeCode>,
+    csv: bool,
+) -> Result<()> {
+    let kp = super::restore_keypair_from_str_with_default(sk_str).c(d!())?;
+    let records = get_history(&kp)
+        .c(d!())?
+        .into_iter()
+        .filter(|r| asset.map(|a| a.val == r.asset).unwrap_or(true))
+        .collect::<Vec<_>>();
+
+    if csv {
+        print_csv(&records);
+    } else {
+        print_table(&records);
+    }
+
+    Ok(())
+}
+
+/// Collect all the transactions related to the account in ascending order,
+/// and replay them to rebuild the balance of every asset over time.
+pub fn get_history(kp: &XfrKeyPair) -> Result<Vec<HistoryRecord>> {
+    let mut items = vec![];
+    let mut cursor = None;
+    loop {
+        let page = utils::get_txn_history(kp.get_pk_ref(), cursor, HISTORY_PAGE_SIZE)
+            .c(d!())?;
+        items.extend(page.items);
+        if page.next_cursor.is_none() {
+            break;
+        }
+        cursor = page.next_cursor;
+    }
+
+    let sids = items.iter().map(|i| i.txn_sid).collect::<Vec<_>>();
+    let txns = utils::get_txns(&sids).c(d!())?;
+
+    let mut replay = Replay::new(kp);
+    let mut records = vec![];
+    for (item, txn) in items.into_iter().zip(txns.iter()) {
+        records.extend(replay.apply(item, txn).c(d!())?);
+    }
+
+    Ok(records)
+}
+
+// The owned records and the balances so far
+struct Replay<'a> {
+    kp: &'a XfrKeyPair,
+    owned: HashMap<TxoSID, (AssetType, u64)>,
+    balances: HashMap<AssetType, i128>,
+}
+
+impl<'a> Replay<'a> {
+    fn new(kp: &'a XfrKeyPair) -> Self {
+        Replay {
+            kp,
+            owned: HashMap::new(),
+            balances: HashMap::new(),
+        }
+    }
+
+    fn apply(
+        &mut self,
+        item: TxnHistoryItem,
+        txn: &FinalizedTransaction,
+    ) -> Result<Vec<HistoryRecord>> {
+        let pk = self.kp.get_pk();
+        // in the order of appearance
+        let mut changes: Vec<(AssetType, i128)> = vec![];
+        let mut add_change = |ty: AssetType, am: i128| {
+            if let Some(c) = changes.iter_mut().find(|(t, _)| *t == ty) {
+                c.1 += am;
+            } else {
+                changes.push((ty, am));
+            }
+        };
+        let mut fee = 0;
+        let mut spent = false;
+        let mut received = false;
+        let mut counterparties = BTreeSet::new();
+
+        for op in txn.txn.body.operations.iter() {
+            let (outputs, memos): (Vec<&TxOutput>, Vec<Option<&OwnerMemo>>) = match op {
+                Operation::TransferAsset(xfr) => {
+                    let mine = xfr
+                        .body
+                        .inputs
+                        .iter()
+                        .zip(xfr.body.transfer.inputs.iter())
+                        .filter(|(_, r)| r.public_key == pk)
+                        .collect::<Vec<_>>();
+                    if mine.is_empty() {
+                        xfr.body
+                            .transfer
+                            .inputs
+                            .iter()
+                            .filter(|r| !is_black_hole(&r.public_key))
+                            .for_each(|r| {
+                                counterparties.insert(r.public_key);
+                            });
+                    }
+                    let spend = !mine.is_empty();
+                    for (txo, r) in mine.into_iter() {
+                        let known = match txo {
+                            TxoRef::Absolute(sid) => self.owned.remove(sid),
+                            TxoRef::Relative(_) => None,
+                        };
+                        let (ty, am) = known
+                            .or_else(|| {
+                                r.asset_type.get_asset_type().zip(r.amount.get_amount())
+                            })
+                            .c(d!(format!(
+                                "unknown confidential input in transaction {}",
+                                item.txn_sid.0
+                            )))?;
+                        add_change(ty, -(am as i128));
+                    }
+                    if spend {
+                        spent = true;
+                        xfr.body
+                            .outputs
+                            .iter()
+                            .filter(|o| o.record.public_key != pk)
+                            .for_each(|o| {
+                                if o.record.public_key == *BLACK_HOLE_PUBKEY {
+                                    fee += o.record.amount.get_amount().unwrap_or(0);
+                                } else if o.record.public_key
+                                    != *BLACK_HOLE_PUBKEY_STAKING
+                                {
+                                    counterparties.insert(o.record.public_key);
+                                }
+                            });
+                    }
+                    (xfr.get_outputs_ref(), xfr.get_owner_memos_ref())
+                }
+                Operation::IssueAsset(issue) => {
+                    (issue.get_outputs_ref(), issue.get_owner_memos_ref())
+                }
+                Operation::MintFra(mint) => (
+                    mint.entries.iter().map(|e| &e.utxo).collect(),
+                    mint.entries.iter().map(|_| None).collect(),
+                ),
+                _ => continue,
+            };
+
+            for (o, memo) in outputs.into_iter().zip(memos.into_iter()) {
+                if o.record.public_key != pk {
+                    continue;
+                }
+                received = true;
+                let oar = open_blind_asset_record(&o.record, &memo.cloned(), self.kp)
+                    .c(d!())?;
+                if let Some(sid) = o.id {
+                    self.owned.insert(sid, (oar.asset_type, oar.amount));
+                }
+                add_change(oar.asset_type, oar.amount as i128);
+            }
+        }
+
+        let (kind, detail) = classify(txn, &pk, spent, received);
+        let detail = alt!(
+            detail.is_empty(),
+            counterparties
+                .iter()
+                .map(wallet::public_key_to_bech32)
+                .collect::<Vec<_>>()
+                .join(" "),
+            detail
+        );
+
+        // operations without any asset change, eg. a claim paid by coinbase later
+        if changes.is_empty() {
+            changes.push((ASSET_TYPE_FRA, 0));
+        }
+
+        Ok(changes
+            .into_iter()
+            .map(|(asset, change)| {
+                let balance = self.balances.entry(asset).or_insert(0);
+                *balance += change;
+                HistoryRecord {
+                    txn: item.clone(),
+                    kind: kind.clone(),
+                    asset,
+                    change,
+                    fee: alt!(asset == ASSET_TYPE_FRA, fee, 0),
+                    balance: *balance,
+                    detail: detail.clone(),
+                }
+            })
+            .collect())
+    }
+}
+
+#[inline(always)]
+fn is_black_hole(pk: &XfrPublicKey) -> bool {
+    *pk == *BLACK_HOLE_PUBKEY || *pk == *BLACK_HOLE_PUBKEY_STAKING
+}
+
+// The kind of a transaction is decided by its most significant operation
+fn classify(
+    txn: &FinalizedTransaction,
+    pk: &XfrPublicKey,
+    spent: bool,
+    received: bool,
+) -> (String, String) {
+    let mut kind = None;
+    for op in txn.txn.body.operations.iter() {
+        let k = match op {
+            Operation::Delegation(d) => Some((
+                alt!(d.is_staking(), "stake", "delegate"),
+                d.get_validator().clone(),
+            )),
+            Operation::UnDelegation(u) => Some((
+                "undelegate",
+                u.get_amount()
+                    .map(|am| format!("partial: {}", am))
+                    .unwrap_or_default(),
+            )),
+            Operation::Claim(c) => Some((
+                "claim",
+                c.get_amount()
+                    .map(|am| format!("amount: {}", am))
+                    .unwrap_or_else(|| "amount: all".to_owned()),
+            )),
+            Operation::ConvertAccount(c) => {
+                Some(("convert-account", format!("{:?}", c.data.address)))
+            }
+            Operation::UpdateStaker(_) => Some(("update-staker", String::new())),
+            Operation::DefineAsset(d) => {
+                Some(("define-asset", d.body.asset.code.to_base64()))
+            }
+            Operation::IssueAsset(i) => Some(("issue", i.body.code.to_base64())),
+            Operation::UpdateMemo(u) => {
+                Some(("update-memo", u.body.asset_type.to_base64()))
+            }
+            Operation::TransferPlan(_) => Some(("transfer-plan", String::new())),
+            Operation::RotateKey(r) => Some(("rotate-key", r.get_new_td_addr())),
+            Operation::MintFra(m) => {
+                m.entries
+                    .iter()
+                    .find(|e| e.target_pk == *pk)
+                    .map(|e| match e.kind {
+                        MintKind::Claim => ("coinbase", "claim".to_owned()),
+                        MintKind::UnStake => ("coinbase", "unstake".to_owned()),
+                        MintKind::Other => ("coinbase", String::new()),
+                        MintKind::TransferPlan(id) => {
+                            ("coinbase", format!("plan {}", id))
+                        }
+                    })
+            }
+            _ => None,
+        };
+        if k.is_some() {
+            kind = k;
+            break;
+        }
+    }
+
+    let (kind, detail) = kind.unwrap_or_else(|| {
+        let kind = match (spent, received) {
+            (true, _) => "send",
+            (false, true) => "receive",
+            _ => "other",
+        };
+        (kind, String::new())
+    });
+
+    (kind.to_owned(), detail)
+}
+
+fn asset_name(asset: &AssetType) -> String {
+    alt!(
+        *asset == ASSET_TYPE_FRA,
+        "FRA".to_owned(),
+        AssetTypeCode { val: *asset }.to_base64()
+    )
+}
+
+fn to_row(r: &HistoryRecord) -> [String; 9] {
+    [
+        r.txn.timestamp.map(fmt_utc).unwrap_or_default(),
+        r.txn.height.map(|h| h.to_string()).unwrap_or_default(),
+        r.txn.txn_sid.0.to_string(),
+        r.kind.clone(),
+        asset_name(&r.asset),
+        r.change.to_string(),
+        r.fee.to_string(),
+        r.balance.to_string(),
+        r.detail.clone(),
+    ]
+}
+
+fn print_table(records: &[HistoryRecord]) {
+    let rows = records.iter().map(to_row).collect::<Vec<_>>();
+    let mut widths = COLUMNS.iter().map(|c| c.len()).collect::<Vec<_>>();
+    rows.iter().for_each(|row| {
+        row.iter()
+            .enumerate()
+            .for_each(|(i, v)| widths[i] = widths[i].max(v.len()))
+    });
+
+    let line = |row: &[String]| {
+        row.iter()
+            .enumerate()
+            .map(|(i, v)| format!("{:w$}", v, w = widths[i]))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    println!(
+        "{}",
+        line(&COLUMNS.iter().map(|c| c.to_string()).collect::<Vec<_>>())
+    );
+    rows.iter().for_each(|row| println!("{}", line(row)));
+}
+
+fn print_csv(records: &[HistoryRecord]) {
+    println!("txn_sid,txn_hash,height,timestamp,kind,asset,change,fee,balance,detail");
+    records.iter().for_each(|r| {
+        println!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            r.txn.txn_sid.0,
+            r.txn.txn_hash.as_deref().unwrap_or_default(),
+            r.txn.height.map(|h| h.to_string()).unwrap_or_default(),
+            r.txn.timestamp.map(|t| t.to_string()).unwrap_or_default(),
+            r.kind,
+            asset_name(&r.asset),
+            r.change,
+            r.fee,
+            r.balance,
+            csv_escape(&r.detail)
+        )
+    });
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+// `YYYY-MM-DD hh:mm:ss` in UTC, from a unix timestamp
+fn fmt_utc(ts: i64) -> String {
+    let (days, secs) = (ts.div_euclid(86400), ts.rem_euclid(86400));
+
+    // days to the civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = alt!(mp < 10, mp + 3, mp - 9);
+    let y = yoe + era * 400 + alt!(m <= 2, 1, 0);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        y,
+        m,
+        d,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        common::utils::{test::new_utxo, TransferTarget},
+        txn_builder::CoinSelection,
+    };
+    use ledger::{
+        data_model::{Transaction, TxnSID, Utxo, TX_FEE_MIN},
+        staking::{
+            gen_random_keypair,
+            ops::mint_fra::{MintEntry, MintFraOps},
+        },
+    };
+    use zei::xfr::asset_record::AssetRecordType;
+
+    // the outputs are numbered from `first_txo`
+    fn finalize(op: Operation, sid: usize, first_txo: u64) -> FinalizedTransaction {
+        let n = match &op {
+            Operation::TransferAsset(xfr) => xfr.body.outputs.len(),
+            Operation::MintFra(mint) => mint.entries.len(),
+            _ => 0,
+        } as u64;
+        let mut txn = FinalizedTransaction {
+            txn: Transaction::from_operation(op, 0),
+            tx_id: TxnSID(sid),
+            txo_ids: (first_txo..first_txo + n).map(TxoSID).collect(),
+            merkle_id: 0,
+        };
+        txn.set_txo_id();
+        txn
+    }
+
+    fn item(sid: usize) -> TxnHistoryItem {
+        TxnHistoryItem {
+            txn_sid: TxnSID(sid),
+            txn_hash: None,
+            height: Some(sid as u64),
+            timestamp: None,
+        }
+    }
+
+    // the outputs of a transfer owned by `pk`, with their owner memos
+    fn owned_utxos(
+        txn: &FinalizedTransaction,
+        pk: &XfrPublicKey,
+    ) -> HashMap<TxoSID, (Utxo, Option<OwnerMemo>)> {
+        match &txn.txn.body.operations[0] {
+            Operation::TransferAsset(xfr) => xfr
+                .get_outputs_ref()
+                .into_iter()
+                .zip(xfr.get_owner_memos_ref().into_iter())
+                .filter(|(o, _)| o.record.public_key == *pk)
+                .map(|(o, memo)| (o.id.unwrap(), (Utxo(o.clone()), memo.cloned())))
+                .collect(),
+            _ => panic!("not a transfer"),
+        }
+    }
+
+    fn summary(r: &HistoryRecord) -> (&str, AssetType, i128, u64, i128, &str) {
+        (
+            r.kind.as_str(),
+            r.asset,
+            r.change,
+            r.fee,
+            r.balance,
+            r.detail.as_str(),
+        )
+    }
+
+    #[test]
+    fn test_replay() {
+        let owner = gen_random_keypair();
+        let other = gen_random_keypair();
+        let third = gen_random_keypair().get_pk();
+        let pk = owner.get_pk();
+        let fee = TX_FEE_MIN as i128;
+        let target = |pk, am, confidential_am| TransferTarget {
+            pk,
+            am,
+            confidential_am,
+            confidential_ty: false,
+            multisig: None,
+        };
+
+        // receive a confidential amount
+        let funds = vec![(
+            TxoSID(0),
+            new_utxo(
+                other.get_pk_ref(),
+                10 * TX_FEE_MIN,
+                ASSET_TYPE_FRA,
+                AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+            ),
+        )]
+        .into_iter()
+        .collect();
+        let receive = finalize(
+            pnk!(utils::gen_transfer_op_with_utxos(
+                &other,
+                funds,
+                CoinSelection::InOrder,
+                vec![target(pk, 3 * TX_FEE_MIN, true)],
+                None,
+                None,
+                true,
+            )),
+            1,
+            1,
+        );
+
+        // send a part of it, the fee is paid from it too
+        let send = finalize(
+            pnk!(utils::gen_transfer_op_with_utxos(
+                &owner,
+                owned_utxos(&receive, &pk),
+                CoinSelection::InOrder,
+                vec![target(third, TX_FEE_MIN, false)],
+                None,
+                None,
+                true,
+            )),
+            2,
+            10,
+        );
+
+        let coinbase = finalize(
+            Operation::MintFra(MintFraOps::new(
+                3,
+                vec![MintEntry::new(
+                    MintKind::Claim,
+                    pk,
+                    None,
+                    500,
+                    ASSET_TYPE_FRA,
+                )],
+            )),
+            3,
+            20,
+        );
+
+        // the confidential input can not be opened without the replay of its receipt
+        assert!(Replay::new(&owner).apply(item(2), &send).is_err());
+
+        let mut replay = Replay::new(&owner);
+        let sender = wallet::public_key_to_bech32(other.get_pk_ref());
+        let r = pnk!(replay.apply(item(1), &receive));
+        assert_eq!(1, r.len());
+        assert_eq!(
+            (
+                "receive",
+                ASSET_TYPE_FRA,
+                3 * fee,
+                0,
+                3 * fee,
+                sender.as_str()
+            ),
+            summary(&r[0])
+        );
+
+        let receiver = wallet::public_key_to_bech32(&third);
+        let r = pnk!(replay.apply(item(2), &send));
+        assert_eq!(1, r.len());
+        assert_eq!(
+            (
+                "send",
+                ASSET_TYPE_FRA,
+                -2 * fee,
+                TX_FEE_MIN,
+                fee,
+                receiver.as_str()
+            ),
+            summary(&r[0])
+        );
+        // only the change is owned now
+        assert_eq!(
+            vec![(ASSET_TYPE_FRA, TX_FEE_MIN)],
+            replay.owned.values().cloned().collect::<Vec<_>>()
+        );
+
+        let r = pnk!(replay.apply(item(3), &coinbase));
+        assert_eq!(1, r.len());
+        assert_eq!(
+            ("coinbase", ASSET_TYPE_FRA, 500, 0, fee + 500, "claim"),
+            summary(&r[0])
+        );
+        assert_eq!(Some(&(fee + 500)), replay.balances.get(&ASSET_TYPE_FRA));
+    }
+
+    #[test]
+    fn test_fmt_utc() {
+        assert_eq!("1970-01-01 00:00:00", fmt_utc(0));
+        assert_eq!("2000-02-29 12:34:56", fmt_utc(951827696));
+        assert_eq!("2021-12-31 23:59:59", fmt_utc(1640995199));
+        assert_eq!("1969-12-31 23:59:59", fmt_utc(-1));
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!("abc", csv_escape("abc"));
+        assert_eq!("\"a,b\"", csv_escape("a,b"));
+        assert_eq!("\"a\"\"b\"", csv_escape("a\"b"));
+    }
+}
@@ -0,0 +1,461 @@
+//!
+//! # Keystore of FN
+//!
+//! Named accounts, each of them is a mnemonic with a bip44 path,
+//! the mnemonics are encrypted by the password of the keystore,
+//! see `globutils::wallet::encrypt_pbkdf2_aes256gcm`.
+//!
+//! The password is read from the `FN_PASSWORD` env, or from the tty.
+//!
+//! The keypair of `--account`, or of the default account if not set,
+//! replaces the `owner-mnemonic-path` for all the signing, including
+//! the staking commands; an account is the default only if it is added
+//! with `--default`.
+//!
+
+use super::CFG_PATH;
+use globutils::wallet::{self, BipPath};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::{ErrorKind, Write},
+};
+use zei::xfr::sig::XfrKeyPair;
+
+const KEYSTORE_VERSION: u8 = 1;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+const PASSWORD_ENV: &str = "FN_PASSWORD";
+// encrypted as `Keystore::check` to verify the password
+const CHECK_DATA: &[u8] = b"findora keystore";
+
+lazy_static! {
+    static ref KEYSTORE_FILE: String = format!("{}/keystore.json", &*CFG_PATH);
+    // read once in a command
+    static ref PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+    // selected by `--account`
+    static ref SELECTED: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// An account in the keystore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    /// bech32 address, shown without the password
+    pub address: String,
+    /// bip44 path, eg. "m/44'/917'/0'/0/0"
+    pub path: String,
+    /// language of the mnemonic
+    pub lang: String,
+    // base64 of the encrypted mnemonic
+    mnemonic: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Keystore {
+    version: u8,
+    n_iter: u32,
+    // base64 of the encrypted `CHECK_DATA`
+    check: String,
+    default: Option<String>,
+    accounts: BTreeMap<String, Account>,
+}
+
+impl Keystore {
+    // `None` if the file does not exist
+    fn load(file: &str) -> Result<Option<Self>> {
+        match fs::read(file) {
+            Ok(data) => serde_json::from_slice(&data)
+                .c(d!("invalid keystore"))
+                .and_then(|ks: Keystore| {
+                    if KEYSTORE_VERSION < ks.version {
+                        Err(eg!("the keystore is created by a newer version of fn"))
+                    } else {
+                        Ok(Some(ks))
+                    }
+                }),
+            Err(e) if ErrorKind::NotFound == e.kind() => Ok(None),
+            Err(e) => Err(eg!(format!("fail to read the keystore: {}", e))),
+        }
+    }
+
+    // create a new keystore, the password is asked twice
+    fn create() -> Result<Self> {
+        let password = read_password(true).c(d!())?;
+        let check =
+            wallet::encrypt_pbkdf2_aes256gcm(CHECK_DATA, &password, PBKDF2_ITERATIONS)
+                .c(d!())?;
+        Ok(Keystore {
+            version: KEYSTORE_VERSION,
+            n_iter: PBKDF2_ITERATIONS,
+            check: base64::encode(&check),
+            default: None,
+            accounts: BTreeMap::new(),
+        })
+    }
+
+    fn save(&self, file: &str) -> Result<()> {
+        if let Some(dir) = std::path::Path::new(file).parent() {
+            fs::create_dir_all(dir).c(d!("fail to create config path"))?;
+        }
+        let data = serde_json::to_vec_pretty(self).c(d!())?;
+
+        // written to a temporary file which is only readable by the owner,
+        // then renamed, so the keystore is never partially written or exposed
+        let tmp = format!("{}.tmp", file);
+        if let Err(e) = fs::remove_file(&tmp) {
+            if ErrorKind::NotFound != e.kind() {
+                return Err(eg!(e));
+            }
+        }
+        let mut opts = fs::OpenOptions::new();
+        opts.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            opts.mode(0o600);
+        }
+        let mut f = opts.open(&tmp).c(d!("fail to create the keystore"))?;
+        f.write_all(&data)
+            .and_then(|_| f.sync_all())
+            .c(d!("fail to write the keystore"))?;
+        fs::rename(&tmp, file).c(d!("fail to write the keystore"))
+    }
+
+    // the password, verified by `check`
+    fn password(&self) -> Result<String> {
+        let password = read_password(false).c(d!())?;
+        base64::decode(&self.check)
+            .c(d!("invalid keystore"))
+            .and_then(|c| {
+                wallet::decrypt_pbkdf2_aes256gcm(&c, &password, self.n_iter).c(d!())
+            })
+            .map(|_| password)
+            .map_err(|_| {
+                *PASSWORD.lock() = None;
+                eg!("wrong password")
+            })
+    }
+
+    fn account(&self, name: &str) -> Result<&Account> {
+        self.accounts.get(name).c(d!(format!(
+            "account '{}' not found, see `fn key list`",
+            name
+        )))
+    }
+
+    fn mnemonic(&self, name: &str) -> Result<String> {
+        let account = self.account(name).c(d!())?;
+        let password = self.password().c(d!())?;
+        base64::decode(&account.mnemonic)
+            .c(d!("invalid keystore"))
+            .and_then(|m| {
+                wallet::decrypt_pbkdf2_aes256gcm(&m, &password, self.n_iter).c(d!())
+            })
+            .and_then(|m| String::from_utf8(m).c(d!()))
+    }
+
+    fn keypair(&self, name: &str) -> Result<XfrKeyPair> {
+        let account = self.account(name).c(d!())?;
+        let mnemonic = self.mnemonic(name).c(d!())?;
+        let path = BipPath::parse(&account.path).c(d!())?;
+        let kp =
+            wallet::restore_keypair_from_mnemonic_bip44(&mnemonic, &account.lang, &path)
+                .c(d!())?;
+        if wallet::public_key_to_bech32(kp.get_pk_ref()) != account.address {
+            return Err(eg!(format!(
+                "the keystore is corrupted, the address of '{}' mismatched",
+                name
+            )));
+        }
+        Ok(kp)
+    }
+
+    // the keypair of the selected account, or of the default one
+    fn selected_keypair(&self, selected: Option<&str>) -> Result<Option<XfrKeyPair>> {
+        match selected.or_else(|| self.default.as_deref()) {
+            Some(name) => self.keypair(name).c(d!()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // add an account, returns its address and the generated mnemonic if any
+    fn add(
+        &mut self,
+        name: &str,
+        mnemonic: Option<&str>,
+        path: Option<&str>,
+        lang: &str,
+        set_default: bool,
+    ) -> Result<(String, Option<String>)> {
+        if self.accounts.contains_key(name) {
+            return Err(eg!(format!("account '{}' already exists", name)));
+        }
+        let password = self.password().c(d!())?;
+
+        let generated = mnemonic.is_none();
+        let mnemonic = match mnemonic {
+            Some(m) => m.split_whitespace().collect::<Vec<_>>().join(" "),
+            None => wallet::generate_mnemonic_custom(24, lang).c(d!())?,
+        };
+        let path = path
+            .map(|p| BipPath::parse(p).c(d!()))
+            .transpose()?
+            .unwrap_or_default();
+        let kp = wallet::restore_keypair_from_mnemonic_bip44(&mnemonic, lang, &path)
+            .c(d!("invalid mnemonic"))?;
+        let address = wallet::public_key_to_bech32(kp.get_pk_ref());
+
+        let enc = wallet::encrypt_pbkdf2_aes256gcm(
+            mnemonic.as_bytes(),
+            &password,
+            self.n_iter,
+        )
+        .c(d!())?;
+        self.accounts.insert(
+            name.to_owned(),
+            Account {
+                address: address.clone(),
+                path: path.to_string(),
+                lang: lang.to_owned(),
+                mnemonic: base64::encode(&enc),
+            },
+        );
+        // never replace the `owner-mnemonic-path` implicitly
+        if set_default {
+            self.default = Some(name.to_owned());
+        }
+
+        Ok((address, alt!(generated, Some(mnemonic), None)))
+    }
+
+    // remove an account, the password is needed as a confirmation
+    fn remove(&mut self, name: &str) -> Result<()> {
+        self.account(name).c(d!())?;
+        self.password().c(d!())?;
+
+        self.accounts.remove(name);
+        // never switch to another account silently
+        if self.default.as_deref() == Some(name) {
+            self.default = None;
+        }
+        Ok(())
+    }
+}
+
+fn read_password(confirm: bool) -> Result<String> {
+    if let Some(p) = PASSWORD.lock().as_ref() {
+        return Ok(p.clone());
+    }
+
+    let password = if let Ok(p) = env::var(PASSWORD_ENV) {
+        p
+    } else {
+        let p = rpassword::read_password_from_tty(Some("Keystore password: "))
+            .c(d!("fail to read the password"))?;
+        if confirm {
+            let again = rpassword::read_password_from_tty(Some("Repeat the password: "))
+                .c(d!("fail to read the password"))?;
+            if p != again {
+                return Err(eg!("passwords mismatched"));
+            }
+        }
+        p
+    };
+    if password.is_empty() {
+        return Err(eg!("the password can not be empty"));
+    }
+
+    *PASSWORD.lock() = Some(password.clone());
+    Ok(password)
+}
+
+/// Read a mnemonic from the tty, it will not be echoed
+pub fn read_mnemonic() -> Result<String> {
+    rpassword::read_password_from_tty(Some("Mnemonic: "))
+        .c(d!("fail to read the mnemonic"))
+}
+
+/// Select the account used by `get_keypair`, aka. `--account`
+pub fn select_account(name: Option<&str>) {
+    *SELECTED.lock() = name.map(|n| n.to_owned());
+}
+
+/// The keypair of the selected account, or the default account,
+/// which is used for all the signing, including the staking commands.
+/// `None` if neither is available, then the `owner-mnemonic-path` is used.
+pub fn get_keypair() -> Result<Option<XfrKeyPair>> {
+    let selected = SELECTED.lock().clone();
+    match (Keystore::load(&KEYSTORE_FILE).c(d!())?, selected) {
+        (Some(ks), selected) => ks.selected_keypair(selected.as_deref()).c(d!()),
+        (None, Some(_)) => Err(eg!("no keystore found, see `fn key add`")),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Add an account to the keystore, the keystore will be created if not exists.
+/// - @param `mnemonic`: a new one will be generated if none
+/// - @param `path`: "m/44'/917'/0'/0/0" if none
+/// - @param `set_default`: use the account if `--account` is not set,
+///   instead of the `owner-mnemonic-path`, for all the signing
+pub fn add_account(
+    name: &str,
+    mnemonic: Option<&str>,
+    path: Option<&str>,
+    lang: &str,
+    set_default: bool,
+) -> Result<()> {
+    let mut ks = match Keystore::load(&KEYSTORE_FILE).c(d!())? {
+        Some(ks) => ks,
+        None => Keystore::create().c(d!())?,
+    };
+    let (address, generated) =
+        ks.add(name, mnemonic, path, lang, set_default).c(d!())?;
+    ks.save(&KEYSTORE_FILE).c(d!())?;
+
+    if let Some(mnemonic) = generated {
+        println!(
+            "\x1b[31;01mMnemonic:\x1b[00m {}\n\x1b[31;01mPlease write it down, it is the only way to recover the account!\x1b[00m\n",
+            mnemonic
+        );
+    }
+    println!(
+        "\x1b[31;01mAccount:\x1b[00m {}\n\x1b[31;01mAddress:\x1b[00m {}",
+        name, address
+    );
+    if set_default {
+        println!(
+            "\x1b[31;01mIt is the default account now, all the commands, including the staking ones, are signed by it unless `--account` is set\x1b[00m"
+        );
+    }
+    Ok(())
+}
+
+/// Show the accounts, no password is needed
+pub fn list_accounts() -> Result<()> {
+    let ks = match Keystore::load(&KEYSTORE_FILE).c(d!())? {
+        Some(ks) => ks,
+        None => {
+            println!("No accounts, see `fn key add`");
+            return Ok(());
+        }
+    };
+    for (name, account) in ks.accounts.iter() {
+        let mark = if ks.default.as_deref() == Some(name.as_str()) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{} {}\t{}\t{}", mark, name, account.address, account.path);
+    }
+    Ok(())
+}
+
+/// Show the mnemonic of an account
+pub fn export_account(name: &str) -> Result<()> {
+    let ks = Keystore::load(&KEYSTORE_FILE)
+        .c(d!())?
+        .c(d!("no keystore found, see `fn key add`"))?;
+    let account = ks.account(name).c(d!())?;
+    let mnemonic = ks.mnemonic(name).c(d!())?;
+    println!(
+        "\x1b[31;01mAddress:\x1b[00m {}\n\x1b[31;01mPath:\x1b[00m {}\n\x1b[31;01mMnemonic:\x1b[00m {}",
+        account.address, account.path, mnemonic
+    );
+    Ok(())
+}
+
+/// Remove an account, the password is needed as a confirmation
+pub fn remove_account(name: &str) -> Result<()> {
+    let mut ks = Keystore::load(&KEYSTORE_FILE)
+        .c(d!())?
+        .c(d!("no keystore found, see `fn key add`"))?;
+    ks.remove(name).c(d!())?;
+    ks.save(&KEYSTORE_FILE).c(d!())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn set_password(p: &str) {
+        *PASSWORD.lock() = Some(p.to_owned());
+    }
+
+    #[test]
+    fn keystore_round_trip() {
+        let dir = globutils::fresh_tmp_dir();
+        let file = dir.join("keystore.json").to_string_lossy().into_owned();
+
+        assert!(pnk!(Keystore::load(&file)).is_none());
+        // not a missing file, eg. a directory
+        assert!(Keystore::load(&dir.to_string_lossy()).is_err());
+
+        let mnemonic = pnk!(wallet::generate_mnemonic_custom(24, "en"));
+        let mnemonic = mnemonic.as_str();
+
+        set_password("secret");
+        let mut ks = pnk!(Keystore::create());
+        let (addr_a, generated) = pnk!(ks.add("a", Some(mnemonic), None, "en", false));
+        assert!(generated.is_none());
+        assert!(ks.add("a", Some(mnemonic), None, "en", false).is_err());
+        let (addr_b, generated) = pnk!(ks.add("b", None, None, "en", true));
+        assert_eq!(24, generated.unwrap().split_whitespace().count());
+        pnk!(ks.save(&file));
+        assert!(!std::path::Path::new(&format!("{}.tmp", file)).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = pnk!(fs::metadata(&file)).permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+
+        // list
+        let mut ks = pnk!(Keystore::load(&file)).unwrap();
+        assert_eq!(
+            vec![("a", addr_a.as_str()), ("b", addr_b.as_str())],
+            ks.accounts
+                .iter()
+                .map(|(n, a)| (n.as_str(), a.address.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some("b"), ks.default.as_deref());
+        assert_eq!(mnemonic, pnk!(ks.mnemonic("a")));
+
+        // the default account is used unless one is selected
+        let pk = |kp: Option<XfrKeyPair>| {
+            wallet::public_key_to_bech32(kp.unwrap().get_pk_ref())
+        };
+        assert_eq!(addr_b, pk(pnk!(ks.selected_keypair(None))));
+        assert_eq!(addr_a, pk(pnk!(ks.selected_keypair(Some("a")))));
+        assert!(ks.selected_keypair(Some("c")).is_err());
+
+        // remove the default one
+        pnk!(ks.remove("b"));
+        assert!(ks.remove("b").is_err());
+        pnk!(ks.save(&file));
+        let ks = pnk!(Keystore::load(&file)).unwrap();
+        assert!(ks.default.is_none());
+        assert!(pnk!(ks.selected_keypair(None)).is_none());
+        assert_eq!(1, ks.accounts.len());
+
+        // wrong password
+        set_password("wrong");
+        assert!(ks.mnemonic("a").is_err());
+        assert!(PASSWORD.lock().is_none());
+        set_password("wrong");
+        assert!(ks.selected_keypair(Some("a")).is_err());
+        let mut ks = pnk!(Keystore::load(&file)).unwrap();
+        set_password("wrong");
+        assert!(ks.add("c", Some(mnemonic), None, "en", false).is_err());
+        assert!(!ks.accounts.contains_key("c"));
+        set_password("wrong");
+        assert!(ks.remove("a").is_err());
+        assert!(ks.accounts.contains_key("a"));
+
+        set_password("secret");
+        assert_eq!(addr_a, pk(pnk!(ks.selected_keypair(Some("a")))));
+    }
+}
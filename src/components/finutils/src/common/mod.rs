@@ -7,25 +7,32 @@
 //!
 
 pub mod evm;
+pub mod history;
+pub mod keystore;
+pub mod multisig;
+pub mod offline;
+pub mod scenario;
+pub mod tracing;
 pub mod utils;
+pub mod validator;
 
-use crate::api::DelegationInfo;
+use crate::{api::DelegationInfo, txn_builder::CoinSelection};
 use globutils::wallet;
 use lazy_static::lazy_static;
 use ledger::{
-    data_model::{AssetRules, AssetTypeCode, Transaction, BLACK_HOLE_PUBKEY_STAKING},
+    data_model::{AssetRules, AssetTypeCode, BLACK_HOLE_PUBKEY_STAKING},
     staking::{
-        check_delegation_amount, gen_random_keypair, td_addr_to_bytes,
-        td_pubkey_to_td_addr, td_pubkey_to_td_addr_bytes, PartialUnDelegation,
-        TendermintAddrRef,
+        check_delegation_amount, init::get_inital_validators, td_addr_to_bytes,
+        td_pubkey_to_td_addr, TendermintAddrRef,
     },
 };
+use offline::UnsignedOp;
 use ruc::*;
 use std::{env, fs};
 use tendermint::PrivateKey;
 use utils::{
     get_block_height, get_local_block_height, get_validator_detail,
-    parse_td_validator_keys,
+    parse_td_validator_keys, TransferTarget,
 };
 use zei::{
     setup::PublicParams,
@@ -35,6 +42,9 @@ use zei::{
     },
 };
 
+/// The default number of utxos merged by `fn wallet consolidate`
+pub const CONSOLIDATE_MAX_INPUTS: usize = 32;
+
 lazy_static! {
     static ref CFG_PATH: String = format!(
         "{}/.____fn_config____",
@@ -66,19 +76,16 @@ pub fn staker_update(cr: Option<&str>, memo: Option<&str>) -> Result<()> {
 
     let td_pubkey = get_td_pubkey().c(d!())?;
 
-    let kp = get_keypair().c(d!())?;
-    let vkp = get_td_privkey().c(d!())?;
-
-    let mut builder = utils::new_tx_builder().c(d!())?;
-
-    builder
-        .add_operation_update_staker(&kp, &vkp, td_pubkey, cr, memo)
-        .c(d!())?;
-    utils::gen_fee_op(&kp)
-        .c(d!())
-        .map(|op| builder.add_operation(op))?;
+    let ops = vec![
+        UnsignedOp::UpdateStaker {
+            td_pubkey,
+            commission_rate: cr,
+            memo,
+        },
+        UnsignedOp::fee(),
+    ];
 
-    utils::send_tx(&builder.take_transaction()).c(d!())
+    offline::dispatch(ops, get_keypair).c(d!())
 }
 
 /// Perform a staking operation to add current tendermint node to validator list
@@ -97,9 +104,6 @@ pub fn stake(
         .and_then(|cr| convert_commission_rate(cr).c(d!()))?;
     let td_pubkey = get_td_pubkey().c(d!())?;
 
-    let kp = get_keypair().c(d!())?;
-    let vkp = get_td_privkey().c(d!())?;
-
     macro_rules! diff {
         ($l:expr, $r:expr) => {
             if $l > $r {
@@ -126,21 +130,16 @@ pub fn stake(
         println!("Continue to stake now...");
     }
 
-    let mut builder = utils::new_tx_builder().c(d!())?;
-    builder
-        .add_operation_staking(&kp, &vkp, td_pubkey, cr, memo.map(|m| m.to_owned()))
-        .c(d!())?;
-    utils::gen_transfer_op(
-        &kp,
-        vec![(&BLACK_HOLE_PUBKEY_STAKING, am)],
-        None,
-        false,
-        false,
-    )
-    .c(d!())
-    .map(|principal_op| builder.add_operation(principal_op))?;
+    let ops = vec![
+        UnsignedOp::Staking {
+            td_pubkey,
+            commission_rate: cr,
+            memo: memo.map(|m| m.to_owned()),
+        },
+        UnsignedOp::transfer(vec![(*BLACK_HOLE_PUBKEY_STAKING, am)], None, false, false),
+    ];
 
-    utils::send_tx(&builder.take_transaction()).c(d!())
+    offline::dispatch(ops, get_keypair).c(d!())
 }
 
 /// Append more FRA token to the specified tendermint node
@@ -158,24 +157,19 @@ pub fn stake_append(
             .map(|td_pk| td_pubkey_to_td_addr(&td_pk))
     })?;
 
-    let kp = staker
-        .c(d!())
-        .and_then(|sk| wallet::restore_keypair_from_mnemonic_default(sk).c(d!()))
-        .or_else(|_| get_keypair().c(d!()))?;
+    let kp = || {
+        staker
+            .c(d!())
+            .and_then(|sk| wallet::restore_keypair_from_mnemonic_default(sk).c(d!()))
+            .or_else(|_| get_keypair().c(d!()))
+    };
 
-    let mut builder = utils::new_tx_builder().c(d!())?;
-    builder.add_operation_delegation(&kp, td_addr);
-    utils::gen_transfer_op(
-        &kp,
-        vec![(&BLACK_HOLE_PUBKEY_STAKING, am)],
-        None,
-        false,
-        false,
-    )
-    .c(d!())
-    .map(|principal_op| builder.add_operation(principal_op))?;
+    let ops = vec![
+        UnsignedOp::Delegation { validator: td_addr },
+        UnsignedOp::transfer(vec![(*BLACK_HOLE_PUBKEY_STAKING, am)], None, false, false),
+    ];
 
-    utils::send_tx(&builder.take_transaction()).c(d!())
+    offline::dispatch(ops, kp).c(d!())
 }
 
 /// Withdraw Fra token from findora network for a staker
@@ -190,39 +184,30 @@ pub fn unstake(
         None
     };
 
-    let kp = staker
-        .c(d!())
-        .and_then(|sk| wallet::restore_keypair_from_mnemonic_default(sk).c(d!()))
-        .or_else(|_| get_keypair().c(d!()))?;
-    let td_addr_bytes = td_addr
+    let kp = || {
+        staker
+            .c(d!())
+            .and_then(|sk| wallet::restore_keypair_from_mnemonic_default(sk).c(d!()))
+            .or_else(|_| get_keypair().c(d!()))
+    };
+    let td_addr = td_addr
         .c(d!())
-        .and_then(|ta| td_addr_to_bytes(ta).c(d!()))
+        .and_then(|ta| td_addr_to_bytes(ta).c(d!()).map(|_| ta.to_owned()))
         .or_else(|_| {
             get_td_pubkey()
                 .c(d!())
-                .map(|td_pk| td_pubkey_to_td_addr_bytes(&td_pk))
+                .map(|td_pk| td_pubkey_to_td_addr(&td_pk))
         })?;
 
-    let mut builder = utils::new_tx_builder().c(d!())?;
-
-    utils::gen_fee_op(&kp).c(d!()).map(|op| {
-        builder.add_operation(op);
-        if let Some(am) = am {
-            // partial undelegation
-            builder.add_operation_undelegation(
-                &kp,
-                Some(PartialUnDelegation::new(
-                    am,
-                    gen_random_keypair().get_pk(),
-                    td_addr_bytes,
-                )),
-            );
-        } else {
-            builder.add_operation_undelegation(&kp, None);
-        }
-    })?;
+    let ops = vec![
+        UnsignedOp::fee(),
+        // partial undelegation if `am` is set
+        UnsignedOp::UnDelegation {
+            partial: am.map(|am| (am, td_addr)),
+        },
+    ];
 
-    utils::send_tx(&builder.take_transaction()).c(d!())
+    offline::dispatch(ops, kp).c(d!())
 }
 
 /// Claim rewards from findora network
@@ -233,16 +218,9 @@ pub fn claim(am: Option<&str>, sk_str: Option<&str>) -> Result<()> {
         None
     };
 
-    let kp = restore_keypair_from_str_with_default(sk_str)?;
-
-    let mut builder = utils::new_tx_builder().c(d!())?;
+    let ops = vec![UnsignedOp::fee(), UnsignedOp::Claim { amount: am }];
 
-    utils::gen_fee_op(&kp).c(d!()).map(|op| {
-        builder.add_operation(op);
-        builder.add_operation_claim(&kp, am);
-    })?;
-
-    utils::send_tx(&builder.take_transaction()).c(d!())
+    offline::dispatch(ops, || restore_keypair_from_str_with_default(sk_str)).c(d!())
 }
 
 /// Show information of current node, including following sections:
@@ -360,6 +338,9 @@ pub fn setup(
             pwd.to_str().c(d!("Invalid path"))?
         };
         fs::write(&*MNEMONIC_FILE, mp).c(d!("fail to cache 'owner-mnemonic-path'"))?;
+        println!(
+            "Tips: the mnemonic is kept in plaintext, `fn key add` is recommended to encrypt it"
+        );
     }
     if let Some(kp) = validator_key_path {
         let kp = if kp.starts_with('/') {
@@ -381,46 +362,69 @@ pub fn transfer_asset(
     am: &str,
     confidential_am: bool,
     confidential_ty: bool,
+    selection: CoinSelection,
 ) -> Result<()> {
-    transfer_asset_batch(
-        owner_sk,
-        &[target_addr],
-        token_code,
+    let am = am.parse::<u64>().c(d!("'amount' must be an integer"))?;
+    let target = TransferTarget {
+        pk: target_addr,
         am,
         confidential_am,
         confidential_ty,
-    )
-    .c(d!())
+        multisig: None,
+    };
+
+    transfer_asset_batch(owner_sk, vec![target], token_code, selection).c(d!())
 }
 
-#[allow(missing_docs)]
+/// Transfer to many receivers in one transaction,
+/// the amount and the confidentiality can be different for every receiver.
 pub fn transfer_asset_batch(
     owner_sk: Option<&str>,
-    target_addr: &[XfrPublicKey],
+    targets: Vec<TransferTarget>,
     token_code: Option<AssetTypeCode>,
-    am: &str,
-    confidential_am: bool,
-    confidential_ty: bool,
+    selection: CoinSelection,
 ) -> Result<()> {
-    let from = restore_keypair_from_str_with_default(owner_sk)?;
-    let am = am.parse::<u64>().c(d!("'amount' must be an integer"))?;
+    let ops = vec![UnsignedOp::Transfer {
+        targets,
+        token_code,
+        selection,
+    }];
+
+    offline::dispatch(ops, || restore_keypair_from_str_with_default(owner_sk)).c(d!())
+}
 
-    utils::transfer_batch(
-        &from,
-        target_addr.iter().map(|addr| (addr, am)).collect(),
+/// Merge up to `max_inputs` of the smallest utxos of an asset(FRA by default)
+/// into one, so that later transfers need fewer inputs.
+pub fn consolidate(
+    owner_sk: Option<&str>,
+    token_code: Option<AssetTypeCode>,
+    max_inputs: Option<&str>,
+) -> Result<()> {
+    let max_inputs = max_inputs
+        .map(|n| n.parse::<usize>().c(d!("'max-inputs' must be an integer")))
+        .transpose()?
+        .unwrap_or(CONSOLIDATE_MAX_INPUTS);
+
+    let ops = vec![UnsignedOp::Consolidate {
         token_code,
-        confidential_am,
-        confidential_ty,
-    )
-    .c(d!())
+        max_inputs,
+    }];
+
+    offline::dispatch(ops, || restore_keypair_from_str_with_default(owner_sk)).c(d!())
 }
 
 /// Mainly for official usage,
 /// and can be also used in test scenes.
+///
+/// The update is co-signed by the validators with `fn tx sign --cosig`
+/// if it is built by `--build-only`.
 pub fn set_initial_validators() -> Result<()> {
-    get_keypair()
-        .c(d!())
-        .and_then(|kp| utils::set_initial_validators(&kp).c(d!()))
+    let mut builder = utils::new_tx_builder().c(d!())?;
+    builder
+        .add_operation_update_validator(&[], 1, get_inital_validators().c(d!())?)
+        .c(d!())?;
+
+    offline::dispatch_with(builder, vec![UnsignedOp::fee()], get_keypair).c(d!())
 }
 
 fn get_serv_addr() -> Result<&'static str> {
@@ -431,9 +435,12 @@ fn get_serv_addr() -> Result<&'static str> {
     }
 }
 
-/// Get keypair from config file
+/// Get keypair of the selected account in the keystore,
+/// or from the mnemonic file in the config
 pub fn get_keypair() -> Result<XfrKeyPair> {
-    if let Some(m_path) = MNEMONIC.as_ref() {
+    if let Some(kp) = keystore::get_keypair().c(d!())? {
+        Ok(kp)
+    } else if let Some(m_path) = MNEMONIC.as_ref() {
         fs::read_to_string(m_path)
             .c(d!("can not read mnemonic from 'owner-mnemonic-path'"))
             .and_then(|m| {
@@ -441,7 +448,9 @@ pub fn get_keypair() -> Result<XfrKeyPair> {
                     .c(d!("invalid 'owner-mnemonic'"))
             })
     } else {
-        Err(eg!("'owner-mnemonic-path' has not been set"))
+        Err(eg!(
+            "no account found, see `fn key add`, or set 'owner-mnemonic-path'"
+        ))
     }
 }
 
@@ -460,18 +469,23 @@ fn get_td_pubkey() -> Result<Vec<u8>> {
 
 fn get_td_privkey() -> Result<PrivateKey> {
     if let Some(key_path) = TD_KEY.as_ref() {
-        fs::read_to_string(key_path)
-            .c(d!("can not read key file from path"))
-            .and_then(|k| {
-                parse_td_validator_keys(k)
-                    .c(d!())
-                    .map(|v_keys| v_keys.priv_key)
-            })
+        read_td_privkey(key_path).c(d!())
     } else {
         Err(eg!("'validator-privkey' has not been set"))
     }
 }
 
+// read the private key from a key file of tendermint, eg. `priv_validator_key.json`
+fn read_td_privkey(key_path: &str) -> Result<PrivateKey> {
+    fs::read_to_string(key_path)
+        .c(d!("can not read key file from path"))
+        .and_then(|k| {
+            parse_td_validator_keys(k)
+                .c(d!())
+                .map(|v_keys| v_keys.priv_key)
+        })
+}
+
 fn convert_commission_rate(cr: f64) -> Result<[u64; 2]> {
     if 1.0 < cr {
         return Err(eg!("commission rate can exceed 100%"));
@@ -517,24 +531,51 @@ pub fn show_account(sk_str: Option<&str>, asset: Option<&str>) -> Result<()> {
     let token_code = asset
         .map(|asset| AssetTypeCode::new_from_base64(asset).c(d!("Invalid asset code")))
         .transpose()?;
-    let balance = utils::get_asset_balance(&kp, token_code).c(d!())?;
+    let (balance, confidential) =
+        utils::get_asset_balance_detail(&kp, token_code).c(d!())?;
 
-    println!("{}: {}", asset.unwrap_or("FRA"), balance);
+    if 0 < confidential {
+        println!(
+            "{}: {} (confidential: {})",
+            asset.unwrap_or("FRA"),
+            balance,
+            confidential
+        );
+    } else {
+        println!("{}: {}", asset.unwrap_or("FRA"), balance);
+    }
     Ok(())
 }
 
 #[allow(missing_docs)]
 pub fn delegate(sk_str: Option<&str>, amount: u64, validator: &str) -> Result<()> {
-    let kp = restore_keypair_from_str_with_default(sk_str)?;
-
-    utils::send_tx(&gen_delegate_tx(&kp, amount, validator).c(d!())?)
+    let ops = vec![
+        UnsignedOp::transfer(
+            vec![(*BLACK_HOLE_PUBKEY_STAKING, amount)],
+            None,
+            false,
+            false,
+        ),
+        UnsignedOp::Delegation {
+            validator: validator.to_owned(),
+        },
+    ];
+
+    offline::dispatch(ops, || restore_keypair_from_str_with_default(sk_str)).c(d!())
 }
 
 #[allow(missing_docs)]
 pub fn undelegate(sk_str: Option<&str>, param: Option<(u64, &str)>) -> Result<()> {
-    let kp = restore_keypair_from_str_with_default(sk_str)?;
+    let partial = param
+        .map(|(amount, validator)| {
+            td_addr_to_bytes(validator)
+                .c(d!())
+                .map(|_| (amount, validator.to_owned()))
+        })
+        .transpose()?;
+    let ops = vec![UnsignedOp::fee(), UnsignedOp::UnDelegation { partial }];
 
-    utils::send_tx(&gen_undelegate_tx(&kp, param).c(d!())?)
+    offline::dispatch(ops, || restore_keypair_from_str_with_default(sk_str)).c(d!())
 }
 
 /// Display delegation information of a findora account
@@ -552,62 +593,12 @@ pub fn show_delegations(sk_str: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-fn gen_undelegate_tx(
-    owner_kp: &XfrKeyPair,
-    param: Option<(u64, &str)>,
-) -> Result<Transaction> {
-    let mut builder = utils::new_tx_builder().c(d!())?;
-    utils::gen_fee_op(owner_kp).c(d!()).map(|op| {
-        builder.add_operation(op);
-    })?;
-    if let Some((amount, validator)) = param {
-        // partial undelegation
-        builder.add_operation_undelegation(
-            owner_kp,
-            Some(PartialUnDelegation::new(
-                amount,
-                gen_random_keypair().get_pk(),
-                td_addr_to_bytes(validator).c(d!())?,
-            )),
-        );
-    } else {
-        builder.add_operation_undelegation(owner_kp, None);
-    }
-
-    Ok(builder.take_transaction())
-}
-
-fn gen_delegate_tx(
-    owner_kp: &XfrKeyPair,
-    amount: u64,
-    validator: &str,
-) -> Result<Transaction> {
-    let mut builder = utils::new_tx_builder().c(d!())?;
-
-    utils::gen_transfer_op(
-        owner_kp,
-        vec![(&BLACK_HOLE_PUBKEY_STAKING, amount)],
-        None,
-        false,
-        false,
-    )
-    .c(d!())
-    .map(|principal_op| {
-        builder.add_operation(principal_op);
-        builder.add_operation_delegation(owner_kp, validator.to_owned());
-    })?;
-
-    Ok(builder.take_transaction())
-}
-
-/// Create a custom asset for a findora account. If no token code string provided,
-/// it will generate a random new one.
+/// Create a custom asset with the given rules for a findora account.
+/// If no token code string provided, it will generate a random new one.
 pub fn create_asset(
     sk_str: Option<&str>,
     memo: &str,
-    decimal: u8,
-    max_units: Option<u64>,
-    tranferable: bool,
+    rules: AssetRules,
     token_code: Option<&str>,
 ) -> Result<()> {
     let code = if token_code.is_none() {
@@ -618,11 +609,6 @@ pub fn create_asset(
     };
     let kp = restore_keypair_from_str_with_default(sk_str)?;
 
-    let mut rules = AssetRules::default();
-    rules.set_decimals(decimal).c(d!())?;
-    rules.set_max_units(max_units);
-    rules.set_transferable(tranferable);
-
     let mut builder = utils::new_tx_builder().c(d!())?;
     builder
         .add_operation_create_asset(&kp, Some(code), rules, memo)
@@ -634,7 +620,8 @@ pub fn create_asset(
     utils::send_tx(&builder.take_transaction())
 }
 
-/// Issue a custom asset with specified amount
+/// Issue a custom asset with specified amount,
+/// the new record is built with the tracing policies of the asset
 pub fn issue_asset(
     sk_str: Option<&str>,
     asset: &str,
@@ -644,15 +631,17 @@ pub fn issue_asset(
     let kp = restore_keypair_from_str_with_default(sk_str)?;
     let code = AssetTypeCode::new_from_base64(asset).c(d!())?;
     let confidentiality_flags = AssetRecordType::from_flags(hidden, false);
+    let tracing_policies = utils::get_tracing_policies(&code).c(d!())?;
 
     let mut builder = utils::new_tx_builder().c(d!())?;
     builder
-        .add_basic_issue_asset(
+        .add_traced_issue_asset(
             &kp,
             &code,
             builder.get_seq_id(),
             amount,
             confidentiality_flags,
+            &tracing_policies,
             &PublicParams::default(),
         )
         .c(d!())?;
@@ -663,6 +652,28 @@ pub fn issue_asset(
     utils::send_tx(&builder.take_transaction())
 }
 
+/// Replace the memo of an updatable custom asset, only its issuer can do this
+pub fn update_asset_memo(sk_str: Option<&str>, asset: &str, memo: &str) -> Result<()> {
+    let kp = restore_keypair_from_str_with_default(sk_str)?;
+    let code = AssetTypeCode::new_from_base64(asset).c(d!())?;
+
+    let asset_type = utils::get_asset_type(asset).c(d!())?;
+    if !asset_type.properties.asset_rules.updatable {
+        return Err(eg!("the memo of this asset is not updatable"));
+    }
+    if asset_type.properties.issuer.key != kp.get_pk() {
+        return Err(eg!("only the issuer can update the memo"));
+    }
+
+    let mut builder = utils::new_tx_builder().c(d!())?;
+    builder.add_operation_update_memo(&kp, code, memo);
+    utils::gen_fee_op(&kp)
+        .c(d!())
+        .map(|op| builder.add_operation(op))?;
+
+    utils::send_tx(&builder.take_transaction())
+}
+
 /// Show a list of custom asset token created by a findora account
 pub fn show_asset(addr: &str) -> Result<()> {
     let pk = wallet::public_key_from_bech32(addr).c(d!())?;
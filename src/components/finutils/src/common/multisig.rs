@@ -0,0 +1,352 @@
+//!
+//! # Multisig accounts
+//!
+//! The assets of a multisig account are controlled by a set of weighted signers,
+//! a transfer from it is valid once the weights of its approvers reach the threshold.
+//! The address of the account is derived from these rules, see
+//! `SignatureRules::multisig_address`, the utxos funded by `fn multisig create`
+//! record them, those sent by `fn transfer` do not, but both can be spent.
+//!
+//! - `fn multisig create`, show the address of an account, and fund it
+//! - `fn multisig propose`, save a transfer from the account into a proposal file
+//! - `fn multisig approve`, add the approval of a signer to the proposal file
+//! - `fn multisig submit`, send the proposal once it is approved
+//!
+//! **NOTE**: the nonce of a proposal expires in about 128 blocks,
+//! so it should be approved and submitted in time.
+//!
+
+use super::{
+    offline::TxFile,
+    restore_keypair_from_str_with_default, transfer_asset_batch,
+    utils::{self, TransferTarget},
+};
+use crate::txn_builder::CoinSelection;
+use globutils::wallet;
+use ledger::data_model::{AssetTypeCode, Operation, SignatureRules, Transaction};
+use ruc::*;
+use zei::xfr::sig::XfrPublicKey;
+
+/// Parse the rules of `--threshold` and `--signers`, the signers are separated
+/// by commas, each of them is `<wallet address>[:<weight>]`, the weight defaults to 1.
+///
+/// The order of the signers is a part of the rules, so is the address of the account.
+pub fn parse_rules(threshold: &str, signers: &str) -> Result<SignatureRules> {
+    let threshold = threshold
+        .parse::<u64>()
+        .c(d!("'threshold' must be an integer"))?;
+    let weights = signers
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let mut fields = s.splitn(2, ':');
+            let pk = fields
+                .next()
+                .c(d!())
+                .and_then(|addr| {
+                    wallet::public_key_from_bech32(addr).c(d!("invalid wallet address"))
+                })
+                .c(d!(s.to_owned()))?;
+            let weight = fields
+                .next()
+                .map(|w| w.parse::<u64>().c(d!("weight must be an integer")))
+                .transpose()?
+                .unwrap_or(1);
+            Ok((pk, weight))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let rules = SignatureRules { threshold, weights };
+    rules.check().c(d!())?;
+    Ok(rules)
+}
+
+/// Show the address of the multisig account controlled by `rules`,
+/// and fund it from the account of `owner_sk` if `am` is set.
+pub fn create(
+    rules: SignatureRules,
+    owner_sk: Option<&str>,
+    token_code: Option<AssetTypeCode>,
+    am: Option<&str>,
+) -> Result<()> {
+    println!(
+        "\x1b[31;01mMultisig Address:\x1b[00m\n{}\n",
+        wallet::public_key_to_bech32(&rules.multisig_address())
+    );
+    println!("\x1b[31;01mThreshold:\x1b[00m\n{}\n", rules.threshold);
+    println!("\x1b[31;01mSigners:\x1b[00m");
+    rules.weights.iter().for_each(|(pk, weight)| {
+        println!("{} {}", wallet::public_key_to_bech32(pk), weight)
+    });
+
+    if let Some(am) = am {
+        let am = am.parse::<u64>().c(d!("'amount' must be an integer"))?;
+        transfer_asset_batch(
+            owner_sk,
+            vec![TransferTarget::multisig(rules, am)],
+            token_code,
+            CoinSelection::default(),
+        )
+        .c(d!())?;
+    }
+
+    Ok(())
+}
+
+/// Save a transfer from the multisig account into a proposal file, no key is needed.
+/// The rules are taken from the utxos of the account if `rules` is none,
+/// they must be set if the account is only funded by plain transfers.
+/// The fee and the change are paid by the account.
+pub fn propose(
+    address: &str,
+    rules: Option<SignatureRules>,
+    target: XfrPublicKey,
+    token_code: Option<AssetTypeCode>,
+    am: &str,
+    selection: CoinSelection,
+    out: &str,
+) -> Result<()> {
+    let pk =
+        wallet::public_key_from_bech32(address).c(d!("invalid multisig address"))?;
+    let am = am.parse::<u64>().c(d!("'amount' must be an integer"))?;
+
+    let utxos = utils::get_owned_utxos(&pk).c(d!())?;
+    let rules = match rules {
+        Some(rules) if rules.multisig_address() == pk => rules,
+        Some(_) => return Err(eg!("the rules do not match the multisig address")),
+        None => utxos
+            .values()
+            .find_map(|(utxo, _)| utxo.0.multisig.clone())
+            .c(d!(
+                "the rules are not recorded, set `--threshold` and `--signers`"
+            ))?,
+    };
+
+    let target = TransferTarget {
+        pk: target,
+        am,
+        confidential_am: false,
+        confidential_ty: false,
+        multisig: None,
+    };
+    let tracing_policies = token_code
+        .map(|code| utils::get_tracing_policies(&code).c(d!()))
+        .transpose()?;
+    let op = utils::gen_multisig_transfer_op(
+        &rules,
+        utxos,
+        selection,
+        vec![target],
+        token_code,
+        tracing_policies.as_ref(),
+    )
+    .c(d!())?;
+
+    let mut builder = utils::new_tx_builder().c(d!())?;
+    builder.add_operation(op);
+    let tx = builder.take_transaction();
+    TxFile::Proposal(tx.clone()).save(out).c(d!())?;
+
+    println!(
+        "The proposal is saved to {}, see `fn multisig approve`",
+        out
+    );
+    show_approvals(&tx);
+    Ok(())
+}
+
+/// Approve a proposal as a signer of the multisig account, the file is updated in place.
+pub fn approve(file: &str, sk_str: Option<&str>) -> Result<()> {
+    let kp = restore_keypair_from_str_with_default(sk_str).c(d!())?;
+    let mut tx = load_proposal(file).c(d!())?;
+
+    if 0 == tx.cosign(&kp).c(d!())? {
+        return Err(eg!(
+            "not a signer of the multisig account, or approved already"
+        ));
+    }
+    TxFile::Proposal(tx.clone()).save(file).c(d!())?;
+
+    println!("The approval is added to {}", file);
+    show_approvals(&tx);
+    Ok(())
+}
+
+/// Send a proposal once it is approved by enough signers.
+pub fn submit(file: &str) -> Result<()> {
+    let tx = load_proposal(file).c(d!())?;
+    check_approvals(&tx).c(d!())?;
+    utils::send_tx(&tx).c(d!())
+}
+
+/// Check that the transfers from multisig accounts
+/// are approved by enough signers, before sending them.
+pub fn check_approvals(tx: &Transaction) -> Result<()> {
+    for (rules, weight) in approvals(tx) {
+        if weight < rules.threshold {
+            return Err(eg!(format!(
+                "{} is approved {} of {}",
+                wallet::public_key_to_bech32(&rules.multisig_address()),
+                weight,
+                rules.threshold
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn load_proposal(file: &str) -> Result<Transaction> {
+    match TxFile::load(file).c(d!())? {
+        TxFile::Proposal(tx) => Ok(tx),
+        _ => Err(eg!("not a multisig proposal, see `fn multisig propose`")),
+    }
+}
+
+fn show_approvals(tx: &Transaction) {
+    approvals(tx).into_iter().for_each(|(rules, weight)| {
+        println!(
+            "{}: approved {} of {}",
+            wallet::public_key_to_bech32(&rules.multisig_address()),
+            weight,
+            rules.threshold
+        )
+    });
+}
+
+// the rules of the multisig accounts spent by the transaction,
+// with the sum of the weights of their valid approvals
+fn approvals(tx: &Transaction) -> Vec<(&SignatureRules, u64)> {
+    let mut res: Vec<(&SignatureRules, u64)> = vec![];
+    for op in tx.body.operations.iter() {
+        if let Operation::TransferAsset(xfr) = op {
+            let approvers = xfr
+                .body_signatures
+                .iter()
+                .filter(|sig| xfr.body.verify_body_signature(sig))
+                .map(|sig| sig.address.key)
+                .collect::<Vec<_>>();
+            let start = res.len();
+            for (_, rules) in xfr.body.multisig_inputs.iter() {
+                if res[start..].iter().any(|(r, _)| *r == rules) {
+                    continue;
+                }
+                let weight = rules
+                    .weights
+                    .iter()
+                    .filter(|(pk, _)| approvers.contains(pk))
+                    .fold(0u64, |acc, (_, w)| acc.saturating_add(*w));
+                res.push((rules, weight));
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::utils::{gen_multisig_transfer_op, test::new_utxo};
+    use ledger::{
+        data_model::{TxoRef, TxoSID, ASSET_TYPE_FRA, TX_FEE_MIN},
+        staking::gen_random_keypair,
+    };
+    use std::collections::HashMap;
+    use zei::xfr::asset_record::AssetRecordType;
+
+    #[test]
+    fn test_parse_rules() {
+        let (a, b) = (gen_random_keypair().get_pk(), gen_random_keypair().get_pk());
+        let (addr_a, addr_b) = (
+            wallet::public_key_to_bech32(&a),
+            wallet::public_key_to_bech32(&b),
+        );
+
+        // the weight defaults to 1, blanks and empty fields are ignored
+        let rules = pnk!(parse_rules("3", &format!(" {}:2, {} ,", addr_a, addr_b)));
+        assert_eq!(3, rules.threshold);
+        assert_eq!(vec![(a, 2), (b, 1)], rules.weights);
+
+        // the order is a part of the address
+        let reversed = pnk!(parse_rules("3", &format!("{},{}:2", addr_b, addr_a)));
+        assert_ne!(rules.multisig_address(), reversed.multisig_address());
+
+        assert!(parse_rules("x", &addr_a).is_err());
+        assert!(parse_rules("1", "fra1invalid").is_err());
+        assert!(parse_rules("1", &format!("{}:x", addr_a)).is_err());
+        assert!(parse_rules("1", "").is_err());
+        assert!(parse_rules("0", &addr_a).is_err());
+        // unreachable or duplicated
+        assert!(parse_rules("2", &addr_a).is_err());
+        assert!(parse_rules("2", &format!("{},{}", addr_a, addr_a)).is_err());
+    }
+
+    #[test]
+    fn test_approvals() {
+        let signers = (0..3).map(|_| gen_random_keypair()).collect::<Vec<_>>();
+        let rules = SignatureRules {
+            threshold: 3,
+            weights: vec![
+                (signers[0].get_pk(), 2),
+                (signers[1].get_pk(), 1),
+                (signers[2].get_pk(), 1),
+            ],
+        };
+        let address = rules.multisig_address();
+
+        // funded by a plain transfer, the rules are not recorded
+        let utxos = vec![(
+            TxoSID(1),
+            new_utxo(
+                &address,
+                3 * TX_FEE_MIN,
+                ASSET_TYPE_FRA,
+                AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+            ),
+        )]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let target = TransferTarget {
+            pk: gen_random_keypair().get_pk(),
+            am: TX_FEE_MIN,
+            confidential_am: false,
+            confidential_ty: false,
+            multisig: None,
+        };
+        let op = pnk!(gen_multisig_transfer_op(
+            &rules,
+            utxos,
+            CoinSelection::default(),
+            vec![target],
+            None,
+            None,
+        ));
+        if let Operation::TransferAsset(xfr) = &op {
+            assert_eq!(vec![TxoRef::Absolute(TxoSID(1))], xfr.body.inputs);
+            assert_eq!(vec![(0, rules.clone())], xfr.body.multisig_inputs);
+        } else {
+            panic!("not a transfer");
+        }
+        let mut tx = Transaction::from_operation(op, 0);
+
+        let weights = |tx: &Transaction| {
+            approvals(tx)
+                .into_iter()
+                .map(|(r, w)| (r.clone(), w))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![(rules.clone(), 0)], weights(&tx));
+        assert!(check_approvals(&tx).is_err());
+
+        // not a signer, or approved already
+        assert_eq!(0, pnk!(tx.cosign(&gen_random_keypair())));
+        assert_eq!(1, pnk!(tx.cosign(&signers[1])));
+        assert_eq!(0, pnk!(tx.cosign(&signers[1])));
+        assert_eq!(vec![(rules.clone(), 1)], weights(&tx));
+        assert!(check_approvals(&tx).is_err());
+
+        assert_eq!(1, pnk!(tx.cosign(&signers[0])));
+        assert_eq!(vec![(rules, 3)], weights(&tx));
+        pnk!(check_approvals(&tx));
+    }
+}
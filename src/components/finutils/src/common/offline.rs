@@ -0,0 +1,614 @@
+//!
+//! # Offline signing
+//!
+//! Build, sign and broadcast a transaction as separate steps,
+//! so the private key can be kept on an air-gapped machine.
+//!
+//! - `--build-only=[File]`, the operations are saved as an unsigned transaction,
+//!   along with the utxos, owner memos and tracing policies needed to sign it
+//! - `fn tx sign`, sign it without the network, co-signatures of the
+//!   governance/validator updates and the approvals of the multisig transfers
+//!   can be collected by `--cosig` one by one
+//! - `fn tx broadcast`, send the signed transaction to the network
+//!
+//! The proposals of multisig accounts share the file format, see `multisig`.
+//!
+//! `--build-only` is supported by `stake`, `staker-update`, `unstake`, `claim`,
+//! `delegate`, `undelegate`, `transfer`, `transfer-batch`, `set-initial-validators`,
+//! `validator rotate-key`, `wallet consolidate` and `multisig create --amount`.
+//! The `asset` and `contract-*` subcommands are not supported, their operations
+//! are signed while they are built.
+//!
+
+use super::{
+    get_td_privkey, multisig, read_td_privkey, restore_keypair_from_str_with_default,
+    utils::{self, TransferTarget},
+};
+use crate::txn_builder::{CoinSelection, TransactionBuilder};
+use globutils::wallet;
+use lazy_static::lazy_static;
+use ledger::{
+    data_model::{AssetTypeCode, Operation, Transaction, TxoRef, TxoSID, Utxo},
+    staking::{
+        gen_random_keypair, td_addr_to_bytes, PartialUnDelegation, StakerMemo,
+        TendermintAddr,
+    },
+};
+use parking_lot::Mutex;
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs};
+use zei::xfr::{
+    sig::{XfrKeyPair, XfrPublicKey},
+    structs::{OwnerMemo, TracingPolicies},
+};
+
+lazy_static! {
+    // set by `--build-only`
+    static ref BUILD_ONLY: Mutex<Option<BuildOnly>> = Mutex::new(None);
+}
+
+struct BuildOnly {
+    file: String,
+    // set by `--owner-address`, so no private key is needed
+    owner: Option<XfrPublicKey>,
+}
+
+/// An operation to be signed by the owner of the transaction
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum UnsignedOp {
+    /// Transfer from the owner, the inputs are selected by `selection` when signing,
+    /// and `TX_FEE_MIN` is paid to the black hole
+    Transfer {
+        targets: Vec<TransferTarget>,
+        token_code: Option<AssetTypeCode>,
+        #[serde(default)]
+        selection: CoinSelection,
+    },
+    /// Merge the small utxos of an asset, see `utils::gen_consolidate_op`
+    Consolidate {
+        token_code: Option<AssetTypeCode>,
+        max_inputs: usize,
+    },
+    /// Self-staking of a validator, the validator key is needed to sign
+    Staking {
+        td_pubkey: Vec<u8>,
+        commission_rate: [u64; 2],
+        memo: Option<String>,
+    },
+    /// The validator key is needed to sign
+    UpdateStaker {
+        td_pubkey: Vec<u8>,
+        commission_rate: [u64; 2],
+        memo: StakerMemo,
+    },
+    /// Replace the tendermint key of `validator`,
+    /// the new key is read from `new_key_path` to sign
+    RotateKey {
+        validator: TendermintAddr,
+        new_key_path: String,
+    },
+    Delegation {
+        validator: TendermintAddr,
+    },
+    /// A partial undelegation if the amount and the validator are set
+    UnDelegation {
+        partial: Option<(u64, TendermintAddr)>,
+    },
+    Claim {
+        amount: Option<u64>,
+    },
+}
+
+impl UnsignedOp {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn transfer(
+        targets: Vec<(XfrPublicKey, u64)>,
+        token_code: Option<AssetTypeCode>,
+        confidential_am: bool,
+        confidential_ty: bool,
+    ) -> Self {
+        UnsignedOp::Transfer {
+            targets: targets
+                .into_iter()
+                .map(|(pk, am)| TransferTarget {
+                    pk,
+                    am,
+                    confidential_am,
+                    confidential_ty,
+                    multisig: None,
+                })
+                .collect(),
+            token_code,
+            selection: CoinSelection::default(),
+        }
+    }
+
+    /// A standalone fee, see `utils::gen_fee_op`
+    #[inline(always)]
+    pub fn fee() -> Self {
+        Self::transfer(vec![], None, false, false)
+    }
+}
+
+/// A transaction built without the private key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsignedTx {
+    /// The signer of `ops`
+    pub owner: XfrPublicKey,
+    /// The nonce is fixed here,
+    /// operations with co-signatures are added in advance
+    pub builder: TransactionBuilder,
+    /// Utxos of the owner with their owner memos, the inputs of transfers
+    pub utxos: HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>,
+    /// Tracing policies of the transferred or consolidated custom assets,
+    /// see `utils::get_tracing_policies`
+    #[serde(default)]
+    pub tracing_policies: Vec<(AssetTypeCode, TracingPolicies)>,
+    #[allow(missing_docs)]
+    pub ops: Vec<UnsignedOp>,
+}
+
+impl UnsignedTx {
+    /// Sign the operations by the owner, no network is needed
+    pub fn sign(self, kp: &XfrKeyPair) -> Result<Transaction> {
+        if kp.get_pk() != self.owner {
+            return Err(eg!(format!(
+                "the transaction must be signed by {}",
+                wallet::public_key_to_bech32(&self.owner)
+            )));
+        }
+
+        let mut builder = self.builder;
+        let mut utxos = self.utxos;
+
+        for op in self.ops.into_iter() {
+            match op {
+                UnsignedOp::Transfer {
+                    targets,
+                    token_code,
+                    selection,
+                } => {
+                    let tracing_policies =
+                        find_tracing_policies(&self.tracing_policies, token_code);
+                    let op = utils::gen_transfer_op_with_utxos(
+                        kp,
+                        utxos.clone(),
+                        selection,
+                        targets,
+                        token_code,
+                        tracing_policies,
+                        true,
+                    )
+                    .c(d!())?;
+                    add_transfer(&mut builder, &mut utxos, op);
+                }
+                UnsignedOp::Consolidate {
+                    token_code,
+                    max_inputs,
+                } => {
+                    let tracing_policies =
+                        find_tracing_policies(&self.tracing_policies, token_code);
+                    let op = utils::gen_consolidate_op(
+                        kp,
+                        utxos.clone(),
+                        token_code,
+                        tracing_policies,
+                        max_inputs,
+                    )
+                    .c(d!())?;
+                    add_transfer(&mut builder, &mut utxos, op);
+                }
+                UnsignedOp::Staking {
+                    td_pubkey,
+                    commission_rate,
+                    memo,
+                } => {
+                    let vkp = get_td_privkey().c(d!())?;
+                    builder
+                        .add_operation_staking(
+                            kp,
+                            &vkp,
+                            td_pubkey,
+                            commission_rate,
+                            memo,
+                        )
+                        .c(d!())?;
+                }
+                UnsignedOp::UpdateStaker {
+                    td_pubkey,
+                    commission_rate,
+                    memo,
+                } => {
+                    let vkp = get_td_privkey().c(d!())?;
+                    builder
+                        .add_operation_update_staker(
+                            kp,
+                            &vkp,
+                            td_pubkey,
+                            commission_rate,
+                            memo,
+                        )
+                        .c(d!())?;
+                }
+                UnsignedOp::RotateKey {
+                    validator,
+                    new_key_path,
+                } => {
+                    let new_vkp = read_td_privkey(&new_key_path).c(d!())?;
+                    builder
+                        .add_operation_rotate_key(kp, &new_vkp, validator)
+                        .c(d!())?;
+                }
+                UnsignedOp::Delegation { validator } => {
+                    builder.add_operation_delegation(kp, validator);
+                }
+                UnsignedOp::UnDelegation { partial } => {
+                    let pu = partial
+                        .map(|(am, validator)| {
+                            td_addr_to_bytes(&validator).c(d!()).map(|addr| {
+                                PartialUnDelegation::new(
+                                    am,
+                                    gen_random_keypair().get_pk(),
+                                    addr,
+                                )
+                            })
+                        })
+                        .transpose()?;
+                    builder.add_operation_undelegation(kp, pu);
+                }
+                UnsignedOp::Claim { amount } => {
+                    builder.add_operation_claim(kp, amount);
+                }
+            }
+        }
+
+        Ok(builder.take_transaction())
+    }
+}
+
+// the policies fetched along with the utxos, if the asset is traced
+fn find_tracing_policies(
+    tracing_policies: &[(AssetTypeCode, TracingPolicies)],
+    token_code: Option<AssetTypeCode>,
+) -> Option<&TracingPolicies> {
+    token_code.and_then(|code| {
+        tracing_policies
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, p)| p)
+    })
+}
+
+// the inputs of a transfer can not be selected again
+fn add_transfer(
+    builder: &mut TransactionBuilder,
+    utxos: &mut HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>,
+    op: Operation,
+) {
+    if let Operation::TransferAsset(ref xfr) = op {
+        xfr.body.inputs.iter().for_each(|i| {
+            if let TxoRef::Absolute(sid) = i {
+                utxos.remove(sid);
+            }
+        });
+    }
+    builder.add_operation(op);
+}
+
+/// Content of the files of `--build-only` and `fn tx sign`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxFile {
+    #[allow(missing_docs)]
+    Unsigned(UnsignedTx),
+    /// Signed by the owner, co-signatures may be still appended
+    Signed(Transaction),
+    /// A transfer from a multisig account, waiting for the approvals of the signers
+    Proposal(Transaction),
+}
+
+impl TxFile {
+    pub(crate) fn load(path: &str) -> Result<Self> {
+        fs::read(path)
+            .c(d!("fail to read the transaction file"))
+            .and_then(|b| serde_json::from_slice(&b).c(d!("invalid transaction file")))
+    }
+
+    pub(crate) fn save(&self, path: &str) -> Result<()> {
+        serde_json::to_vec_pretty(self)
+            .c(d!())
+            .and_then(|b| fs::write(path, b).c(d!("fail to write the transaction file")))
+    }
+}
+
+/// Set by `--build-only` and `--owner-address` of the subcommands
+pub fn set_build_only(file: Option<&str>, owner: Option<&str>) -> Result<()> {
+    let owner = owner
+        .map(|addr| wallet::public_key_from_bech32(addr).c(d!("invalid owner address")))
+        .transpose()?;
+    *BUILD_ONLY.lock() = file.map(|file| BuildOnly {
+        file: file.to_owned(),
+        owner,
+    });
+    Ok(())
+}
+
+/// Sign and send the operations, or save them
+/// as an unsigned transaction with `--build-only`.
+/// - @param `kp`: the owner, it is not called if `--owner-address` is set
+pub fn dispatch(
+    ops: Vec<UnsignedOp>,
+    kp: impl FnOnce() -> Result<XfrKeyPair>,
+) -> Result<()> {
+    let builder = utils::new_tx_builder().c(d!())?;
+    dispatch_with(builder, ops, kp).c(d!())
+}
+
+/// Same as `dispatch`, but the transaction is started from `builder`,
+/// which may contain the operations to be co-signed.
+pub fn dispatch_with(
+    builder: TransactionBuilder,
+    ops: Vec<UnsignedOp>,
+    kp: impl FnOnce() -> Result<XfrKeyPair>,
+) -> Result<()> {
+    let get_utxos = |owner: &XfrPublicKey| {
+        if ops.iter().any(|op| {
+            matches!(
+                op,
+                UnsignedOp::Transfer { .. } | UnsignedOp::Consolidate { .. }
+            )
+        }) {
+            utils::get_owned_utxos(owner).c(d!())
+        } else {
+            Ok(HashMap::new())
+        }
+    };
+    let get_tracing_policies = || -> Result<Vec<(AssetTypeCode, TracingPolicies)>> {
+        let mut codes = ops
+            .iter()
+            .filter_map(|op| match op {
+                UnsignedOp::Transfer {
+                    token_code: Some(code),
+                    ..
+                }
+                | UnsignedOp::Consolidate {
+                    token_code: Some(code),
+                    ..
+                } => Some(*code),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        codes.sort_unstable();
+        codes.dedup();
+
+        let mut res = vec![];
+        for code in codes.into_iter() {
+            let policies = utils::get_tracing_policies(&code).c(d!())?;
+            if !policies.is_empty() {
+                res.push((code, policies));
+            }
+        }
+        Ok(res)
+    };
+
+    let build_only = BUILD_ONLY.lock().take();
+    if let Some(bo) = build_only {
+        let owner = match bo.owner {
+            Some(pk) => pk,
+            None => kp().c(d!())?.get_pk(),
+        };
+        let utxos = get_utxos(&owner).c(d!())?;
+        let tracing_policies = get_tracing_policies().c(d!())?;
+        TxFile::Unsigned(UnsignedTx {
+            owner,
+            builder,
+            utxos,
+            tracing_policies,
+            ops,
+        })
+        .save(&bo.file)
+        .c(d!())?;
+        println!(
+            "The unsigned transaction is saved to {}, see `fn tx sign`",
+            bo.file
+        );
+        Ok(())
+    } else {
+        let kp = kp().c(d!())?;
+        let utxos = get_utxos(kp.get_pk_ref()).c(d!())?;
+        let tracing_policies = get_tracing_policies().c(d!())?;
+        UnsignedTx {
+            owner: kp.get_pk(),
+            builder,
+            utxos,
+            tracing_policies,
+            ops,
+        }
+        .sign(&kp)
+        .c(d!())
+        .and_then(|tx| utils::send_tx(&tx).c(d!()))
+    }
+}
+
+/// Sign a transaction file of `--build-only`, it works without the network.
+/// - @param `out`: the file is signed in place if none
+/// - @param `cosig`: add a co-signature to the governance/validator updates,
+///   or approve the transfers from multisig accounts
+pub fn sign(
+    file: &str,
+    out: Option<&str>,
+    sk_str: Option<&str>,
+    cosig: bool,
+) -> Result<()> {
+    let kp = restore_keypair_from_str_with_default(sk_str).c(d!())?;
+    let mut content = TxFile::load(file).c(d!())?;
+
+    if cosig {
+        let n = match content {
+            TxFile::Unsigned(ref mut tx) => tx.builder.cosign(&kp).c(d!())?,
+            TxFile::Signed(ref mut tx) | TxFile::Proposal(ref mut tx) => {
+                // they cover the co-signatures
+                if !tx.signatures.is_empty() {
+                    return Err(eg!("the transaction is sealed by its signatures"));
+                }
+                tx.cosign(&kp).c(d!())?
+            }
+        };
+        if 0 == n {
+            return Err(eg!("no operations need a co-signature"));
+        }
+        println!("{} co-signature(s) added", n);
+    }
+
+    content = match content {
+        TxFile::Unsigned(tx) if tx.owner == kp.get_pk() => {
+            TxFile::Signed(tx.sign(&kp).c(d!())?)
+        }
+        TxFile::Unsigned(tx) if !cosig => {
+            return Err(eg!(format!(
+                "the transaction must be signed by {}, or use `--cosig` to co-sign it",
+                wallet::public_key_to_bech32(&tx.owner)
+            )));
+        }
+        TxFile::Signed(_) if !cosig => {
+            return Err(eg!(
+                "the transaction has been signed, use `--cosig` to co-sign it"
+            ));
+        }
+        TxFile::Proposal(_) if !cosig => {
+            return Err(eg!(
+                "the transaction is a multisig proposal, use `--cosig` to approve it"
+            ));
+        }
+        c => c,
+    };
+
+    let out = out.unwrap_or(file);
+    content.save(out).c(d!())?;
+    match content {
+        TxFile::Signed(_) => println!("The signed transaction is saved to {}", out),
+        TxFile::Proposal(_) => println!("The approved proposal is saved to {}", out),
+        TxFile::Unsigned(tx) => println!(
+            "Saved to {}, it must be signed by {} yet",
+            out,
+            wallet::public_key_to_bech32(&tx.owner)
+        ),
+    }
+
+    Ok(())
+}
+
+/// Send a transaction signed by `fn tx sign`
+pub fn broadcast(file: &str) -> Result<()> {
+    match TxFile::load(file).c(d!())? {
+        TxFile::Signed(tx) => utils::send_tx(&tx).c(d!()),
+        TxFile::Proposal(tx) => multisig::check_approvals(&tx)
+            .c(d!())
+            .and_then(|_| utils::send_tx(&tx).c(d!())),
+        TxFile::Unsigned(_) => {
+            Err(eg!("the transaction has not been signed, see `fn tx sign`"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::utils::test::new_utxo;
+    use ledger::data_model::{ASSET_TYPE_FRA, TX_FEE_MIN};
+    use std::collections::HashSet;
+    use zei::xfr::asset_record::AssetRecordType;
+
+    fn sk_str(kp: &XfrKeyPair) -> String {
+        serde_json::to_string(kp.get_sk_ref())
+            .unwrap()
+            .trim_matches('"')
+            .to_owned()
+    }
+
+    #[test]
+    fn sign_tx_file() {
+        let owner = gen_random_keypair();
+        let receiver = gen_random_keypair().get_pk();
+        let art = AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType;
+        // one of them is enough for a transfer
+        let utxos = (1..=2)
+            .map(|i| {
+                let utxo =
+                    new_utxo(owner.get_pk_ref(), 2 * TX_FEE_MIN, ASSET_TYPE_FRA, art);
+                (TxoSID(i), utxo)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let dir = globutils::fresh_tmp_dir();
+        let file = dir.join("unsigned.json").to_string_lossy().into_owned();
+        let out = dir.join("signed.json").to_string_lossy().into_owned();
+        pnk!(TxFile::Unsigned(UnsignedTx {
+            owner: owner.get_pk(),
+            builder: TransactionBuilder::from_seq_id(1),
+            utxos,
+            tracing_policies: vec![],
+            ops: vec![
+                UnsignedOp::transfer(vec![(receiver, 500)], None, false, false),
+                UnsignedOp::fee(),
+            ],
+        })
+        .save(&file));
+
+        // only the owner can sign it
+        let other = gen_random_keypair();
+        assert!(sign(&file, Some(&out), Some(&sk_str(&other)), false).is_err());
+        assert!(matches!(pnk!(TxFile::load(&file)), TxFile::Unsigned(_)));
+
+        pnk!(sign(&file, Some(&out), Some(&sk_str(&owner)), false));
+        assert!(matches!(pnk!(TxFile::load(&file)), TxFile::Unsigned(_)));
+        let tx = match pnk!(TxFile::load(&out)) {
+            TxFile::Signed(tx) => tx,
+            _ => panic!("not signed"),
+        };
+        assert!(sign(&out, None, Some(&sk_str(&owner)), false).is_err());
+
+        let xfrs = tx
+            .body
+            .operations
+            .iter()
+            .filter_map(|op| match op {
+                Operation::TransferAsset(xfr) => Some(xfr),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(2, xfrs.len());
+
+        // an utxo is never spent twice
+        let mut inputs = HashSet::new();
+        for xfr in xfrs.iter() {
+            for i in xfr.body.inputs.iter() {
+                match i {
+                    TxoRef::Absolute(sid) => assert!(inputs.insert(*sid)),
+                    TxoRef::Relative(_) => panic!("relative input"),
+                }
+            }
+        }
+        assert_eq!(2, inputs.len());
+
+        let amounts = |pk: &XfrPublicKey| {
+            xfrs.iter()
+                .flat_map(|xfr| xfr.body.outputs.iter())
+                .filter(|o| &o.record.public_key == pk)
+                .map(|o| o.record.amount.get_amount().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![500], amounts(&receiver));
+        assert_eq!(
+            vec![TX_FEE_MIN, TX_FEE_MIN],
+            amounts(&*ledger::data_model::BLACK_HOLE_PUBKEY)
+        );
+        assert_eq!(
+            2 * TX_FEE_MIN - 500,
+            amounts(owner.get_pk_ref()).iter().sum::<u64>()
+        );
+    }
+}
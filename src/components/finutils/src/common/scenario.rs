@@ -0,0 +1,816 @@
+//!
+//! # Scenario runner
+//!
+//! Run a scenario file against a network, eg. a local devnet set by `fn setup -S`,
+//! and report which of its expectations pass or fail.
+//!
+//! A scenario is a YAML file (so JSON works too) with:
+//!
+//! - `accounts`, the named accounts, restored from `mnemonic`, or random ones
+//! - `validators`, aliases of the tendermint addresses of the validators
+//! - `steps`, the actions run in order, and `expect` steps checking the results
+//!
+//! ```yaml
+//! name: delegate and claim
+//! accounts:
+//!   root: { mnemonic: "zoo nerve assault ..." }
+//!   alice: {}
+//! validators:
+//!   v1: 611C922247C3BE7EA13455B191B6EFD909F10196
+//! steps:
+//!   - transfer: { from: root, to: alice, amount: 100000000000 }
+//!   - wait: { blocks: 3 }
+//!   - delegate: { from: alice, validator: v1, amount: 50000000000 }
+//!   - expect:
+//!       min_height: 100
+//!       balances:
+//!         - { account: alice, max: 50000000000 }
+//!       delegations:
+//!         - { account: alice, bond: 50000000000, min_rewards: 1 }
+//! ```
+//!
+//! All the amounts are in the smallest units of their assets.
+//!
+//! `min_height` is only a lower bound of the height at which an `expect` runs,
+//! the results are always queried from the latest state, not from the state
+//! at that height, so the expectations must also hold at any later height,
+//! eg. `min_rewards` rather than an exact amount of rewards.
+//! The heights of the queries are reported along with the mismatches.
+//!
+//! **NOTE**: an action only checks that its transaction is accepted by the server,
+//! the transactions are executed asynchronously, so there should be a `wait`
+//! between the actions depending on each other, and before checking their results.
+//!
+
+use super::{evm, get_serv_addr, utils};
+use fp_types::crypto::MultiSigner;
+use globutils::wallet;
+use ledger::{
+    data_model::{AssetRules, AssetTypeCode, BLACK_HOLE_PUBKEY_STAKING},
+    staking::{
+        gen_random_keypair, td_addr_to_bytes, PartialUnDelegation, TendermintAddr,
+    },
+    store::utils::fra_gen_initial_tx,
+};
+use ruc::*;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    str::FromStr,
+};
+use zei::{
+    setup::PublicParams,
+    xfr::{
+        asset_record::AssetRecordType,
+        sig::{XfrKeyPair, XfrPublicKey},
+    },
+};
+
+// the height is checked once per second when waiting
+const WAIT_POLL_MS: u64 = 1000;
+
+/// A scenario loaded from a file, see the module doc for its format.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// shown in the report
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    accounts: BTreeMap<String, AccountCfg>,
+    #[serde(default)]
+    validators: BTreeMap<String, TendermintAddr>,
+    /// how many seconds a `wait` lasts without a new block before it fails
+    #[serde(default = "default_wait_timeout")]
+    pub wait_timeout: u64,
+    steps: Vec<Step>,
+}
+
+fn default_wait_timeout() -> u64 {
+    60
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountCfg {
+    // a random account is used if not set
+    mnemonic: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum Step {
+    // define and issue FRA, only works on a new network
+    FraGenesis {
+        issuer: String,
+    },
+    SetInitialValidators {
+        owner: String,
+    },
+    // `name` is used by the later steps to refer to the asset
+    DefineAsset {
+        name: String,
+        issuer: String,
+        code: Option<String>,
+        decimals: Option<u8>,
+        max_units: Option<u64>,
+        #[serde(default = "yes")]
+        transferable: bool,
+        #[serde(default)]
+        memo: String,
+    },
+    IssueAsset {
+        asset: String,
+        amount: u64,
+        #[serde(default)]
+        confidential: bool,
+    },
+    // FRA if `asset` is not set, `to` is an account or a wallet address
+    Transfer {
+        from: String,
+        to: String,
+        amount: u64,
+        asset: Option<String>,
+        #[serde(default)]
+        confidential_amount: bool,
+        #[serde(default)]
+        confidential_type: bool,
+    },
+    Delegate {
+        from: String,
+        validator: String,
+        amount: u64,
+    },
+    // a partial undelegation if `amount` and `validator` are set
+    Undelegate {
+        from: String,
+        amount: Option<u64>,
+        validator: Option<String>,
+    },
+    Claim {
+        from: String,
+        amount: Option<u64>,
+    },
+    // `to` is an address of the EVM side, the account of `from` if not set
+    EvmDeposit {
+        from: String,
+        amount: u64,
+        to: Option<String>,
+    },
+    // wait for so many new blocks, or until the height
+    Wait {
+        blocks: Option<u64>,
+        height: Option<u64>,
+    },
+    Expect(Box<Expect>),
+}
+
+impl Step {
+    fn label(&self) -> &'static str {
+        match self {
+            Step::FraGenesis { .. } => "fra-genesis",
+            Step::SetInitialValidators { .. } => "set-initial-validators",
+            Step::DefineAsset { .. } => "define-asset",
+            Step::IssueAsset { .. } => "issue-asset",
+            Step::Transfer { .. } => "transfer",
+            Step::Delegate { .. } => "delegate",
+            Step::Undelegate { .. } => "undelegate",
+            Step::Claim { .. } => "claim",
+            Step::EvmDeposit { .. } => "evm-deposit",
+            Step::Wait { .. } => "wait",
+            Step::Expect(_) => "expect",
+        }
+    }
+}
+
+#[inline(always)]
+fn yes() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expect {
+    // a lower bound of the height of the check, see the module doc
+    min_height: Option<u64>,
+    #[serde(default)]
+    balances: Vec<BalanceCheck>,
+    #[serde(default)]
+    delegations: Vec<DelegationCheck>,
+    #[serde(default)]
+    evm_balances: Vec<EvmBalanceCheck>,
+}
+
+// `amount` is the exact value, `min` and `max` are the inclusive bounds
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BalanceCheck {
+    account: String,
+    asset: Option<String>,
+    amount: Option<u64>,
+    min: Option<u64>,
+    max: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DelegationCheck {
+    account: String,
+    bond: Option<u64>,
+    unbond: Option<u64>,
+    min_rewards: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EvmBalanceCheck {
+    account: String,
+    amount: Option<u64>,
+    min: Option<u64>,
+    max: Option<u64>,
+}
+
+impl Scenario {
+    /// Load a scenario from a YAML or JSON file.
+    pub fn load(path: &str) -> Result<Self> {
+        fs::read_to_string(path)
+            .c(d!("can not read the scenario file"))
+            .and_then(|s| serde_yaml::from_str(&s).c(d!("invalid scenario")))
+    }
+}
+
+/// Run the scenario in `path` and print a report,
+/// an error is returned if any step fails.
+///
+/// The failure of an action stops the scenario, as the later steps depend on it,
+/// while all the expectations are checked and reported.
+pub fn run(path: &str) -> Result<()> {
+    let scenario = Scenario::load(path).c(d!())?;
+    let mut runner = Runner::new(&scenario).c(d!())?;
+
+    println!(
+        "\x1b[31;01mScenario:\x1b[00m {} ({} steps)\n",
+        alt!(scenario.name.is_empty(), path, scenario.name.as_str()),
+        scenario.steps.len()
+    );
+
+    let mut failed = 0;
+    let mut skipped = 0;
+    for (i, step) in scenario.steps.iter().enumerate() {
+        print!("[{:>3}] {} ... ", 1 + i, step.label());
+        // the step may wait for long
+        let _ = io::stdout().flush();
+        match runner.run_step(step) {
+            Ok(mismatches) if mismatches.is_empty() => println!("ok"),
+            Ok(mismatches) => {
+                failed += 1;
+                println!("\x1b[31;01mFAILED\x1b[00m");
+                mismatches.iter().for_each(|m| println!("      {}", m));
+            }
+            Err(e) => {
+                failed += 1;
+                skipped = scenario.steps.len() - i - 1;
+                println!("\x1b[31;01mFAILED\x1b[00m\n{}", e);
+                break;
+            }
+        }
+    }
+
+    println!(
+        "\n\x1b[31;01mResult:\x1b[00m {} passed, {} failed, {} skipped",
+        scenario.steps.len() - failed - skipped,
+        failed,
+        skipped
+    );
+    alt!(
+        0 == failed,
+        Ok(()),
+        Err(eg!(format!("{} of the steps failed", failed)))
+    )
+}
+
+struct Runner<'a> {
+    scenario: &'a Scenario,
+    accounts: BTreeMap<&'a str, XfrKeyPair>,
+    assets: BTreeMap<&'a str, AssetTypeCode>,
+}
+
+impl<'a> Runner<'a> {
+    fn new(scenario: &'a Scenario) -> Result<Self> {
+        let accounts = scenario
+            .accounts
+            .iter()
+            .map(|(name, cfg)| {
+                let kp = if let Some(m) = cfg.mnemonic.as_ref() {
+                    wallet::restore_keypair_from_mnemonic_default(m.trim())
+                        .c(d!(format!("invalid mnemonic of {}", name)))?
+                } else {
+                    gen_random_keypair()
+                };
+                Ok((name.as_str(), kp))
+            })
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        Ok(Runner {
+            scenario,
+            accounts,
+            assets: BTreeMap::new(),
+        })
+    }
+
+    // the mismatches of an `expect` step are returned,
+    // while the failures of the actions are errors
+    fn run_step(&mut self, step: &'a Step) -> Result<Vec<String>> {
+        match step {
+            Step::FraGenesis { issuer } => {
+                let kp = self.keypair(issuer).c(d!())?;
+                utils::send_tx(&fra_gen_initial_tx(kp)).c(d!())?;
+            }
+            Step::SetInitialValidators { owner } => {
+                let kp = self.keypair(owner).c(d!())?;
+                utils::set_initial_validators(kp).c(d!())?;
+            }
+            Step::DefineAsset {
+                name,
+                issuer,
+                code,
+                decimals,
+                max_units,
+                transferable,
+                memo,
+            } => {
+                let code = code
+                    .as_deref()
+                    .map_or(Ok(AssetTypeCode::gen_random()), |c| {
+                        AssetTypeCode::new_from_base64(c).c(d!("invalid asset code"))
+                    })?;
+                let kp = self.keypair(issuer).c(d!())?;
+
+                let mut rules = AssetRules::default();
+                if let Some(decimals) = decimals {
+                    rules.set_decimals(*decimals).c(d!())?;
+                }
+                rules.set_max_units(*max_units);
+                rules.set_transferable(*transferable);
+
+                let mut builder = utils::new_tx_builder().c(d!())?;
+                builder
+                    .add_operation_create_asset(kp, Some(code), rules, memo)
+                    .c(d!())?;
+                builder.add_operation(utils::gen_fee_op(kp).c(d!())?);
+                utils::send_tx(&builder.take_transaction()).c(d!())?;
+
+                self.assets.insert(name.as_str(), code);
+            }
+            Step::IssueAsset {
+                asset,
+                amount,
+                confidential,
+            } => {
+                let code = self.asset(asset).c(d!())?.c(d!("FRA can not be issued"))?;
+                let issuer = self.issuer_of(asset).c(d!())?;
+
+                let mut builder = utils::new_tx_builder().c(d!())?;
+                builder
+                    .add_basic_issue_asset(
+                        issuer,
+                        &code,
+                        builder.get_seq_id(),
+                        *amount,
+                        AssetRecordType::from_flags(*confidential, false),
+                        &PublicParams::default(),
+                    )
+                    .c(d!())?;
+                builder.add_operation(utils::gen_fee_op(issuer).c(d!())?);
+                utils::send_tx(&builder.take_transaction()).c(d!())?;
+            }
+            Step::Transfer {
+                from,
+                to,
+                amount,
+                asset,
+                confidential_amount,
+                confidential_type,
+            } => {
+                let kp = self.keypair(from).c(d!())?;
+                let target = self.pubkey(to).c(d!())?;
+                let code = asset
+                    .as_deref()
+                    .map(|a| self.asset(a))
+                    .transpose()
+                    .c(d!())?
+                    .flatten();
+                utils::transfer(
+                    kp,
+                    &target,
+                    *amount,
+                    code,
+                    *confidential_amount,
+                    *confidential_type,
+                )
+                .c(d!())?;
+            }
+            Step::Delegate {
+                from,
+                validator,
+                amount,
+            } => {
+                let kp = self.keypair(from).c(d!())?;
+                let validator = self.validator(validator);
+
+                let mut builder = utils::new_tx_builder().c(d!())?;
+                let op = utils::gen_transfer_op(
+                    kp,
+                    vec![(&BLACK_HOLE_PUBKEY_STAKING, *amount)],
+                    None,
+                    false,
+                    false,
+                )
+                .c(d!())?;
+                builder
+                    .add_operation(op)
+                    .add_operation_delegation(kp, validator);
+                utils::send_tx(&builder.take_transaction()).c(d!())?;
+            }
+            Step::Undelegate {
+                from,
+                amount,
+                validator,
+            } => {
+                let kp = self.keypair(from).c(d!())?;
+                let partial = match (amount, validator) {
+                    (Some(am), Some(v)) => Some(PartialUnDelegation::new(
+                        *am,
+                        gen_random_keypair().get_pk(),
+                        td_addr_to_bytes(&self.validator(v)).c(d!())?,
+                    )),
+                    (None, None) => None,
+                    _ => {
+                        return Err(eg!(
+                            "'amount' and 'validator' must be set together"
+                        ));
+                    }
+                };
+
+                let mut builder = utils::new_tx_builder().c(d!())?;
+                builder
+                    .add_operation(utils::gen_fee_op(kp).c(d!())?)
+                    .add_operation_undelegation(kp, partial);
+                utils::send_tx(&builder.take_transaction()).c(d!())?;
+            }
+            Step::Claim { from, amount } => {
+                let kp = self.keypair(from).c(d!())?;
+
+                let mut builder = utils::new_tx_builder().c(d!())?;
+                builder
+                    .add_operation(utils::gen_fee_op(kp).c(d!())?)
+                    .add_operation_claim(kp, *amount);
+                utils::send_tx(&builder.take_transaction()).c(d!())?;
+            }
+            Step::EvmDeposit { from, amount, to } => {
+                let kp = self.keypair(from).c(d!())?;
+                let target = match to {
+                    Some(addr) => self.evm_address(addr).c(d!())?,
+                    None => MultiSigner::Xfr(kp.get_pk()),
+                };
+                evm::deposit_to_account(kp, *amount, target).c(d!())?;
+            }
+            Step::Wait { blocks, height } => {
+                let target = match (blocks, height) {
+                    (Some(n), None) => self.height().c(d!())? + n,
+                    (None, Some(h)) => *h,
+                    _ => return Err(eg!("one of 'blocks' and 'height' must be set")),
+                };
+                self.wait_until(target).c(d!())?;
+            }
+            Step::Expect(expect) => {
+                if let Some(h) = expect.min_height {
+                    self.wait_until(h).c(d!())?;
+                }
+                let from = self.height().c(d!())?;
+                let mut mismatches = self.check(expect).c(d!())?;
+                if !mismatches.is_empty() {
+                    let to = self.height().c(d!())?;
+                    mismatches.push(alt!(
+                        from == to,
+                        format!("(queried at height {})", from),
+                        format!("(queried between heights {} and {})", from, to)
+                    ));
+                }
+                return Ok(mismatches);
+            }
+        }
+
+        Ok(vec![])
+    }
+
+    fn check(&self, expect: &Expect) -> Result<Vec<String>> {
+        let mut mismatches = vec![];
+
+        for bc in expect.balances.iter() {
+            let kp = self.keypair(&bc.account).c(d!())?;
+            let code = bc
+                .asset
+                .as_deref()
+                .map(|a| self.asset(a))
+                .transpose()
+                .c(d!())?
+                .flatten();
+            let balance = utils::get_asset_balance(kp, code).c(d!())?;
+            let what = format!(
+                "balance of {} in {}",
+                bc.account,
+                bc.asset.as_deref().unwrap_or("FRA")
+            );
+            check_bounds(
+                &mut mismatches,
+                &what,
+                balance as u128,
+                bc.amount,
+                bc.min,
+                bc.max,
+            );
+        }
+
+        for dc in expect.delegations.iter() {
+            let pk = self.pubkey(&dc.account).c(d!())?;
+            let di = utils::get_delegation_info(&pk).c(d!())?;
+            let bond = format!("bond of {}", dc.account);
+            check_bounds(&mut mismatches, &bond, di.bond as u128, dc.bond, None, None);
+            let unbond = format!("unbond of {}", dc.account);
+            check_bounds(
+                &mut mismatches,
+                &unbond,
+                di.unbond as u128,
+                dc.unbond,
+                None,
+                None,
+            );
+            let rewards = format!("rewards of {}", dc.account);
+            check_bounds(
+                &mut mismatches,
+                &rewards,
+                di.rewards as u128,
+                None,
+                dc.min_rewards,
+                None,
+            );
+        }
+
+        for ec in expect.evm_balances.iter() {
+            let address = self.evm_address(&ec.account).c(d!())?;
+            let (_, info) = evm::account_info(address).c(d!())?;
+            let what = format!("EVM balance of {}", ec.account);
+            check_bounds(
+                &mut mismatches,
+                &what,
+                info.balance,
+                ec.amount,
+                ec.min,
+                ec.max,
+            );
+        }
+
+        Ok(mismatches)
+    }
+
+    fn keypair(&self, name: &str) -> Result<&XfrKeyPair> {
+        self.accounts
+            .get(name)
+            .c(d!(format!("account '{}' is not defined", name)))
+    }
+
+    // an account, or a wallet address
+    fn pubkey(&self, name: &str) -> Result<XfrPublicKey> {
+        if let Some(kp) = self.accounts.get(name) {
+            Ok(kp.get_pk())
+        } else {
+            wallet::public_key_from_bech32(name).c(d!(format!(
+                "'{}' is not an account or a wallet address",
+                name
+            )))
+        }
+    }
+
+    // an account, or an address of the EVM side
+    fn evm_address(&self, name: &str) -> Result<MultiSigner> {
+        if let Some(kp) = self.accounts.get(name) {
+            Ok(MultiSigner::Xfr(kp.get_pk()))
+        } else {
+            MultiSigner::from_str(name)
+                .c(d!(format!("'{}' is not an account or an address", name)))
+        }
+    }
+
+    // `None` for FRA, the asset is a name defined by `define_asset`, or an asset code
+    fn asset(&self, name: &str) -> Result<Option<AssetTypeCode>> {
+        if "FRA" == name {
+            Ok(None)
+        } else if let Some(code) = self.assets.get(name) {
+            Ok(Some(*code))
+        } else {
+            AssetTypeCode::new_from_base64(name)
+                .c(d!(format!("asset '{}' is not defined", name)))
+                .map(Some)
+        }
+    }
+
+    // only the assets defined in the scenario can be issued
+    fn issuer_of(&self, asset: &str) -> Result<&XfrKeyPair> {
+        self.scenario
+            .steps
+            .iter()
+            .find_map(|s| match s {
+                Step::DefineAsset { name, issuer, .. } if name == asset => Some(issuer),
+                _ => None,
+            })
+            .c(d!(format!(
+                "asset '{}' is not defined by the scenario",
+                asset
+            )))
+            .and_then(|issuer| self.keypair(issuer).c(d!()))
+    }
+
+    fn validator(&self, name: &str) -> TendermintAddr {
+        self.scenario
+            .validators
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_owned())
+    }
+
+    fn height(&self) -> Result<u64> {
+        let h = utils::get_block_height(get_serv_addr().c(d!())?);
+        alt!(
+            0 == h,
+            Err(eg!("can not get the height of the network")),
+            Ok(h)
+        )
+    }
+
+    // fails if there is no new block in `wait_timeout` seconds
+    fn wait_until(&self, target: u64) -> Result<()> {
+        let timeout = self.scenario.wait_timeout * 1000 / WAIT_POLL_MS;
+        let mut last = self.height().c(d!())?;
+        let mut idle = 0;
+        while last < target {
+            sleep_ms!(WAIT_POLL_MS);
+            let h = self.height().c(d!())?;
+            if h > last {
+                last = h;
+                idle = 0;
+            } else {
+                idle += 1;
+                if idle > timeout {
+                    return Err(eg!(format!(
+                        "no new block in {} seconds, stuck at height {}",
+                        self.scenario.wait_timeout, last
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_bounds(
+    mismatches: &mut Vec<String>,
+    what: &str,
+    actual: u128,
+    amount: Option<u64>,
+    min: Option<u64>,
+    max: Option<u64>,
+) {
+    if let Some(am) = amount.filter(|am| *am as u128 != actual) {
+        mismatches.push(format!("{}: expected {}, got {}", what, am, actual));
+    }
+    if let Some(min) = min.filter(|min| actual < *min as u128) {
+        mismatches.push(format!(
+            "{}: expected at least {}, got {}",
+            what, min, actual
+        ));
+    }
+    if let Some(max) = max.filter(|max| actual > *max as u128) {
+        mismatches.push(format!(
+            "{}: expected at most {}, got {}",
+            what, max, actual
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_scenario() {
+        let scenario = pnk!(serde_yaml::from_str::<Scenario>(include_str!(
+            "../../../../../tools/scenarios/staking.yml"
+        )));
+        assert_eq!("staking on a new devnet", scenario.name);
+        assert_eq!(60, scenario.wait_timeout);
+        assert_eq!(24, scenario.steps.len());
+        assert_eq!(
+            5,
+            scenario
+                .steps
+                .iter()
+                .filter(|s| "expect" == s.label())
+                .count()
+        );
+
+        match scenario.steps.last() {
+            Some(Step::Expect(expect)) => {
+                assert_eq!(Some(100), expect.min_height);
+                assert_eq!(1, expect.delegations.len());
+                assert_eq!(Some(1), expect.delegations[0].min_rewards);
+            }
+            _ => panic!("the last step is not an expectation"),
+        }
+
+        // the accounts are restored without the network
+        let runner = pnk!(Runner::new(&scenario));
+        assert_eq!(4, runner.accounts.len());
+        let root = pnk!(runner.keypair("root")).get_pk();
+        let mnemonic = pnk!(scenario.accounts["root"].mnemonic.as_deref());
+        assert_eq!(
+            pnk!(wallet::restore_keypair_from_mnemonic_default(mnemonic)).get_pk(),
+            root
+        );
+        // the accounts without mnemonics are random ones
+        assert_ne!(
+            pnk!(runner.keypair("alice")).get_pk(),
+            pnk!(runner.keypair("bob")).get_pk()
+        );
+        assert!(runner.keypair("carol").is_err());
+        assert_eq!(root, pnk!(runner.pubkey("root")));
+        assert_eq!(
+            root,
+            pnk!(runner.pubkey(&wallet::public_key_to_bech32(&root)))
+        );
+        assert_eq!(
+            "611C922247C3BE7EA13455B191B6EFD909F10196",
+            runner.validator("v1")
+        );
+        assert!(pnk!(runner.asset("FRA")).is_none());
+        assert!(runner.asset("usd").is_err());
+        assert!(runner.issuer_of("usd").is_ok());
+    }
+
+    #[test]
+    fn test_invalid_scenario() {
+        // the expected height of a check is `min_height`
+        let s = "steps:\n  - expect: { height: 10 }\n";
+        assert!(serde_yaml::from_str::<Scenario>(s).is_err());
+        let s = "steps:\n  - expect: { min_height: 10 }\n";
+        assert!(serde_yaml::from_str::<Scenario>(s).is_ok());
+
+        let s = "steps:\n  - mint: { to: root }\n";
+        assert!(serde_yaml::from_str::<Scenario>(s).is_err());
+
+        // an account with a bad mnemonic
+        let s = "accounts:\n  root: { mnemonic: bad }\nsteps: []\n";
+        let scenario = pnk!(serde_yaml::from_str::<Scenario>(s));
+        assert!(Runner::new(&scenario).is_err());
+    }
+
+    #[test]
+    fn test_check_bounds() {
+        let check = |actual, amount, min, max| {
+            let mut mismatches = vec![];
+            check_bounds(&mut mismatches, "x", actual, amount, min, max);
+            mismatches
+        };
+
+        assert!(check(5, None, None, None).is_empty());
+        assert!(check(5, Some(5), Some(5), Some(5)).is_empty());
+        assert!(check(5, None, Some(1), Some(9)).is_empty());
+
+        assert_eq!(vec!["x: expected 4, got 5"], check(5, Some(4), None, None));
+        assert_eq!(
+            vec!["x: expected at least 6, got 5"],
+            check(5, None, Some(6), None)
+        );
+        assert_eq!(
+            vec!["x: expected at most 4, got 5"],
+            check(5, None, None, Some(4))
+        );
+        assert_eq!(
+            vec!["x: expected 9, got 5", "x: expected at least 6, got 5"],
+            check(5, Some(9), Some(6), Some(9))
+        );
+
+        // EVM balances can be out of the range of u64
+        let big = u64::MAX as u128 + 1;
+        assert_eq!(
+            vec![format!("x: expected {}, got {}", u64::MAX, big)],
+            check(big, Some(u64::MAX), Some(1), None)
+        );
+        assert_eq!(
+            vec![format!("x: expected at most {}, got {}", u64::MAX, big)],
+            check(big, None, None, Some(u64::MAX))
+        );
+    }
+}
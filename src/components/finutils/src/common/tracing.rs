@@ -0,0 +1,171 @@
+//!
+//! # Asset tracing
+//!
+//! The transfers of a traced asset carry tracing memos, the amounts, types
+//! and owners of the records are encrypted to the tracers of the asset,
+//! see `AssetRules::tracing_policies`.
+//!
+//! - `fn asset --gen-tracer-key`, generate a tracer key
+//! - `fn asset --create --tracer-key`, define an asset traced by the key
+//! - `fn asset --trace`, decrypt the tracing memos of the transfers with the key
+//!
+
+use super::utils;
+use crate::api::{HistoryPage, TxnHistoryItem};
+use globutils::wallet;
+use ledger::data_model::{AssetTypeCode, Operation};
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use ruc::*;
+use std::{fs, path::Path};
+use zei::xfr::{
+    lib::trace_assets,
+    sig::XfrPublicKey,
+    structs::{AssetTracerKeyPair, TracingPolicy},
+};
+
+// the page size of the history queries
+const HISTORY_PAGE_SIZE: usize = 200;
+
+/// A record decrypted from the tracing memos of a transfer
+#[derive(Clone, Debug)]
+pub struct TracedRecord {
+    #[allow(missing_docs)]
+    pub txn: TxnHistoryItem,
+    #[allow(missing_docs)]
+    pub asset: AssetTypeCode,
+    #[allow(missing_docs)]
+    pub amount: u64,
+    #[allow(missing_docs)]
+    pub owner: XfrPublicKey,
+}
+
+/// Generate a tracer key and save it to `path` in JSON,
+/// an existing file is never overwritten.
+pub fn gen_tracer_key(path: &str) -> Result<()> {
+    if Path::new(path).exists() {
+        return Err(eg!(format!("'{}' already exists", path)));
+    }
+
+    let kp = AssetTracerKeyPair::generate(&mut ChaChaRng::from_entropy());
+    serde_json::to_vec_pretty(&kp)
+        .c(d!())
+        .and_then(|b| fs::write(path, b).c(d!("fail to write the tracer key")))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).c(d!())?;
+    }
+
+    println!("The tracer key is saved to {}, keep it secret", path);
+    Ok(())
+}
+
+/// Read a tracer key saved by `gen_tracer_key`
+pub fn read_tracer_key(path: &str) -> Result<AssetTracerKeyPair> {
+    fs::read(path)
+        .c(d!("fail to read the tracer key"))
+        .and_then(|b| serde_json::from_slice(&b).c(d!("invalid tracer key")))
+}
+
+/// The policy of the tracer key in `path`,
+/// the amounts and types of the records are traced, but not the identities.
+pub fn asset_tracing_policy(path: &str) -> Result<TracingPolicy> {
+    read_tracer_key(path).c(d!()).map(|kp| TracingPolicy {
+        enc_keys: kp.enc_key,
+        asset_tracing: true,
+        identity_tracing: None,
+    })
+}
+
+/// Print the records of the transfers of `asset`, or of the transfers
+/// related to `addr`, that can be decrypted by the tracer key in `path`.
+pub fn show_traced(
+    path: &str,
+    asset: Option<AssetTypeCode>,
+    addr: Option<&str>,
+) -> Result<()> {
+    let kp = read_tracer_key(path).c(d!())?;
+    let addr = addr
+        .map(|a| wallet::public_key_from_bech32(a).c(d!("invalid wallet address")))
+        .transpose()?;
+
+    println!(
+        "{:<10}  {:<8}  {:<24}  {:>20}  OWNER",
+        "TXN", "HEIGHT", "ASSET", "AMOUNT"
+    );
+    for r in trace(&kp, asset, addr.as_ref()).c(d!())?.iter() {
+        println!(
+            "{:<10}  {:<8}  {:<24}  {:>20}  {}",
+            r.txn.txn_sid.0,
+            r.txn.height.map(|h| h.to_string()).unwrap_or_default(),
+            r.asset.to_base64(),
+            r.amount,
+            wallet::public_key_to_bech32(&r.owner)
+        );
+    }
+
+    Ok(())
+}
+
+/// Decrypt the tracing memos of the transfers with `kp`, like `trace_assets` of the wasm,
+/// both the spent inputs and the new outputs of a transfer are listed.
+///
+/// The transfers are found by `xfr_history` of the query server if only `asset` is set,
+/// which does not index the transfers with a confidential asset type,
+/// set `addr` to scan the transactions related to an account instead.
+pub fn trace(
+    kp: &AssetTracerKeyPair,
+    asset: Option<AssetTypeCode>,
+    addr: Option<&XfrPublicKey>,
+) -> Result<Vec<TracedRecord>> {
+    let get_page = |cursor| -> Result<HistoryPage<TxnHistoryItem>> {
+        match (addr, asset.as_ref()) {
+            (Some(pk), _) => utils::get_txn_history(pk, cursor, HISTORY_PAGE_SIZE),
+            (None, Some(code)) => {
+                utils::get_xfr_history(code, cursor, HISTORY_PAGE_SIZE)
+            }
+            (None, None) => Err(eg!("either the asset or the address is needed")),
+        }
+    };
+
+    let mut items = vec![];
+    let mut cursor = None;
+    loop {
+        let page = get_page(cursor).c(d!())?;
+        items.extend(page.items);
+        if page.next_cursor.is_none() {
+            break;
+        }
+        cursor = page.next_cursor;
+    }
+
+    let sids = items.iter().map(|i| i.txn_sid).collect::<Vec<_>>();
+    let txns = utils::get_txns(&sids).c(d!())?;
+
+    let mut records = vec![];
+    for (item, txn) in items.into_iter().zip(txns.into_iter()) {
+        for op in txn.txn.body.operations.iter() {
+            if let Operation::TransferAsset(xfr) = op {
+                let traced = trace_assets(&xfr.body.transfer, kp).c(d!(format!(
+                    "fail to trace the transaction {}",
+                    item.txn_sid.0
+                )))?;
+                records.extend(
+                    traced
+                        .into_iter()
+                        .map(|(amount, asset_type, _, owner)| TracedRecord {
+                            txn: item.clone(),
+                            asset: AssetTypeCode { val: asset_type },
+                            amount,
+                            owner,
+                        })
+                        .filter(|r| asset.map(|a| a == r.asset).unwrap_or(true)),
+                );
+            }
+        }
+    }
+
+    Ok(records)
+}
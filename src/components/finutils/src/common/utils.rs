@@ -3,18 +3,24 @@
 //!
 
 use crate::{
-    api::{DelegationInfo, ValidatorDetail},
+    api::{
+        DelegationInfo, HistoryPage, SigningReport, TxnHistoryItem, ValidatorDelegation,
+        ValidatorDetail, TXN_LIST_LIMIT,
+    },
     common::get_serv_addr,
-    txn_builder::{TransactionBuilder, TransferOperationBuilder},
+    txn_builder::{CoinSelection, TransactionBuilder, TransferOperationBuilder},
 };
 use globutils::{wallet, HashOf, SignatureOf};
 use ledger::{
     data_model::{
-        AssetType, AssetTypeCode, DefineAsset, Operation, StateCommitmentData,
-        Transaction, TransferType, TxoRef, TxoSID, Utxo, ASSET_TYPE_FRA,
-        BLACK_HOLE_PUBKEY, TX_FEE_MIN,
+        AssetType, AssetTypeCode, DefineAsset, FinalizedTransaction, Operation,
+        SignatureRules, StateCommitmentData, Transaction, TransferType, TxnSID, TxoRef,
+        TxoSID, Utxo, ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY, TX_FEE_MIN,
+    },
+    staking::{
+        gen_random_keypair, init::get_inital_validators, TendermintAddrRef,
+        FRA_TOTAL_AMOUNT,
     },
-    staking::{init::get_inital_validators, TendermintAddrRef, FRA_TOTAL_AMOUNT},
 };
 use ruc::*;
 use serde::{self, Deserialize, Serialize};
@@ -23,7 +29,7 @@ use tendermint::{PrivateKey, PublicKey};
 use zei::xfr::{
     asset_record::{open_blind_asset_record, AssetRecordType},
     sig::{XfrKeyPair, XfrPublicKey},
-    structs::{AssetRecordTemplate, OwnerMemo},
+    structs::{AssetRecordTemplate, OpenAssetRecord, OwnerMemo, TracingPolicies},
 };
 
 ///////////////////////////////////////
@@ -136,66 +142,202 @@ pub fn gen_transfer_op(
 #[allow(missing_docs)]
 pub fn gen_transfer_op_x(
     owner_kp: &XfrKeyPair,
-    mut target_list: Vec<(&XfrPublicKey, u64)>,
+    target_list: Vec<(&XfrPublicKey, u64)>,
     token_code: Option<AssetTypeCode>,
     auto_fee: bool,
     confidential_am: bool,
     confidential_ty: bool,
 ) -> Result<Operation> {
-    let mut op_fee: u64 = 0;
-    if auto_fee {
-        target_list.push((&*BLACK_HOLE_PUBKEY, TX_FEE_MIN));
-        op_fee += TX_FEE_MIN;
-    }
-    let asset_type = token_code.map(|code| code.val).unwrap_or(ASSET_TYPE_FRA);
+    let utxos = get_owned_utxos(owner_kp.get_pk_ref()).c(d!())?;
+    let targets = target_list
+        .into_iter()
+        .map(|(pk, am)| TransferTarget {
+            pk: *pk,
+            am,
+            confidential_am,
+            confidential_ty,
+            multisig: None,
+        })
+        .collect();
+    let tracing_policies = token_code
+        .map(|code| get_tracing_policies(&code).c(d!()))
+        .transpose()?;
+    gen_transfer_op_with_utxos(
+        owner_kp,
+        utxos,
+        CoinSelection::default(),
+        targets,
+        token_code,
+        tracing_policies.as_ref(),
+        auto_fee,
+    )
+    .c(d!())
+}
 
-    let mut trans_builder = TransferOperationBuilder::new();
+/// An output of a transfer, the confidentiality can be different for every output
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferTarget {
+    /// the receiver
+    pub pk: XfrPublicKey,
+    #[allow(missing_docs)]
+    pub am: u64,
+    /// mask the amount on the transaction log
+    pub confidential_am: bool,
+    /// mask the asset type on the transaction log
+    pub confidential_ty: bool,
+    /// set if the receiver is a multisig account,
+    /// then `pk` is the address of it and the output is non-confidential
+    #[serde(default)]
+    pub multisig: Option<SignatureRules>,
+}
 
-    let mut am = target_list.iter().map(|(_, am)| *am).sum();
-    if asset_type != ASSET_TYPE_FRA {
-        am -= op_fee;
-    } else {
-        // if this is a FRA asset, set op_fee to 0, because fee has been added to am already.
-        op_fee = 0;
+impl TransferTarget {
+    /// Parse a line of the batch files of `fn transfer-batch`:
+    /// `<receiver> [amount] [confidential-amount] [confidential-type]`,
+    /// `am` and the confidentiality flags are used when they are not in the line.
+    pub fn parse_line(
+        line: &str,
+        parse_pk: impl Fn(&str) -> Result<XfrPublicKey>,
+        am: Option<u64>,
+        confidential_am: bool,
+        confidential_ty: bool,
+    ) -> Result<Self> {
+        let mut fields = line.split_whitespace();
+        let pk = fields
+            .next()
+            .c(d!("empty line"))
+            .and_then(|pk| parse_pk(pk))?;
+
+        let mut target = TransferTarget {
+            pk,
+            am: 0,
+            confidential_am,
+            confidential_ty,
+            multisig: None,
+        };
+        let mut line_am = None;
+        for f in fields {
+            match f {
+                "confidential-amount" => target.confidential_am = true,
+                "confidential-type" => target.confidential_ty = true,
+                _ if line_am.is_none() => {
+                    line_am =
+                        Some(f.parse::<u64>().c(d!(format!("invalid amount '{}'", f)))?);
+                }
+                _ => return Err(eg!(format!("unknown field '{}'", f))),
+            }
+        }
+        target.am = line_am.or(am).c(d!("amount is missing"))?;
+
+        Ok(target)
     }
-    let mut i_am;
-    let utxos = get_owned_utxos(owner_kp.get_pk_ref()).c(d!())?.into_iter();
 
-    for (sid, (utxo, owner_memo)) in utxos {
-        let oar =
-            open_blind_asset_record(&utxo.0.record, &owner_memo, owner_kp).c(d!())?;
+    /// An output to the multisig account controlled by `rules`
+    pub fn multisig(rules: SignatureRules, am: u64) -> Self {
+        TransferTarget {
+            pk: rules.multisig_address(),
+            am,
+            confidential_am: false,
+            confidential_ty: false,
+            multisig: Some(rules),
+        }
+    }
 
-        if oar.asset_type != asset_type && oar.asset_type != ASSET_TYPE_FRA {
-            continue;
-        } else if oar.asset_type == ASSET_TYPE_FRA && op_fee != 0 {
-            // asset_type is a custom asset, need handle fee here
-            alt!(oar.amount < op_fee, i_am = oar.amount, i_am = op_fee);
-            op_fee -= i_am;
+    #[inline(always)]
+    fn record_type(&self) -> AssetRecordType {
+        AssetRecordType::from_flags(self.confidential_am, self.confidential_ty)
+    }
+}
 
+// add the outputs of the targets, the receivers may be multisig accounts
+fn add_target_outputs(
+    trans_builder: &mut TransferOperationBuilder,
+    targets: Vec<TransferTarget>,
+    code: AssetTypeCode,
+    tracing_policies: Option<&TracingPolicies>,
+) -> Result<()> {
+    for t in targets.into_iter() {
+        if let Some(rules) = t.multisig {
             trans_builder
-                .add_input(TxoRef::Absolute(sid), oar, None, None, i_am)
+                .add_multisig_output(rules, t.am, code, tracing_policies.cloned())
                 .c(d!())?;
-
-            continue;
-        } else if am != 0 {
-            alt!(oar.amount < am, i_am = oar.amount, i_am = am);
-            //am = am.saturating_sub(i_am);
-            am -= i_am;
-
+        } else {
+            let output = if let Some(policies) = tracing_policies {
+                AssetRecordTemplate::with_asset_tracing(
+                    t.am,
+                    code.val,
+                    t.record_type(),
+                    t.pk,
+                    policies.clone(),
+                )
+            } else {
+                AssetRecordTemplate::with_no_asset_tracing(
+                    t.am,
+                    code.val,
+                    t.record_type(),
+                    t.pk,
+                )
+            };
             trans_builder
-                .add_input(TxoRef::Absolute(sid), oar, None, None, i_am)
+                .add_output(&output, tracing_policies.cloned(), None, None)
                 .c(d!())?;
         }
+    }
+    Ok(())
+}
+
+/// Same as `gen_transfer_op_x`, but the inputs are selected from the given utxos
+/// by `selection`, so it works without the network, eg. signing an offline transaction.
+///
+/// The records of a custom asset carry tracing memos if `tracing_policies` is set,
+/// see `get_tracing_policies`.
+pub fn gen_transfer_op_with_utxos(
+    owner_kp: &XfrKeyPair,
+    utxos: HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>,
+    selection: CoinSelection,
+    targets: Vec<TransferTarget>,
+    token_code: Option<AssetTypeCode>,
+    tracing_policies: Option<&TracingPolicies>,
+    auto_fee: bool,
+) -> Result<Operation> {
+    let mut op_fee: u64 = alt!(auto_fee, TX_FEE_MIN, 0);
+    let asset_type = token_code.map(|code| code.val).unwrap_or(ASSET_TYPE_FRA);
+    let tracing_policies =
+        tracing_policies.filter(|p| asset_type != ASSET_TYPE_FRA && !p.is_empty());
+
+    let mut trans_builder = TransferOperationBuilder::new();
 
-        alt!(0 == am && 0 == op_fee, break);
+    let mut am: u64 = targets.iter().map(|t| t.am).sum();
+    if asset_type == ASSET_TYPE_FRA {
+        // if this is a FRA asset, the fee is paid with the same inputs
+        am = am.checked_add(op_fee).c(d!("amount overflow"))?;
+        op_fee = 0;
     }
+    let (fra, others) = open_utxos(owner_kp, utxos)
+        .0
+        .into_iter()
+        .filter(|(_, oar)| {
+            oar.asset_type == asset_type || oar.asset_type == ASSET_TYPE_FRA
+        })
+        .partition::<Vec<_>, _>(|(_, oar)| oar.asset_type == ASSET_TYPE_FRA);
 
-    if 0 != am || 0 != op_fee {
-        return Err(eg!("insufficient balance"));
+    if asset_type == ASSET_TYPE_FRA {
+        trans_builder.add_inputs_by(selection, fra, am).c(d!())?;
+    } else {
+        // asset_type is a custom asset, need handle fee here
+        if let Some(policies) = tracing_policies {
+            trans_builder
+                .add_traced_inputs_by(selection, others, policies, am)
+                .c(d!())?;
+        } else {
+            trans_builder.add_inputs_by(selection, others, am).c(d!())?;
+        }
+        trans_builder
+            .add_inputs_by(selection, fra, op_fee)
+            .c(d!())?;
     }
 
     if auto_fee {
-        target_list.pop();
         trans_builder
             .add_output(
                 &AssetRecordTemplate::with_no_asset_tracing(
@@ -211,28 +353,227 @@ pub fn gen_transfer_op_x(
             .c(d!())?;
     }
 
-    let art = match (confidential_am, confidential_ty) {
-        (true, true) => AssetRecordType::ConfidentialAmount_ConfidentialAssetType,
-        (true, false) => AssetRecordType::ConfidentialAmount_NonConfidentialAssetType,
-        (false, true) => AssetRecordType::NonConfidentialAmount_ConfidentialAssetType,
-        _ => AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
-    };
+    add_target_outputs(
+        &mut trans_builder,
+        targets,
+        AssetTypeCode { val: asset_type },
+        tracing_policies,
+    )
+    .c(d!())?;
 
-    let outputs = target_list.into_iter().map(|(pk, n)| {
-        AssetRecordTemplate::with_no_asset_tracing(
-            n,
-            token_code.map(|code| code.val).unwrap_or(ASSET_TYPE_FRA),
-            art,
-            *pk,
+    trans_builder
+        .balance()
+        .c(d!())?
+        .create(TransferType::Standard)
+        .c(d!())?
+        .sign(owner_kp)
+        .c(d!())?
+        .transaction()
+        .c(d!())
+}
+
+/// Transfer from the multisig account controlled by `rules`, only the utxos
+/// recording the rules are spent, the fee and the change are paid by the account.
+/// The operation is not signed, the signers approve it by `Transaction::cosign`.
+///
+/// The records of a custom asset carry tracing memos if `tracing_policies` is set,
+/// see `get_tracing_policies`.
+pub fn gen_multisig_transfer_op(
+    rules: &SignatureRules,
+    utxos: HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>,
+    selection: CoinSelection,
+    targets: Vec<TransferTarget>,
+    token_code: Option<AssetTypeCode>,
+    tracing_policies: Option<&TracingPolicies>,
+) -> Result<Operation> {
+    let asset_type = token_code.map(|code| code.val).unwrap_or(ASSET_TYPE_FRA);
+    let tracing_policies =
+        tracing_policies.filter(|p| asset_type != ASSET_TYPE_FRA && !p.is_empty());
+
+    let mut am = targets
+        .iter()
+        .try_fold(0u64, |acc, t| acc.checked_add(t.am))
+        .c(d!("amount overflow"))?;
+    let mut fee = TX_FEE_MIN;
+    if asset_type == ASSET_TYPE_FRA {
+        am = am.checked_add(fee).c(d!("amount overflow"))?;
+        fee = 0;
+    }
+
+    // the records of multisig accounts are non-confidential,
+    // so they can be opened with any key,
+    // those funded by plain transfers do not record the rules
+    let kp = gen_random_keypair();
+    let address = rules.multisig_address();
+    let mut utxos = utxos
+        .into_iter()
+        .filter(|(_, (utxo, _))| {
+            utxo.0.record.public_key == address
+                && utxo.0.multisig.as_ref().map_or(true, |r| r == rules)
+        })
+        .collect::<Vec<_>>();
+    utxos.sort_by_key(|(sid, _)| *sid);
+    let (fra, others) = utxos
+        .into_iter()
+        .filter_map(|(sid, (utxo, _))| {
+            open_blind_asset_record(&utxo.0.record, &None, &kp)
+                .ok()
+                .map(|oar| (TxoRef::Absolute(sid), oar))
+        })
+        .filter(|(_, oar)| {
+            oar.asset_type == asset_type || oar.asset_type == ASSET_TYPE_FRA
+        })
+        .partition::<Vec<_>, _>(|(_, oar)| oar.asset_type == ASSET_TYPE_FRA);
+
+    let mut trans_builder = TransferOperationBuilder::new();
+    if asset_type == ASSET_TYPE_FRA {
+        trans_builder
+            .add_multisig_inputs_by(selection, fra, rules, am)
+            .c(d!())?;
+    } else {
+        if let Some(policies) = tracing_policies {
+            trans_builder
+                .add_traced_multisig_inputs_by(selection, others, rules, policies, am)
+                .c(d!())?;
+        } else {
+            trans_builder
+                .add_multisig_inputs_by(selection, others, rules, am)
+                .c(d!())?;
+        }
+        trans_builder
+            .add_multisig_inputs_by(selection, fra, rules, fee)
+            .c(d!())?;
+    }
+
+    trans_builder
+        .add_output(
+            &AssetRecordTemplate::with_no_asset_tracing(
+                TX_FEE_MIN,
+                ASSET_TYPE_FRA,
+                AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                *BLACK_HOLE_PUBKEY,
+            ),
+            None,
+            None,
+            None,
         )
-    });
+        .c(d!())?;
+    add_target_outputs(
+        &mut trans_builder,
+        targets,
+        AssetTypeCode { val: asset_type },
+        tracing_policies,
+    )
+    .c(d!())?;
+
+    trans_builder
+        .balance()
+        .c(d!())?
+        .create(TransferType::Standard)
+        .c(d!())?
+        .transaction()
+        .c(d!())
+}
+
+/// Merge the smallest utxos of an asset into one, at most `max_inputs` of them,
+/// the fee is paid from the merged FRA, or from other FRA utxos for a custom asset.
+///
+/// The records of a custom asset carry tracing memos if `tracing_policies` is set,
+/// see `get_tracing_policies`.
+pub fn gen_consolidate_op(
+    owner_kp: &XfrKeyPair,
+    utxos: HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>,
+    token_code: Option<AssetTypeCode>,
+    tracing_policies: Option<&TracingPolicies>,
+    max_inputs: usize,
+) -> Result<Operation> {
+    let asset_type = token_code.map(|code| code.val).unwrap_or(ASSET_TYPE_FRA);
+    let tracing_policies =
+        tracing_policies.filter(|p| asset_type != ASSET_TYPE_FRA && !p.is_empty());
 
-    for output in outputs {
+    let (mut merged, rest) = open_utxos(owner_kp, utxos)
+        .0
+        .into_iter()
+        .partition::<Vec<_>, _>(|(_, oar)| oar.asset_type == asset_type);
+    merged.sort_by_key(|(_, oar)| oar.amount);
+    merged.truncate(max_inputs);
+    if merged.len() < 2 {
+        return Err(eg!("nothing to consolidate"));
+    }
+
+    // keep the confidentiality of the inputs
+    let (conf_am, conf_ty) =
+        merged.iter().fold((false, false), |acc, (_, oar)| {
+            match oar.get_record_type() {
+                AssetRecordType::ConfidentialAmount_ConfidentialAssetType => {
+                    (true, true)
+                }
+                AssetRecordType::ConfidentialAmount_NonConfidentialAssetType => {
+                    (true, acc.1)
+                }
+                AssetRecordType::NonConfidentialAmount_ConfidentialAssetType => {
+                    (acc.0, true)
+                }
+                _ => acc,
+            }
+        });
+    let total = merged
+        .iter()
+        .try_fold(0u64, |acc, (_, oar)| acc.checked_add(oar.amount))
+        .c(d!("amount overflow"))?;
+
+    let mut trans_builder = TransferOperationBuilder::new();
+    for (sid, oar) in merged.into_iter() {
+        let am = oar.amount;
         trans_builder
-            .add_output(&output, None, None, None)
+            .add_input(sid, oar, tracing_policies.cloned(), None, am)
             .c(d!())?;
     }
 
+    let am = if asset_type == ASSET_TYPE_FRA {
+        total
+            .checked_sub(TX_FEE_MIN)
+            .filter(|am| 0 < *am)
+            .c(d!("the merged amount is too small to pay the fee"))?
+    } else {
+        let fra = rest
+            .into_iter()
+            .filter(|(_, oar)| oar.asset_type == ASSET_TYPE_FRA)
+            .collect();
+        trans_builder
+            .add_inputs_by(CoinSelection::LargestFirst, fra, TX_FEE_MIN)
+            .c(d!())?;
+        total
+    };
+
+    trans_builder
+        .add_output(
+            &AssetRecordTemplate::with_no_asset_tracing(
+                TX_FEE_MIN,
+                ASSET_TYPE_FRA,
+                AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                *BLACK_HOLE_PUBKEY,
+            ),
+            None,
+            None,
+            None,
+        )
+        .c(d!())?;
+    let target = TransferTarget {
+        pk: owner_kp.get_pk(),
+        am,
+        confidential_am: conf_am,
+        confidential_ty: conf_ty,
+        multisig: None,
+    };
+    add_target_outputs(
+        &mut trans_builder,
+        vec![target],
+        AssetTypeCode { val: asset_type },
+        tracing_policies,
+    )
+    .c(d!())?;
+
     trans_builder
         .balance()
         .c(d!())?
@@ -244,6 +585,40 @@ pub fn gen_transfer_op_x(
         .c(d!())
 }
 
+// open the utxos of the owner with their owner memos, the oldest first,
+// confidential records without a valid owner memo can not be spent,
+// so they are skipped with a warning, and their sids are returned
+fn open_utxos(
+    owner_kp: &XfrKeyPair,
+    utxos: HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>,
+) -> (Vec<(TxoRef, OpenAssetRecord)>, Vec<TxoSID>) {
+    let mut utxos = utxos.into_iter().collect::<Vec<_>>();
+    utxos.sort_by_key(|(sid, _)| *sid);
+
+    let mut opened = vec![];
+    let mut skipped = vec![];
+    for (sid, (utxo, owner_memo)) in utxos.into_iter() {
+        match open_blind_asset_record(&utxo.0.record, &owner_memo, owner_kp) {
+            Ok(oar) => opened.push((TxoRef::Absolute(sid), oar)),
+            Err(_) => skipped.push(sid),
+        }
+    }
+
+    if !skipped.is_empty() {
+        eprintln!(
+            "\x1b[33;01mWarning:\x1b[00m {} utxo(s) can not be opened, they are not counted: {}",
+            skipped.len(),
+            skipped
+                .iter()
+                .map(|sid| sid.0.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+    }
+
+    (opened, skipped)
+}
+
 /// for scenes that need to pay a standalone fee without other transfers
 #[inline(always)]
 #[allow(missing_docs)]
@@ -379,6 +754,16 @@ pub fn get_asset_type(code: &str) -> Result<AssetType> {
         .and_then(|b| serde_json::from_slice::<AssetType>(&b).c(d!()))
 }
 
+/// The tracing policies of a custom asset, empty if it is not traced
+pub fn get_tracing_policies(code: &AssetTypeCode) -> Result<TracingPolicies> {
+    if code.val == ASSET_TYPE_FRA {
+        return Ok(TracingPolicies::new());
+    }
+    get_asset_type(&code.to_base64())
+        .c(d!())
+        .map(|at| at.get_tracing_policies_ref().clone())
+}
+
 /// Retrieve a list of assets created by the specified findora account
 pub fn get_created_assets(addr: &XfrPublicKey) -> Result<Vec<DefineAsset>> {
     let url = format!(
@@ -399,17 +784,10 @@ pub fn get_created_assets(addr: &XfrPublicKey) -> Result<Vec<DefineAsset>> {
 
 #[allow(missing_docs)]
 pub fn get_balance(kp: &XfrKeyPair) -> Result<u64> {
-    let balance = get_owned_utxos(kp.get_pk_ref())
-        .c(d!())?
-        .values()
-        .map(|(utxo, owner_memo)| {
-            open_blind_asset_record(&utxo.0.record, owner_memo, kp)
-                .c(d!())
-                .map(|obr| obr.amount)
-        })
-        .collect::<Result<Vec<_>>>()
-        .c(d!())?
+    let balance = open_utxos(kp, get_owned_utxos(kp.get_pk_ref()).c(d!())?)
+        .0
         .iter()
+        .map(|(_, oar)| oar.amount)
         .sum();
 
     Ok(balance)
@@ -418,24 +796,36 @@ pub fn get_balance(kp: &XfrKeyPair) -> Result<u64> {
 /// Retrieve Utxos of a findora keypair and calcultate the balance of the specified asset
 /// FRA is the default asset type
 pub fn get_asset_balance(kp: &XfrKeyPair, asset: Option<AssetTypeCode>) -> Result<u64> {
+    get_asset_balance_detail(kp, asset)
+        .c(d!())
+        .map(|(balance, _)| balance)
+}
+
+/// Same as `get_asset_balance`, and how much of the balance is in confidential records,
+/// which are opened with their owner memos.
+pub fn get_asset_balance_detail(
+    kp: &XfrKeyPair,
+    asset: Option<AssetTypeCode>,
+) -> Result<(u64, u64)> {
     let asset_type = asset.map(|code| code.val).unwrap_or(ASSET_TYPE_FRA);
-    let balance = get_owned_utxos(kp.get_pk_ref())
-        .c(d!())?
-        .values()
-        .map(|(utxo, owner_memo)| {
-            open_blind_asset_record(&utxo.0.record, owner_memo, kp)
-                .c(d!())
-                .map(|obr| alt!(obr.asset_type == asset_type, obr.amount, 0))
-        })
-        .collect::<Result<Vec<_>>>()
-        .c(d!())?
+    let balance = open_utxos(kp, get_owned_utxos(kp.get_pk_ref()).c(d!())?)
+        .0
         .iter()
-        .sum();
+        .filter(|(_, oar)| oar.asset_type == asset_type)
+        .fold((0, 0), |(total, confidential), (_, oar)| {
+            let is_confidential = oar.get_record_type()
+                != AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType;
+            (
+                total + oar.amount,
+                confidential + alt!(is_confidential, oar.amount, 0),
+            )
+        });
 
     Ok(balance)
 }
 
-fn get_owned_utxos(
+/// Utxos of a findora account, with their owner memos
+pub fn get_owned_utxos(
     addr: &XfrPublicKey,
 ) -> Result<HashMap<TxoSID, (Utxo, Option<OwnerMemo>)>> {
     let url = format!(
@@ -502,6 +892,111 @@ pub fn get_owner_memo_batch(ids: &[TxoSID]) -> Result<Vec<Option<OwnerMemo>>> {
         .and_then(|b| serde_json::from_slice(&b).c(d!()))
 }
 
+/// A page of the transactions related to `addr`, in ascending order,
+/// start after `cursor`, see `txn_history` of the query server.
+pub fn get_txn_history(
+    addr: &XfrPublicKey,
+    cursor: Option<u64>,
+    limit: usize,
+) -> Result<HistoryPage<TxnHistoryItem>> {
+    let mut url = format!(
+        "{}:8667/txn_history?address={}&limit={}",
+        get_serv_addr().c(d!())?,
+        wallet::public_key_to_base64(addr),
+        limit
+    );
+    if let Some(c) = cursor {
+        url += &format!("&cursor={}", c);
+    }
+
+    attohttpc::get(&url)
+        .send()
+        .c(d!())?
+        .error_for_status()
+        .c(d!())?
+        .bytes()
+        .c(d!())
+        .and_then(|b| serde_json::from_slice(&b).c(d!()))
+}
+
+/// A page of the transfers of `code` in ascending order, start after `cursor`,
+/// see `xfr_history` of the query server, transfers with a confidential
+/// asset type are not indexed by it.
+pub fn get_xfr_history(
+    code: &AssetTypeCode,
+    cursor: Option<u64>,
+    limit: usize,
+) -> Result<HistoryPage<TxnHistoryItem>> {
+    let mut url = format!(
+        "{}:8667/xfr_history?asset={}&limit={}",
+        get_serv_addr().c(d!())?,
+        code.to_base64(),
+        limit
+    );
+    if let Some(c) = cursor {
+        url += &format!("&cursor={}", c);
+    }
+
+    attohttpc::get(&url)
+        .send()
+        .c(d!())?
+        .error_for_status()
+        .c(d!())?
+        .bytes()
+        .c(d!())
+        .and_then(|b| serde_json::from_slice(&b).c(d!()))
+}
+
+/// A transaction with the ids of its outputs
+pub fn get_txn(sid: TxnSID) -> Result<FinalizedTransaction> {
+    let url = format!("{}:8668/txn_sid_light/{}", get_serv_addr().c(d!())?, sid.0);
+
+    attohttpc::get(&url)
+        .send()
+        .c(d!())?
+        .error_for_status()
+        .c(d!())?
+        .bytes()
+        .c(d!())
+        .and_then(|b| serde_json::from_slice::<FinalizedTransaction>(&b).c(d!()))
+}
+
+/// Get the transactions of `sids` in batches, see `TXN_LIST_LIMIT`,
+/// an error is returned if any of them does not exist.
+pub fn get_txns(sids: &[TxnSID]) -> Result<Vec<FinalizedTransaction>> {
+    let mut txns = Vec::with_capacity(sids.len());
+    for chunk in sids.chunks(TXN_LIST_LIMIT) {
+        let list = chunk
+            .iter()
+            .map(|sid| sid.0.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let url = format!(
+            "{}:8668/txn_sid_light_list/{}",
+            get_serv_addr().c(d!())?,
+            list
+        );
+
+        let batch = attohttpc::get(&url)
+            .send()
+            .c(d!())?
+            .error_for_status()
+            .c(d!())?
+            .bytes()
+            .c(d!())
+            .and_then(|b| {
+                serde_json::from_slice::<Vec<Option<FinalizedTransaction>>>(&b).c(d!())
+            })?;
+        if batch.len() != chunk.len() {
+            return Err(eg!("mismatched transactions"));
+        }
+        for (sid, txn) in chunk.iter().zip(batch.into_iter()) {
+            txns.push(txn.c(d!(format!("transaction {} not found", sid.0)))?);
+        }
+    }
+    Ok(txns)
+}
+
 /// Delegation info(and staking info if `pk` is a validator).
 pub fn get_delegation_info(pk: &XfrPublicKey) -> Result<DelegationInfo> {
     let url = format!(
@@ -538,6 +1033,60 @@ pub fn get_validator_detail(td_addr: TendermintAddrRef) -> Result<ValidatorDetai
         .and_then(|b| serde_json::from_slice::<ValidatorDetail>(&b).c(d!()))
 }
 
+/// The current stakes and return rate of a validator,
+/// no key of the caller is needed.
+pub fn get_validator_delegation(
+    td_addr: TendermintAddrRef,
+) -> Result<ValidatorDelegation> {
+    // only the current height is wanted
+    let url = format!(
+        "{}:8668/validator_delegation?address={}&epoch_size=1&epoch_cnt=0",
+        get_serv_addr().c(d!())?,
+        td_addr
+    );
+
+    attohttpc::get(&url)
+        .send()
+        .c(d!())?
+        .error_for_status()
+        .c(d!())?
+        .bytes()
+        .c(d!())
+        .and_then(|b| serde_json::from_slice::<Vec<ValidatorDelegation>>(&b).c(d!()))
+        .and_then(|mut hist| {
+            alt!(
+                hist.is_empty(),
+                Err(eg!("empty delegation history")),
+                Ok(hist.remove(0))
+            )
+        })
+}
+
+/// The blocks signed and missed by a validator recently,
+/// the default range of the server is used if `blocks` is not set.
+pub fn get_signing_report(
+    td_addr: TendermintAddrRef,
+    blocks: Option<u64>,
+) -> Result<SigningReport> {
+    let mut url = format!(
+        "{}:8668/validator_signing?address={}",
+        get_serv_addr().c(d!())?,
+        td_addr
+    );
+    if let Some(n) = blocks {
+        url += &format!("&blocks={}", n);
+    }
+
+    attohttpc::get(&url)
+        .send()
+        .c(d!())?
+        .error_for_status()
+        .c(d!())?
+        .bytes()
+        .c(d!())
+        .and_then(|b| serde_json::from_slice::<SigningReport>(&b).c(d!()))
+}
+
 #[derive(Serialize, Deserialize)]
 #[allow(missing_docs)]
 pub struct ValidatorKey {
@@ -550,3 +1099,363 @@ pub struct ValidatorKey {
 pub fn parse_td_validator_keys(key_data: String) -> Result<ValidatorKey> {
     serde_json::from_str(key_data.as_str()).c(d!())
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use ledger::data_model::TxOutput;
+    use ledger::staking::gen_random_keypair;
+    use rand_chacha::ChaChaRng;
+    use rand_core::SeedableRng;
+    use zei::{
+        setup::PublicParams,
+        xfr::{
+            asset_record::build_blind_asset_record,
+            lib::trace_assets,
+            structs::{AssetTracerKeyPair, AssetType as ZeiAssetType, TracingPolicy},
+        },
+    };
+
+    /// An utxo of `pk` with its owner memo, the memo is none if not confidential
+    pub(crate) fn new_utxo(
+        pk: &XfrPublicKey,
+        am: u64,
+        asset_type: ZeiAssetType,
+        art: AssetRecordType,
+    ) -> (Utxo, Option<OwnerMemo>) {
+        let template =
+            AssetRecordTemplate::with_no_asset_tracing(am, asset_type, art, *pk);
+        let (record, _, memo) = build_blind_asset_record(
+            &mut ChaChaRng::from_entropy(),
+            &PublicParams::default().pc_gens,
+            &template,
+            vec![],
+        );
+        let utxo = Utxo(TxOutput {
+            id: None,
+            record,
+            lien: None,
+            multisig: None,
+        });
+        (utxo, memo)
+    }
+
+    // (owner, amount) of the outputs, `None` if the amount is confidential
+    fn outputs(op: &Operation) -> Vec<(XfrPublicKey, Option<u64>)> {
+        match op {
+            Operation::TransferAsset(xfr) => xfr
+                .body
+                .outputs
+                .iter()
+                .map(|o| (o.record.public_key, o.record.amount.get_amount()))
+                .collect(),
+            _ => panic!("not a transfer"),
+        }
+    }
+
+    fn inputs(op: &Operation) -> Vec<TxoRef> {
+        match op {
+            Operation::TransferAsset(xfr) => xfr.body.inputs.clone(),
+            _ => panic!("not a transfer"),
+        }
+    }
+
+    #[test]
+    fn test_gen_consolidate_op() {
+        let kp = gen_random_keypair();
+        let pk = kp.get_pk();
+        let fra = |am| {
+            new_utxo(
+                &pk,
+                am,
+                ASSET_TYPE_FRA,
+                AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+            )
+        };
+        let fee = (*BLACK_HOLE_PUBKEY, Some(TX_FEE_MIN));
+
+        // the smallest ones are merged, the fee is paid from them
+        let utxos = vec![
+            (TxoSID(1), fra(3 * TX_FEE_MIN)),
+            (TxoSID(2), fra(TX_FEE_MIN)),
+            (TxoSID(3), fra(2 * TX_FEE_MIN)),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let op = pnk!(gen_consolidate_op(&kp, utxos.clone(), None, None, 2));
+        assert_eq!(
+            vec![TxoRef::Absolute(TxoSID(2)), TxoRef::Absolute(TxoSID(3))],
+            inputs(&op)
+        );
+        assert_eq!(vec![fee, (pk, Some(2 * TX_FEE_MIN))], outputs(&op));
+
+        let op = pnk!(gen_consolidate_op(&kp, utxos, None, None, 32));
+        assert_eq!(3, inputs(&op).len());
+        assert_eq!(vec![fee, (pk, Some(5 * TX_FEE_MIN))], outputs(&op));
+
+        // the merged fra must cover the fee
+        let utxos = vec![(TxoSID(1), fra(1)), (TxoSID(2), fra(2))]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        assert!(gen_consolidate_op(&kp, utxos, None, None, 32).is_err());
+
+        // nothing to merge
+        let utxos = vec![(TxoSID(1), fra(TX_FEE_MIN))].into_iter().collect();
+        assert!(gen_consolidate_op(&kp, utxos, None, None, 32).is_err());
+
+        // a custom asset, the fee is paid by other fra utxos,
+        // and the confidentiality of the inputs is kept
+        let code = AssetTypeCode::gen_random();
+        let utxos = vec![
+            (
+                TxoSID(1),
+                new_utxo(
+                    &pk,
+                    5,
+                    code.val,
+                    AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                ),
+            ),
+            (
+                TxoSID(2),
+                new_utxo(
+                    &pk,
+                    7,
+                    code.val,
+                    AssetRecordType::ConfidentialAmount_NonConfidentialAssetType,
+                ),
+            ),
+            (TxoSID(3), fra(TX_FEE_MIN)),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        assert!(gen_consolidate_op(&kp, utxos.clone(), Some(code), None, 1).is_err());
+        let op = pnk!(gen_consolidate_op(&kp, utxos, Some(code), None, 32));
+        let mut spent = inputs(&op);
+        spent.sort_by_key(|i| match i {
+            TxoRef::Absolute(sid) => *sid,
+            TxoRef::Relative(_) => panic!("relative input"),
+        });
+        assert_eq!(
+            vec![
+                TxoRef::Absolute(TxoSID(1)),
+                TxoRef::Absolute(TxoSID(2)),
+                TxoRef::Absolute(TxoSID(3))
+            ],
+            spent
+        );
+        assert_eq!(vec![fee, (pk, None)], outputs(&op));
+    }
+
+    #[test]
+    fn test_gen_consolidate_op_traced() {
+        let mut prng = ChaChaRng::from_entropy();
+        let kp = gen_random_keypair();
+        let pk = kp.get_pk();
+        let tracer = AssetTracerKeyPair::generate(&mut prng);
+        let policies = TracingPolicies::from_policy(TracingPolicy {
+            enc_keys: tracer.enc_key.clone(),
+            asset_tracing: true,
+            identity_tracing: None,
+        });
+
+        let code = AssetTypeCode::gen_random();
+        let art = AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType;
+        let utxos = vec![
+            (TxoSID(1), new_utxo(&pk, 5, code.val, art)),
+            (
+                TxoSID(2),
+                new_utxo(
+                    &pk,
+                    7,
+                    code.val,
+                    AssetRecordType::ConfidentialAmount_NonConfidentialAssetType,
+                ),
+            ),
+            (TxoSID(3), new_utxo(&pk, TX_FEE_MIN, ASSET_TYPE_FRA, art)),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let op = pnk!(gen_consolidate_op(
+            &kp,
+            utxos,
+            Some(code),
+            Some(&policies),
+            32
+        ));
+
+        // the merged inputs and the merged output are traced, the fee is not
+        let xfr = match op {
+            Operation::TransferAsset(xfr) => xfr,
+            _ => panic!("not a transfer"),
+        };
+        let mut traced = pnk!(trace_assets(&xfr.body.transfer, &tracer))
+            .into_iter()
+            .map(|(am, ty, _, pk)| (am, ty, pk))
+            .collect::<Vec<_>>();
+        traced.sort_by_key(|(am, _, _)| *am);
+        assert_eq!(
+            vec![(5, code.val, pk), (7, code.val, pk), (12, code.val, pk)],
+            traced
+        );
+    }
+
+    #[test]
+    fn test_open_utxos() {
+        let kp = gen_random_keypair();
+        let pk = kp.get_pk();
+        let conf = AssetRecordType::ConfidentialAmount_ConfidentialAssetType;
+        let (conf_utxo, memo) = new_utxo(&pk, 7, ASSET_TYPE_FRA, conf);
+        let utxos = vec![
+            (TxoSID(3), (conf_utxo.clone(), memo)),
+            // the owner memo is lost
+            (TxoSID(2), (conf_utxo, None)),
+            (
+                TxoSID(1),
+                new_utxo(
+                    &pk,
+                    5,
+                    ASSET_TYPE_FRA,
+                    AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+                ),
+            ),
+            // not owned
+            (
+                TxoSID(4),
+                new_utxo(&gen_random_keypair().get_pk(), 9, ASSET_TYPE_FRA, conf),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let (opened, skipped) = open_utxos(&kp, utxos);
+        assert_eq!(
+            vec![
+                (TxoRef::Absolute(TxoSID(1)), 5),
+                (TxoRef::Absolute(TxoSID(3)), 7)
+            ],
+            opened
+                .iter()
+                .map(|(sid, oar)| (*sid, oar.amount))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec![TxoSID(2), TxoSID(4)], skipped);
+    }
+
+    #[test]
+    fn test_spend_confidential_utxo() {
+        let kp = gen_random_keypair();
+        let receiver = gen_random_keypair();
+        let conf = AssetRecordType::ConfidentialAmount_ConfidentialAssetType;
+        let utxos = vec![(
+            TxoSID(1),
+            new_utxo(kp.get_pk_ref(), 3 * TX_FEE_MIN, ASSET_TYPE_FRA, conf),
+        )]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let target = TransferTarget {
+            pk: receiver.get_pk(),
+            am: TX_FEE_MIN,
+            confidential_am: true,
+            confidential_ty: true,
+            multisig: None,
+        };
+
+        let op = pnk!(gen_transfer_op_with_utxos(
+            &kp,
+            utxos.clone(),
+            CoinSelection::default(),
+            vec![target.clone()],
+            None,
+            None,
+            true,
+        ));
+        assert_eq!(vec![TxoRef::Absolute(TxoSID(1))], inputs(&op));
+        let xfr = match op {
+            Operation::TransferAsset(xfr) => xfr,
+            _ => panic!("not a transfer"),
+        };
+
+        // the fee, the target and the change, opened by their owners
+        let memos = xfr.get_owner_memos_ref();
+        let opened = xfr
+            .body
+            .outputs
+            .iter()
+            .zip(memos.into_iter())
+            .map(|(o, memo)| {
+                let owner = [&kp, &receiver]
+                    .iter()
+                    .find(|k| k.get_pk() == o.record.public_key)
+                    .copied();
+                match owner {
+                    Some(owner) => {
+                        let oar = pnk!(open_blind_asset_record(
+                            &o.record,
+                            &memo.cloned(),
+                            owner
+                        ));
+                        (o.record.public_key, oar.amount)
+                    }
+                    None => (o.record.public_key, o.record.amount.get_amount().unwrap()),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (*BLACK_HOLE_PUBKEY, TX_FEE_MIN),
+                (receiver.get_pk(), TX_FEE_MIN),
+                (kp.get_pk(), TX_FEE_MIN),
+            ],
+            opened
+        );
+
+        // it can not be spent without the owner memo
+        let utxos = utxos
+            .into_iter()
+            .map(|(sid, (utxo, _))| (sid, (utxo, None)))
+            .collect();
+        assert!(gen_transfer_op_with_utxos(
+            &kp,
+            utxos,
+            CoinSelection::default(),
+            vec![target],
+            None,
+            None,
+            true,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_transfer_target() {
+        let pk = gen_random_keypair().get_pk();
+        let addr = wallet::public_key_to_base64(&pk);
+        let parse = |l: &str, am| {
+            TransferTarget::parse_line(
+                l,
+                wallet::public_key_from_base64,
+                am,
+                false,
+                true,
+            )
+        };
+
+        let t = pnk!(parse(&addr, Some(7)));
+        assert_eq!(
+            (pk, 7, false, true),
+            (t.pk, t.am, t.confidential_am, t.confidential_ty)
+        );
+
+        let t = pnk!(parse(&format!("{} 100 confidential-amount", addr), Some(7)));
+        assert_eq!(
+            (100, true, true),
+            (t.am, t.confidential_am, t.confidential_ty)
+        );
+
+        assert!(parse(&addr, None).is_err());
+        assert!(parse(&format!("{} 1 2", addr), None).is_err());
+        assert!(parse(&format!("{} abc", addr), None).is_err());
+        assert!(parse("xx 100", None).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//!
+//! # Validator operator toolkit
+//!
+//! - `fn validator rotate-key`, replace the tendermint key of your validator
+//! - `fn validator status`, show the state of a validator,
+//!   and the blocks it signed and missed recently
+//! - `fn validator rewards`, project the rewards and commissions of a validator
+//!
+//! The validator is the one of the key set by `fn setup -K`,
+//! if `--validator-addr` is not set.
+//!
+//! **NOTE**: the signing history is only kept by the nodes with
+//! `FINDORA_KEEP_STAKING_HIST` set, eg. the full nodes serving the query server.
+//!
+
+use super::{
+    convert_commission_rate, get_keypair, get_td_pubkey,
+    offline::{self, UnsignedOp},
+    utils,
+};
+use ledger::{data_model::FRA_DECIMALS, staking::td_pubkey_to_td_addr};
+use ruc::*;
+use std::fs;
+
+// at most so many missed heights are listed by `status`
+const MISSED_HEIGHTS_SHOWN: usize = 20;
+
+/// Replace the tendermint key of your validator with the key in `new_key_path`,
+/// the power and the delegations of the validator are kept.
+///
+/// The new key takes over the consensus in several blocks,
+/// the node should be restarted with it at that time,
+/// or it will miss the blocks after that.
+pub fn rotate_key(new_key_path: &str) -> Result<()> {
+    let validator = get_td_pubkey().map(|i| td_pubkey_to_td_addr(&i)).c(d!())?;
+    let new_addr = fs::read_to_string(new_key_path)
+        .c(d!("can not read key file from path"))
+        .and_then(|k| utils::parse_td_validator_keys(k).c(d!()))
+        .map(|v_keys| td_pubkey_to_td_addr(&v_keys.pub_key.to_vec()))?;
+    if new_addr == validator {
+        return Err(eg!("the new key is the current key of the validator"));
+    }
+    utils::get_validator_detail(&validator).c(d!("Validator not found"))?;
+
+    let ops = vec![
+        UnsignedOp::RotateKey {
+            validator: validator.clone(),
+            new_key_path: new_key_path.to_owned(),
+        },
+        UnsignedOp::fee(),
+    ];
+    offline::dispatch(ops, get_keypair).c(d!())?;
+
+    println!(
+        "\x1b[31;01mKey Rotation:\x1b[00m\n{} => {}\n",
+        validator, new_addr
+    );
+    println!(
+        "Once the transaction is committed, restart your node with the new key, and run `fn setup -K {}`",
+        new_key_path
+    );
+    Ok(())
+}
+
+/// Show the state of a validator,
+/// and the blocks it signed and missed in the recent `blocks` blocks.
+pub fn status(addr: Option<&str>, blocks: Option<&str>) -> Result<()> {
+    let addr = get_validator_addr(addr).c(d!())?;
+    let blocks = blocks
+        .map(|n| n.parse::<u64>().c(d!("'blocks' must be an integer")))
+        .transpose()?;
+
+    let vd = utils::get_validator_detail(&addr).c(d!("Validator not found"))?;
+    println!("\x1b[31;01mValidator Node Addr:\x1b[00m\n{}\n", vd.addr);
+    println!(
+        "\x1b[31;01mSigned Last Block:\x1b[00m\n{}\n",
+        alt!(vd.is_online, "yes", "no")
+    );
+    println!(
+        "\x1b[31;01mVoting Power:\x1b[00m\n{} (rank {})\n",
+        vd.voting_power, vd.voting_power_rank
+    );
+    println!(
+        "\x1b[31;01mCommission Rate:\x1b[00m\n{:.2}%\n",
+        percent(vd.commission_rate[0], vd.commission_rate[1])
+    );
+    println!(
+        "\x1b[31;01mSigned Since Height {}:\x1b[00m\n{} of {} blocks, proposed {}\n",
+        vd.start_height,
+        vd.block_signed_cnt,
+        1 + vd.cur_height.saturating_sub(vd.start_height),
+        vd.block_proposed_cnt
+    );
+
+    let report = utils::get_signing_report(&addr, blocks).c(d!())?;
+    println!(
+        "\x1b[31;01mUptime Of Heights {}~{}:\x1b[00m",
+        report.from_height, report.to_height
+    );
+    if 0 == report.expected {
+        println!(
+            "no signing history, the server may not keep it, see `FINDORA_KEEP_STAKING_HIST`"
+        );
+        return Ok(());
+    }
+    println!(
+        "{:.2}%, signed {} of {} expected blocks, missed {}",
+        percent(report.signed, report.expected),
+        report.signed,
+        report.expected,
+        report.missed_heights.len()
+    );
+    if !report.missed_heights.is_empty() {
+        let skip = report
+            .missed_heights
+            .len()
+            .saturating_sub(MISSED_HEIGHTS_SHOWN);
+        println!(
+            "\n\x1b[31;01mLast Missed Heights:\x1b[00m\n{}",
+            report.missed_heights[skip..]
+                .iter()
+                .map(|h| h.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Project the yearly and daily rewards of a validator from the current return rate,
+/// `commission_rate` overrides the current rate of the validator to compare.
+///
+/// The projection assumes the rate and the delegations stay unchanged,
+/// and every block is fully voted, the proposer rewards are not included.
+pub fn rewards(addr: Option<&str>, commission_rate: Option<&str>) -> Result<()> {
+    let addr = get_validator_addr(addr).c(d!())?;
+    let vd = utils::get_validator_detail(&addr).c(d!("Validator not found"))?;
+    let cr = commission_rate
+        .map_or(Ok(vd.commission_rate), |s| {
+            s.parse::<f64>()
+                .c(d!("commission rate must be a float number"))
+                .and_then(convert_commission_rate)
+        })
+        .c(d!())?;
+    let return_rate = utils::get_validator_delegation(&addr).c(d!())?.return_rate;
+
+    let rate = return_rate[0] as f64 / return_rate[1] as f64;
+    let cr = cr[0] as f64 / cr[1] as f64;
+    let delegated = vd.voting_power.saturating_sub(vd.self_staking);
+    let self_rwd = vd.self_staking as f64 * rate;
+    let commission = delegated as f64 * rate * cr;
+
+    println!("\x1b[31;01mValidator Node Addr:\x1b[00m\n{}\n", vd.addr);
+    println!(
+        "\x1b[31;01mReturn Rate:\x1b[00m\n{:.2}% per year, {:.2}% for the delegators after {:.2}% commission\n",
+        rate * 100.0,
+        rate * (1.0 - cr) * 100.0,
+        cr * 100.0
+    );
+    println!(
+        "\x1b[31;01mSelf Staking Rewards:\x1b[00m\n{:.6} FRA per year, {:.6} FRA per day\n",
+        fra(self_rwd),
+        fra(self_rwd) / 365.0
+    );
+    println!(
+        "\x1b[31;01mCommissions Of {} FRA Delegated:\x1b[00m\n{:.6} FRA per year, {:.6} FRA per day",
+        fra(delegated as f64),
+        fra(commission),
+        fra(commission) / 365.0
+    );
+
+    Ok(())
+}
+
+fn get_validator_addr(addr: Option<&str>) -> Result<String> {
+    if let Some(addr) = addr {
+        Ok(addr.to_owned())
+    } else {
+        get_td_pubkey().map(|i| td_pubkey_to_td_addr(&i)).c(d!())
+    }
+}
+
+#[inline(always)]
+fn percent(n: u64, d: u64) -> f64 {
+    alt!(0 == d, 0.0, n as f64 * 100.0 / d as f64)
+}
+
+#[inline(always)]
+fn fra(units: f64) -> f64 {
+    units / 10_f64.powi(FRA_DECIMALS as i32)
+}
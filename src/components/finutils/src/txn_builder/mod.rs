@@ -5,18 +5,19 @@
 #![deny(warnings)]
 #![allow(clippy::needless_borrow)]
 
-use credentials::CredUserSecretKey;
+use credentials::{CredUserSecretKey, Credential as PlatformCredential};
 use curve25519_dalek::scalar::Scalar;
 use fp_types::crypto::MultiSigner;
 use globutils::SignatureOf;
 use ledger::{
     converter::ConvertAccount,
     data_model::{
-        AssetRules, AssetTypeCode, ConfidentialMemo, DefineAsset, DefineAssetBody,
-        IndexedSignature, IssueAsset, IssueAssetBody, IssuerKeyPair, IssuerPublicKey,
-        Memo, NoReplayToken, Operation, Transaction, TransactionBody, TransferAsset,
-        TransferAssetBody, TransferType, TxOutput, TxoRef, UpdateMemo, UpdateMemoBody,
-        ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY, TX_FEE_MIN,
+        AssetRules, AssetTypeCode, ConfidentialMemo, CredentialProof, DefineAsset,
+        DefineAssetBody, IndexedSignature, IssueAsset, IssueAssetBody, IssuerKeyPair,
+        IssuerPublicKey, Memo, NoReplayToken, Operation, SignatureRules, Transaction,
+        TransactionBody, TransferAsset, TransferAssetBody, TransferType, TxOutput,
+        TxoRef, UpdateMemo, UpdateMemoBody, ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY,
+        TX_FEE_MIN,
     },
     staking::{
         is_valid_tendermint_addr,
@@ -25,6 +26,8 @@ use ledger::{
             delegation::DelegationOps,
             fra_distribution::FraDistributionOps,
             governance::{ByzantineKind, GovernanceOps},
+            rotate_key::RotateKeyOps,
+            transfer_plan::{PlanId, TransferPlan, TransferPlanAction, TransferPlanOps},
             undelegation::UnDelegationOps,
             update_staker::UpdateStakerOps,
             update_validator::UpdateValidatorOps,
@@ -33,13 +36,15 @@ use ledger::{
         Validator,
     },
 };
+use rand::seq::SliceRandom;
 use rand_chacha::ChaChaRng;
 use rand_core::{CryptoRng, RngCore, SeedableRng};
 use ruc::*;
 use serde::{Deserialize, Serialize};
 use std::{
-    cmp::Ordering,
+    cmp::{min, Ordering},
     collections::{BTreeMap, HashSet},
+    str::FromStr,
 };
 use tendermint::PrivateKey;
 use zei::{
@@ -298,17 +303,46 @@ impl TransactionBuilder {
         amount: u64,
         confidentiality_flags: AssetRecordType,
         zei_params: &PublicParams,
+    ) -> Result<&mut Self> {
+        self.add_traced_issue_asset(
+            key_pair,
+            token_code,
+            seq_num,
+            amount,
+            confidentiality_flags,
+            &TracingPolicies::new(),
+            zei_params,
+        )
+        .c(d!())
+    }
+
+    /// Same as `add_basic_issue_asset`, but the new record is built with
+    /// the asset tracing of `tracing_policies`, which should be the policies
+    /// of the asset, see `utils::get_tracing_policies`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_traced_issue_asset(
+        &mut self,
+        key_pair: &XfrKeyPair,
+        token_code: &AssetTypeCode,
+        seq_num: u64,
+        amount: u64,
+        confidentiality_flags: AssetRecordType,
+        tracing_policies: &TracingPolicies,
+        zei_params: &PublicParams,
     ) -> Result<&mut Self> {
         let mut prng = ChaChaRng::from_entropy();
-        let ar = AssetRecordTemplate::with_no_asset_tracing(
+        let ar = AssetRecordTemplate::with_asset_tracing(
             amount,
             token_code.val,
             confidentiality_flags,
             key_pair.get_pk(),
+            tracing_policies.clone(),
         );
 
+        // no identity tracing for any of the policies
+        let attrs = (0..tracing_policies.len()).map(|_| vec![]).collect();
         let (ba, _, owner_memo) =
-            build_blind_asset_record(&mut prng, &zei_params.pc_gens, &ar, vec![]);
+            build_blind_asset_record(&mut prng, &zei_params.pc_gens, &ar, attrs);
         self.add_operation_issue_asset(
             key_pair,
             token_code,
@@ -318,6 +352,7 @@ impl TransactionBuilder {
                     id: None,
                     record: ba,
                     lien: None,
+                    multisig: None,
                 },
                 owner_memo,
             )],
@@ -341,6 +376,33 @@ impl TransactionBuilder {
         self
     }
 
+    /// Append a credential proof,
+    /// recipients of credential-gated assets must have one
+    pub fn add_credential_proof(&mut self, proof: CredentialProof) -> &mut Self {
+        self.txn.body.credentials.push(proof);
+        self
+    }
+
+    /// Link a credential to `address`, reveal the given attributes,
+    /// and append the generated proof
+    pub fn add_credential(
+        &mut self,
+        user_sk: &CredUserSecretKey,
+        credential: &PlatformCredential,
+        address: &XfrPublicKey,
+        reveal_fields: &[String],
+    ) -> Result<&mut Self> {
+        let proof = CredentialProof::new(
+            &mut ChaChaRng::from_entropy(),
+            user_sk,
+            credential,
+            address,
+            reveal_fields,
+        )
+        .c(d!())?;
+        Ok(self.add_credential_proof(proof))
+    }
+
     /// Add asset creating operation to builder an return modified builder
     pub fn add_operation_create_asset(
         &mut self,
@@ -513,6 +575,29 @@ impl TransactionBuilder {
         Ok(self.add_operation(Operation::UpdateStaker(op)))
     }
 
+    /// Add a operation to replace the tendermint key of a validator,
+    /// `validator` is its current tendermint address
+    pub fn add_operation_rotate_key(
+        &mut self,
+        keypair: &XfrKeyPair,
+        new_vltor_key: &PrivateKey,
+        validator: TendermintAddr,
+    ) -> Result<&mut Self> {
+        let op = RotateKeyOps::new(
+            keypair,
+            new_vltor_key,
+            validator,
+            self.txn.body.no_replay_token,
+        )
+        .c(d!())?;
+
+        if !is_valid_tendermint_addr(&op.get_new_td_addr()) {
+            return Err(eg!("invalid pubkey, invalid address"));
+        }
+
+        Ok(self.add_operation(Operation::RotateKey(op)))
+    }
+
     /// Add a staking operation to add a tendermint node as a validator
     pub fn add_operation_staking(
         &mut self,
@@ -570,6 +655,37 @@ impl TransactionBuilder {
         self.add_operation(Operation::Claim(op))
     }
 
+    /// Add a operation to register a scheduled transfer plan.
+    /// The transfer operation to BLACK_HOLE_PUBKEY_STAKING,
+    /// which escrows the total amount of the plan, should be sent along with.
+    pub fn add_operation_transfer_plan(
+        &mut self,
+        keypair: &XfrKeyPair,
+        plan: TransferPlan,
+    ) -> &mut Self {
+        let op = TransferPlanOps::new(
+            keypair,
+            TransferPlanAction::Register(plan),
+            self.txn.body.no_replay_token,
+        );
+        self.add_operation(Operation::TransferPlan(op))
+    }
+
+    /// Add a operation to cancel a scheduled transfer plan,
+    /// the remaining escrow will be refunded to the owner.
+    pub fn add_operation_cancel_transfer_plan(
+        &mut self,
+        keypair: &XfrKeyPair,
+        id: PlanId,
+    ) -> &mut Self {
+        let op = TransferPlanOps::new(
+            keypair,
+            TransferPlanAction::Cancel(id),
+            self.txn.body.no_replay_token,
+        );
+        self.add_operation(Operation::TransferPlan(op))
+    }
+
     #[allow(missing_docs)]
     pub fn add_operation_fra_distribution(
         &mut self,
@@ -638,6 +754,11 @@ impl TransactionBuilder {
         self
     }
 
+    /// Co-sign the governance/validator updates, see `Transaction::cosign`
+    pub fn cosign(&mut self, kp: &XfrKeyPair) -> Result<usize> {
+        self.txn.cosign(kp).c(d!())
+    }
+
     /// Check and append signature to transaction
     pub fn add_signature(
         &mut self,
@@ -736,6 +857,143 @@ pub(crate) fn build_record_and_get_blinds<R: CryptoRng + RngCore>(
     ))
 }
 
+// the search of `CoinSelection::BranchAndBound` is bounded by this
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// Strategies to select the inputs of a transfer from the candidate utxos
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CoinSelection {
+    /// In the order of the candidates
+    InOrder,
+    /// The largest first, the fewest inputs are used
+    LargestFirst,
+    /// The smallest first, sweep the dust
+    SmallestFirst,
+    /// Search an exact match without change, or fall back to `LargestFirst`
+    BranchAndBound,
+    /// In a random order, the selected utxos are less relevant
+    Random,
+}
+
+// the candidates are the oldest first, as before the strategies were added
+impl Default for CoinSelection {
+    fn default() -> Self {
+        CoinSelection::InOrder
+    }
+}
+
+impl FromStr for CoinSelection {
+    type Err = Box<dyn RucError>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "in-order" => Ok(CoinSelection::InOrder),
+            "largest-first" => Ok(CoinSelection::LargestFirst),
+            "smallest-first" => Ok(CoinSelection::SmallestFirst),
+            "exact" => Ok(CoinSelection::BranchAndBound),
+            "random" => Ok(CoinSelection::Random),
+            _ => Err(eg!(format!(
+                "unknown coin selection '{}', valid ones: in-order, largest-first, smallest-first, exact, random",
+                s
+            ))),
+        }
+    }
+}
+
+impl CoinSelection {
+    /// Select from the amounts of the candidates to cover `target`,
+    /// returns the indexes of the selected ones, `None` if the sum is insufficient.
+    pub fn select(self, amounts: &[u64], target: u64) -> Option<Vec<usize>> {
+        let target = target as u128;
+        if amounts.iter().map(|am| *am as u128).sum::<u128>() < target {
+            return None;
+        }
+
+        let mut idx = (0..amounts.len()).collect::<Vec<_>>();
+        match self {
+            CoinSelection::InOrder => {}
+            CoinSelection::LargestFirst => {
+                idx.sort_by(|a, b| amounts[*b].cmp(&amounts[*a]));
+            }
+            CoinSelection::SmallestFirst => {
+                idx.sort_by_key(|i| amounts[*i]);
+            }
+            CoinSelection::BranchAndBound => {
+                if let Some(exact) = branch_and_bound(amounts, target) {
+                    return Some(exact);
+                }
+                idx.sort_by(|a, b| amounts[*b].cmp(&amounts[*a]));
+            }
+            CoinSelection::Random => {
+                idx.shuffle(&mut rand::thread_rng());
+            }
+        }
+
+        let mut sum = 0;
+        let mut selected = vec![];
+        for i in idx.into_iter() {
+            if sum >= target {
+                break;
+            }
+            sum += amounts[i] as u128;
+            selected.push(i);
+        }
+        Some(selected)
+    }
+}
+
+// a subset of the amounts whose sum is exactly `target`
+fn branch_and_bound(amounts: &[u64], target: u128) -> Option<Vec<usize>> {
+    fn search(
+        amounts: &[u64],
+        idx: &[usize],
+        rest: &[u128],
+        remain: u128,
+        picked: &mut Vec<usize>,
+        tries: &mut usize,
+    ) -> bool {
+        if 0 == remain {
+            return true;
+        }
+        if idx.is_empty() || rest[0] < remain || BNB_MAX_TRIES <= *tries {
+            return false;
+        }
+        *tries += 1;
+
+        let am = amounts[idx[0]] as u128;
+        if am <= remain {
+            picked.push(idx[0]);
+            if search(amounts, &idx[1..], &rest[1..], remain - am, picked, tries) {
+                return true;
+            }
+            picked.pop();
+        }
+        search(amounts, &idx[1..], &rest[1..], remain, picked, tries)
+    }
+
+    if 0 == target {
+        return Some(vec![]);
+    }
+
+    let mut idx = (0..amounts.len())
+        .filter(|i| 0 < amounts[*i] && amounts[*i] as u128 <= target)
+        .collect::<Vec<_>>();
+    idx.sort_by(|a, b| amounts[*b].cmp(&amounts[*a]));
+
+    // rest[k] = the sum of idx[k..]
+    let mut rest = vec![0; idx.len() + 1];
+    for k in (0..idx.len()).rev() {
+        rest[k] = rest[k + 1] + amounts[idx[k]] as u128;
+    }
+
+    let mut picked = vec![];
+    if search(amounts, &idx, &rest, target, &mut picked, &mut 0) {
+        Some(picked)
+    } else {
+        None
+    }
+}
+
 /// TransferOperationBuilder constructs transfer operations using the factory pattern
 /// Inputs and outputs are added iteratively before being signed by all input record owners
 #[derive(Clone, Serialize, Deserialize, Default)]
@@ -750,6 +1008,11 @@ pub struct TransferOperationBuilder {
     output_identity_commitments: Vec<Option<ACCommitment>>,
     transfer: Option<TransferAsset>,
     transfer_type: TransferType,
+    // (idx, rules) of the inputs and outputs belonging to multisig accounts
+    #[serde(default)]
+    multisig_inputs: Vec<(usize, SignatureRules)>,
+    #[serde(default)]
+    multisig_outputs: Vec<(usize, SignatureRules)>,
 }
 
 impl TransferOperationBuilder {
@@ -790,6 +1053,144 @@ impl TransferOperationBuilder {
         Ok(self)
     }
 
+    /// Add an input of the multisig account controlled by `rules`, it must be
+    /// approved by the signers of the rules instead of signed by its owner.
+    pub fn add_multisig_input(
+        &mut self,
+        txo_sid: TxoRef,
+        open_ar: OpenAssetRecord,
+        rules: SignatureRules,
+        amount: u64,
+    ) -> Result<&mut Self> {
+        self.add_traced_multisig_input(txo_sid, open_ar, rules, None, amount)
+            .c(d!())
+    }
+
+    fn add_traced_multisig_input(
+        &mut self,
+        txo_sid: TxoRef,
+        open_ar: OpenAssetRecord,
+        rules: SignatureRules,
+        policies: Option<TracingPolicies>,
+        amount: u64,
+    ) -> Result<&mut Self> {
+        if *open_ar.get_pub_key() != rules.multisig_address() {
+            return Err(eg!("the input does not belong to the multisig account"));
+        }
+        let idx = self.input_records.len();
+        self.add_input(txo_sid, open_ar, policies, None, amount)
+            .c(d!())?;
+        self.multisig_inputs.push((idx, rules));
+        Ok(self)
+    }
+
+    /// Add the inputs selected by `strategy` from the candidates to cover `amount`,
+    /// the candidates must be of the same asset type, the last one may be partially spent.
+    pub fn add_inputs_by(
+        &mut self,
+        strategy: CoinSelection,
+        candidates: Vec<(TxoRef, OpenAssetRecord)>,
+        amount: u64,
+    ) -> Result<&mut Self> {
+        self.add_selected_inputs(strategy, candidates, None, None, amount)
+            .c(d!())
+    }
+
+    /// Same as `add_inputs_by`, but the candidates are of an asset traced by
+    /// `policies`, the tracing memos of the inputs and the change are generated.
+    pub fn add_traced_inputs_by(
+        &mut self,
+        strategy: CoinSelection,
+        candidates: Vec<(TxoRef, OpenAssetRecord)>,
+        policies: &TracingPolicies,
+        amount: u64,
+    ) -> Result<&mut Self> {
+        self.add_selected_inputs(strategy, candidates, None, Some(policies), amount)
+            .c(d!())
+    }
+
+    /// Same as `add_inputs_by`, but the candidates belong to
+    /// the multisig account controlled by `rules`.
+    pub fn add_multisig_inputs_by(
+        &mut self,
+        strategy: CoinSelection,
+        candidates: Vec<(TxoRef, OpenAssetRecord)>,
+        rules: &SignatureRules,
+        amount: u64,
+    ) -> Result<&mut Self> {
+        self.add_selected_inputs(strategy, candidates, Some(rules), None, amount)
+            .c(d!())
+    }
+
+    /// Same as `add_multisig_inputs_by`, but the candidates are of an asset
+    /// traced by `policies`, see `add_traced_inputs_by`.
+    pub fn add_traced_multisig_inputs_by(
+        &mut self,
+        strategy: CoinSelection,
+        candidates: Vec<(TxoRef, OpenAssetRecord)>,
+        rules: &SignatureRules,
+        policies: &TracingPolicies,
+        amount: u64,
+    ) -> Result<&mut Self> {
+        self.add_selected_inputs(
+            strategy,
+            candidates,
+            Some(rules),
+            Some(policies),
+            amount,
+        )
+        .c(d!())
+    }
+
+    fn add_selected_inputs(
+        &mut self,
+        strategy: CoinSelection,
+        candidates: Vec<(TxoRef, OpenAssetRecord)>,
+        rules: Option<&SignatureRules>,
+        policies: Option<&TracingPolicies>,
+        amount: u64,
+    ) -> Result<&mut Self> {
+        if let Some((_, first)) = candidates.first() {
+            if candidates
+                .iter()
+                .any(|(_, oar)| oar.asset_type != first.asset_type)
+            {
+                return Err(eg!("candidates of different asset types"));
+            }
+        }
+
+        let amounts = candidates
+            .iter()
+            .map(|(_, oar)| oar.amount)
+            .collect::<Vec<_>>();
+        let selected = strategy
+            .select(&amounts, amount)
+            .c(d!("insufficient balance"))?;
+
+        let mut candidates = candidates.into_iter().map(Some).collect::<Vec<_>>();
+        let mut remain = amount;
+        for i in selected.into_iter() {
+            let (sid, oar) = candidates[i].take().c(d!())?;
+            let am = min(remain, oar.amount);
+            remain -= am;
+            if let Some(rules) = rules {
+                self.add_traced_multisig_input(
+                    sid,
+                    oar,
+                    rules.clone(),
+                    policies.cloned(),
+                    am,
+                )
+                .c(d!())?;
+            } else {
+                self.add_input(sid, oar, policies.cloned(), None, am)
+                    .c(d!())?;
+            }
+        }
+
+        Ok(self)
+    }
+
     #[allow(missing_docs)]
     pub fn add_output(
         &mut self,
@@ -826,6 +1227,41 @@ impl TransferOperationBuilder {
         Ok(self)
     }
 
+    /// Add a non-confidential output to the multisig account controlled by `rules`,
+    /// it carries tracing memos if `tracing_policies` is set.
+    pub fn add_multisig_output(
+        &mut self,
+        rules: SignatureRules,
+        amount: u64,
+        token_code: AssetTypeCode,
+        tracing_policies: Option<TracingPolicies>,
+    ) -> Result<&mut Self> {
+        rules.check().c(d!())?;
+        let art = AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType;
+        let address = rules.multisig_address();
+        let template = if let Some(policies) = tracing_policies.as_ref() {
+            AssetRecordTemplate::with_asset_tracing(
+                amount,
+                token_code.val,
+                art,
+                address,
+                policies.clone(),
+            )
+        } else {
+            AssetRecordTemplate::with_no_asset_tracing(
+                amount,
+                token_code.val,
+                art,
+                address,
+            )
+        };
+        let idx = self.output_records.len();
+        self.add_output(&template, tracing_policies, None, None)
+            .c(d!())?;
+        self.multisig_outputs.push((idx, rules));
+        Ok(self)
+    }
+
     /// Adds output to the records, and stores the asset amount blinds and type blind in the blinds parameter passed in.
     pub fn add_output_and_store_blinds<R: CryptoRng + RngCore>(
         &mut self,
@@ -909,6 +1345,8 @@ impl TransferOperationBuilder {
 
         let spend_total: u64 = self.spend_amounts.iter().sum();
         let mut partially_consumed_inputs = Vec::new();
+        // the change of a multisig input goes back to the multisig account
+        let mut multisig_changes = Vec::new();
 
         for (idx, ((spend_amount, ar), policies)) in self
             .spend_amounts
@@ -938,6 +1376,14 @@ impl TransferOperationBuilder {
                         &ar_template,
                     )
                     .c(d!())?;
+                    if let Some((_, rules)) =
+                        self.multisig_inputs.iter().find(|(i, _)| *i == idx)
+                    {
+                        multisig_changes.push((
+                            self.output_records.len() + partially_consumed_inputs.len(),
+                            rules.clone(),
+                        ));
+                    }
                     partially_consumed_inputs.push(ar);
                     self.outputs_tracing_policies.push(policies.clone());
                     self.output_identity_commitments.push(None);
@@ -957,6 +1403,7 @@ impl TransferOperationBuilder {
             return Err(eg!(format!("{} != {}", spend_total, output_total)));
         }
         self.output_records.append(&mut partially_consumed_inputs);
+        self.multisig_outputs.append(&mut multisig_changes);
 
         // for: repeated/idempotent balance
         amt_cache.into_iter().for_each(|(idx, am)| {
@@ -980,7 +1427,7 @@ impl TransferOperationBuilder {
             self.outputs_tracing_policies.clone(),
             vec![None; num_outputs],
         );
-        let body = TransferAssetBody::new(
+        let mut body = TransferAssetBody::new(
             &mut prng,
             self.input_sids.clone(),
             &self.input_records,
@@ -990,6 +1437,10 @@ impl TransferOperationBuilder {
             transfer_type,
         )
         .c(d!())?;
+        body.multisig_inputs = self.multisig_inputs.clone();
+        for (idx, rules) in self.multisig_outputs.iter() {
+            body.outputs.get_mut(*idx).c(d!())?.multisig = Some(rules.clone());
+        }
         self.transfer = Some(TransferAsset::new(body).c(d!())?);
         Ok(self)
     }
@@ -1063,8 +1514,14 @@ impl TransferOperationBuilder {
             sig_keys.insert(sig.address.key.zei_to_bytes());
         }
 
-        for record in &trn.body.transfer.inputs {
-            if !sig_keys.contains(&record.public_key.zei_to_bytes()) {
+        for (idx, record) in trn.body.transfer.inputs.iter().enumerate() {
+            if let Some((_, rules)) =
+                trn.body.multisig_inputs.iter().find(|(i, _)| *i == idx)
+            {
+                rules
+                    .check_signature_set(&sig_keys)
+                    .c(d!("Not enough multisig approvals"))?;
+            } else if !sig_keys.contains(&record.public_key.zei_to_bytes()) {
                 return Err(eg!(("Not all signatures present")));
             }
         }
@@ -1076,14 +1533,16 @@ impl TransferOperationBuilder {
 #[allow(missing_docs)]
 mod tests {
     use super::*;
-    use ledger::data_model::{TxnEffect, TxoRef};
+    use ledger::data_model::{TxnEffect, TxoRef, TxoSID};
     use ledger::store::{utils::fra_gen_initial_tx, LedgerState};
     use rand_chacha::ChaChaRng;
     use rand_core::SeedableRng;
     use zei::setup::PublicParams;
     use zei::xfr::asset_record::AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType;
     use zei::xfr::asset_record::{build_blind_asset_record, open_blind_asset_record};
+    use zei::xfr::lib::trace_assets;
     use zei::xfr::sig::XfrKeyPair;
+    use zei::xfr::structs::AssetTracerKeyPair;
 
     // Defines an asset type
     #[derive(Clone, Debug, Eq, PartialEq)]
@@ -1429,4 +1888,208 @@ mod tests {
         let mut block = ledger.start_block().unwrap();
         assert!(ledger.apply_transaction(&mut block, effect, false).is_err());
     }
+
+    #[test]
+    fn test_traced_inputs_by() {
+        let mut prng = ChaChaRng::from_entropy();
+        let params = PublicParams::default();
+        let code = AssetTypeCode::gen_random();
+        let alice = XfrKeyPair::generate(&mut prng);
+        let bob = XfrKeyPair::generate(&mut prng);
+        let tracer = AssetTracerKeyPair::generate(&mut prng);
+        let policies = TracingPolicies::from_policy(TracingPolicy {
+            enc_keys: tracer.enc_key.clone(),
+            asset_tracing: true,
+            identity_tracing: None,
+        });
+
+        let ar = AssetRecordTemplate::with_no_asset_tracing(
+            100,
+            code.val,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            alice.get_pk(),
+        );
+        let (ba, _, memo) =
+            build_blind_asset_record(&mut prng, &params.pc_gens, &ar, vec![]);
+        let oar = pnk!(open_blind_asset_record(&ba, &memo, &alice));
+        let output = AssetRecordTemplate::with_asset_tracing(
+            30,
+            code.val,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            bob.get_pk(),
+            policies.clone(),
+        );
+
+        let op = pnk!(TransferOperationBuilder::new()
+            .add_traced_inputs_by(
+                CoinSelection::InOrder,
+                vec![(TxoRef::Relative(0), oar)],
+                &policies,
+                30,
+            )
+            .and_then(|b| b.add_output(&output, Some(policies.clone()), None, None))
+            .and_then(|b| b.balance())
+            .and_then(|b| b.create(TransferType::Standard))
+            .and_then(|b| b.sign(&alice))
+            .and_then(|b| b.transaction()));
+        let xfr = match op {
+            Operation::TransferAsset(xfr) => xfr,
+            _ => panic!("not a transfer"),
+        };
+
+        // the input, the output and the change are all traced
+        let traced = pnk!(trace_assets(&xfr.body.transfer, &tracer))
+            .into_iter()
+            .map(|(am, ty, _, pk)| (am, ty, pk))
+            .collect::<Vec<_>>();
+        assert_eq!(3, traced.len());
+        assert!(traced.contains(&(100, code.val, alice.get_pk())));
+        assert!(traced.contains(&(30, code.val, bob.get_pk())));
+        assert!(traced.contains(&(70, code.val, alice.get_pk())));
+    }
+
+    #[test]
+    fn test_traced_issue_asset() {
+        let mut prng = ChaChaRng::from_entropy();
+        let params = PublicParams::default();
+        let code = AssetTypeCode::gen_random();
+        let alice = XfrKeyPair::generate(&mut prng);
+        let tracer = AssetTracerKeyPair::generate(&mut prng);
+        let policies = TracingPolicies::from_policy(TracingPolicy {
+            enc_keys: tracer.enc_key.clone(),
+            asset_tracing: true,
+            identity_tracing: None,
+        });
+
+        let mut builder = TransactionBuilder::from_seq_id(0);
+        pnk!(builder.add_traced_issue_asset(
+            &alice,
+            &code,
+            0,
+            100,
+            NonConfidentialAmount_NonConfidentialAssetType,
+            &policies,
+            &params,
+        ));
+        let (output, memo) = match &builder.transaction().body.operations[0] {
+            Operation::IssueAsset(iss) => iss.body.records[0].clone(),
+            _ => panic!("not an issuance"),
+        };
+
+        // the issued record is still opened by its owner
+        let oar = pnk!(open_blind_asset_record(&output.record, &memo, &alice));
+        assert_eq!(100, oar.amount);
+        assert_eq!(code.val, oar.asset_type);
+    }
+
+    #[test]
+    fn test_add_inputs_by() {
+        let mut prng = ChaChaRng::from_entropy();
+        let params = PublicParams::default();
+        let alice = XfrKeyPair::generate(&mut prng);
+        let code = AssetTypeCode::gen_random();
+        let mut open = |am: u64, ty: AssetTypeCode| {
+            let ar = AssetRecordTemplate::with_no_asset_tracing(
+                am,
+                ty.val,
+                NonConfidentialAmount_NonConfidentialAssetType,
+                alice.get_pk(),
+            );
+            let (ba, _, memo) =
+                build_blind_asset_record(&mut prng, &params.pc_gens, &ar, vec![]);
+            pnk!(open_blind_asset_record(&ba, &memo, &alice))
+        };
+        let candidates = [5, 1, 8]
+            .iter()
+            .enumerate()
+            .map(|(i, am)| (TxoRef::Absolute(TxoSID(i as u64)), open(*am, code)))
+            .collect::<Vec<_>>();
+        let spent = |b: &TransferOperationBuilder| {
+            b.input_sids
+                .iter()
+                .zip(b.spend_amounts.iter())
+                .map(|(sid, am)| match sid {
+                    TxoRef::Absolute(sid) => (sid.0, *am),
+                    TxoRef::Relative(_) => panic!("relative input"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the last selected input is spent partially
+        let mut b = TransferOperationBuilder::new();
+        pnk!(b.add_inputs_by(CoinSelection::SmallestFirst, candidates.clone(), 4));
+        assert_eq!(vec![(1, 1), (0, 3)], spent(&b));
+
+        let mut b = TransferOperationBuilder::new();
+        pnk!(b.add_inputs_by(CoinSelection::LargestFirst, candidates.clone(), 6));
+        assert_eq!(vec![(2, 6)], spent(&b));
+
+        // the default one, in the order of the candidates
+        let mut b = TransferOperationBuilder::new();
+        pnk!(b.add_inputs_by(CoinSelection::default(), candidates.clone(), 6));
+        assert_eq!(vec![(0, 5), (1, 1)], spent(&b));
+
+        let mut b = TransferOperationBuilder::new();
+        assert!(b
+            .add_inputs_by(CoinSelection::InOrder, candidates.clone(), 15)
+            .is_err());
+        assert!(b.input_sids.is_empty());
+
+        // the candidates must be of the same asset type
+        let mut mixed = candidates;
+        mixed.push((
+            TxoRef::Absolute(TxoSID(3)),
+            open(10, AssetTypeCode::gen_random()),
+        ));
+        assert!(TransferOperationBuilder::new()
+            .add_inputs_by(CoinSelection::InOrder, mixed, 1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_coin_selection() {
+        let amounts = [5, 1, 8, 3, 0, 2];
+        let sum = |selected: &[usize]| selected.iter().map(|i| amounts[*i]).sum::<u64>();
+
+        assert_eq!(Some(vec![0, 1]), CoinSelection::InOrder.select(&amounts, 6));
+        assert_eq!(
+            Some(vec![2]),
+            CoinSelection::LargestFirst.select(&amounts, 6)
+        );
+        assert_eq!(
+            Some(vec![4, 1, 5, 3]),
+            CoinSelection::SmallestFirst.select(&amounts, 6)
+        );
+
+        // an exact match, no change is needed
+        let exact = pnk!(CoinSelection::BranchAndBound.select(&amounts, 10));
+        assert_eq!(10, sum(&exact));
+        let exact = pnk!(CoinSelection::BranchAndBound.select(&amounts, 19));
+        assert_eq!(19, sum(&exact));
+        // no exact match, fall back to the largest first
+        assert_eq!(
+            Some(vec![1, 2]),
+            CoinSelection::BranchAndBound.select(&[7, 10, 10], 15)
+        );
+
+        for _ in 0..10 {
+            let selected = pnk!(CoinSelection::Random.select(&amounts, 11));
+            assert!(11 <= sum(&selected));
+        }
+
+        // the sum is 19
+        for s in [
+            CoinSelection::InOrder,
+            CoinSelection::LargestFirst,
+            CoinSelection::SmallestFirst,
+            CoinSelection::BranchAndBound,
+            CoinSelection::Random,
+        ]
+        .iter()
+        {
+            assert_eq!(19, sum(&pnk!(s.select(&amounts, 19))));
+            assert_eq!(None, s.select(&amounts, 20));
+            assert_eq!(Some(vec![]), s.select(&amounts, 0));
+        }
+    }
 }
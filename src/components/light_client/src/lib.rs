@@ -0,0 +1,323 @@
+//!
+//! # Light Client Of The Findora Ledger
+//!
+//! Tracks a contiguous chain of `StateCommitmentData`(the block header of findora ledger),
+//! and verifies the proofs served by a query server against it,
+//! so that a client does not need to trust the node it talks to.
+//!
+//! The verification part does no I/O and only needs `alloc`,
+//! the syncing part(http and json) is behind the default `std` feature,
+//! build with `--no-default-features` for wasm or no-std targets,
+//! eg. `make light_client_wasm`.
+//!
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![deny(warnings)]
+#![deny(missing_docs)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod sync;
+
+mod test;
+
+use cryptohash::sha256;
+use globutils::HashOf;
+use ledger::data_model::{
+    AuthenticatedBlock, AuthenticatedTransaction, AuthenticatedUtxo,
+    AuthenticatedUtxoRangeStatus, AuthenticatedUtxoStatus, StateCommitmentData,
+    UtxoStatus,
+};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::VecDeque, format, vec::Vec};
+// not in the prelude of no-std, but used by the expansions of `eg!` and `d!`
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use alloc::{boxed::Box, string::String};
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+
+/// The hash of a `StateCommitmentData`, aka the state commitment.
+pub type Commitment = HashOf<Option<StateCommitmentData>>;
+
+/// Headers kept in memory by default.
+pub const DEFAULT_HEADER_LIMIT: usize = 8192;
+
+/// A light client of the findora ledger.
+///
+/// Headers are appended in order of block height(the `block_commit_count` of ledger),
+/// each of them must point to its predecessor by `previous_state_commitment`.
+/// Since the hash chain authenticates every header before the highest trusted one,
+/// a header can be used for verification iff its height <= `trusted_height`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LightClient {
+    // (height, commitment, header), contiguous by height
+    headers: VecDeque<(u64, Commitment, StateCommitmentData)>,
+    // the commitment of the header just before `headers`
+    anchor: (u64, Commitment),
+    // the highest height which has been authenticated
+    trusted_height: u64,
+    // how many headers to keep in memory
+    header_limit: usize,
+}
+
+impl Default for LightClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LightClient {
+    /// Start from the genesis of a ledger, the first header
+    /// must point to an empty state commitment.
+    #[inline(always)]
+    pub fn new() -> Self {
+        LightClient {
+            headers: VecDeque::new(),
+            anchor: (0, HashOf::new(&None)),
+            trusted_height: 0,
+            header_limit: DEFAULT_HEADER_LIMIT,
+        }
+    }
+
+    /// Start from a trusted checkpoint, such as a state commitment
+    /// obtained from a tendermint-signed app hash out of band.
+    ///
+    /// The first header appended must be the one at `height + 1`.
+    #[inline(always)]
+    pub fn from_checkpoint(height: u64, commitment: Commitment) -> Self {
+        LightClient {
+            headers: VecDeque::new(),
+            anchor: (height, commitment),
+            trusted_height: height,
+            header_limit: DEFAULT_HEADER_LIMIT,
+        }
+    }
+
+    /// Limit the count of headers kept in memory,
+    /// the oldest headers will be dropped first.
+    #[inline(always)]
+    pub fn set_header_limit(&mut self, limit: usize) {
+        self.header_limit = limit.max(1);
+        self.prune();
+    }
+
+    /// The height and commitment of the latest known header.
+    #[inline(always)]
+    pub fn tip(&self) -> (u64, &Commitment) {
+        self.headers
+            .back()
+            .map(|(h, c, _)| (*h, c))
+            .unwrap_or((self.anchor.0, &self.anchor.1))
+    }
+
+    /// The highest height which has been authenticated.
+    #[inline(always)]
+    pub fn trusted_height(&self) -> u64 {
+        self.trusted_height
+    }
+
+    /// Get a known header by its height.
+    pub fn get_header(&self, height: u64) -> Option<&StateCommitmentData> {
+        self.locate(height).map(|idx| &self.headers[idx].2)
+    }
+
+    /// Get a known commitment by its height.
+    pub fn get_commitment(&self, height: u64) -> Option<&Commitment> {
+        if height == self.anchor.0 {
+            return Some(&self.anchor.1);
+        }
+        self.locate(height).map(|idx| &self.headers[idx].1)
+    }
+
+    /// Append the header of the next height,
+    /// it must be linked to the current tip.
+    pub fn append_header(&mut self, header: StateCommitmentData) -> Result<u64> {
+        let (tip_height, tip_commitment) = self.tip();
+        if header.previous_state_commitment != *tip_commitment {
+            return Err(eg!(format!(
+                "header {} is not linked to the current tip",
+                tip_height + 1
+            )));
+        }
+
+        let height = tip_height + 1;
+        let commitment = header.compute_commitment();
+        self.headers.push_back((height, commitment, header));
+        self.prune();
+
+        Ok(height)
+    }
+
+    /// Mark all headers up to `height` as authenticated,
+    /// `commitment` should come from a trusted source.
+    pub fn trust_commitment(
+        &mut self,
+        height: u64,
+        commitment: &Commitment,
+    ) -> Result<()> {
+        let known = self.get_commitment(height).c(d!("unknown height"))?;
+        if known != commitment {
+            return Err(eg!("commitment mismatch, the node is not trustworthy"));
+        }
+        self.trusted_height = self.trusted_height.max(height);
+        Ok(())
+    }
+
+    /// Mark all headers up to `ledger_height` as authenticated
+    /// by a tendermint-signed app hash of the same ledger state.
+    ///
+    /// NOTE: `ledger_height` is the `block_commit_count` of the ledger, not the
+    /// height of the tendermint block carrying the app hash, they differ once
+    /// there are empty blocks, which do not advance the ledger.
+    /// Use `find_app_hash` to map a tendermint app hash to the ledger height.
+    ///
+    /// The app hash equals to the state commitment,
+    /// or `sha256(commitment || chain_state_hash)` if the evm state is enabled.
+    pub fn trust_app_hash(
+        &mut self,
+        ledger_height: u64,
+        app_hash: &[u8],
+        chain_state_hash: Option<&[u8]>,
+    ) -> Result<()> {
+        let commitment = self
+            .get_commitment(ledger_height)
+            .c(d!("unknown height"))?
+            .clone();
+        if !app_hash_matches(&commitment, app_hash, chain_state_hash) {
+            return Err(eg!("app hash mismatch, the node is not trustworthy"));
+        }
+        self.trusted_height = self.trusted_height.max(ledger_height);
+        Ok(())
+    }
+
+    /// The ledger height of the known state matching an app hash,
+    /// the newest first, so that the app hash of any tendermint block,
+    /// empty or not, can be mapped to a ledger height for `trust_app_hash`.
+    pub fn find_app_hash(
+        &self,
+        app_hash: &[u8],
+        chain_state_hash: Option<&[u8]>,
+    ) -> Option<u64> {
+        self.headers
+            .iter()
+            .rev()
+            .map(|(h, c, _)| (*h, c))
+            .chain(Some((self.anchor.0, &self.anchor.1)))
+            .find(|(_, c)| app_hash_matches(c, app_hash, chain_state_hash))
+            .map(|(h, _)| h)
+    }
+
+    /// Verify that a utxo exists and is unspent, return the height of the proof.
+    pub fn verify_utxo(&self, proof: &AuthenticatedUtxo) -> Result<u64> {
+        let commitment = proof.state_commitment_data.compute_commitment();
+        let height = self.trusted_height_of(&commitment).c(d!())?;
+        if proof.is_valid(commitment) {
+            Ok(height)
+        } else {
+            Err(eg!("invalid utxo proof"))
+        }
+    }
+
+    /// Verify the status of a txo, including non-inclusion(spent or nonexistent),
+    /// return the proven status.
+    pub fn verify_utxo_status(
+        &self,
+        proof: &AuthenticatedUtxoStatus,
+    ) -> Result<UtxoStatus> {
+        self.trusted_height_of(&proof.state_commitment).c(d!())?;
+        if proof.is_valid(proof.state_commitment.clone()) {
+            Ok(proof.status.clone())
+        } else {
+            Err(eg!("invalid utxo status proof"))
+        }
+    }
+
+    /// Verify the statuses of a range of txos, return them in order.
+    pub fn verify_utxo_range_status(
+        &self,
+        proof: &AuthenticatedUtxoRangeStatus,
+    ) -> Result<Vec<UtxoStatus>> {
+        self.trusted_height_of(&proof.state_commitment).c(d!())?;
+        proof.verify(proof.state_commitment.clone()).c(d!())
+    }
+
+    /// Verify that a transaction has been included in the ledger,
+    /// return the height of the proof.
+    pub fn verify_txn(&self, proof: &AuthenticatedTransaction) -> Result<u64> {
+        let height = self.trusted_height_of(&proof.state_commitment).c(d!())?;
+        if proof.is_valid(proof.state_commitment.clone()) {
+            Ok(height)
+        } else {
+            Err(eg!("invalid transaction proof"))
+        }
+    }
+
+    /// Verify that a block has been included in the ledger,
+    /// return the height of the proof.
+    pub fn verify_block(&self, proof: &AuthenticatedBlock) -> Result<u64> {
+        let height = self.trusted_height_of(&proof.state_commitment).c(d!())?;
+        if proof.is_valid(proof.state_commitment.clone()) {
+            Ok(height)
+        } else {
+            Err(eg!("invalid block proof"))
+        }
+    }
+
+    // Search the newest first, proofs are usually generated at the tip.
+    fn trusted_height_of(&self, commitment: &Commitment) -> Result<u64> {
+        let height = self
+            .headers
+            .iter()
+            .rev()
+            .find(|(_, c, _)| c == commitment)
+            .map(|(h, _, _)| *h)
+            .c(d!("unknown state commitment, sync headers first"))?;
+
+        if height > self.trusted_height {
+            return Err(eg!(format!(
+                "header {} has not been authenticated, trusted height: {}",
+                height, self.trusted_height
+            )));
+        }
+
+        Ok(height)
+    }
+
+    fn locate(&self, height: u64) -> Option<usize> {
+        let first = self.headers.front()?.0;
+        height
+            .checked_sub(first)
+            .map(|idx| idx as usize)
+            .filter(|idx| *idx < self.headers.len())
+    }
+
+    fn prune(&mut self) {
+        while self.headers.len() > self.header_limit {
+            if let Some((h, c, _)) = self.headers.pop_front() {
+                self.anchor = (h, c);
+            }
+        }
+    }
+}
+
+/// Check whether an app hash(from a tendermint block header)
+/// matches the given state commitment of the ledger.
+pub fn app_hash_matches(
+    commitment: &Commitment,
+    app_hash: &[u8],
+    chain_state_hash: Option<&[u8]>,
+) -> bool {
+    if let Some(chain_state_hash) = chain_state_hash {
+        let mut data = commitment.as_ref().to_vec();
+        data.extend_from_slice(chain_state_hash);
+        sha256::hash(&data).0[..] == *app_hash
+    } else {
+        commitment.as_ref() == app_hash
+    }
+}
@@ -0,0 +1,128 @@
+//!
+//! # Sync Headers From A Query Server
+//!
+//! Nothing fetched here is trusted,
+//! everything goes through the verification of `LightClient`.
+//!
+
+use crate::{Commitment, LightClient};
+use ledger::data_model::{
+    AuthenticatedTransaction, AuthenticatedUtxo, AuthenticatedUtxoRangeStatus,
+    AuthenticatedUtxoStatus, StateCommitmentData, TxnSID, TxoSID, UtxoStatus,
+};
+use ruc::*;
+use serde::de::DeserializeOwned;
+
+/// Headers fetched in one round of `sync`
+pub const MAX_HEADERS_PER_SYNC: u64 = 1024;
+
+/// Syncs a `LightClient` from the ledger api of a query server,
+/// eg. `http://127.0.0.1:8668`.
+pub struct Syncer {
+    ledger_api: String,
+}
+
+impl Syncer {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(ledger_api: &str) -> Self {
+        Syncer {
+            ledger_api: ledger_api.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// The latest (commitment, height) claimed by the node.
+    pub fn get_global_state(&self) -> Result<(Commitment, u64)> {
+        self.get::<(Commitment, u64, String)>("global_state")
+            .c(d!())
+            .map(|(c, h, _)| (c, h))
+    }
+
+    /// Fetch the header of the given height.
+    pub fn get_header(&self, height: u64) -> Result<StateCommitmentData> {
+        self.get(&format!("state_commitment_data/{}", height))
+            .c(d!())
+    }
+
+    /// Append headers to the light client until it reaches the tip of the node,
+    /// at most `MAX_HEADERS_PER_SYNC` headers per call, return the new tip height.
+    ///
+    /// NOTE: the new headers are not trusted until they are
+    /// confirmed by `LightClient::trust_app_hash`, the app hash of a tendermint
+    /// block can be mapped to a ledger height by `LightClient::find_app_hash`.
+    pub fn sync(&self, lc: &mut LightClient) -> Result<u64> {
+        let (_, remote_height) = self.get_global_state().c(d!())?;
+        let (local_height, _) = lc.tip();
+        let target = remote_height.min(local_height + MAX_HEADERS_PER_SYNC);
+
+        for h in (local_height + 1)..=target {
+            let header = self.get_header(h).c(d!())?;
+            lc.append_header(header).c(d!())?;
+        }
+
+        Ok(lc.tip().0)
+    }
+
+    /// Fetch and verify an unspent utxo.
+    pub fn fetch_utxo(
+        &self,
+        lc: &LightClient,
+        sid: TxoSID,
+    ) -> Result<AuthenticatedUtxo> {
+        let proof = self
+            .get::<AuthenticatedUtxo>(&format!("utxo_sid/{}", sid.0))
+            .c(d!())?;
+        lc.verify_utxo(&proof).c(d!()).map(|_| proof)
+    }
+
+    /// Fetch and verify the status of a txo.
+    pub fn fetch_utxo_status(
+        &self,
+        lc: &LightClient,
+        sid: TxoSID,
+    ) -> Result<UtxoStatus> {
+        self.get::<AuthenticatedUtxoStatus>(&format!("utxo_sid_status/{}", sid.0))
+            .c(d!())
+            .and_then(|proof| lc.verify_utxo_status(&proof).c(d!()))
+    }
+
+    /// Fetch and verify the statuses of txos in `[start, start + count)`.
+    pub fn fetch_utxo_range_status(
+        &self,
+        lc: &LightClient,
+        start: TxoSID,
+        count: u64,
+    ) -> Result<Vec<UtxoStatus>> {
+        self.get::<AuthenticatedUtxoRangeStatus>(&format!(
+            "utxo_sid_range_status?start={}&count={}",
+            start.0, count
+        ))
+        .c(d!())
+        .and_then(|proof| lc.verify_utxo_range_status(&proof).c(d!()))
+    }
+
+    /// Fetch and verify a transaction.
+    pub fn fetch_txn(
+        &self,
+        lc: &LightClient,
+        sid: TxnSID,
+    ) -> Result<AuthenticatedTransaction> {
+        let proof = self
+            .get::<AuthenticatedTransaction>(&format!("txn_sid/{}", sid.0))
+            .c(d!())?;
+        lc.verify_txn(&proof).c(d!()).map(|_| proof)
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let url = format!("{}/{}", self.ledger_api, path);
+
+        attohttpc::get(&url)
+            .send()
+            .c(d!())?
+            .error_for_status()
+            .c(d!())?
+            .bytes()
+            .c(d!())
+            .and_then(|b| serde_json::from_slice::<T>(&b).c(d!()))
+    }
+}
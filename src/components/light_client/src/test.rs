@@ -0,0 +1,141 @@
+#![cfg(test)]
+#![allow(missing_docs)]
+
+use super::*;
+use ledger::{
+    data_model::{AssetRules, AssetTypeCode, TxoSID},
+    store::{helpers::*, LedgerState},
+};
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use zei::{setup::PublicParams, xfr::asset_record::AssetRecordType};
+
+// define an asset and issue it, two blocks in total
+fn gen_ledger() -> (LedgerState, TxoSID) {
+    let mut ledger = LedgerState::tmp_ledger();
+    let params = PublicParams::default();
+    let mut prng = ChaChaRng::from_entropy();
+    let kp = build_keys(&mut prng);
+    let code = AssetTypeCode::gen_random();
+
+    let tx = create_definition_transaction(
+        &code,
+        &kp,
+        AssetRules::default(),
+        None,
+        ledger.get_block_commit_count(),
+    )
+    .unwrap();
+    apply_transaction(&mut ledger, tx);
+
+    let tx = create_issuance_txn(
+        &mut ledger,
+        &params,
+        &code,
+        100,
+        0,
+        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+        &kp,
+    );
+    let (_, txos) = apply_transaction(&mut ledger, tx);
+
+    (ledger, txos[0])
+}
+
+fn sync_all(ledger: &LedgerState, lc: &mut LightClient) {
+    let (_, height) = ledger.get_state_commitment();
+    for h in (lc.tip().0 + 1)..=height {
+        let header = ledger.get_state_commitment_data_at_block_height(h).unwrap();
+        lc.append_header(header).unwrap();
+    }
+}
+
+#[test]
+fn test_header_chain() {
+    let (ledger, _) = gen_ledger();
+    let (commitment, height) = ledger.get_state_commitment();
+
+    let mut lc = LightClient::new();
+    sync_all(&ledger, &mut lc);
+    assert_eq!(lc.tip(), (height, &commitment));
+    assert_eq!(0, lc.trusted_height());
+
+    // a header can not be appended twice
+    let header = ledger.get_state_commitment_data_at_block_height(1).unwrap();
+    assert!(lc.append_header(header).is_err());
+
+    assert!(lc.trust_app_hash(height, &[0; 32], None).is_err());
+    // the app hash of an older ledger height
+    let old = lc.get_commitment(height - 1).unwrap().clone();
+    assert!(lc.trust_app_hash(height, old.as_ref(), None).is_err());
+
+    // map the app hashes to the ledger heights
+    assert_eq!(Some(height - 1), lc.find_app_hash(old.as_ref(), None));
+    assert_eq!(Some(height), lc.find_app_hash(commitment.as_ref(), None));
+    assert_eq!(None, lc.find_app_hash(&[0; 32], None));
+
+    pnk!(lc.trust_app_hash(height, commitment.as_ref(), None));
+    assert_eq!(height, lc.trusted_height());
+
+    // app hash combined with the chain state of evm
+    let mut data = commitment.as_ref().to_vec();
+    data.extend_from_slice(&[1; 32]);
+    let app_hash = sha256::hash(&data);
+    assert!(app_hash_matches(
+        &commitment,
+        &app_hash.0[..],
+        Some(&[1; 32])
+    ));
+    assert!(!app_hash_matches(&commitment, &app_hash.0[..], None));
+}
+
+#[test]
+fn test_checkpoint_and_pruning() {
+    let (ledger, _) = gen_ledger();
+    let (commitment, height) = ledger.get_state_commitment();
+    let first = ledger.get_state_commitment_at_block_height(1).unwrap();
+
+    let mut lc = LightClient::from_checkpoint(1, first);
+    sync_all(&ledger, &mut lc);
+    assert_eq!(lc.tip(), (height, &commitment));
+    assert_eq!(1, lc.trusted_height());
+
+    lc.set_header_limit(1);
+    assert!(lc.get_header(height - 1).is_none());
+    assert!(lc.get_header(height).is_some());
+    assert!(lc.get_commitment(height - 1).is_some());
+}
+
+#[test]
+fn test_verify_proofs() {
+    let (ledger, sid) = gen_ledger();
+    let (commitment, height) = ledger.get_state_commitment();
+
+    let mut lc = LightClient::new();
+    sync_all(&ledger, &mut lc);
+
+    let utxo = ledger.get_utxo(sid).unwrap();
+    let status = ledger.get_utxo_status(TxoSID(sid.0 + 1));
+
+    // not authenticated yet
+    assert!(lc.verify_utxo(&utxo).is_err());
+
+    pnk!(lc.trust_commitment(height, &commitment));
+    assert_eq!(height, pnk!(lc.verify_utxo(&utxo)));
+    assert_eq!(
+        UtxoStatus::Nonexistent,
+        pnk!(lc.verify_utxo_status(&status))
+    );
+    assert_eq!(height, pnk!(lc.verify_txn(&utxo.authenticated_txn)));
+
+    let statuses =
+        pnk!(lc.verify_utxo_range_status(&pnk!(ledger.get_utxo_range_status(sid, 2))));
+    assert_eq!(vec![UtxoStatus::Unspent, UtxoStatus::Nonexistent], statuses);
+
+    // a forged status
+    let mut forged = ledger.get_utxo_status(sid);
+    forged.status = UtxoStatus::Spent;
+    assert!(lc.verify_utxo_status(&forged).is_err());
+    forged.utxo_map_bytes = None;
+    assert!(lc.verify_utxo_status(&forged).is_err());
+}
@@ -44,8 +44,9 @@ use fp_utils::ecdsa::SecpPair;
 use globutils::{wallet, HashOf};
 use ledger::{
     data_model::{
-        AssetTypeCode, AuthenticatedTransaction, Operation, TransferType, TxOutput,
-        ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY, BLACK_HOLE_PUBKEY_STAKING, TX_FEE_MIN,
+        AssetTypeCode, AuthenticatedTransaction, AuthenticatedUtxoRangeStatus,
+        Operation, TransferType, TxOutput, ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY,
+        BLACK_HOLE_PUBKEY_STAKING, TX_FEE_MIN,
     },
     staking::{
         gen_random_keypair, td_addr_to_bytes, PartialUnDelegation, TendermintAddr,
@@ -131,6 +132,40 @@ pub fn verify_authenticated_txn(
     Ok(authenticated_txn.is_valid(state_commitment))
 }
 
+#[wasm_bindgen]
+/// Given a serialized state commitment and a proof of the statuses of a range of txos,
+/// returns the status of every txo in the range ("Spent", "Unspent" or "Nonexistent").
+/// @param {string} state_commitment - String representing the state commitment.
+/// @param {string} range_status - String representing the proof,
+/// which is returned by the `utxo_sid_range_status` route of the ledger.
+/// @throws Will throw an error if either of the inputs fails to deserialize,
+/// or the proof is invalid.
+pub fn verify_utxo_range_status(
+    state_commitment: String,
+    range_status: String,
+) -> Result<JsValue, JsValue> {
+    let range_status =
+        serde_json::from_str::<AuthenticatedUtxoRangeStatus>(&range_status)
+            .c(d!())
+            .map_err(|e| {
+                JsValue::from_str(&format!("Could not deserialize proof: {}", e))
+            })?;
+    let state_commitment = serde_json::from_str::<HashOf<_>>(&state_commitment)
+        .c(d!())
+        .map_err(|e| {
+            JsValue::from_str(&format!("Could not deserialize state commitment: {}", e))
+        })?;
+    range_status
+        .verify(state_commitment)
+        .c(d!())
+        .map_err(error_to_jsvalue)
+        .and_then(|statuses| {
+            JsValue::from_serde(&statuses)
+                .c(d!())
+                .map_err(error_to_jsvalue)
+        })
+}
+
 #[wasm_bindgen]
 /// ...
 pub fn get_null_pk() -> XfrPublicKey {
@@ -260,6 +295,7 @@ impl TransactionBuilder {
                             id: None,
                             record: new.0,
                             lien: None,
+                            multisig: None,
                         },
                     }
                     .to_json()
@@ -516,6 +552,34 @@ impl TransactionBuilder {
         Ok(self)
     }
 
+    /// Links a credential to a recipient address and attaches the proof to the transaction,
+    /// which is required when transferring credential-gated assets to the address.
+    /// @param {CredUserSecretKey} user_secret_key - Secret key of the credential user.
+    /// @param {Credential} credential - Credential of the recipient.
+    /// @param {XfrPublicKey} address - Address of the recipient.
+    /// @param {JsValue} reveal_fields - Array of strings representing attribute fields to reveal.
+    /// @throws Will throw an error if the proof cannot be generated
+    /// or `reveal_fields` fails to deserialize.
+    pub fn add_credential(
+        mut self,
+        user_secret_key: &CredUserSecretKey,
+        credential: &Credential,
+        address: &XfrPublicKey,
+        reveal_fields: JsValue,
+    ) -> Result<TransactionBuilder, JsValue> {
+        let reveal_fields: Vec<String> = reveal_fields.into_serde().c(d!()).map_err(|e| JsValue::from(&format!("Could not deserialize reveal fields. Please ensure that reveal fields are of the form [String]: {}", e)))?;
+        self.get_builder_mut()
+            .add_credential(
+                user_secret_key,
+                credential.get_cred_ref(),
+                address,
+                &reveal_fields,
+            )
+            .c(d!())
+            .map_err(error_to_jsvalue)?;
+        Ok(self)
+    }
+
     #[allow(missing_docs)]
     pub fn sign(mut self, kp: &XfrKeyPair) -> Result<TransactionBuilder, JsValue> {
         self.get_builder_mut().sign(kp);
@@ -1220,11 +1284,6 @@ pub fn trace_assets(
 // Author: Chao Ma, github.com/chaosma. //
 //////////////////////////////////////////
 
-use aes_gcm::aead::{generic_array::GenericArray, Aead, NewAead};
-use aes_gcm::Aes256Gcm;
-use rand::{thread_rng, Rng};
-use ring::pbkdf2;
-use std::num::NonZeroU32;
 use std::str;
 
 #[wasm_bindgen]
@@ -1255,70 +1314,23 @@ pub fn base64_to_bech32(pk: &str) -> Result<String, JsValue> {
     Ok(public_key_to_bech32(&pub_key))
 }
 
+// iterations of pbkdf2, keep it for the data encrypted before
+const PBKDF2_ITERATIONS: u32 = 32;
+
 #[wasm_bindgen]
 #[allow(missing_docs)]
 pub fn encryption_pbkdf2_aes256gcm(key_pair: String, password: String) -> Vec<u8> {
-    const CREDENTIAL_LEN: usize = 32;
-    const IV_LEN: usize = 12;
-    let n_iter = NonZeroU32::new(32).unwrap();
-    let mut rng = thread_rng();
-
-    let mut salt = [0u8; CREDENTIAL_LEN];
-    rng.fill(&mut salt);
-    let mut derived_key = [0u8; CREDENTIAL_LEN];
-    pbkdf2::derive(
-        pbkdf2::PBKDF2_HMAC_SHA512,
-        n_iter,
-        &salt,
-        password.as_bytes(),
-        &mut derived_key,
-    );
-
-    let mut iv = [0u8; IV_LEN];
-    rng.fill(&mut iv);
-
-    let cipher = Aes256Gcm::new(GenericArray::from_slice(&derived_key));
-    let ciphertext = cipher
-        .encrypt(GenericArray::from_slice(&iv), key_pair.as_ref())
-        .unwrap_or_default();
-
-    // this is a hack, wasm-bindgen not support tuple of vectors
-    let mut res: Vec<u8> = Vec::new();
-    res.append(&mut salt.to_vec());
-    res.append(&mut iv.to_vec());
-    res.append(&mut ciphertext.to_vec());
-    res
+    wallet::encrypt_pbkdf2_aes256gcm(key_pair.as_bytes(), &password, PBKDF2_ITERATIONS)
+        .unwrap_or_default()
 }
 
 #[wasm_bindgen]
 #[allow(missing_docs)]
 pub fn decryption_pbkdf2_aes256gcm(enc_key_pair: Vec<u8>, password: String) -> String {
-    const CREDENTIAL_LEN: usize = 32;
-    const IV_LEN: usize = 12;
-    let n_iter = NonZeroU32::new(32).unwrap();
-
-    if enc_key_pair.len() <= CREDENTIAL_LEN + IV_LEN {
-        return "".to_string();
-    }
-
-    let salt = &enc_key_pair[0..CREDENTIAL_LEN];
-    let iv = &enc_key_pair[CREDENTIAL_LEN..(CREDENTIAL_LEN + IV_LEN)];
-    let ciphertext = &enc_key_pair[(CREDENTIAL_LEN + IV_LEN)..];
-
-    let mut derived_key = [0u8; CREDENTIAL_LEN];
-    pbkdf2::derive(
-        pbkdf2::PBKDF2_HMAC_SHA512,
-        n_iter,
-        salt,
-        password.as_bytes(),
-        &mut derived_key,
-    );
-    let cipher = Aes256Gcm::new(GenericArray::from_slice(&derived_key));
-    let plaintext = cipher
-        .decrypt(GenericArray::from_slice(iv), ciphertext.as_ref())
-        .unwrap_or_default();
-
-    String::from_utf8(plaintext).unwrap_or_else(|_| "".to_string())
+    wallet::decrypt_pbkdf2_aes256gcm(&enc_key_pair, &password, PBKDF2_ITERATIONS)
+        .ok()
+        .and_then(|plaintext| String::from_utf8(plaintext).ok())
+        .unwrap_or_default()
 }
 
 #[wasm_bindgen]
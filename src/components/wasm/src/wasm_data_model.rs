@@ -7,6 +7,7 @@ use credentials::{
 use globutils::{wallet, HashOf};
 use ledger::data_model::{
     AssetRules as PlatformAssetRules, AssetType as PlatformAssetType, AuthenticatedUtxo,
+    CredentialRequirement as PlatformCredentialRequirement,
     SignatureRules as PlatformSignatureRules, TxOutput, TxoRef as PlatformTxoRef,
     TxoSID,
 };
@@ -700,6 +701,27 @@ impl AssetRules {
         self
     }
 
+    /// Credential requirement. Recipients of transfers must attach a credential proof signed by
+    /// the credential issuer, revealing the required attributes.
+    /// @param {CredIssuerPublicKey} issuer_pub_key - Public key of credential issuer.
+    /// @param {JsValue} attributes - Array of required attributes of the form `[{name: "kyc_passed",
+    /// val: "yes"}]`, an empty `val` means any value.
+    /// @throws Will throw an error if `attributes` fails to deserialize.
+    pub fn set_credential_requirement(
+        mut self,
+        issuer_pub_key: &CredIssuerPublicKey,
+        attributes: JsValue,
+    ) -> Result<AssetRules, JsValue> {
+        let attributes: Vec<AttributeAssignment> = attributes.into_serde().c(d!()).map_err(|e| JsValue::from(&format!("Could not deserialize attributes. Please ensure that attribute definition is of the form [{{name: string, val: string}}]: {}", e)))?;
+        let mut requirement = PlatformCredentialRequirement::new(issuer_pub_key.clone());
+        for attr in attributes.iter() {
+            let val = Some(attr.val.as_bytes()).filter(|v| !v.is_empty());
+            requirement.add_attr(&attr.name, val);
+        }
+        self.rules.set_credential_requirement(Some(requirement));
+        Ok(self)
+    }
+
     /// Set the decimal number of asset. Return error string if failed, otherwise return changed asset.
     /// #param {Number} decimals - The number of decimals used to set its user representation.
     /// Decimals should be 0 ~ 255.
@@ -8,7 +8,6 @@
 
 #![allow(missing_docs)]
 
-use crate::data_model::AssetTypeCode;
 use fixed::types::I20F12;
 use serde::{Deserialize, Serialize};
 use zei::xfr::sig::XfrPublicKey;
@@ -124,11 +123,6 @@ pub struct Policy {
     pub txn_choices: Vec<TxnCheck>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
-pub struct TxnCheckInputs {
-    pub which_check: String,
-}
-
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct PolicyGlobals {
     pub id_vars: Vec<XfrPublicKey>,
@@ -136,6 +130,3 @@ pub struct PolicyGlobals {
     pub amt_vars: Vec<u64>,
     pub frac_vars: Vec<Fraction>,
 }
-
-#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
-pub struct TxnPolicyData(pub Vec<(AssetTypeCode, TxnCheckInputs)>);
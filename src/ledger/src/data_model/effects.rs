@@ -9,6 +9,7 @@ use crate::{
         ops::{
             claim::ClaimOps, delegation::DelegationOps,
             fra_distribution::FraDistributionOps, governance::GovernanceOps,
+            rotate_key::RotateKeyOps, transfer_plan::TransferPlanOps,
             undelegation::UnDelegationOps, update_staker::UpdateStakerOps,
             update_validator::UpdateValidatorOps,
         },
@@ -23,6 +24,7 @@ use ruc::*;
 use serde::Serialize;
 use std::{
     collections::{HashMap, HashSet},
+    result::Result as StdResult,
     sync::Arc,
 };
 use zei::{
@@ -88,6 +90,10 @@ pub struct TxnEffect {
     pub fra_distributions: Vec<FraDistributionOps>,
     /// Staking operations
     pub update_stakers: Vec<UpdateStakerOps>,
+    /// Staking operations
+    pub transfer_plans: Vec<TransferPlanOps>,
+    /// Staking operations
+    pub rotate_keys: Vec<RotateKeyOps>,
 }
 
 impl TxnEffect {
@@ -114,84 +120,115 @@ impl TxnEffect {
     /// exist unspent in the ledger and correspond to the correct
     /// TxOutput).
     pub fn compute_effect(txn: Transaction) -> Result<TxnEffect> {
+        Self::compute_effect_with_op_index(txn).map_err(|(_, e)| e)
+    }
+
+    /// Same as `compute_effect`, but the index of the failed operation
+    /// is returned along with the error, to tell users what is wrong.
+    pub fn compute_effect_with_op_index(
+        txn: Transaction,
+    ) -> StdResult<TxnEffect, (usize, Box<dyn RucError>)> {
         let mut te = TxnEffect::default();
         let mut txo_count: usize = 0;
 
-        for op in txn.body.operations.iter() {
-            macro_rules! check_nonce {
-                ($i: expr) => {
-                    if $i.get_nonce() != txn.body.no_replay_token {
-                        return Err(eg!(("nonce does not match")));
-                    }
-                };
-            }
+        for (idx, op) in txn.body.operations.iter().enumerate() {
+            te.add_operation(&txn, op, &mut txo_count)
+                .c(d!())
+                .map_err(|e| (idx, e))?;
+        }
 
-            match op {
-                Operation::MintFra(i) => {
-                    i.entries.iter().for_each(|et| {
-                        te.txos.push(Some(et.utxo.clone()));
-                        txo_count += 1;
-                    });
-                }
-                Operation::TransferAsset(trn) => {
-                    te.add_transfer_asset(trn, &mut txo_count).c(d!())?;
-                }
-                Operation::Claim(i) => {
-                    check_nonce!(i);
-                    i.verify().c(d!())?;
-                    te.claims.push(i.clone());
-                }
-                Operation::Delegation(i) => {
-                    check_nonce!(i);
-                    i.verify().c(d!())?;
-                    te.delegations.push(i.clone());
-                }
-                Operation::UnDelegation(i) => {
-                    check_nonce!(i);
-                    i.verify().c(d!())?;
-                    te.undelegations.push(i.as_ref().clone());
-                }
-                Operation::UpdateStaker(i) => {
-                    check_nonce!(i);
-                    i.verify().c(d!())?;
-                    te.update_stakers.push(i.clone());
-                }
-                Operation::UpdateValidator(i) => {
-                    check_nonce!(i);
-                    // Only one update is allowed at the same height.
-                    if te
-                        .update_validators
-                        .insert(i.data.height, i.clone())
-                        .is_some()
-                    {
-                        return Err(eg!("dup entries"));
-                    }
-                }
-                Operation::DefineAsset(def) => {
-                    te.add_define_asset(def).c(d!())?;
-                }
-                Operation::IssueAsset(iss) => {
-                    te.add_issue_asset(iss, &mut txo_count).c(d!())?;
-                }
-                Operation::UpdateMemo(update_memo) => {
-                    te.add_update_memo(&txn, update_memo).c(d!())?;
-                }
-                Operation::Governance(i) => {
-                    check_nonce!(i);
-                    te.governances.push(i.clone());
-                }
-                Operation::FraDistribution(i) => {
-                    check_nonce!(i);
-                    te.fra_distributions.push(i.clone());
+        te.txn = txn;
+        Ok(te)
+    }
+
+    fn add_operation(
+        &mut self,
+        txn: &Transaction,
+        op: &Operation,
+        txo_count: &mut usize,
+    ) -> Result<()> {
+        macro_rules! check_nonce {
+            ($i: expr) => {
+                if $i.get_nonce() != txn.body.no_replay_token {
+                    return Err(eg!(("nonce does not match")));
                 }
-                Operation::ConvertAccount(i) => {
-                    check_nonce!(i)
+            };
+        }
+
+        match op {
+            Operation::MintFra(i) => {
+                i.entries.iter().for_each(|et| {
+                    self.txos.push(Some(et.utxo.clone()));
+                    *txo_count += 1;
+                });
+            }
+            Operation::TransferAsset(trn) => {
+                self.add_transfer_asset(trn, txo_count).c(d!())?;
+            }
+            Operation::Claim(i) => {
+                check_nonce!(i);
+                i.verify().c(d!())?;
+                self.claims.push(i.clone());
+            }
+            Operation::Delegation(i) => {
+                check_nonce!(i);
+                i.verify().c(d!())?;
+                self.delegations.push(i.clone());
+            }
+            Operation::UnDelegation(i) => {
+                check_nonce!(i);
+                i.verify().c(d!())?;
+                self.undelegations.push(i.as_ref().clone());
+            }
+            Operation::UpdateStaker(i) => {
+                check_nonce!(i);
+                i.verify().c(d!())?;
+                self.update_stakers.push(i.clone());
+            }
+            Operation::UpdateValidator(i) => {
+                check_nonce!(i);
+                // Only one update is allowed at the same height.
+                if self
+                    .update_validators
+                    .insert(i.data.height, i.clone())
+                    .is_some()
+                {
+                    return Err(eg!("dup entries"));
                 }
             }
+            Operation::DefineAsset(def) => {
+                self.add_define_asset(def).c(d!())?;
+            }
+            Operation::IssueAsset(iss) => {
+                self.add_issue_asset(iss, txo_count).c(d!())?;
+            }
+            Operation::UpdateMemo(update_memo) => {
+                self.add_update_memo(txn, update_memo).c(d!())?;
+            }
+            Operation::Governance(i) => {
+                check_nonce!(i);
+                self.governances.push(i.clone());
+            }
+            Operation::FraDistribution(i) => {
+                check_nonce!(i);
+                self.fra_distributions.push(i.clone());
+            }
+            Operation::ConvertAccount(i) => {
+                check_nonce!(i)
+            }
+            Operation::TransferPlan(i) => {
+                check_nonce!(i);
+                i.verify().c(d!())?;
+                self.transfer_plans.push(i.clone());
+            }
+            Operation::RotateKey(i) => {
+                check_nonce!(i);
+                i.verify().c(d!())?;
+                self.rotate_keys.push(i.clone());
+            }
         }
 
-        te.txn = txn;
-        Ok(te)
+        Ok(())
     }
 
     // An asset creation is valid iff:
@@ -285,6 +322,7 @@ impl TxnEffect {
                     id: None,
                     record: output.record.clone(),
                     lien: None,
+                    multisig: None,
                 })
             {
                 return Err(eg!());
@@ -322,6 +360,13 @@ impl TxnEffect {
     //            external checks later
     //          - For simplicity, only Standard transfers are allowed
     //            to have lien assignments
+    //     5) Inputs of multisig accounts are approved under their rules
+    //          - Checked within a transaction here, the rules are
+    //            recorded with the inputs for external checks later
+    //          - Outputs of multisig accounts must be non-confidential
+    //            and sent to the address derived from valid rules
+    //          - For simplicity, only Standard transfers are allowed
+    //            to spend multisig inputs
     fn add_transfer_asset(
         &mut self,
         trn: &TransferAsset,
@@ -347,6 +392,18 @@ impl TxnEffect {
             if output.record != *record {
                 return Err(eg!());
             }
+            // (5)
+            if let Some(rules) = output.multisig.as_ref() {
+                rules.check().c(d!())?;
+                if record.public_key != rules.multisig_address() {
+                    return Err(eg!("multisig output sent to a wrong address"));
+                }
+                if !matches!(record.amount, XfrAmount::NonConfidential(_))
+                    || !matches!(record.asset_type, XfrAssetType::NonConfidential(_))
+                {
+                    return Err(eg!("multisig output must be non-confidential"));
+                }
+            }
         }
 
         // Simplify (4)
@@ -385,6 +442,33 @@ impl TxnEffect {
             (inps, outs)
         };
 
+        // Simplify (5)
+        if !trn.body.multisig_inputs.is_empty()
+            && trn.body.transfer_type != TransferType::Standard
+        {
+            return Err(eg!());
+        }
+        let multisig_inputs = {
+            let mut inps = trn
+                .body
+                .transfer
+                .inputs
+                .iter()
+                .map(|_| None)
+                .collect::<Vec<_>>();
+            for (inp_ix, rules) in trn.body.multisig_inputs.iter() {
+                match inps.get_mut(*inp_ix) {
+                    Some(ele) if ele.is_none() => {
+                        *ele = Some(rules);
+                    }
+                    _ => {
+                        return Err(eg!());
+                    }
+                }
+            }
+            inps
+        };
+
         match trn.body.transfer_type {
             TransferType::DebtSwap => {}
             TransferType::Standard => {
@@ -397,9 +481,18 @@ impl TxnEffect {
                     input_keys.insert(sig.address.key.zei_to_bytes());
                 }
 
-                // (1b) all input record owners have signed
-                for record in trn.body.transfer.inputs.iter() {
-                    if !input_keys.contains(&record.public_key.zei_to_bytes()) {
+                // (1b) all input record owners have signed,
+                // or the rules of the multisig inputs are satisfied
+                for (record, rules) in
+                    trn.body.transfer.inputs.iter().zip(multisig_inputs.iter())
+                {
+                    if let Some(rules) = rules {
+                        // (5)
+                        if record.public_key != rules.multisig_address() {
+                            return Err(eg!());
+                        }
+                        rules.check_signature_set(&input_keys).c(d!())?;
+                    } else if !input_keys.contains(&record.public_key.zei_to_bytes()) {
                         return Err(eg!());
                     }
                 }
@@ -415,12 +508,13 @@ impl TxnEffect {
         }
         // (3)
         let mut input_types = HashSet::new();
-        for ((inp, record), lien) in trn
+        for (((inp, record), lien), rules) in trn
             .body
             .inputs
             .iter()
             .zip(trn.body.transfer.inputs.iter())
             .zip(lien_inputs)
+            .zip(multisig_inputs)
         {
             // NOTE: We assume that any confidential-type asset records
             // have no atypical transfer restrictions. Be careful!
@@ -444,7 +538,13 @@ impl TxnEffect {
                         }
                         Some(txo) => {
                             // (2).(b)
-                            if &txo.record != record || txo.lien != lien.cloned() {
+                            // a multisig input may be an output to its address
+                            // without the rules, see `LedgerStatus::check_txn_effects`
+                            if &txo.record != record
+                                || txo.lien != lien.cloned()
+                                || (txo.multisig.is_some()
+                                    && txo.multisig.as_ref() != rules)
+                            {
                                 return Err(eg!());
                             }
                             self.internally_spent_txos.push(txo.clone());
@@ -464,6 +564,7 @@ impl TxnEffect {
                             id: None,
                             record: record.clone(),
                             lien: lien.cloned(),
+                            multisig: rules.cloned(),
                         },
                     );
                 }
@@ -472,7 +573,14 @@ impl TxnEffect {
 
         self.txos.reserve(trn.body.transfer.outputs.len());
         let mut conf_transfer = false;
-        for (out, lien) in trn.body.transfer.outputs.iter().zip(lien_outputs) {
+        for ((out, lien), output) in trn
+            .body
+            .transfer
+            .outputs
+            .iter()
+            .zip(lien_outputs)
+            .zip(trn.body.outputs.iter())
+        {
             if let XfrAssetType::Confidential(_) = out.asset_type {
                 conf_transfer = true;
             }
@@ -484,6 +592,7 @@ impl TxnEffect {
                 id: None,
                 record: out.clone(),
                 lien: lien.cloned(),
+                multisig: output.multisig.clone(),
             }));
             *txo_count += 1;
         }
@@ -702,6 +811,16 @@ impl BlockEffect {
                 .c(d!())?;
         }
 
+        for i in txn_effect.transfer_plans.iter() {
+            i.check_run(&mut self.staking_simulator, &txn_effect.txn)
+                .c(d!())?;
+        }
+
+        for i in txn_effect.rotate_keys.iter() {
+            i.check_run(&mut self.staking_simulator, &txn_effect.txn)
+                .c(d!())?;
+        }
+
         Ok(())
     }
 
@@ -17,13 +17,19 @@ use crate::staking::{
     ops::{
         claim::ClaimOps, delegation::DelegationOps,
         fra_distribution::FraDistributionOps, governance::GovernanceOps,
-        mint_fra::MintFraOps, undelegation::UnDelegationOps,
-        update_staker::UpdateStakerOps, update_validator::UpdateValidatorOps,
+        mint_fra::MintFraOps, rotate_key::RotateKeyOps, transfer_plan::TransferPlanOps,
+        undelegation::UnDelegationOps, update_staker::UpdateStakerOps,
+        update_validator::UpdateValidatorOps,
     },
     Staking,
 };
-use __trash__::{Policy, PolicyGlobals, TxnPolicyData};
+use __trash__::{Policy, PolicyGlobals};
 use bitmap::SparseMap;
+use credentials::{
+    credential_commit, credential_open_commitment, credential_verify,
+    credential_verify_commitment, CredCommitment, CredIssuerPublicKey, CredPoK,
+    CredUserSecretKey, Credential,
+};
 use cryptohash::{sha256::Digest as BitDigest, HashValue};
 use globutils::{HashOf, ProofOf, Serialized, SignatureOf};
 use lazy_static::lazy_static;
@@ -463,6 +469,43 @@ impl SignatureRules {
         }
         Ok(())
     }
+
+    /// Returns Ok(()) if the rules can ever be satisfied:
+    /// a positive threshold, no duplicate keys,
+    /// and the sum of all weights reaching the threshold.
+    pub fn check(&self) -> Result<()> {
+        if 0 == self.threshold {
+            return Err(eg!("threshold must be positive"));
+        }
+        let mut keys = HashSet::new();
+        let mut sum: u64 = 0;
+        for (key, weight) in self.weights.iter() {
+            if !keys.insert(key.as_bytes()) {
+                return Err(eg!("duplicate key in signature rules"));
+            }
+            sum = sum.checked_add(*weight).c(d!())?;
+        }
+        if sum < self.threshold {
+            return Err(eg!("threshold is unreachable"));
+        }
+        Ok(())
+    }
+
+    /// The address of the multisig account controlled by these rules.
+    ///
+    /// It is derived by hashing the rules until the digest is a valid
+    /// public key, so nobody knows the corresponding secret key, and
+    /// assets sent to it can only be spent under the rules.
+    pub fn multisig_address(&self) -> XfrPublicKey {
+        let mut counter: u64 = 0;
+        loop {
+            let h = HashOf::new(&(self, counter));
+            if let Ok(pk) = XfrPublicKey::zei_from_bytes(h.as_ref()) {
+                return pk;
+            }
+            counter += 1;
+        }
+    }
 }
 
 /// Simple asset rules
@@ -485,6 +528,11 @@ pub struct AssetRules {
     pub max_units: Option<u64>,
     /// Decimals: default to FRA_DECIMALS
     pub decimals: u8,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    /// Credential requirement: recipients of a transfer must prove
+    ///   that they hold a qualified credential.
+    pub credential_requirement: Option<CredentialRequirement>,
 }
 impl Default for AssetRules {
     #[inline(always)]
@@ -496,6 +544,7 @@ impl Default for AssetRules {
             max_units: None,
             transfer_multisig_rules: None,
             decimals: FRA_DECIMALS,
+            credential_requirement: None,
         }
     }
 }
@@ -548,6 +597,45 @@ impl AssetRules {
         self.decimals = decimals;
         Ok(self)
     }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn set_credential_requirement(
+        &mut self,
+        requirement: Option<CredentialRequirement>,
+    ) -> &mut Self {
+        self.credential_requirement = requirement;
+        self
+    }
+}
+
+/// Credentials required to receive an asset.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CredentialRequirement {
+    /// The credential must be signed by this issuer
+    pub issuer_pub_key: CredIssuerPublicKey,
+    /// Attributes must be revealed by the recipient,
+    /// with the given value if any, eg. `("kyc_passed", Some(b"yes"))`
+    pub attrs: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl CredentialRequirement {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(issuer_pub_key: CredIssuerPublicKey) -> Self {
+        CredentialRequirement {
+            issuer_pub_key,
+            attrs: vec![],
+        }
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn add_attr(&mut self, name: &str, value: Option<&[u8]>) -> &mut Self {
+        self.attrs
+            .push((name.to_owned(), value.map(|v| v.to_vec())));
+        self
+    }
 }
 
 #[allow(missing_docs)]
@@ -622,14 +710,83 @@ pub struct CustomAssetPolicy {
     policy: Vec<u8>, // serialized policy, underlying form TBD.
 }
 
-#[allow(missing_docs)]
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
-pub struct CredentialProofKey([u8; 16]);
-
-#[allow(missing_docs)]
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+/// A proof that an address holds a credential,
+/// with some of its attributes revealed.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CredentialProof {
-    pub key: CredentialProofKey,
+    /// The address linked to the credential
+    pub address: XfrPublicKey,
+    /// Commitment to the credential
+    pub commitment: CredCommitment,
+    /// Proof that the commitment is linked to `address`
+    pub commitment_pok: CredPoK,
+    /// Revealed attributes
+    pub attrs: Vec<(String, Vec<u8>)>,
+    /// Proof that the commitment contains the revealed attributes
+    pub reveal_pok: CredPoK,
+}
+
+impl CredentialProof {
+    /// Link a credential to `address`, and reveal the given attributes.
+    pub fn new<R: CryptoRng + RngCore>(
+        prng: &mut R,
+        user_sk: &CredUserSecretKey,
+        credential: &Credential,
+        address: &XfrPublicKey,
+        reveal_fields: &[String],
+    ) -> Result<Self> {
+        let (commitment, commitment_pok, key) =
+            credential_commit(prng, user_sk, credential, &address.as_bytes()).c(d!())?;
+        let reveal_pok =
+            credential_open_commitment(prng, user_sk, credential, &key, reveal_fields)
+                .c(d!())?;
+        let attrs = credential
+            .attributes
+            .iter()
+            .filter(|(name, _)| reveal_fields.contains(name))
+            .cloned()
+            .collect();
+
+        Ok(CredentialProof {
+            address: *address,
+            commitment,
+            commitment_pok,
+            attrs,
+            reveal_pok,
+        })
+    }
+
+    /// Check the proof against a credential requirement.
+    pub fn verify(&self, requirement: &CredentialRequirement) -> Result<()> {
+        for (name, value) in requirement.attrs.iter() {
+            let revealed = self
+                .attrs
+                .iter()
+                .find(|(n, _)| n == name)
+                .c(d!(format!("attribute '{}' is not revealed", name)))?;
+            if let Some(v) = value {
+                if v != &revealed.1 {
+                    return Err(eg!(format!("attribute '{}' mismatch", name)));
+                }
+            }
+        }
+
+        let pk = &requirement.issuer_pub_key;
+        credential_verify_commitment(
+            pk,
+            &self.commitment,
+            &self.commitment_pok,
+            &self.address.as_bytes(),
+        )
+        .c(d!())?;
+
+        let attrs = self
+            .attrs
+            .iter()
+            .map(|(n, v)| (n.clone(), v.as_slice()))
+            .collect::<Vec<_>>();
+        credential_verify(pk, &attrs, &self.commitment, &self.reveal_pok).c(d!())
+    }
 }
 
 #[derive(
@@ -695,6 +852,12 @@ pub struct TxOutput {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
     pub lien: Option<HashOf<Vec<TxOutput>>>,
+    /// Set if the output belongs to a multisig account,
+    /// see `SignatureRules::multisig_address`, the outputs sent to
+    /// the address without it can also be spent under the rules
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub multisig: Option<SignatureRules>,
 }
 
 #[allow(missing_docs)]
@@ -765,6 +928,12 @@ pub struct TransferAssetBody {
     /// (inp_idx,out_idx,hash) triples signifying that the lien `hash` on
     /// the input `inp_idx` gets assigned to the output `out_idx`
     pub lien_assignments: Vec<(usize, usize, HashOf<Vec<TxOutput>>)>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    /// (inp_idx, rules) pairs signifying that the input `inp_idx` belongs to
+    /// a multisig account, and must be approved under `rules` instead of
+    /// being signed by its owner
+    pub multisig_inputs: Vec<(usize, SignatureRules)>,
     /// TODO(joe): we probably don't need the whole XfrNote with input records
     /// once it's on the chain
     /// Encrypted transfer note
@@ -822,6 +991,7 @@ impl TransferAssetBody {
                 id: None,
                 record: rec.clone(),
                 lien: None,
+                multisig: None,
             })
             .collect();
         Ok(TransferAssetBody {
@@ -829,6 +999,7 @@ impl TransferAssetBody {
             outputs,
             policies,
             lien_assignments,
+            multisig_inputs: vec![],
             transfer,
             transfer_type,
         })
@@ -1007,6 +1178,19 @@ impl TransferAsset {
         self.body.compute_body_signature(keypair, None)
     }
 
+    /// Whether `pk` is a signer of some multisig input
+    /// and has not approved this transfer yet.
+    pub fn needs_multisig_approval(&self, pk: &XfrPublicKey) -> bool {
+        self.body
+            .multisig_inputs
+            .iter()
+            .any(|(_, rules)| rules.weights.iter().any(|(k, _)| k == pk))
+            && !self
+                .body_signatures
+                .iter()
+                .any(|sig| &sig.address.key == pk)
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn get_owner_memos_ref(&self) -> Vec<Option<&OwnerMemo>> {
@@ -1150,6 +1334,10 @@ pub enum Operation {
     MintFra(MintFraOps),
     /// Convert UTXO to Account
     ConvertAccount(ConvertAccount),
+    /// Register or cancel a scheduled transfer plan
+    TransferPlan(TransferPlanOps),
+    /// Replace the tendermint key of a validator
+    RotateKey(RotateKeyOps),
 }
 
 fn set_no_replay_token(op: &mut Operation, no_replay_token: NoReplayToken) {
@@ -1177,10 +1365,29 @@ fn set_no_replay_token(op: &mut Operation, no_replay_token: NoReplayToken) {
         }
         Operation::UpdateMemo(i) => i.body.no_replay_token = no_replay_token,
         Operation::ConvertAccount(i) => i.set_nonce(no_replay_token),
+        Operation::TransferPlan(i) => i.set_nonce(no_replay_token),
+        Operation::RotateKey(i) => i.set_nonce(no_replay_token),
         _ => {}
     }
 }
 
+/// Inputs of a transaction for a registered policy,
+/// see `crate::store::policy` for details
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TxnCheckInputs {
+    /// The name of the policy
+    pub which_check: String,
+    /// Policy specific arguments, eg. a json string
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub params: String,
+}
+
+/// Policy inputs attached to a transaction, grouped by asset
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TxnPolicyData(pub Vec<(AssetTypeCode, TxnCheckInputs)>);
+
 #[allow(missing_docs)]
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, Default)]
 pub struct TransactionBody {
@@ -1425,11 +1632,22 @@ impl AuthenticatedUtxoStatus {
             return utxo_sid >= state_commitment_data.txo_count;
         }
 
-        // If the txo exists, the proof must also contain a bitmap
-        let utxo_map = SparseMap::new(&self.utxo_map_bytes.as_ref().unwrap()).unwrap();
+        // If the txo exists, the proof must also contain a bitmap,
+        // a missing or malformed one is an invalid proof rather than a panic
+        let utxo_map = match self
+            .utxo_map_bytes
+            .as_ref()
+            .and_then(|bytes| SparseMap::new(bytes).ok())
+        {
+            Some(map) => map,
+            None => return false,
+        };
 
         // 4) The status matches the bit stored in the bitmap
-        let spent = !utxo_map.query(utxo_sid).unwrap();
+        let spent = match utxo_map.query(utxo_sid) {
+            Ok(bit) => !bit,
+            Err(_) => return false,
+        };
 
         if (self.status == UtxoStatus::Spent && !spent)
             || (self.status == UtxoStatus::Unspent && spent)
@@ -1447,6 +1665,83 @@ impl AuthenticatedUtxoStatus {
     }
 }
 
+/// The max count of txos in one `AuthenticatedUtxoRangeStatus`
+pub const UTXO_RANGE_LIMIT: u64 = 1 << 16;
+
+/// The spent status of a range of txos: `[first_sid, first_sid + count)`,
+/// proved by a partial bitmap which only contains the blocks covering the range.
+#[allow(missing_docs)]
+#[derive(Serialize, Clone, Deserialize)]
+pub struct AuthenticatedUtxoRangeStatus {
+    pub first_sid: TxoSID,
+    pub count: u64,
+    pub state_commitment_data: StateCommitmentData,
+    // None if all txos of the range are nonexistent
+    pub utxo_map_bytes: Option<Vec<u8>>,
+    pub state_commitment: HashOf<Option<StateCommitmentData>>,
+}
+
+impl AuthenticatedUtxoRangeStatus {
+    /// Verify the proof and return the status of every txo in the range, in order.
+    ///
+    /// 1) The state commitment of the proof matches the state commitment passed in
+    /// 2) The state commitment data hashes to the state commitment
+    /// 3) The partial bitmap is consistent with its checksum,
+    ///    which matches digest in state commitment data
+    /// 4) Txos in the range less than `txo_count` are present in the bitmap,
+    ///    the others are nonexistent
+    pub fn verify(
+        &self,
+        state_commitment: HashOf<Option<StateCommitmentData>>,
+    ) -> Result<Vec<UtxoStatus>> {
+        let state_commitment_data = &self.state_commitment_data;
+
+        // 1, 2)
+        if state_commitment != self.state_commitment
+            || self.state_commitment != state_commitment_data.compute_commitment()
+        {
+            return Err(eg!("invalid state commitment"));
+        }
+
+        if self.count > UTXO_RANGE_LIMIT {
+            return Err(eg!("range too large"));
+        }
+
+        let first = self.first_sid.0;
+        let last = first.checked_add(self.count).c(d!("range overflow"))?;
+        let exist_end = last.min(state_commitment_data.txo_count).max(first);
+
+        let mut res = if first < exist_end {
+            // 3)
+            let utxo_map = self
+                .utxo_map_bytes
+                .as_ref()
+                .c(d!("missing bitmap"))
+                .and_then(|bytes| SparseMap::new(bytes).c(d!()))?;
+            if utxo_map.checksum() != state_commitment_data.bitmap
+                || !utxo_map.validate_checksum()
+            {
+                return Err(eg!("invalid bitmap checksum"));
+            }
+
+            // 4)
+            utxo_map
+                .query_range(first, exist_end)
+                .c(d!())?
+                .into_iter()
+                .map(|unspent| alt!(unspent, UtxoStatus::Unspent, UtxoStatus::Spent))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        // 4)
+        res.resize(self.count as usize, UtxoStatus::Nonexistent);
+
+        Ok(res)
+    }
+}
+
 #[allow(missing_docs)]
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct FinalizedBlock {
@@ -1645,6 +1940,28 @@ impl Transaction {
         self.signatures.push(SignatureOf::new(keypair, &self.body));
     }
 
+    /// Attach a co-signature to the operations which need them,
+    /// eg. `Governance`, `UpdateValidator` and transfers from
+    /// multisig accounts, returns the number of them.
+    pub fn cosign(&mut self, keypair: &XfrKeyPair) -> Result<usize> {
+        let mut n = 0;
+        for op in self.body.operations.iter_mut() {
+            match op {
+                Operation::UpdateValidator(i) => i.sign(keypair).c(d!())?,
+                Operation::Governance(i) => i.sign(keypair).c(d!())?,
+                Operation::FraDistribution(i) => i.sign(keypair).c(d!())?,
+                Operation::TransferAsset(i)
+                    if i.needs_multisig_approval(keypair.get_pk_ref()) =>
+                {
+                    i.sign(keypair)
+                }
+                _ => continue,
+            }
+            n += 1;
+        }
+        Ok(n)
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn check_signature(
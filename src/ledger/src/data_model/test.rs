@@ -190,6 +190,7 @@ fn gen_sample_tx() -> Transaction {
         policies,
         transfer: Box::new(xfr_note),
         lien_assignments: Vec::new(),
+        multisig_inputs: Vec::new(),
         transfer_type: TransferType::Standard,
     };
 
@@ -282,8 +283,10 @@ fn gen_fee_operation(
                     public_key: dest_pubkey,
                 },
                 lien: None,
+                multisig: None,
             }],
             lien_assignments: Vec::new(),
+            multisig_inputs: Vec::new(),
             transfer: Box::new(XfrBody {
                 inputs: Vec::new(),
                 outputs: Vec::new(),
@@ -375,3 +378,33 @@ fn test_check_fee() {
     tx.add_operation(invalid_destination_not_black_hole);
     assert!(tx.check_fee());
 }
+
+#[test]
+fn test_cosign() {
+    let mut tx = gen_sample_tx();
+    let mut prng = rand_chacha::ChaChaRng::from_entropy();
+    let kps = (0..2)
+        .map(|_| XfrKeyPair::generate(&mut prng))
+        .collect::<Vec<_>>();
+
+    // no operations need a co-signature
+    assert_eq!(0, pnk!(tx.cosign(&kps[0])));
+
+    let op = pnk!(UpdateValidatorOps::new(
+        &[],
+        1,
+        vec![],
+        tx.body.no_replay_token
+    ));
+    tx.add_operation(Operation::UpdateValidator(op));
+
+    // signatures are collected one by one, signing twice is idempotent
+    for kp in kps.iter().chain(kps.iter()) {
+        assert_eq!(1, pnk!(tx.cosign(kp)));
+    }
+    if let Some(Operation::UpdateValidator(op)) = tx.body.operations.last() {
+        assert_eq!(2, op.cosigs.len());
+    } else {
+        panic!("missing operation");
+    }
+}
@@ -23,7 +23,8 @@ use globutils::wallet;
 use lazy_static::lazy_static;
 use ops::{
     fra_distribution::FraDistributionOps,
-    mint_fra::{MintKind, MINT_AMOUNT_LIMIT},
+    mint_fra::{MintEntry, MintKind, MINT_AMOUNT_LIMIT},
+    transfer_plan::{PlanId, ScheduledTransfer, TransferPlan},
 };
 use parking_lot::Mutex;
 use rand_chacha::ChaChaRng;
@@ -38,11 +39,15 @@ use std::{
     iter::FromIterator,
     mem,
     sync::{
+        atomic::{AtomicBool, Ordering},
         mpsc::{channel, Receiver, Sender},
         Arc,
     },
 };
-use zei::xfr::sig::{XfrKeyPair, XfrPublicKey};
+use zei::xfr::{
+    sig::{XfrKeyPair, XfrPublicKey},
+    structs::AssetType,
+};
 
 // height, reward rate
 type GRH = (BlockHeight, [u128; 2]);
@@ -57,6 +62,10 @@ type DAHCP = (Arc<Mutex<Sender<DAH>>>, Arc<Mutex<Receiver<DAH>>>);
 type DRH = (XfrPublicKey, BlockHeight, DelegationRwdDetail);
 type DRHCP = (Arc<Mutex<Sender<DRH>>>, Arc<Mutex<Receiver<DRH>>>);
 
+// pk, height, if signed
+type VSH = (XfrPublicKey, BlockHeight, bool);
+type VSHCP = (Arc<Mutex<Sender<VSH>>>, Arc<Mutex<Receiver<VSH>>>);
+
 macro_rules! chan {
     () => {{
         let (s, r) = channel();
@@ -83,6 +92,8 @@ lazy_static! {
     pub static ref CHAN_D_AMOUNT_HIST: DAHCP = chan!();
     #[allow(missing_docs)]
     pub static ref CHAN_D_RWD_HIST: DRHCP = chan!();
+    /// Signing status of the validators in every last commit, by validator ids.
+    pub static ref CHAN_V_SIGN_HIST: VSHCP = chan!();
 }
 
 /// Reserved accounts of Findora Foundation.
@@ -164,6 +175,29 @@ pub const UNBOND_BLOCK_CNT: u64 = 5;
 // minimal number of validators
 pub(crate) const VALIDATORS_MIN: usize = 5;
 
+// set by a local single-process devnet, see `relax_validator_rules`
+static VALIDATOR_RULES_RELAXED: AtomicBool = AtomicBool::new(false);
+
+/// Allow a validator set of any non-empty size, and lift the cap of
+/// `MAX_POWER_PERCENT_PER_VALIDATOR`, so a local devnet can run with one validator.
+///
+/// **NOTE**: the consensus rules of the whole process are changed,
+/// NEVER call it on a node of a real network.
+pub fn relax_validator_rules() {
+    VALIDATOR_RULES_RELAXED.store(true, Ordering::Relaxed);
+}
+
+#[inline(always)]
+fn validator_rules_relaxed() -> bool {
+    VALIDATOR_RULES_RELAXED.load(Ordering::Relaxed)
+}
+
+// minimal number of validators, see `relax_validator_rules`
+#[inline(always)]
+pub(crate) fn validators_min() -> usize {
+    alt!(validator_rules_relaxed(), 1, VALIDATORS_MIN)
+}
+
 /// The minimum weight threshold required
 /// when updating validator information, 2/3.
 pub const COSIG_THRESHOLD_DEFAULT: [u64; 2] = [2, 3];
@@ -425,6 +459,10 @@ impl Staking {
                     .into_iter()
                     .filter(|(_, xfr_pk)| vd.body.contains_key(xfr_pk))
                     .collect();
+                vd.retired_td_pubkeys = mem::take(&mut vd.retired_td_pubkeys)
+                    .into_iter()
+                    .filter(|(xfr_pk, _)| vd.body.contains_key(xfr_pk))
+                    .collect();
             }
         }
     }
@@ -499,6 +537,52 @@ impl Staking {
         }
     }
 
+    /// Replace the tendermint key of a validator, see `RotateKeyOps`.
+    ///
+    /// The old address keeps pointing to the validator until the old key
+    /// has been removed from tendermint, so the votes and evidences
+    /// of the old key are still counted during the transition.
+    pub fn validator_rotate_td_key(
+        &mut self,
+        id: &XfrPublicKey,
+        new_td_pubkey: Vec<u8>,
+    ) -> Result<()> {
+        let new_addr = td_pubkey_to_td_addr(&new_td_pubkey);
+        let vd = self.validator_get_current_mut().c(d!())?;
+
+        if vd.addr_td_to_app.contains_key(&new_addr) {
+            return Err(eg!("the new key is in use"));
+        }
+        if vd.retired_td_pubkeys.contains_key(id) {
+            return Err(eg!("the previous rotation has not finished"));
+        }
+
+        let v = vd.body.get_mut(id).c(d!("validator not exists"))?;
+        let old_td_pubkey = mem::replace(&mut v.td_pubkey, new_td_pubkey);
+        v.td_addr = td_pubkey_to_td_addr_bytes(&v.td_pubkey);
+
+        vd.addr_td_to_app.insert(new_addr, *id);
+        vd.retired_td_pubkeys.insert(*id, old_td_pubkey);
+
+        Ok(())
+    }
+
+    /// Forget the retired tendermint keys
+    /// which are not in the consensus any more,
+    /// `active` are the addresses of the last commit.
+    pub fn validator_clean_retired_td_keys(&mut self, active: &BTreeSet<&Vec<u8>>) {
+        if let Some(vd) = self.validator_get_current_mut() {
+            let (keep, done): (BTreeMap<_, _>, BTreeMap<_, _>) =
+                mem::take(&mut vd.retired_td_pubkeys).into_iter().partition(
+                    |(_, pk)| active.contains(&td_pubkey_to_td_addr_bytes(pk)),
+                );
+            vd.retired_td_pubkeys = keep;
+            done.values().for_each(|pk| {
+                vd.addr_td_to_app.remove(&td_pubkey_to_td_addr(pk));
+            });
+        }
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn validator_check_power_x(
@@ -511,12 +595,13 @@ impl Staking {
             return Err(eg!("global power overflow"));
         }
 
-        if ((power + new_power) as u128)
-            .checked_mul(MAX_POWER_PERCENT_PER_VALIDATOR[1])
-            .c(d!())?
-            > MAX_POWER_PERCENT_PER_VALIDATOR[0]
-                .checked_mul(global_power as u128)
+        if !validator_rules_relaxed()
+            && ((power + new_power) as u128)
+                .checked_mul(MAX_POWER_PERCENT_PER_VALIDATOR[1])
                 .c(d!())?
+                > MAX_POWER_PERCENT_PER_VALIDATOR[0]
+                    .checked_mul(global_power as u128)
+                    .c(d!())?
         {
             return Err(eg!("validator power overflow"));
         }
@@ -1269,6 +1354,21 @@ impl Staking {
     }
 
     fn coinbase_pay(&mut self, tx: &Transaction) -> Result<()> {
+        // check payments of transfer plans first, to avoid partial updates
+        let mut plan_ids = BTreeSet::new();
+        for o in tx.body.operations.iter() {
+            if let Operation::MintFra(ref ops) = o {
+                for et in ops.entries.iter() {
+                    if let MintKind::TransferPlan(id) = et.kind {
+                        if !plan_ids.insert(id) {
+                            return Err(eg!("duplicate payments of a transfer plan"));
+                        }
+                        self.transfer_plan_check_pay(id, et).c(d!())?;
+                    }
+                }
+            }
+        }
+
         let mut cbb = self.coinbase.balance;
         let mut cbb_principal = self.coinbase.principal_balance;
 
@@ -1309,6 +1409,9 @@ impl Staking {
                             *am = 0;
                         }
                     }
+                    if let MintKind::TransferPlan(id) = et.kind {
+                        self.transfer_plan_pay(id, et);
+                    }
                 }
             }
         }
@@ -1355,6 +1458,124 @@ impl Staking {
         &self.coinbase.distribution_plan
     }
 
+    /// Register a new transfer plan, its escrow has been checked by the caller.
+    pub fn transfer_plan_register(
+        &mut self,
+        owner: XfrPublicKey,
+        plan: TransferPlan,
+    ) -> Result<PlanId> {
+        let id = self.coinbase.next_plan_id;
+        self.coinbase.next_plan_id = id.checked_add(1).c(d!("overflow"))?;
+
+        let next_height = self.cur_height.checked_add(plan.interval).c(d!())?;
+        self.coinbase.transfer_plans.insert(
+            id,
+            ScheduledTransfer {
+                id,
+                owner,
+                plan,
+                next_height,
+                paid_cnt: 0,
+                cancelled: false,
+            },
+        );
+
+        Ok(id)
+    }
+
+    /// Cancel a transfer plan, the remaining escrow will be
+    /// refunded to its owner by the next CoinBase transaction.
+    pub fn transfer_plan_cancel(
+        &mut self,
+        owner: &XfrPublicKey,
+        id: PlanId,
+    ) -> Result<()> {
+        let p = self
+            .coinbase
+            .transfer_plans
+            .get_mut(&id)
+            .c(d!("plan not found"))?;
+        if &p.owner != owner {
+            return Err(eg!("not the owner of the plan"));
+        }
+        if p.cancelled {
+            return Err(eg!("already cancelled"));
+        }
+        p.cancelled = true;
+        Ok(())
+    }
+
+    /// Get a registered transfer plan by its id.
+    #[inline(always)]
+    pub fn transfer_plan_get(&self, id: PlanId) -> Option<&ScheduledTransfer> {
+        self.coinbase.transfer_plans.get(&id)
+    }
+
+    /// All the active transfer plans, ordered by id.
+    #[inline(always)]
+    pub fn transfer_plan_get_all(&self) -> &BTreeMap<PlanId, ScheduledTransfer> {
+        &self.coinbase.transfer_plans
+    }
+
+    /// Payments and refunds to do at current height, ordered by plan id,
+    /// return (plan id, receiver, amount, asset type).
+    pub fn transfer_plan_get_due(
+        &self,
+    ) -> Vec<(PlanId, XfrPublicKey, Amount, AssetType)> {
+        self.coinbase
+            .transfer_plans
+            .values()
+            .filter_map(|p| {
+                p.due(self.cur_height)
+                    .map(|(pk, am)| (p.id, pk, am, p.plan.asset_type))
+            })
+            .collect()
+    }
+
+    /// Every entry must exactly match a payment or a refund of the plan,
+    /// or the CoinBase transaction will be rejected,
+    /// return `true` if it is a refund.
+    ///
+    /// NOTE:
+    /// a payment which has been scheduled before the cancellation
+    /// in the same block is still valid, the refund will be done later.
+    pub fn transfer_plan_check_pay(&self, id: PlanId, et: &MintEntry) -> Result<bool> {
+        let p = self
+            .coinbase
+            .transfer_plans
+            .get(&id)
+            .c(d!("plan not found"))?;
+
+        if et.asset_type != p.plan.asset_type {
+            return Err(eg!("asset type mismatch"));
+        }
+
+        if et.target_pk == p.plan.recipient
+            && et.amount == p.plan.amount
+            && p.next_height <= self.cur_height
+            && p.paid_cnt < p.plan.count
+        {
+            Ok(false)
+        } else if p.cancelled && et.target_pk == p.owner && et.amount == p.remaining() {
+            Ok(true)
+        } else {
+            Err(eg!("invalid payment of transfer plan"))
+        }
+    }
+
+    // Must be checked by `transfer_plan_check_pay` first.
+    fn transfer_plan_pay(&mut self, id: PlanId, et: &MintEntry) {
+        let is_refund = pnk!(self.transfer_plan_check_pay(id, et));
+        let p = pnk!(self.coinbase.transfer_plans.get_mut(&id));
+        if !is_refund {
+            p.paid_cnt += 1;
+            p.next_height = p.next_height.saturating_add(p.plan.interval);
+        }
+        if is_refund || p.paid_cnt == p.plan.count {
+            self.coinbase.transfer_plans.remove(&id);
+        }
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     pub(crate) fn set_proposer_rewards(
         &mut self,
@@ -1493,6 +1714,10 @@ pub struct ValidatorData {
     pub body: BTreeMap<XfrPublicKey, Validator>,
     // <tendermint validator address> => XfrPublicKey
     addr_td_to_app: BTreeMap<TendermintAddr, XfrPublicKey>,
+    // XfrPublicKey => <tendermint pubkey replaced by a key rotation>,
+    // kept until tendermint has removed the old key from the consensus
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    retired_td_pubkeys: BTreeMap<XfrPublicKey, Vec<u8>>,
 }
 
 impl Default for ValidatorData {
@@ -1502,6 +1727,7 @@ impl Default for ValidatorData {
             cosig_rule: pnk!(Self::gen_cosig_rule()),
             body: BTreeMap::new(),
             addr_td_to_app: BTreeMap::new(),
+            retired_td_pubkeys: BTreeMap::new(),
         }
     }
 }
@@ -1529,6 +1755,7 @@ impl ValidatorData {
             cosig_rule,
             body,
             addr_td_to_app,
+            retired_td_pubkeys: BTreeMap::new(),
         })
     }
 
@@ -1579,6 +1806,13 @@ impl ValidatorData {
     pub fn get_validator_addr_map(&self) -> &BTreeMap<TendermintAddr, XfrPublicKey> {
         &self.addr_td_to_app
     }
+
+    /// The tendermint pubkeys replaced by key rotations,
+    /// which have not been removed from tendermint yet.
+    #[inline(always)]
+    pub fn get_retired_td_pubkeys(&self) -> &BTreeMap<XfrPublicKey, Vec<u8>> {
+        &self.retired_td_pubkeys
+    }
 }
 
 // the same address is not allowed to delegate twice at the same time,
@@ -1954,6 +2188,12 @@ struct CoinBase {
 
     // this will be updated dynamiclly along with txs
     principal_balance: Amount,
+
+    // active transfer plans, paid by CoinBase
+    #[serde(default)]
+    transfer_plans: BTreeMap<PlanId, ScheduledTransfer>,
+    #[serde(default)]
+    next_plan_id: PlanId,
 }
 
 impl Default for CoinBase {
@@ -1969,6 +2209,8 @@ impl CoinBase {
             distribution_plan: BTreeMap::new(),
             balance: ops::mint_fra::MINT_AMOUNT_LIMIT,
             principal_balance: 0,
+            transfer_plans: BTreeMap::new(),
+            next_plan_id: 0,
         }
     }
 }
@@ -2076,6 +2318,7 @@ pub fn gen_random_keypair() -> XfrKeyPair {
 #[allow(missing_docs)]
 mod test {
     use super::*;
+    use crate::data_model::ASSET_TYPE_FRA;
     use rand::random;
 
     // **NOTE**
@@ -2109,6 +2352,125 @@ mod test {
         });
     }
 
+    #[test]
+    fn staking_transfer_plan() {
+        let mut staking = Staking::new();
+        let owner = gen_random_keypair().get_pk();
+        let recipient = gen_random_keypair().get_pk();
+
+        let plan = TransferPlan::new(recipient, ASSET_TYPE_FRA, 100, 2, 3);
+        let id = pnk!(staking.transfer_plan_register(owner, plan));
+        assert!(staking.transfer_plan_get_due().is_empty());
+
+        let pay = |staking: &mut Staking| {
+            let entries = staking
+                .transfer_plan_get_due()
+                .into_iter()
+                .map(|(id, k, n, ty)| {
+                    MintEntry::new(MintKind::TransferPlan(id), k, None, n, ty)
+                })
+                .collect::<Vec<_>>();
+            let tx = Transaction::from_operation_coinbase_mint(
+                Operation::MintFra(ops::mint_fra::MintFraOps::new(
+                    staking.cur_height(),
+                    entries,
+                )),
+                staking.cur_height(),
+            );
+            staking.coinbase_check_and_pay(&tx)
+        };
+
+        staking.set_custom_block_height(2);
+        assert_eq!(
+            vec![(id, recipient, 100, ASSET_TYPE_FRA)],
+            staking.transfer_plan_get_due()
+        );
+        pnk!(pay(&mut staking));
+        assert_eq!(1, staking.transfer_plan_get(id).unwrap().paid_cnt);
+
+        // paid, not due again until the next interval
+        assert!(staking.transfer_plan_get_due().is_empty());
+        assert!(pay(&mut staking).is_ok());
+
+        // a forged payment
+        let forged = Transaction::from_operation_coinbase_mint(
+            Operation::MintFra(ops::mint_fra::MintFraOps::new(
+                2,
+                vec![MintEntry::new(
+                    MintKind::TransferPlan(id),
+                    recipient,
+                    None,
+                    100,
+                    ASSET_TYPE_FRA,
+                )],
+            )),
+            2,
+        );
+        assert!(staking.coinbase_check_and_pay(&forged).is_err());
+
+        assert!(staking.transfer_plan_cancel(&recipient, id).is_err());
+        pnk!(staking.transfer_plan_cancel(&owner, id));
+        assert!(staking.transfer_plan_cancel(&owner, id).is_err());
+
+        // refund the remaining escrow
+        assert_eq!(
+            vec![(id, owner, 200, ASSET_TYPE_FRA)],
+            staking.transfer_plan_get_due()
+        );
+        pnk!(pay(&mut staking));
+        assert!(staking.transfer_plan_get(id).is_none());
+    }
+
+    #[test]
+    fn staking_rotate_td_key() {
+        let mut staking = Staking::new();
+        let id = gen_random_keypair().get_pk();
+        let (old_pk, new_pk) = (vec![1; 32], vec![2; 32]);
+        let (old_addr, new_addr) =
+            (td_pubkey_to_td_addr(&old_pk), td_pubkey_to_td_addr(&new_pk));
+
+        let v = pnk!(Validator::new(
+            old_pk.clone(),
+            100,
+            id,
+            [1, 100],
+            StakerMemo::default(),
+            ValidatorKind::Initor,
+        ));
+        pnk!(staking.validator_set_at_height(1, pnk!(ValidatorData::new(1, vec![v]))));
+        staking.set_custom_block_height(1);
+
+        let stranger = gen_random_keypair().get_pk();
+        assert!(staking
+            .validator_rotate_td_key(&stranger, new_pk.clone())
+            .is_err());
+        assert!(staking
+            .validator_rotate_td_key(&id, old_pk.clone())
+            .is_err());
+
+        pnk!(staking.validator_rotate_td_key(&id, new_pk.clone()));
+        assert_eq!(new_addr, pnk!(staking.validator_app_pk_to_td_addr(&id)));
+        // the old address is kept during the transition
+        assert_eq!(id, pnk!(staking.validator_td_addr_to_app_pk(&old_addr)));
+        assert_eq!(id, pnk!(staking.validator_td_addr_to_app_pk(&new_addr)));
+        // only one rotation at the same time
+        assert!(staking.validator_rotate_td_key(&id, vec![3; 32]).is_err());
+
+        // the old key is still in the consensus
+        let old_td_addr = td_pubkey_to_td_addr_bytes(&old_pk);
+        let active = vec![&old_td_addr].into_iter().collect::<BTreeSet<_>>();
+        staking.validator_clean_retired_td_keys(&active);
+        assert!(staking.validator_td_addr_to_app_pk(&old_addr).is_ok());
+
+        // removed from the consensus
+        staking.validator_clean_retired_td_keys(&BTreeSet::new());
+        assert!(staking.validator_td_addr_to_app_pk(&old_addr).is_err());
+        assert_eq!(id, pnk!(staking.validator_td_addr_to_app_pk(&new_addr)));
+        assert!(pnk!(staking.validator_get_current())
+            .get_retired_td_pubkeys()
+            .is_empty());
+    }
+
     fn gen_round_vote_percent(lower_bound: u64, upper_bound: u64) -> [u64; 2] {
         let itv = upper_bound - lower_bound;
         let lb = if 0 == itv {
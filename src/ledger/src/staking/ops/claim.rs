@@ -51,6 +51,12 @@ impl ClaimOps {
         vec![self.pubkey]
     }
 
+    /// How much to claim, all the rewards if none
+    #[inline(always)]
+    pub fn get_amount(&self) -> Option<u64> {
+        self.body.amount
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn new(keypair: &XfrKeyPair, amount: Option<u64>, nonce: NoReplayToken) -> Self {
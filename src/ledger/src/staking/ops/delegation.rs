@@ -111,6 +111,18 @@ impl DelegationOps {
         vec![self.pubkey]
     }
 
+    /// The validator delegated to
+    #[inline(always)]
+    pub fn get_validator(&self) -> &TendermintAddr {
+        &self.body.validator
+    }
+
+    /// If this is the self-delegation of a new validator
+    #[inline(always)]
+    pub fn is_staking(&self) -> bool {
+        self.body.new_validator.is_some()
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn new(
@@ -7,7 +7,7 @@
 use crate::staking::BlockHeight;
 use crate::{
     data_model::TxOutput,
-    staking::{Amount, FRA},
+    staking::{ops::transfer_plan::PlanId, Amount, FRA},
 };
 use rand_chacha::ChaChaRng;
 use rand_core::SeedableRng;
@@ -85,6 +85,7 @@ impl MintEntry {
             id: None,
             record: ba,
             lien: None,
+            multisig: None,
         };
 
         MintEntry {
@@ -103,4 +104,6 @@ pub enum MintKind {
     Claim,
     UnStake,
     Other,
+    /// Payments and refunds of a transfer plan
+    TransferPlan(PlanId),
 }
@@ -12,6 +12,8 @@ pub mod delegation;
 pub mod fra_distribution;
 pub mod governance;
 pub mod mint_fra;
+pub mod rotate_key;
+pub mod transfer_plan;
 pub mod undelegation;
 pub mod update_staker;
 pub mod update_validator;
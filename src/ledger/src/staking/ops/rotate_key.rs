@@ -0,0 +1,159 @@
+//!
+//! # Consensus Key Rotation
+//!
+//! Replace the tendermint key of a validator, without unstaking and staking again.
+//!
+//! The new key joins the consensus with the power of the validator,
+//! and the old key is removed from tendermint at the same update,
+//! see `Staking::validator_rotate_td_key`.
+//!
+
+use crate::{
+    data_model::{NoReplayToken, Transaction},
+    staking::{td_pubkey_to_td_addr, Staking, TendermintAddr},
+};
+use ed25519_dalek::Signer;
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use tendermint::{signature::Ed25519Signature, PrivateKey, PublicKey, Signature};
+use zei::xfr::sig::{XfrKeyPair, XfrPublicKey, XfrSignature};
+
+/// Used as the inner object of a `RotateKey Operation`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RotateKeyOps {
+    pub(crate) body: Box<Data>,
+    pub(crate) pubkey: XfrPublicKey,
+    signature: XfrSignature,
+    // signed by the new tendermint key
+    v_signature: Option<Ed25519Signature>,
+}
+
+impl RotateKeyOps {
+    /// Check the validity of an operation by running it in a staking simulator.
+    #[inline(always)]
+    pub fn check_run(
+        &self,
+        staking_simulator: &mut Staking,
+        tx: &Transaction,
+    ) -> Result<()> {
+        self.apply(staking_simulator, tx).c(d!())
+    }
+
+    fn apply(&self, staking: &mut Staking, _tx: &Transaction) -> Result<()> {
+        self.verify().c(d!())?;
+
+        let id = staking
+            .validator_td_addr_to_app_pk(&self.body.validator)
+            .c(d!())?;
+        if id != self.pubkey {
+            return Err(eg!("not the owner of the validator"));
+        }
+
+        staking
+            .validator_rotate_td_key(&id, self.body.new_td_pubkey.clone())
+            .c(d!())
+    }
+
+    /// verify signature
+    #[inline(always)]
+    pub fn verify(&self) -> Result<()> {
+        // the new key must be owned by the validator
+        let v_sig = self
+            .v_signature
+            .as_ref()
+            .ok_or(eg!("missing validator signature"))?;
+        if PublicKey::from_raw_ed25519(&self.body.new_td_pubkey)
+            .c(d!())?
+            .verify(&self.body.to_bytes(), &Signature::from(*v_sig))
+            .is_err()
+        {
+            return Err(eg!("tendermint key verification failed"));
+        }
+        self.pubkey
+            .verify(&self.body.to_bytes(), &self.signature)
+            .c(d!())
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_related_pubkeys(&self) -> Vec<XfrPublicKey> {
+        vec![self.pubkey]
+    }
+
+    /// The tendermint address of the new key.
+    #[inline(always)]
+    pub fn get_new_td_addr(&self) -> TendermintAddr {
+        td_pubkey_to_td_addr(&self.body.new_td_pubkey)
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(
+        keypair: &XfrKeyPair,
+        new_vltor_key: &PrivateKey,
+        validator: TendermintAddr,
+        nonce: NoReplayToken,
+    ) -> Result<Self> {
+        let vkp = new_vltor_key
+            .ed25519_keypair()
+            .c(d!("unsupported tendermint key"))?;
+        let new_td_pubkey = vkp.public.to_bytes().to_vec();
+        let body = Box::new(Data::new(validator, new_td_pubkey, nonce));
+        let signature = keypair.sign(&body.to_bytes());
+        let v_signature = vkp.sign(&body.to_bytes());
+        Ok(RotateKeyOps {
+            body,
+            pubkey: keypair.get_pk(),
+            signature,
+            v_signature: Some(v_signature),
+        })
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn set_nonce(&mut self, nonce: NoReplayToken) {
+        self.body.set_nonce(nonce);
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_nonce(&self) -> NoReplayToken {
+        self.body.get_nonce()
+    }
+}
+
+/// The body of a key rotation operation.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Data {
+    /// the current tendermint address of the validator
+    pub validator: TendermintAddr,
+    /// the new tendermint pubkey
+    pub new_td_pubkey: Vec<u8>,
+    nonce: NoReplayToken,
+}
+
+impl Data {
+    #[inline(always)]
+    fn new(v: TendermintAddr, new_td_pubkey: Vec<u8>, nonce: NoReplayToken) -> Self {
+        Data {
+            validator: v,
+            new_td_pubkey,
+            nonce,
+        }
+    }
+
+    #[inline(always)]
+    fn to_bytes(&self) -> Vec<u8> {
+        pnk!(bincode::serialize(self))
+    }
+
+    #[inline(always)]
+    fn set_nonce(&mut self, nonce: NoReplayToken) {
+        self.nonce = nonce;
+    }
+
+    #[inline(always)]
+    fn get_nonce(&self) -> NoReplayToken {
+        self.nonce
+    }
+}
@@ -0,0 +1,339 @@
+//!
+//! # Transfer Plan
+//!
+//! Scheduled and recurring transfers, eg. payrolls.
+//!
+//! The total amount of a plan is escrowed to `BLACK_HOLE_PUBKEY_STAKING`
+//! by a `TransferAsset` in the same transaction, then it will be paid
+//! by the CoinBase(`MintFra`) every `interval` blocks,
+//! until all payments have been done or the plan is cancelled by its owner,
+//! the remaining escrow will be refunded to the owner in the latter case.
+//!
+//! FRA and custom non-confidential assets can be planned,
+//! the escrow and the payments are of the asset type of the plan.
+//! The coinbase pays the plans without checking the rules of the asset,
+//! eg. `transferable`, the transfer signature rules, the credentials
+//! or the tracing policies, so the ledger only accepts plans of
+//! the assets without transfer restrictions.
+//!
+
+use crate::{
+    data_model::{NoReplayToken, Operation, Transaction, BLACK_HOLE_PUBKEY_STAKING},
+    staking::{deny_relative_inputs, Amount, BlockHeight, Staking},
+};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use zei::xfr::{
+    sig::{XfrKeyPair, XfrPublicKey, XfrSignature},
+    structs::{AssetType, XfrAmount, XfrAssetType},
+};
+
+/// The unique id of a registered plan.
+pub type PlanId = u64;
+
+/// Used as the inner object of a `TransferPlan Operation`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TransferPlanOps {
+    pub(crate) body: Data,
+    pub(crate) pubkey: XfrPublicKey,
+    signature: XfrSignature,
+}
+
+impl TransferPlanOps {
+    /// Check the validity of an operation by running it in a staking simulator.
+    #[inline(always)]
+    pub fn check_run(
+        &self,
+        staking_simulator: &mut Staking,
+        tx: &Transaction,
+    ) -> Result<()> {
+        self.apply(staking_simulator, tx).c(d!())
+    }
+
+    /// Apply the plan action to the target `Staking` instance.
+    pub fn apply(&self, staking: &mut Staking, tx: &Transaction) -> Result<()> {
+        self.verify().c(d!())?;
+
+        match &self.body.action {
+            TransferPlanAction::Register(plan) => {
+                plan.check().c(d!())?;
+                let am = check_transfer_plan_context(tx, self.pubkey, plan.asset_type)
+                    .c(d!("escrow is not paid correctly"))?;
+                if am != plan.total().c(d!())? {
+                    return Err(eg!("escrow amount mismatch"));
+                }
+                staking
+                    .transfer_plan_register(self.pubkey, plan.clone())
+                    .c(d!())
+                    .map(|_| ())
+            }
+            TransferPlanAction::Cancel(id) => {
+                staking.transfer_plan_cancel(&self.pubkey, *id).c(d!())
+            }
+        }
+    }
+
+    /// Verify signature.
+    #[inline(always)]
+    pub fn verify(&self) -> Result<()> {
+        self.pubkey
+            .verify(&self.body.to_bytes(), &self.signature)
+            .c(d!())
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_related_pubkeys(&self) -> Vec<XfrPublicKey> {
+        match &self.body.action {
+            TransferPlanAction::Register(plan) => vec![self.pubkey, plan.recipient],
+            TransferPlanAction::Cancel(_) => vec![self.pubkey],
+        }
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(
+        keypair: &XfrKeyPair,
+        action: TransferPlanAction,
+        nonce: NoReplayToken,
+    ) -> Self {
+        let body = Data::new(action, nonce);
+        let signature = keypair.sign(&body.to_bytes());
+        TransferPlanOps {
+            body,
+            pubkey: keypair.get_pk(),
+            signature,
+        }
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn set_nonce(&mut self, nonce: NoReplayToken) {
+        self.body.set_nonce(nonce);
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_nonce(&self) -> NoReplayToken {
+        self.body.get_nonce()
+    }
+}
+
+/// The body of a transfer plan operation.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Data {
+    /// what to do
+    pub action: TransferPlanAction,
+    nonce: NoReplayToken,
+}
+
+impl Data {
+    #[inline(always)]
+    fn new(action: TransferPlanAction, nonce: NoReplayToken) -> Self {
+        Data { action, nonce }
+    }
+
+    #[inline(always)]
+    fn to_bytes(&self) -> Vec<u8> {
+        pnk!(bincode::serialize(self))
+    }
+
+    #[inline(always)]
+    fn set_nonce(&mut self, nonce: NoReplayToken) {
+        self.nonce = nonce;
+    }
+
+    #[inline(always)]
+    fn get_nonce(&self) -> NoReplayToken {
+        self.nonce
+    }
+}
+
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TransferPlanAction {
+    /// Register a new plan, the escrow must be paid in the same transaction.
+    Register(TransferPlan),
+    /// Cancel an existing plan, only its owner can do this.
+    Cancel(PlanId),
+}
+
+/// Pay `amount` of `asset_type` to `recipient` every `interval` blocks,
+/// `count` times in total.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TransferPlan {
+    #[allow(missing_docs)]
+    pub recipient: XfrPublicKey,
+    /// FRA, or a custom asset without transfer restrictions
+    pub asset_type: AssetType,
+    /// amount of each payment
+    pub amount: Amount,
+    /// blocks between two payments,
+    /// the first one will be done `interval` blocks after the registration
+    pub interval: BlockHeight,
+    /// how many payments in total
+    pub count: u64,
+}
+
+impl TransferPlan {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn new(
+        recipient: XfrPublicKey,
+        asset_type: AssetType,
+        amount: Amount,
+        interval: BlockHeight,
+        count: u64,
+    ) -> Self {
+        TransferPlan {
+            recipient,
+            asset_type,
+            amount,
+            interval,
+            count,
+        }
+    }
+
+    /// The amount to escrow.
+    #[inline(always)]
+    pub fn total(&self) -> Result<Amount> {
+        self.amount.checked_mul(self.count).c(d!("overflow"))
+    }
+
+    #[inline(always)]
+    fn check(&self) -> Result<()> {
+        if 0 == self.amount || 0 == self.interval || 0 == self.count {
+            return Err(eg!("amount, interval and count must be positive"));
+        }
+        if *BLACK_HOLE_PUBKEY_STAKING == self.recipient {
+            return Err(eg!("invalid recipient"));
+        }
+        self.total().c(d!()).map(|_| ())
+    }
+}
+
+/// A registered plan, tracked by `Staking`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledTransfer {
+    #[allow(missing_docs)]
+    pub id: PlanId,
+    #[allow(missing_docs)]
+    pub owner: XfrPublicKey,
+    #[allow(missing_docs)]
+    pub plan: TransferPlan,
+    /// the height at which the next payment is due
+    pub next_height: BlockHeight,
+    /// how many payments have been done
+    pub paid_cnt: u64,
+    /// cancelled plans are waiting for the refund
+    pub cancelled: bool,
+}
+
+impl ScheduledTransfer {
+    /// The escrowed amount which has not been paid.
+    #[inline(always)]
+    pub fn remaining(&self) -> Amount {
+        self.plan.amount * (self.plan.count - self.paid_cnt)
+    }
+
+    /// The payment to do at height `h`, if any: (receiver, amount).
+    ///
+    /// For a cancelled plan, it is the refund to its owner.
+    #[inline(always)]
+    pub fn due(&self, h: BlockHeight) -> Option<(XfrPublicKey, Amount)> {
+        if self.cancelled {
+            Some((self.owner, self.remaining()))
+        } else if self.next_height <= h {
+            Some((self.plan.recipient, self.plan.amount))
+        } else {
+            None
+        }
+    }
+}
+
+// Return the amount escrowed by the owner of the plan.
+fn check_transfer_plan_context(
+    tx: &Transaction,
+    owner: XfrPublicKey,
+    asset_type: AssetType,
+) -> Result<Amount> {
+    // Transfers to `BLACK_HOLE_PUBKEY_STAKING` are also
+    // counted by delegations and account conversions,
+    // so they can not be mixed with a plan in one transaction.
+    let mut plan_cnt = 0;
+    for op in tx.body.operations.iter() {
+        match op {
+            Operation::TransferPlan(_) => plan_cnt += 1,
+            Operation::Delegation(_) | Operation::ConvertAccount(_) => {
+                return Err(eg!("can not be mixed with delegations or conversions"));
+            }
+            _ => {}
+        }
+    }
+    if 1 != plan_cnt {
+        return Err(eg!("only one plan operation is allowed per transaction"));
+    }
+
+    let target_pk = *BLACK_HOLE_PUBKEY_STAKING;
+
+    let am = tx
+        .body
+        .operations
+        .iter()
+        .map(|op| {
+            if let Operation::TransferAsset(ref x) = op {
+                deny_relative_inputs(x).c(d!())?;
+
+                if x.body.outputs.iter().any(|o| {
+                    matches!(o.record.asset_type, XfrAssetType::Confidential(_))
+                        || matches!(o.record.amount, XfrAmount::Confidential(_))
+                }) {
+                    return Err(eg!(
+                        "Confidential TXO outputs is not allowed in transfer plans"
+                    ));
+                }
+
+                let keynum = x
+                    .body
+                    .transfer
+                    .inputs
+                    .iter()
+                    .map(|i| i.public_key)
+                    .collect::<HashSet<_>>()
+                    .len();
+
+                // all inputs must be owned by the owner of the plan
+                if 1 == keynum && owner == x.body.transfer.inputs[0].public_key {
+                    let am = x
+                        .body
+                        .outputs
+                        .iter()
+                        .flat_map(|o| {
+                            if let (
+                                XfrAssetType::NonConfidential(ty),
+                                XfrAmount::NonConfidential(i_am),
+                            ) = (o.record.asset_type, o.record.amount)
+                            {
+                                if ty == asset_type && target_pk == o.record.public_key {
+                                    return Some(i_am);
+                                }
+                            }
+                            None
+                        })
+                        .try_fold(0u64, |acc, i| acc.checked_add(i))
+                        .c(d!("overflow"))?;
+
+                    return Ok(am);
+                }
+            }
+            Ok(0)
+        })
+        .collect::<Result<Vec<_>>>()
+        .c(d!())?
+        .iter()
+        .try_fold(0u64, |acc, i| acc.checked_add(*i))
+        .c(d!("overflow"))?;
+
+    alt!(0 < am, Ok(am), Err(eg!()))
+}
@@ -6,7 +6,7 @@
 
 use crate::{
     data_model::{NoReplayToken, Operation, Transaction},
-    staking::{PartialUnDelegation, Staking},
+    staking::{Amount, PartialUnDelegation, Staking},
 };
 use ruc::*;
 use serde::{Deserialize, Serialize};
@@ -58,6 +58,12 @@ impl UnDelegationOps {
         vec![self.pubkey]
     }
 
+    /// The amount of a partial undelegation, all the delegation if none
+    #[inline(always)]
+    pub fn get_amount(&self) -> Option<Amount> {
+        self.body.pu.as_ref().map(|pu| pu.am)
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn new(
@@ -8,8 +8,8 @@
 use crate::{
     data_model::NoReplayToken,
     staking::{
-        cosig::CoSigOp, BlockHeight, Staking, Validator, ValidatorData,
-        COSIG_THRESHOLD_DEFAULT, VALIDATORS_MIN,
+        cosig::CoSigOp, validators_min, BlockHeight, Staking, Validator, ValidatorData,
+        COSIG_THRESHOLD_DEFAULT,
     },
 };
 use ruc::*;
@@ -50,7 +50,7 @@ impl UpdateValidatorOps {
 
     #[inline(always)]
     fn check_context(&self) -> Result<()> {
-        if VALIDATORS_MIN > self.data.body.len() {
+        if validators_min() > self.data.body.len() {
             return Err(eg!("too few validators"));
         }
 
@@ -156,6 +156,7 @@ pub fn create_issue_and_transfer_txn(
                 id: None,
                 record: ba.clone(),
                 lien: None,
+                multisig: None,
             },
             None,
         )],
@@ -242,6 +243,7 @@ pub fn create_issue_and_transfer_txn_with_asset_tracing(
                 id: None,
                 record: ba.clone(),
                 lien: None,
+                multisig: None,
             },
             None,
         )],
@@ -328,6 +330,7 @@ pub fn create_issuance_txn(
                 id: None,
                 record: ba,
                 lien: None,
+                multisig: None,
             },
             None,
         )],
@@ -4,6 +4,7 @@
 //!
 
 pub mod helpers;
+pub mod policy;
 mod test;
 pub mod utils;
 
@@ -12,19 +13,23 @@ pub use bnc;
 use crate::{
     data_model::{
         AssetType, AssetTypeCode, AuthenticatedBlock, AuthenticatedTransaction,
-        AuthenticatedUtxo, AuthenticatedUtxoStatus, BlockEffect, BlockSID,
-        FinalizedBlock, FinalizedTransaction, IssuerKeyPair, IssuerPublicKey,
-        OutputPosition, StateCommitmentData, Transaction, TransferType, TxnEffect,
-        TxnSID, TxnTempSID, TxoSID, UnAuthenticatedUtxo, Utxo, UtxoStatus,
-        BLACK_HOLE_PUBKEY,
+        AuthenticatedUtxo, AuthenticatedUtxoRangeStatus, AuthenticatedUtxoStatus,
+        BlockEffect, BlockSID, FinalizedBlock, FinalizedTransaction, IssuerKeyPair,
+        IssuerPublicKey, Operation, OutputPosition, StateCommitmentData, Transaction,
+        TransferType, TxnEffect, TxnSID, TxnTempSID, TxoSID, UnAuthenticatedUtxo, Utxo,
+        UtxoStatus, ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY, UTXO_RANGE_LIMIT,
+    },
+    staking::{
+        ops::transfer_plan::TransferPlanAction, Amount, Power, Staking,
+        TendermintAddrRef, FF_PK_LIST, FRA_TOTAL_AMOUNT,
     },
-    staking::{Amount, Power, Staking, TendermintAddrRef, FF_PK_LIST, FRA_TOTAL_AMOUNT},
 };
 use bitmap::{BitMap, SparseMap};
 use bnc::{new_mapx, new_vecx, Mapx, Vecx};
 use cryptohash::sha256::Digest as BitDigest;
 use globutils::{HashOf, ProofOf};
 use merkle_tree::AppendOnlyMerkle;
+use policy::{TxnPolicy, TxnPolicyRegistry};
 use rand_chacha::ChaChaRng;
 use rand_core::SeedableRng;
 use ruc::*;
@@ -36,6 +41,7 @@ use std::{
     io::{BufRead, BufReader, ErrorKind},
     mem,
     ops::{Deref, DerefMut},
+    sync::Arc,
 };
 use zei::xfr::{
     lib::XfrNotePolicies,
@@ -66,6 +72,8 @@ pub struct LedgerState {
     utxo_map: BitMap,
     // current block effect (middle cache)
     block_ctx: Option<BlockEffect>,
+    // custom policies, registered at node start
+    policies: TxnPolicyRegistry,
 
     prng: ChaChaRng,
 }
@@ -82,6 +90,26 @@ impl LedgerState {
         &mut self.prng
     }
 
+    /// Register a custom transaction policy,
+    /// must be done at node start, before any transaction is applied
+    #[inline(always)]
+    pub fn register_txn_policy(&mut self, policy: Arc<dyn TxnPolicy>) -> Result<()> {
+        self.policies.register(policy).c(d!())
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn get_txn_policies(&self) -> &TxnPolicyRegistry {
+        &self.policies
+    }
+
+    /// Run the registered policies against a transaction effect
+    /// without applying it, this is needed by `CheckTx`.
+    #[inline(always)]
+    pub fn check_txn_policies(&self, txe: &TxnEffect) -> Result<()> {
+        self.policies.check(txe, &self.status).c(d!())
+    }
+
     /// Consume a block context and assemble a BlockEffect
     #[inline(always)]
     pub fn start_block(&mut self) -> Result<BlockEffect> {
@@ -105,6 +133,10 @@ impl LedgerState {
         self.status
             .check_txn_effects(&txe)
             .c(d!())
+            .and_then(|_| {
+                // transactions loaded from logs have been accepted
+                alt!(is_loading, Ok(()), self.policies.check(&txe, &self.status))
+            })
             .and_then(|_| block.add_txn_effect(txe, is_loading).c(d!()))
             .and_then(|tmpid| {
                 // NOTE: set at the last position
@@ -409,6 +441,7 @@ impl LedgerState {
             tx_to_block_location: new_mapx!(&tx_to_block_location_path),
             utxo_map: LedgerState::init_utxo_map(&utxo_map_path).c(d!())?,
             block_ctx: Some(BlockEffect::default()),
+            policies: TxnPolicyRegistry::default(),
         };
 
         Ok(ledger)
@@ -797,6 +830,40 @@ impl LedgerState {
         }
     }
 
+    /// Get the status of txos in `[first, first + count)` and a partial bitmap as proof,
+    /// only the bitmap blocks covering the range are included
+    pub fn get_utxo_range_status(
+        &self,
+        first: TxoSID,
+        count: u64,
+    ) -> Result<AuthenticatedUtxoRangeStatus> {
+        if count > UTXO_RANGE_LIMIT {
+            return Err(eg!(format!("at most {} txos", UTXO_RANGE_LIMIT)));
+        }
+
+        let state_commitment_data =
+            self.status.state_commitment_data.as_ref().c(d!())?;
+        let last = first.0.checked_add(count).c(d!("range overflow"))?;
+        let exist_end = last.min(state_commitment_data.txo_count);
+
+        let utxo_map_bytes = alt!(
+            first.0 < exist_end,
+            Some(
+                self.utxo_map
+                    .serialize_range(first.0 as usize, exist_end as usize, 0)
+            ),
+            None
+        );
+
+        Ok(AuthenticatedUtxoRangeStatus {
+            first_sid: first,
+            count,
+            state_commitment_data: state_commitment_data.clone(),
+            utxo_map_bytes,
+            state_commitment: state_commitment_data.compute_commitment(),
+        })
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
     pub fn get_staking(&self) -> &Staking {
@@ -877,6 +944,19 @@ impl LedgerState {
             .state_commitment_versions
             .get((block_height - 1) as usize)
     }
+
+    /// Query the full state commitment data(aka the block header of a light client)
+    /// at the given block height, its hash is the state commitment of that height
+    #[inline(always)]
+    pub fn get_state_commitment_data_at_block_height(
+        &self,
+        block_height: u64,
+    ) -> Option<StateCommitmentData> {
+        block_height
+            .checked_sub(1)
+            .and_then(|idx| self.blocks.get(idx as usize))
+            .map(|b| b.state)
+    }
 }
 
 /// The main LedgerStatus of findora ledger
@@ -932,25 +1012,31 @@ impl LedgerStatus {
 
     #[inline(always)]
     #[allow(missing_docs)]
-    fn get_utxo(&self, id: TxoSID) -> Option<Utxo> {
+    pub fn get_utxo(&self, id: TxoSID) -> Option<Utxo> {
         self.utxos.get(&id)
     }
 
+    /// All the unspent txos, in no particular order
+    #[inline(always)]
+    pub fn iter_utxos(&self) -> impl Iterator<Item = (TxoSID, Utxo)> + '_ {
+        self.utxos.iter()
+    }
+
     #[inline(always)]
     #[allow(missing_docs)]
-    fn get_spent_utxo(&self, addr: TxoSID) -> Option<Utxo> {
+    pub fn get_spent_utxo(&self, addr: TxoSID) -> Option<Utxo> {
         self.spent_utxos.get(&addr)
     }
 
     #[inline(always)]
     #[allow(missing_docs)]
-    fn get_issuance_num(&self, code: &AssetTypeCode) -> Option<u64> {
+    pub fn get_issuance_num(&self, code: &AssetTypeCode) -> Option<u64> {
         self.issuance_num.get(code)
     }
 
     #[inline(always)]
     #[allow(missing_docs)]
-    fn get_asset_type(&self, code: &AssetTypeCode) -> Option<AssetType> {
+    pub fn get_asset_type(&self, code: &AssetTypeCode) -> Option<AssetType> {
         self.asset_types.get(code)
     }
 
@@ -1072,7 +1158,17 @@ impl LedgerStatus {
             // (1)
             let inp_utxo = self.utxos.get(inp_sid).c(d!("Input must be unspent"))?;
             let record = &(inp_utxo.0);
-            if record != inp_record {
+            // the rules of a multisig input are not recorded by the funds
+            // sent to its address by a plain transfer, they can still be spent
+            // under the rules, which are checked against the address by `TxnEffect`
+            let matched = if record.multisig.is_none() && inp_record.multisig.is_some() {
+                let mut inp_record = inp_record.clone();
+                inp_record.multisig = None;
+                *record == inp_record
+            } else {
+                record == inp_record
+            };
+            if !matched {
                 return Err(eg!((format!(
                     "Input must correspond to claimed record: {} != {}",
                     serde_json::to_string(&record).c(d!())?,
@@ -1124,6 +1220,32 @@ impl LedgerStatus {
             }
         }
 
+        // Recipients of credential-gated assets must prove their credentials
+        self.check_credentials(txn_effect).c(d!())?;
+
+        // Transfer plans are paid by the coinbase without any transfer checks,
+        // so only the assets without transfer restrictions can be planned
+        for i in txn_effect.transfer_plans.iter() {
+            if let TransferPlanAction::Register(plan) = &i.body.action {
+                if ASSET_TYPE_FRA == plan.asset_type {
+                    continue;
+                }
+                let code = AssetTypeCode {
+                    val: plan.asset_type,
+                };
+                let asset_type = self
+                    .asset_types
+                    .get(&code)
+                    .or_else(|| txn_effect.new_asset_codes.get(&code).cloned())
+                    .c(d!("unknown asset type"))?;
+                if asset_type.has_transfer_restrictions() {
+                    return Err(eg!(
+                        "assets with transfer restrictions can not be planned"
+                    ));
+                }
+            }
+        }
+
         // New asset types must not already exist
         for (code, _asset_type) in txn_effect.new_asset_codes.iter() {
             if self.asset_types.contains_key(&code) {
@@ -1237,6 +1359,78 @@ impl LedgerStatus {
         Ok(())
     }
 
+    // Every output of a transfer, whose asset type has a credential requirement,
+    // must be accompanied by a valid `CredentialProof` of its recipient,
+    // except those sent back to the asset issuer.
+    //
+    // NOTE: confidential asset types are not checked here,
+    // since such assets can not become confidential at all,
+    // see the check of `confidential_transfer_inputs`.
+    fn check_credentials(&self, txn_effect: &TxnEffect) -> Result<()> {
+        let proofs = &txn_effect.txn.body.credentials;
+        let mut verified: HashSet<(AssetTypeCode, XfrPublicKey)> = HashSet::new();
+
+        let outputs = txn_effect
+            .txn
+            .body
+            .operations
+            .iter()
+            .filter_map(|op| match op {
+                Operation::TransferAsset(xfr) => Some(xfr.body.outputs.iter()),
+                _ => None,
+            })
+            .flatten();
+
+        for output in outputs {
+            let code = if let Some(code) = output
+                .record
+                .asset_type
+                .get_asset_type()
+                .map(|v| AssetTypeCode { val: v })
+            {
+                code
+            } else {
+                continue;
+            };
+            let recipient = output.record.public_key;
+            if verified.contains(&(code, recipient)) {
+                continue;
+            }
+
+            let asset_type = self
+                .asset_types
+                .get(&code)
+                .or_else(|| txn_effect.new_asset_codes.get(&code).cloned())
+                .c(d!())?;
+            let requirement = if let Some(r) = asset_type
+                .properties
+                .asset_rules
+                .credential_requirement
+                .as_ref()
+            {
+                r
+            } else {
+                continue;
+            };
+            if asset_type.properties.issuer.key == recipient {
+                continue;
+            }
+
+            proofs
+                .iter()
+                .filter(|p| p.address == recipient)
+                .find(|p| p.verify(requirement).is_ok())
+                .c(d!(format!(
+                    "missing valid credential of {} for asset {}",
+                    globutils::wallet::public_key_to_bech32(&recipient),
+                    code.to_base64()
+                )))?;
+            verified.insert((code, recipient));
+        }
+
+        Ok(())
+    }
+
     // This function assumes that `block` is COMPLETELY CONSISTENT with the
     // ledger state. Calling `check_txn_effects` for each TxnEffect getting
     // mixed into the BlockEffect *should* be enough to guarantee that (if
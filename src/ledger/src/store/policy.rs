@@ -0,0 +1,227 @@
+//!
+//! # Pluggable Transaction Policies
+//!
+//! Custom business rules(KYC-only assets, transfer limits, sanctions lists ...)
+//! can be attached to a ledger without touching the core validation logics.
+//!
+//! A policy is invoked with the effect of a transaction and the current `LedgerStatus`,
+//! after all the builtin checks have passed, and before the effect is added to the block.
+//!
+//! All policies must be registered at node start, and every validator must
+//! register the same set of policies, or the network will fork.
+//! For the same reason, a policy must be a pure function of its inputs,
+//! never depend on local clocks, randomness or any other node-local data.
+//!
+
+use super::LedgerStatus;
+use crate::data_model::{AssetTypeCode, TxnCheckInputs, TxnEffect};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+use zei::xfr::{sig::XfrPublicKey, structs::BlindAssetRecord};
+
+/// A validation hook for transactions.
+pub trait TxnPolicy: Send + Sync {
+    /// The unique name of the policy,
+    /// a transaction refers to a policy by this name
+    /// in `TxnCheckInputs::which_check` of its `policy_options`.
+    fn name(&self) -> &str;
+
+    /// Whether the policy applies to the asset,
+    /// a policy is invoked only if at least one of the assets
+    /// involved in a transaction is applicable.
+    fn applies_to(&self, code: &AssetTypeCode) -> bool;
+
+    /// Whether the policy applies to every transaction,
+    /// including those only involving confidential asset types.
+    fn applies_to_all(&self) -> bool {
+        false
+    }
+
+    /// Check the effect of a transaction,
+    /// `inputs` are those carried by the transaction for this policy, if any.
+    fn check(
+        &self,
+        txe: &TxnEffect,
+        status: &LedgerStatus,
+        inputs: &[(AssetTypeCode, &TxnCheckInputs)],
+    ) -> Result<()>;
+}
+
+/// The policies registered in a ledger, they are invoked in the order of their names.
+#[derive(Clone, Default)]
+pub struct TxnPolicyRegistry {
+    policies: BTreeMap<String, Arc<dyn TxnPolicy>>,
+}
+
+impl fmt::Debug for TxnPolicyRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.policies.keys()).finish()
+    }
+}
+
+impl TxnPolicyRegistry {
+    /// Register a policy, names must be unique.
+    pub fn register(&mut self, policy: Arc<dyn TxnPolicy>) -> Result<()> {
+        let name = policy.name().to_owned();
+        if name.is_empty() {
+            return Err(eg!("empty policy name"));
+        }
+        if self.policies.contains_key(&name) {
+            return Err(eg!(format!("policy '{}' has been registered", name)));
+        }
+        self.policies.insert(name, policy);
+        Ok(())
+    }
+
+    /// Names of all the registered policies, in order.
+    #[inline(always)]
+    pub fn names(&self) -> Vec<&str> {
+        self.policies.keys().map(|k| k.as_str()).collect()
+    }
+
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// Run all applicable policies against a transaction effect.
+    pub fn check(&self, txe: &TxnEffect, status: &LedgerStatus) -> Result<()> {
+        let options = txe
+            .txn
+            .body
+            .policy_options
+            .as_ref()
+            .map(|o| o.0.as_slice())
+            .unwrap_or_default();
+
+        // Inputs for an unknown policy can not be checked,
+        // reject them instead of ignoring silently.
+        //
+        // NOTE: this is a consensus rule, transactions carrying such inputs
+        // were accepted before the policies existed, so a height gate is needed
+        // before enabling it on a chain with history, or the replay will diverge.
+        for (_, inputs) in options.iter() {
+            if !self.policies.contains_key(&inputs.which_check) {
+                return Err(eg!(format!("unknown policy '{}'", inputs.which_check)));
+            }
+        }
+
+        if self.policies.is_empty() {
+            return Ok(());
+        }
+
+        // Sort to keep the order of invocation deterministic.
+        let mut codes = txe.asset_types_involved.iter().collect::<Vec<_>>();
+        codes.sort();
+
+        for (name, policy) in self.policies.iter() {
+            if !policy.applies_to_all()
+                && !codes.iter().any(|code| policy.applies_to(code))
+            {
+                continue;
+            }
+
+            let inputs = options
+                .iter()
+                .filter(|(_, i)| &i.which_check == name)
+                .map(|(code, i)| (*code, i))
+                .collect::<Vec<_>>();
+
+            policy
+                .check(txe, status, &inputs)
+                .c(d!(format!("rejected by policy '{}'", name)))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A builtin policy: addresses in the list can neither send nor receive
+/// the specified assets(all assets if not specified).
+///
+/// With `assets` specified, only the inputs and outputs of those assets are checked,
+/// eg. a denied address can still pay the FRA fee, records of confidential
+/// asset types can not be told apart and are not checked.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DenyListPolicy {
+    /// None means all assets
+    #[serde(default)]
+    pub assets: Option<HashSet<AssetTypeCode>>,
+    #[allow(missing_docs)]
+    pub addresses: HashSet<XfrPublicKey>,
+}
+
+impl DenyListPolicy {
+    #[allow(missing_docs)]
+    pub const NAME: &'static str = "deny_list";
+
+    /// Load from a json file, eg.
+    /// `{"assets": [[0,0,...]], "addresses": ["<base64 public key>"]}`
+    pub fn from_file(path: &str) -> Result<Self> {
+        std::fs::read(path)
+            .c(d!())
+            .and_then(|b| serde_json::from_slice(&b).c(d!()))
+    }
+}
+
+impl DenyListPolicy {
+    // Whether the asset of a record is in the list
+    fn covers(&self, record: &BlindAssetRecord) -> bool {
+        match self.assets.as_ref() {
+            None => true,
+            Some(assets) => record
+                .asset_type
+                .get_asset_type()
+                .map(|val| assets.contains(&AssetTypeCode { val }))
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl TxnPolicy for DenyListPolicy {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn applies_to(&self, code: &AssetTypeCode) -> bool {
+        self.assets
+            .as_ref()
+            .map(|a| a.contains(code))
+            .unwrap_or(true)
+    }
+
+    fn applies_to_all(&self) -> bool {
+        self.assets.is_none()
+    }
+
+    fn check(
+        &self,
+        txe: &TxnEffect,
+        _status: &LedgerStatus,
+        _inputs: &[(AssetTypeCode, &TxnCheckInputs)],
+    ) -> Result<()> {
+        let senders = txe.input_txos.values();
+        let receivers = txe.txos.iter().flatten();
+
+        for pk in senders
+            .chain(receivers)
+            .filter(|o| self.covers(&o.record))
+            .map(|o| &o.record.public_key)
+        {
+            if self.addresses.contains(pk) {
+                return Err(eg!(format!(
+                    "address {} is denied",
+                    globutils::wallet::public_key_to_bech32(pk)
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
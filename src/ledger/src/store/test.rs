@@ -2,20 +2,37 @@
 #![allow(missing_docs)]
 
 use super::{helpers::*, *};
-use crate::data_model::{
-    AssetRules, AssetTypeCode, IssueAsset, IssueAssetBody, Memo, Operation, Transaction,
-    TransferAsset, TransferAssetBody, TxOutput, TxnEffect, TxoRef, TxoSID,
-    ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY, TX_FEE_MIN,
+use crate::{
+    converter::ConvertAccount,
+    data_model::{
+        AssetRules, AssetTypeCode, CredentialProof, CredentialRequirement, IssueAsset,
+        IssueAssetBody, Memo, Operation, SignatureRules, Transaction, TransferAsset,
+        TransferAssetBody, TxOutput, TxnCheckInputs, TxnEffect, TxnPolicyData, TxoRef,
+        TxoSID, ASSET_TYPE_FRA, BLACK_HOLE_PUBKEY, BLACK_HOLE_PUBKEY_STAKING,
+        TX_FEE_MIN,
+    },
+    staking::{
+        ops::{
+            rotate_key::RotateKeyOps,
+            transfer_plan::{TransferPlan, TransferPlanAction, TransferPlanOps},
+        },
+        td_pubkey_to_td_addr, StakerMemo, Validator, ValidatorData, ValidatorKind,
+    },
+};
+use credentials::{
+    credential_issuer_key_gen, credential_sign, credential_user_key_gen, Credential,
 };
+use fp_types::crypto::MultiSigner;
 use rand_core::SeedableRng;
+use tendermint::PrivateKey;
 use zei::{
     setup::PublicParams,
     xfr::{
         asset_record::{
             build_blind_asset_record, open_blind_asset_record, AssetRecordType,
         },
-        sig::XfrKeyPair,
-        structs::{AssetRecord, AssetRecordTemplate},
+        sig::{XfrKeyPair, XfrPublicKey},
+        structs::{AssetRecord, AssetRecordTemplate, BlindAssetRecord},
     },
 };
 
@@ -191,6 +208,7 @@ fn test_asset_transfer() {
                     id: None,
                     record: ba,
                     lien: None,
+                    multisig: None,
                 },
                 None,
             ),
@@ -199,6 +217,7 @@ fn test_asset_transfer() {
                     id: None,
                     record: second_ba,
                     lien: None,
+                    multisig: None,
                 },
                 None,
             ),
@@ -294,6 +313,26 @@ fn test_asset_transfer() {
     assert!(!input_bar_proof.is_valid(state_commitment));
     assert!(utxo_status.status == UtxoStatus::Spent);
 
+    // Check the statuses of a range of txos at once
+    let range_status = ledger.get_utxo_range_status(TxoSID(0), 5).unwrap();
+    assert_eq!(
+        range_status.verify(state_commitment.clone()).unwrap(),
+        vec![
+            UtxoStatus::Spent,
+            UtxoStatus::Unspent,
+            UtxoStatus::Unspent,
+            UtxoStatus::Nonexistent,
+            UtxoStatus::Nonexistent,
+        ]
+    );
+    let mut forged_status = range_status.clone();
+    forged_status.utxo_map_bytes = ledger
+        .get_utxo_range_status(TxoSID(7), 1)
+        .unwrap()
+        .utxo_map_bytes;
+    assert!(forged_status.verify(state_commitment.clone()).is_err());
+    assert!(ledger.get_utxo_range_status(TxoSID(0), u64::MAX).is_err());
+
     // Adversary will attempt to spend the same blind asset record at another index
     second_transfer.body.inputs = vec![TxoRef::Absolute(second_txo_id)];
 
@@ -379,6 +418,7 @@ fn asset_issued() {
                 id: None,
                 record: ba,
                 lien: None,
+                multisig: None,
             },
             None,
         )],
@@ -784,3 +824,820 @@ fn test_check_fee_with_ledger() {
     let mut block = ledger.start_block().unwrap();
     assert!(ledger.apply_transaction(&mut block, effect, false).is_err());
 }
+
+#[test]
+fn test_txn_policies() {
+    struct MaxIssuance(AssetTypeCode, u64);
+
+    impl policy::TxnPolicy for MaxIssuance {
+        fn name(&self) -> &str {
+            "max_issuance"
+        }
+
+        fn applies_to(&self, code: &AssetTypeCode) -> bool {
+            *code == self.0
+        }
+
+        fn check(
+            &self,
+            txe: &TxnEffect,
+            _status: &LedgerStatus,
+            _inputs: &[(AssetTypeCode, &TxnCheckInputs)],
+        ) -> Result<()> {
+            let am = txe.issuance_amounts.get(&self.0).copied().unwrap_or(0);
+            alt!(am > self.1, Err(eg!("too much")), Ok(()))
+        }
+    }
+
+    let mut ledger = LedgerState::tmp_ledger();
+    let params = PublicParams::default();
+    let code = AssetTypeCode::gen_random();
+    let kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+    let denied_kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+    let art = AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType;
+
+    pnk!(ledger.register_txn_policy(Arc::new(MaxIssuance(code, 100))));
+    pnk!(ledger.register_txn_policy(Arc::new(policy::DenyListPolicy {
+        assets: None,
+        addresses: vec![denied_kp.get_pk()].into_iter().collect(),
+    })));
+    assert!(ledger
+        .register_txn_policy(Arc::new(MaxIssuance(code, 1)))
+        .is_err());
+    assert_eq!(
+        vec!["deny_list", "max_issuance"],
+        ledger.get_txn_policies().names()
+    );
+
+    let tx = create_definition_transaction(
+        &code,
+        &kp,
+        AssetRules::default(),
+        None,
+        ledger.get_block_commit_count(),
+    )
+    .unwrap();
+    apply_transaction(&mut ledger, tx);
+
+    let try_apply = |ledger: &mut LedgerState, tx: Transaction| {
+        let effect = TxnEffect::compute_effect(tx).unwrap();
+        let mut block = ledger.start_block().unwrap();
+        let res = ledger.apply_transaction(&mut block, effect, false);
+        abort_block(block);
+        res
+    };
+
+    // exceeds the limit of `MaxIssuance`
+    let tx = create_issuance_txn(&mut ledger, &params, &code, 101, 0, art, &kp);
+    assert!(try_apply(&mut ledger, tx).is_err());
+
+    // inputs for an unknown policy
+    let mut tx = create_issuance_txn(&mut ledger, &params, &code, 100, 0, art, &kp);
+    tx.body.policy_options = Some(TxnPolicyData(vec![(
+        code,
+        TxnCheckInputs {
+            which_check: "unknown".to_owned(),
+            params: String::new(),
+        },
+    )]));
+    assert!(try_apply(&mut ledger, tx).is_err());
+
+    let tx = create_issuance_txn(&mut ledger, &params, &code, 100, 0, art, &kp);
+    assert!(try_apply(&mut ledger, tx).is_ok());
+
+    // issued by a denied address
+    let denied_code = AssetTypeCode::gen_random();
+    let tx = create_definition_transaction(
+        &denied_code,
+        &denied_kp,
+        AssetRules::default(),
+        None,
+        ledger.get_block_commit_count(),
+    )
+    .unwrap();
+    apply_transaction(&mut ledger, tx);
+    let tx =
+        create_issuance_txn(&mut ledger, &params, &denied_code, 1, 0, art, &denied_kp);
+    assert!(try_apply(&mut ledger, tx).is_err());
+}
+
+#[test]
+fn test_deny_list_of_assets() {
+    let mut ledger = LedgerState::tmp_ledger();
+    let params = PublicParams::default();
+    let code = AssetTypeCode::gen_random();
+    let denied_code = AssetTypeCode::gen_random();
+    let other_code = AssetTypeCode::gen_random();
+    let kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+    let denied_kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+    let art = AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType;
+
+    pnk!(ledger.register_txn_policy(Arc::new(policy::DenyListPolicy {
+        assets: Some(vec![code, denied_code].into_iter().collect()),
+        addresses: vec![denied_kp.get_pk()].into_iter().collect(),
+    })));
+
+    for (c, k) in [
+        (code, &kp),
+        (denied_code, &denied_kp),
+        (other_code, &denied_kp),
+    ]
+    .iter()
+    {
+        let tx = create_definition_transaction(
+            c,
+            k,
+            AssetRules::default(),
+            None,
+            ledger.get_block_commit_count(),
+        )
+        .unwrap();
+        apply_transaction(&mut ledger, tx);
+    }
+
+    // the policies are checked without a block, as `CheckTx` does
+    let try_apply = |ledger: &mut LedgerState, tx: Transaction| {
+        let effect = TxnEffect::compute_effect(tx).unwrap();
+        ledger.check_txn_policies(&effect).c(d!())?;
+        let mut block = ledger.start_block().unwrap();
+        let res = ledger.apply_transaction(&mut block, effect, false);
+        abort_block(block);
+        res
+    };
+
+    // a listed asset
+    let tx =
+        create_issuance_txn(&mut ledger, &params, &denied_code, 1, 0, art, &denied_kp);
+    assert!(try_apply(&mut ledger, tx).is_err());
+
+    // other assets of the denied address are not affected,
+    // even if they are in the same transaction with a listed asset
+    let mut tx = create_issuance_txn(&mut ledger, &params, &code, 1, 0, art, &kp);
+    let other =
+        create_issuance_txn(&mut ledger, &params, &other_code, 1, 0, art, &denied_kp);
+    tx.body.operations.extend(other.body.operations);
+    assert!(try_apply(&mut ledger, tx).is_ok());
+}
+
+#[test]
+fn test_credential_gated_transfer() {
+    let mut ledger = LedgerState::tmp_ledger();
+    let params = PublicParams::default();
+    let issuer = XfrKeyPair::generate(&mut ledger.get_prng());
+    let alice = XfrKeyPair::generate(&mut ledger.get_prng());
+
+    // A credential issuer, and a credential of alice signed by it
+    let (cred_issuer_pk, cred_issuer_sk) =
+        credential_issuer_key_gen(ledger.get_prng(), &[("kyc".to_owned(), 3)]);
+    let (user_pk, user_sk) = credential_user_key_gen(ledger.get_prng(), &cred_issuer_pk);
+    let signature = pnk!(credential_sign(
+        ledger.get_prng(),
+        &cred_issuer_sk,
+        &user_pk,
+        &[("kyc".to_owned(), b"yes")],
+    ));
+    let credential = Credential {
+        attributes: vec![("kyc".to_owned(), b"yes".to_vec())],
+        issuer_pub_key: cred_issuer_pk.clone(),
+        signature,
+    };
+
+    let code = AssetTypeCode::gen_random();
+    let mut requirement = CredentialRequirement::new(cred_issuer_pk);
+    requirement.add_attr("kyc", Some(b"yes"));
+    let tx = create_definition_transaction(
+        &code,
+        &issuer,
+        AssetRules::default()
+            .set_credential_requirement(Some(requirement.clone()))
+            .clone(),
+        None,
+        ledger.get_block_commit_count(),
+    )
+    .unwrap();
+    apply_transaction(&mut ledger, tx);
+
+    // The issuer itself needs no credential
+    let tx = create_issuance_txn(
+        &mut ledger,
+        &params,
+        &code,
+        100,
+        0,
+        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+        &issuer,
+    );
+    let (_, sids) = apply_transaction(&mut ledger, tx);
+    let sid = sids[0];
+    let bar = ledger.get_utxo_light(sid).unwrap().utxo.0.record;
+
+    let gen_tx = |ledger: &mut LedgerState, credentials: Vec<CredentialProof>| {
+        let template = AssetRecordTemplate::with_no_asset_tracing(
+            100,
+            code.val,
+            AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+            alice.get_pk(),
+        );
+        let record =
+            AssetRecord::from_template_no_identity_tracing(ledger.get_prng(), &template)
+                .unwrap();
+        let mut transfer = TransferAsset::new(
+            TransferAssetBody::new(
+                ledger.get_prng(),
+                vec![TxoRef::Absolute(sid)],
+                &[AssetRecord::from_open_asset_record_no_asset_tracing(
+                    open_blind_asset_record(&bar, &None, &issuer).unwrap(),
+                )],
+                &[record],
+                None,
+                vec![],
+                TransferType::Standard,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        transfer.sign(&issuer);
+        let mut tx = Transaction::from_operation(
+            Operation::TransferAsset(transfer),
+            ledger.get_block_commit_count(),
+        );
+        tx.body.credentials = credentials;
+        TxnEffect::compute_effect(tx).unwrap()
+    };
+
+    // No credential
+    let effect = gen_tx(&mut ledger, vec![]);
+    let mut block = ledger.start_block().unwrap();
+    assert!(ledger.apply_transaction(&mut block, effect, false).is_err());
+    abort_block(block);
+
+    // A credential linked to another address
+    let proof = pnk!(CredentialProof::new(
+        ledger.get_prng(),
+        &user_sk,
+        &credential,
+        issuer.get_pk_ref(),
+        &["kyc".to_owned()],
+    ));
+    let mut forged = proof.clone();
+    forged.address = alice.get_pk();
+    assert!(forged.verify(&requirement).is_err());
+    let effect = gen_tx(&mut ledger, vec![proof, forged]);
+    let mut block = ledger.start_block().unwrap();
+    assert!(ledger.apply_transaction(&mut block, effect, false).is_err());
+    abort_block(block);
+
+    // Required attribute not revealed
+    let proof = pnk!(CredentialProof::new(
+        ledger.get_prng(),
+        &user_sk,
+        &credential,
+        alice.get_pk_ref(),
+        &[],
+    ));
+    assert!(proof.verify(&requirement).is_err());
+
+    let proof = pnk!(CredentialProof::new(
+        ledger.get_prng(),
+        &user_sk,
+        &credential,
+        alice.get_pk_ref(),
+        &["kyc".to_owned()],
+    ));
+    pnk!(proof.verify(&requirement));
+    let effect = gen_tx(&mut ledger, vec![proof]);
+    let mut block = ledger.start_block().unwrap();
+    pnk!(ledger.apply_transaction(&mut block, effect, false));
+    pnk!(ledger.finish_block(block));
+}
+
+// Moves the whole amount of `input` to `output`,
+// either of them may belong to a multisig account.
+fn gen_multisig_transfer(
+    ledger: &mut LedgerState,
+    input: (TxoSID, &BlindAssetRecord, Option<&SignatureRules>),
+    output: (XfrPublicKey, Option<&SignatureRules>),
+) -> TransferAsset {
+    let (sid, bar, input_rules) = input;
+    let (receiver, output_rules) = output;
+
+    // Non-confidential records can be opened with any key
+    let oar = pnk!(open_blind_asset_record(
+        bar,
+        &None,
+        &build_keys(ledger.get_prng())
+    ));
+    let template = AssetRecordTemplate::with_no_asset_tracing(
+        oar.get_amount().to_owned(),
+        oar.get_asset_type().to_owned(),
+        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+        receiver,
+    );
+    let record = pnk!(AssetRecord::from_template_no_identity_tracing(
+        ledger.get_prng(),
+        &template
+    ));
+    let mut body = pnk!(TransferAssetBody::new(
+        ledger.get_prng(),
+        vec![TxoRef::Absolute(sid)],
+        &[AssetRecord::from_open_asset_record_no_asset_tracing(oar)],
+        &[record],
+        None,
+        vec![],
+        TransferType::Standard,
+    ));
+    if let Some(rules) = input_rules {
+        body.multisig_inputs = vec![(0, rules.clone())];
+    }
+    body.outputs[0].multisig = output_rules.cloned();
+    pnk!(TransferAsset::new(body))
+}
+
+#[test]
+fn test_multisig_transfer() {
+    let mut ledger = LedgerState::tmp_ledger();
+    let params = PublicParams::default();
+    let issuer = build_keys(ledger.get_prng());
+    let signers = (0..3)
+        .map(|_| build_keys(ledger.get_prng()))
+        .collect::<Vec<_>>();
+
+    // A 2-of-3 account
+    let rules = SignatureRules {
+        threshold: 2,
+        weights: signers.iter().map(|kp| (kp.get_pk(), 1)).collect(),
+    };
+    pnk!(rules.check());
+    let address = rules.multisig_address();
+    assert_eq!(address, rules.clone().multisig_address());
+
+    let mut unreachable = rules.clone();
+    unreachable.threshold = 4;
+    assert!(unreachable.check().is_err());
+    let mut duplicated = rules.clone();
+    duplicated.weights[1].0 = signers[0].get_pk();
+    assert!(duplicated.check().is_err());
+
+    let code = AssetTypeCode::gen_random();
+    let tx = pnk!(create_definition_transaction(
+        &code,
+        &issuer,
+        AssetRules::default(),
+        None,
+        ledger.get_block_commit_count(),
+    ));
+    apply_transaction(&mut ledger, tx);
+    let tx = create_issuance_txn(
+        &mut ledger,
+        &params,
+        &code,
+        100,
+        0,
+        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+        &issuer,
+    );
+    let (_, sids) = apply_transaction(&mut ledger, tx);
+    let bar = ledger.get_utxo_light(sids[0]).unwrap().utxo.0.record;
+
+    // The rules must be recorded on an output sent to their address
+    let mut transfer = gen_multisig_transfer(
+        &mut ledger,
+        (sids[0], &bar, None),
+        (issuer.get_pk(), Some(&rules)),
+    );
+    transfer.sign(&issuer);
+    let tx = Transaction::from_operation(
+        Operation::TransferAsset(transfer),
+        ledger.get_block_commit_count(),
+    );
+    assert!(TxnEffect::compute_effect(tx).is_err());
+
+    let mut transfer = gen_multisig_transfer(
+        &mut ledger,
+        (sids[0], &bar, None),
+        (address, Some(&rules)),
+    );
+    transfer.sign(&issuer);
+    let tx = Transaction::from_operation(
+        Operation::TransferAsset(transfer),
+        ledger.get_block_commit_count(),
+    );
+    let (_, sids) = apply_transaction(&mut ledger, tx);
+    let utxo = ledger.get_utxo_light(sids[0]).unwrap().utxo.0;
+    assert_eq!(utxo.multisig.as_ref(), Some(&rules));
+    let bar = utxo.record;
+
+    let gen_tx = |ledger: &mut LedgerState,
+                  input_rules: Option<&SignatureRules>,
+                  approvers: &[&XfrKeyPair]| {
+        let transfer = gen_multisig_transfer(
+            ledger,
+            (sids[0], &bar, input_rules),
+            (issuer.get_pk(), None),
+        );
+        let mut tx = Transaction::from_operation(
+            Operation::TransferAsset(transfer),
+            ledger.get_block_commit_count(),
+        );
+        for kp in approvers {
+            assert_eq!(1, pnk!(tx.cosign(kp)));
+        }
+        tx
+    };
+
+    // Below the threshold
+    let tx = gen_tx(&mut ledger, Some(&rules), &[&signers[0]]);
+    assert!(TxnEffect::compute_effect(tx).is_err());
+
+    // Not a signer
+    let mut tx = gen_tx(&mut ledger, Some(&rules), &[&signers[0]]);
+    assert_eq!(0, pnk!(tx.cosign(&issuer)));
+    assert_eq!(0, pnk!(tx.cosign(&signers[0])));
+    assert!(TxnEffect::compute_effect(tx).is_err());
+
+    // Spent as an ordinary input
+    let mut tx = gen_tx(&mut ledger, None, &[]);
+    if let Operation::TransferAsset(i) = &mut tx.body.operations[0] {
+        i.sign(&signers[0]);
+        i.sign(&signers[1]);
+    }
+    assert!(TxnEffect::compute_effect(tx).is_err());
+
+    // Declared with other rules
+    let mut other = rules.clone();
+    other.threshold = 1;
+    let tx = gen_tx(&mut ledger, Some(&other), &[&signers[0]]);
+    assert!(TxnEffect::compute_effect(tx).is_err());
+
+    let tx = gen_tx(&mut ledger, Some(&rules), &[&signers[0], &signers[2]]);
+    let effect = pnk!(TxnEffect::compute_effect(tx));
+    let mut block = ledger.start_block().unwrap();
+    pnk!(ledger.apply_transaction(&mut block, effect, false));
+    pnk!(ledger.finish_block(block));
+    assert_eq!(ledger.get_utxo_status(sids[0]).status, UtxoStatus::Spent);
+}
+
+#[test]
+fn test_multisig_plain_funding() {
+    let mut ledger = LedgerState::tmp_ledger();
+    let params = PublicParams::default();
+    let issuer = build_keys(ledger.get_prng());
+    let signers = (0..2)
+        .map(|_| build_keys(ledger.get_prng()))
+        .collect::<Vec<_>>();
+    let rules = SignatureRules {
+        threshold: 2,
+        weights: signers.iter().map(|kp| (kp.get_pk(), 1)).collect(),
+    };
+    let address = rules.multisig_address();
+
+    let code = AssetTypeCode::gen_random();
+    let tx = pnk!(create_definition_transaction(
+        &code,
+        &issuer,
+        AssetRules::default(),
+        None,
+        ledger.get_block_commit_count(),
+    ));
+    apply_transaction(&mut ledger, tx);
+    let tx = create_issuance_txn(
+        &mut ledger,
+        &params,
+        &code,
+        100,
+        0,
+        AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+        &issuer,
+    );
+    let (_, sids) = apply_transaction(&mut ledger, tx);
+    let bar = ledger.get_utxo_light(sids[0]).unwrap().utxo.0.record;
+
+    // Funded by a plain transfer, the rules are not recorded
+    let mut transfer =
+        gen_multisig_transfer(&mut ledger, (sids[0], &bar, None), (address, None));
+    transfer.sign(&issuer);
+    let tx = Transaction::from_operation(
+        Operation::TransferAsset(transfer),
+        ledger.get_block_commit_count(),
+    );
+    let (_, sids) = apply_transaction(&mut ledger, tx);
+    let utxo = ledger.get_utxo_light(sids[0]).unwrap().utxo.0;
+    assert!(utxo.multisig.is_none());
+    let bar = utxo.record;
+
+    let gen_effect = |ledger: &mut LedgerState, approvers: &[&XfrKeyPair]| {
+        let transfer = gen_multisig_transfer(
+            ledger,
+            (sids[0], &bar, Some(&rules)),
+            (issuer.get_pk(), None),
+        );
+        let mut tx = Transaction::from_operation(
+            Operation::TransferAsset(transfer),
+            ledger.get_block_commit_count(),
+        );
+        for kp in approvers {
+            assert_eq!(1, pnk!(tx.cosign(kp)));
+        }
+        TxnEffect::compute_effect(tx)
+    };
+
+    // Still approved under the rules
+    assert!(gen_effect(&mut ledger, &[&signers[0]]).is_err());
+    let effect = pnk!(gen_effect(&mut ledger, &[&signers[0], &signers[1]]));
+    let mut block = ledger.start_block().unwrap();
+    pnk!(ledger.apply_transaction(&mut block, effect, false));
+    pnk!(ledger.finish_block(block));
+    assert_eq!(ledger.get_utxo_status(sids[0]).status, UtxoStatus::Spent);
+}
+
+// Escrow `am` of `txo_sid` to `BLACK_HOLE_PUBKEY_STAKING`,
+// the change is sent back to the owner as a `change_type` record.
+fn gen_escrow_operation(
+    l: &mut LedgerState,
+    txo_sid: TxoSID,
+    owner_kp: &XfrKeyPair,
+    am: u64,
+    change_type: AssetRecordType,
+) -> Operation {
+    let input_bar = (l.get_utxo_light(txo_sid).unwrap().utxo.0).record;
+    let input_oar = open_blind_asset_record(&input_bar, &None, &owner_kp).unwrap();
+
+    let outputs = [
+        (
+            am,
+            *BLACK_HOLE_PUBKEY_STAKING,
+            AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+        ),
+        (input_oar.amount - am, owner_kp.get_pk(), change_type),
+    ]
+    .iter()
+    .map(|(am, pk, ty)| {
+        let template = AssetRecordTemplate::with_no_asset_tracing(
+            *am,
+            input_oar.asset_type,
+            *ty,
+            *pk,
+        );
+        AssetRecord::from_template_no_identity_tracing(l.get_prng(), &template).unwrap()
+    })
+    .collect::<Vec<_>>();
+
+    let mut transfer = TransferAsset::new(
+        TransferAssetBody::new(
+            l.get_prng(),
+            vec![TxoRef::Absolute(txo_sid)],
+            &[AssetRecord::from_open_asset_record_no_asset_tracing(
+                input_oar,
+            )],
+            &outputs,
+            None,
+            vec![],
+            TransferType::Standard,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    transfer.sign(&owner_kp);
+
+    Operation::TransferAsset(transfer)
+}
+
+#[test]
+fn test_transfer_plan_context() {
+    let mut ledger = LedgerState::tmp_ledger();
+    let fra_owner_kp = XfrKeyPair::generate(&mut ChaChaRng::from_entropy());
+    let recipient = build_keys(ledger.get_prng()).get_pk();
+    let stranger_kp = build_keys(ledger.get_prng());
+
+    let tx = utils::fra_gen_initial_tx(&fra_owner_kp);
+    let (_, sids) = apply_transaction(&mut ledger, tx);
+    let txo_sid = sids[0];
+
+    let plan = TransferPlan::new(recipient, ASSET_TYPE_FRA, 100, 2, 3);
+
+    let gen_tx = |ledger: &mut LedgerState,
+                  am: u64,
+                  plan: &TransferPlan,
+                  plan_kp: &XfrKeyPair,
+                  change_type: AssetRecordType,
+                  with_conversion: bool| {
+        let mut tx = Transaction::from_seq_id(ledger.get_block_commit_count());
+        let nonce = tx.body.no_replay_token;
+        tx.add_operation(gen_escrow_operation(
+            ledger,
+            txo_sid,
+            &fra_owner_kp,
+            am,
+            change_type,
+        ));
+        tx.add_operation(Operation::TransferPlan(TransferPlanOps::new(
+            plan_kp,
+            TransferPlanAction::Register(plan.clone()),
+            nonce,
+        )));
+        if with_conversion {
+            tx.add_operation(Operation::ConvertAccount(ConvertAccount::new(
+                &fra_owner_kp,
+                nonce,
+                MultiSigner::Xfr(fra_owner_kp.get_pk()),
+            )));
+        }
+        tx
+    };
+
+    let is_accepted = |ledger: &mut LedgerState, tx: Transaction| {
+        let effect = pnk!(TxnEffect::compute_effect(tx));
+        let mut block = ledger.start_block().unwrap();
+        let res = ledger.apply_transaction(&mut block, effect, false).is_ok();
+        abort_block(block);
+        res
+    };
+
+    let non_conf = AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType;
+
+    // The escrow must be equal to the total amount of the plan
+    for am in [299, 301].iter() {
+        let tx = gen_tx(&mut ledger, *am, &plan, &fra_owner_kp, non_conf, false);
+        assert!(!is_accepted(&mut ledger, tx));
+    }
+
+    // Can not be mixed with account conversions,
+    // which also count the transfers to `BLACK_HOLE_PUBKEY_STAKING`
+    let tx = gen_tx(&mut ledger, 300, &plan, &fra_owner_kp, non_conf, true);
+    assert!(!is_accepted(&mut ledger, tx));
+
+    // The inputs of the escrow must be owned by the owner of the plan
+    let tx = gen_tx(&mut ledger, 300, &plan, &stranger_kp, non_conf, false);
+    assert!(!is_accepted(&mut ledger, tx));
+
+    // No confidential outputs are allowed
+    let tx = gen_tx(
+        &mut ledger,
+        300,
+        &plan,
+        &fra_owner_kp,
+        AssetRecordType::ConfidentialAmount_NonConfidentialAssetType,
+        false,
+    );
+    assert!(!is_accepted(&mut ledger, tx));
+
+    // The escrow must be of the asset type of the plan
+    let mut other = plan.clone();
+    other.asset_type = AssetTypeCode::gen_random().val;
+    let tx = gen_tx(&mut ledger, 300, &other, &fra_owner_kp, non_conf, false);
+    assert!(!is_accepted(&mut ledger, tx));
+
+    // Custom assets can be planned, unless they have transfer restrictions
+    let params = PublicParams::default();
+    let gen_custom_tx = |ledger: &mut LedgerState, rules: AssetRules| {
+        let code = AssetTypeCode::gen_random();
+        let tx = create_definition_transaction(
+            &code,
+            &fra_owner_kp,
+            rules,
+            None,
+            ledger.get_block_commit_count(),
+        )
+        .unwrap();
+        apply_transaction(ledger, tx);
+        let tx = create_issuance_txn(
+            ledger,
+            &params,
+            &code,
+            1000,
+            0,
+            non_conf,
+            &fra_owner_kp,
+        );
+        let (_, sids) = apply_transaction(ledger, tx);
+
+        let mut tx = Transaction::from_seq_id(ledger.get_block_commit_count());
+        let nonce = tx.body.no_replay_token;
+        tx.add_operation(gen_escrow_operation(
+            ledger,
+            sids[0],
+            &fra_owner_kp,
+            300,
+            non_conf,
+        ));
+        tx.add_operation(Operation::TransferPlan(TransferPlanOps::new(
+            &fra_owner_kp,
+            TransferPlanAction::Register(TransferPlan::new(
+                recipient, code.val, 100, 2, 3,
+            )),
+            nonce,
+        )));
+        tx
+    };
+
+    let tx = gen_custom_tx(&mut ledger, AssetRules::default());
+    assert!(is_accepted(&mut ledger, tx));
+
+    let mut rules = AssetRules::default();
+    rules.set_transferable(false);
+    let tx = gen_custom_tx(&mut ledger, rules);
+    assert!(!is_accepted(&mut ledger, tx));
+
+    let tx = gen_tx(&mut ledger, 300, &plan, &fra_owner_kp, non_conf, false);
+    let (_, sids) = apply_transaction(&mut ledger, tx);
+    let escrow = ledger.get_utxo_light(sids[0]).unwrap().utxo.0.record;
+    assert_eq!(escrow.public_key, *BLACK_HOLE_PUBKEY_STAKING);
+    assert_eq!(escrow.amount, XfrAmount::NonConfidential(300));
+}
+
+#[test]
+fn test_rotate_key() {
+    let mut ledger = LedgerState::tmp_ledger();
+    let owner_kp = build_keys(ledger.get_prng());
+    let stranger_kp = build_keys(ledger.get_prng());
+
+    let old_td_pubkey = vec![1; 32];
+    let validator = td_pubkey_to_td_addr(&old_td_pubkey);
+    let v = pnk!(Validator::new(
+        old_td_pubkey,
+        100,
+        owner_kp.get_pk(),
+        [1, 100],
+        StakerMemo::default(),
+        ValidatorKind::Initor,
+    ));
+    let staking = ledger.get_staking_mut();
+    pnk!(staking.validator_set_at_height(1, pnk!(ValidatorData::new(1, vec![v]))));
+    staking.set_custom_block_height(1);
+
+    let gen_td_key = |ledger: &mut LedgerState| {
+        PrivateKey::Ed25519(ed25519_dalek::Keypair::generate(ledger.get_prng()))
+    };
+
+    let gen_tx = |ledger: &mut LedgerState, kp: &XfrKeyPair, new_key: &PrivateKey| {
+        let mut tx = Transaction::from_seq_id(ledger.get_block_commit_count());
+        let nonce = tx.body.no_replay_token;
+        tx.add_operation(Operation::RotateKey(pnk!(RotateKeyOps::new(
+            kp,
+            new_key,
+            validator.clone(),
+            nonce
+        ))));
+        tx
+    };
+
+    // replace a field of the key rotation, keeping the other signatures
+    let set_field = |tx: &mut Transaction, field: &str, value: serde_json::Value| {
+        if let Operation::RotateKey(i) = &mut tx.body.operations[0] {
+            let mut op = pnk!(serde_json::to_value(&*i));
+            op[field] = value;
+            *i = pnk!(serde_json::from_value(op));
+        }
+    };
+
+    let is_accepted = |ledger: &mut LedgerState, tx: Transaction| {
+        let effect = pnk!(TxnEffect::compute_effect(tx));
+        let mut block = ledger.start_block().unwrap();
+        let res = ledger.apply_transaction(&mut block, effect, false).is_ok();
+        abort_block(block);
+        res
+    };
+
+    let new_key = gen_td_key(&mut ledger);
+    let new_addr =
+        td_pubkey_to_td_addr(&new_key.ed25519_keypair().unwrap().public.to_bytes());
+
+    // The new tendermint key must sign the rotation
+    let other_key = gen_td_key(&mut ledger);
+    let other = gen_tx(&mut ledger, &owner_kp, &other_key);
+    let other_sig = match &other.body.operations[0] {
+        Operation::RotateKey(i) => pnk!(serde_json::to_value(i))["v_signature"].clone(),
+        _ => unreachable!(),
+    };
+    let mut tx = gen_tx(&mut ledger, &owner_kp, &new_key);
+    set_field(&mut tx, "v_signature", other_sig);
+    assert!(TxnEffect::compute_effect(tx).is_err());
+
+    let mut tx = gen_tx(&mut ledger, &owner_kp, &new_key);
+    set_field(&mut tx, "v_signature", serde_json::Value::Null);
+    assert!(TxnEffect::compute_effect(tx).is_err());
+
+    // Only the owner of the validator can rotate its key
+    let tx = gen_tx(&mut ledger, &stranger_kp, &new_key);
+    assert!(!is_accepted(&mut ledger, tx));
+    assert!(ledger
+        .get_staking()
+        .validator_td_addr_to_app_pk(&new_addr)
+        .is_err());
+
+    let tx = gen_tx(&mut ledger, &owner_kp, &new_key);
+    apply_transaction(&mut ledger, tx);
+    let staking = ledger.get_staking();
+    assert_eq!(
+        new_addr,
+        pnk!(staking.validator_app_pk_to_td_addr(&owner_kp.get_pk()))
+    );
+    assert_eq!(
+        owner_kp.get_pk(),
+        pnk!(staking.validator_td_addr_to_app_pk(&new_addr))
+    );
+
+    // The previous rotation has not finished
+    let tx = gen_tx(&mut ledger, &owner_kp, &other_key);
+    assert!(!is_accepted(&mut ledger, tx));
+}
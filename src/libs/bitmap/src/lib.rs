@@ -219,6 +219,12 @@ impl SparseMap {
         )))
     }
 
+    /// Query the values of the bits in the range [first, last).
+    /// All of them must be present in the map.
+    pub fn query_range(&self, first: u64, last: u64) -> Result<Vec<bool>> {
+        (first..last).map(|id| self.query(id).c(d!())).collect()
+    }
+
     /// Validate that the tree actually matches the checksum
     /// in the download. The checksum of blocks that were
     /// downloaded are checked as well, so that further query
@@ -999,26 +1005,39 @@ impl BitMap {
         bit_list: Vec<usize>,
         version: usize,
     ) -> Vec<u8> {
-        // Reserve space for the version number as a u64.
-        let mut bytes = DESCRIPTOR_SIZE;
         let mut set = HashSet::new();
 
         for b in bit_list.iter() {
             set.insert(b / BLOCK_BITS);
         }
 
-        // Add the space needed for each block.
+        let mut result = self.reserve_partial(&set);
+        self.append_descriptor(version as u64, &mut result);
+
         for i in 0..self.blocks.len() {
-            if set.contains(&i) {
-                bytes += self.serial_size(i);
-            } else {
-                bytes += BLOCK_INFO_SIZE;
-            }
+            self.serialize_block(i, &mut result, set.contains(&i));
         }
 
-        let mut result = Vec::new();
-        result.reserve(bytes);
-        self.append_descriptor(version as u64, &mut result);
+        result
+    }
+
+    /// Serialize the bitmap to a compressed form that contains
+    /// bit values only for the blocks covering the bits in
+    /// the range [first, last). Other blocks are represented
+    /// only by a header with a checksum.
+    ///
+    /// Like serialize, this method must only be called in cases
+    /// where the cached checksum would be up to date.
+    pub fn serialize_range(&self, first: usize, last: usize, version: usize) -> Vec<u8> {
+        let set = if first < last {
+            (first / BLOCK_BITS..=(last - 1) / BLOCK_BITS).collect()
+        } else {
+            HashSet::new()
+        };
+
+        let mut result = self.reserve_partial(&set);
+        result.extend_from_slice(&(version as u64).to_le_bytes());
+        result.extend_from_slice(&self.checksum[0..]);
 
         for i in 0..self.blocks.len() {
             self.serialize_block(i, &mut result, set.contains(&i));
@@ -1027,6 +1046,24 @@ impl BitMap {
         result
     }
 
+    // Allocate the result of a partial serialization, the
+    // blocks in the set will be included with their bits.
+    fn reserve_partial(&self, set: &HashSet<usize>) -> Vec<u8> {
+        // Reserve space for the version number as a u64.
+        let mut bytes = DESCRIPTOR_SIZE;
+
+        // Add the space needed for each block.
+        for i in 0..self.blocks.len() {
+            if set.contains(&i) {
+                bytes += self.serial_size(i);
+            } else {
+                bytes += BLOCK_INFO_SIZE;
+            }
+        }
+
+        Vec::with_capacity(bytes)
+    }
+
     // Append the global data for the bitmap. That data
     // currently consists of the version number from the
     // caller and the checksum of the tree.
@@ -264,6 +264,25 @@ fn test_basic_bitmap() {
         panic!("partial_map query at size passed.");
     }
 
+    // Serialize a range crossing the first two blocks, the
+    // checksum has been computed above.
+    let first = BLOCK_BITS - 2;
+    let last = BLOCK_BITS + 2;
+    let s3 = bitmap.serialize_range(first, last, 2);
+    let range_map = SparseMap::new(&s3).unwrap();
+    assert!(range_map.validate_checksum());
+    assert!(range_map.checksum() == sparse_map.checksum());
+    assert!(s3.len() < s2.len());
+
+    let bits = range_map.query_range(first as u64, last as u64).unwrap();
+    for (i, bit) in (first..last).zip(bits.iter()) {
+        assert!(*bit == bitmap.query(i).unwrap());
+    }
+    assert!(range_map.query(2 * BLOCK_BITS as u64).is_err());
+    assert!(range_map
+        .query_range(first as u64, bitmap.size() as u64)
+        .is_err());
+
     // Test some more manipulations of the bitmap.
     for i in 0..bitmap.size() {
         if i & 1 == 0 {
@@ -4,10 +4,17 @@
 //! Separating mnemonic to a standalone library is needed by tests.
 //!
 
+use aes_gcm::{
+    aead::{generic_array::GenericArray, Aead, NewAead},
+    Aes256Gcm,
+};
 use bech32::{self, FromBase32, ToBase32};
 use bip0039::{Count, Language, Mnemonic};
 use ed25519_dalek_bip32::{DerivationPath, ExtendedSecretKey};
+use rand::{thread_rng, Rng};
+use ring::pbkdf2;
 use ruc::*;
+use std::{fmt, num::NonZeroU32};
 use zei::{
     serialization::ZeiFromToBytes,
     xfr::sig::{XfrKeyPair, XfrPublicKey, XfrSecretKey},
@@ -87,15 +94,49 @@ impl BipPath {
             address,
         }
     }
+
+    /// Parse a path in the form of "m/44'/917'/0'/0/0",
+    /// the purpose and the hardened marks are not checked.
+    pub fn parse(path: &str) -> Result<Self> {
+        let levels = path
+            .trim()
+            .split('/')
+            .skip(2)
+            .map(|l| l.trim_end_matches('\'').parse::<u32>().c(d!()))
+            .collect::<Result<Vec<_>>>()
+            .c(d!("invalid bip path"))?;
+        if !path.trim().starts_with("m/") || 4 != levels.len() {
+            return Err(eg!("invalid bip path, eg. \"m/44'/917'/0'/0/0\""));
+        }
+        Ok(BipPath::new(levels[0], levels[1], levels[2], levels[3]))
+    }
 }
 
+impl Default for BipPath {
+    /// "m/44'/917'/0'/0/0"
+    fn default() -> Self {
+        BipPath::new(FRA_COIN, 0, 0, 0)
+    }
+}
+
+impl fmt::Display for BipPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "m/44'/{}'/{}'/{}/{}",
+            self.coin, self.account, self.change, self.address
+        )
+    }
+}
+
+/// The coin type of FRA in bip44 paths
+pub const FRA_COIN: u32 = 917;
+
 /// Restore the XfrKeyPair from a mnemonic with a default bip44-path,
 /// that is "m/44'/917'/0'/0/0" ("m/44'/coin'/account'/change/address").
 #[inline(always)]
 pub fn restore_keypair_from_mnemonic_default(phrase: &str) -> Result<XfrKeyPair> {
-    const FRA: u32 = 917;
-    restore_keypair_from_mnemonic!(phrase, "en", BipPath::new(FRA, 0, 0, 0), bip44)
-        .c(d!())
+    restore_keypair_from_mnemonic!(phrase, "en", BipPath::default(), bip44).c(d!())
 }
 
 /// Restore the XfrKeyPair from a mnemonic with custom params,
@@ -198,6 +239,71 @@ fn bech32dec(input: &str) -> Result<Vec<u8>> {
 
 /////////////////////////////////////////////////////////////////
 
+const CREDENTIAL_LEN: usize = 32;
+const IV_LEN: usize = 12;
+
+fn derive_key(password: &str, salt: &[u8], n_iter: u32) -> Result<[u8; CREDENTIAL_LEN]> {
+    let n_iter = NonZeroU32::new(n_iter).c(d!("iterations must be positive"))?;
+    let mut derived_key = [0u8; CREDENTIAL_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA512,
+        n_iter,
+        salt,
+        password.as_bytes(),
+        &mut derived_key,
+    );
+    Ok(derived_key)
+}
+
+/// Encrypt `data` with a key derived from `password` by pbkdf2(`n_iter` rounds),
+/// the result is `salt(32 bytes) | iv(12 bytes) | aes256gcm ciphertext`.
+pub fn encrypt_pbkdf2_aes256gcm(
+    data: &[u8],
+    password: &str,
+    n_iter: u32,
+) -> Result<Vec<u8>> {
+    let mut rng = thread_rng();
+
+    let mut salt = [0u8; CREDENTIAL_LEN];
+    rng.fill(&mut salt);
+    let mut iv = [0u8; IV_LEN];
+    rng.fill(&mut iv);
+
+    let key = derive_key(password, &salt, n_iter).c(d!())?;
+    let ciphertext = Aes256Gcm::new(GenericArray::from_slice(&key))
+        .encrypt(GenericArray::from_slice(&iv), data)
+        .map_err(|_| eg!("encryption failed"))?;
+
+    let mut res = Vec::with_capacity(CREDENTIAL_LEN + IV_LEN + ciphertext.len());
+    res.extend_from_slice(&salt);
+    res.extend_from_slice(&iv);
+    res.extend_from_slice(&ciphertext);
+    Ok(res)
+}
+
+/// Decrypt the result of `encrypt_pbkdf2_aes256gcm`,
+/// fails if the password is wrong or the data has been tampered with.
+pub fn decrypt_pbkdf2_aes256gcm(
+    enc: &[u8],
+    password: &str,
+    n_iter: u32,
+) -> Result<Vec<u8>> {
+    if enc.len() <= CREDENTIAL_LEN + IV_LEN {
+        return Err(eg!("invalid encrypted data"));
+    }
+
+    let salt = &enc[..CREDENTIAL_LEN];
+    let iv = &enc[CREDENTIAL_LEN..(CREDENTIAL_LEN + IV_LEN)];
+    let ciphertext = &enc[(CREDENTIAL_LEN + IV_LEN)..];
+
+    let key = derive_key(password, salt, n_iter).c(d!())?;
+    Aes256Gcm::new(GenericArray::from_slice(&key))
+        .decrypt(GenericArray::from_slice(iv), ciphertext)
+        .map_err(|_| eg!("wrong password or corrupted data"))
+}
+
+/////////////////////////////////////////////////////////////////
+
 #[cfg(test)]
 #[allow(missing_docs)]
 mod test {
@@ -247,4 +353,27 @@ mod test {
         assert_eq!(pk, pnk!(public_key_from_base64(&public_key_to_base64(&pk))));
         assert_eq!(pk, pnk!(public_key_from_bech32(&public_key_to_bech32(&pk))));
     }
+
+    #[test]
+    fn t_bip_path() {
+        let path = pnk!(BipPath::parse("m/44'/917'/3'/1/2"));
+        assert_eq!("m/44'/917'/3'/1/2", path.to_string());
+        assert_eq!("m/44'/917'/0'/0/0", BipPath::default().to_string());
+        assert!(BipPath::parse("m/44'/917'/0'").is_err());
+        assert!(BipPath::parse("44'/917'/0'/0/0").is_err());
+        assert!(BipPath::parse("m/44'/x'/0'/0/0").is_err());
+    }
+
+    #[test]
+    fn t_pbkdf2_aes256gcm() {
+        let data = b"hello world";
+        let enc = pnk!(encrypt_pbkdf2_aes256gcm(data, "12345", 1000));
+        assert_eq!(
+            &data[..],
+            &pnk!(decrypt_pbkdf2_aes256gcm(&enc, "12345", 1000))[..]
+        );
+        assert!(decrypt_pbkdf2_aes256gcm(&enc, "54321", 1000).is_err());
+        assert!(decrypt_pbkdf2_aes256gcm(&enc, "12345", 32).is_err());
+        assert!(decrypt_pbkdf2_aes256gcm(&enc[..40], "12345", 1000).is_err());
+    }
 }
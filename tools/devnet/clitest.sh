@@ -66,6 +66,24 @@ if [ "${BALANCE}" -ne $((1000*1000*1000*1000)) ]; then echo "Incorrect custom ba
 BALANCE=$(fn wallet --show --seckey usersk --asset "${CODE}" 2>&1 |head -1 | awk '{print $NF}')
 if [ "${BALANCE}" -ne $((1000*1000*1000*1000*999)) ]; then echo "Incorrect custom balance"; exit 2; fi
 
+echo "Traced asset operations"
+rm -f tracer.key
+fn asset --gen-tracer-key --tracer-key tracer.key 2>&1 >/dev/null
+fn asset --create --seckey usersk --memo "asset1" --transferable --updatable --tracer-key tracer.key 2>&1 >/dev/null
+sleep 20
+CODE_1=$(fn asset --show --addr "${USERADDR}" 2>&1 | grep -v "${CODE}" | head -1)
+fn asset --issue --seckey usersk --code "${CODE_1}" --amount 1000 2>&1 >/dev/null
+sleep 20
+fn transfer -f usersk -T "${USERADDR_1}" --asset "${CODE_1}" -n 400 2>&1 > /dev/null
+sleep 20
+TRACED=$(fn asset --trace --tracer-key tracer.key --code "${CODE_1}" 2>&1 | grep -w "${USERADDR_1}" | awk '{print $(NF-1)}')
+echo "   traced: ${TRACED}"
+if [ "${TRACED}" != "400" ]; then echo "Incorrect traced amount"; exit 6; fi
+fn asset --update-memo --seckey usersk --code "${CODE_1}" --memo "asset1-v2" 2>&1 >/dev/null
+sleep 20
+MEMO=$(curl -s "http://127.0.0.1:8668/asset_token/${CODE_1}" | jq -r '.properties.memo')
+if [ "${MEMO}" != "asset1-v2" ]; then echo "Incorrect asset memo"; exit 6; fi
+
 echo "Delegation operations"
 fn delegate --seckey usersk --amount $((1000*1000*1000*1000*999)) --validator "${V1_ADDR}" 2>&1 >/dev/null
 sleep 20
@@ -102,4 +120,4 @@ REWARDS=$(fn delegate --info --seckey usersk | grep -w "rewards" | awk '{print $
 echo "   rewards: ${REWARDS}"
 if [ "${REWARDS}" -ne 0 ]; then echo "Incorrect rewards amount"; exit 5; fi
 
-rm -f banksk usersk usersk_1
+rm -f banksk usersk usersk_1 tracer.key